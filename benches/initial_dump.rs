@@ -0,0 +1,181 @@
+// SPDX-License-Identifier: MIT
+
+//! Compares the rtnetlink dumps of the initial state with and without the filters the driver uses,
+//! and the cost of a steady state route update which did not change a state with the full routing table of a router.
+//!
+//! Run with `cargo bench --bench initial_dump`, the difference grows with the amount of interfaces and addresses of the system.
+
+#[cfg(target_os = "linux")]
+use futures::TryStreamExt;
+#[cfg(target_os = "linux")]
+use network_connectivity::state::{Interfaces, LinkInfo, RouteDetails};
+#[cfg(target_os = "linux")]
+use rtnetlink::{
+    packet::constants::{AF_INET, AF_INET6, AF_UNSPEC, RTEXT_FILTER_SKIP_STATS},
+    Handle,
+};
+#[cfg(target_os = "linux")]
+use std::{
+    error::Error,
+    future::Future,
+    hint::black_box,
+    net::{IpAddr, Ipv4Addr},
+    time::{Duration, Instant},
+};
+
+/// The amount of times every dump is repeated
+#[cfg(target_os = "linux")]
+const ITERATIONS: u32 = 200;
+
+/// The amount of routes in the state the steady state updates are measured with, about the size of a full routing table of a router
+#[cfg(target_os = "linux")]
+const ROUTES: u32 = 100_000;
+
+/// Runs `dump` [`ITERATIONS`] times and prints the average duration and the amount of messages of a dump.
+#[cfg(target_os = "linux")]
+async fn measure<F>(
+    name: &str,
+    mut dump: impl FnMut() -> F,
+) -> Result<Duration, Box<dyn Error + Send + Sync>>
+where
+    F: Future<Output = Result<usize, rtnetlink::Error>>,
+{
+    let mut messages = 0;
+    let started = Instant::now();
+    for _ in 0..ITERATIONS {
+        messages = dump().await?;
+    }
+    let average = started.elapsed() / ITERATIONS;
+    println!("{name:<40} {average:>12?} {messages:>6} messages");
+    Ok(average)
+}
+
+/// Dumps the links without a filter.
+#[cfg(target_os = "linux")]
+async fn links(handle: Handle) -> Result<usize, rtnetlink::Error> {
+    handle
+        .link()
+        .get()
+        .execute()
+        .try_fold(0, |count, _| async move { Ok(count + 1) })
+        .await
+}
+
+/// Dumps the links without their statistics, like the driver.
+#[cfg(target_os = "linux")]
+async fn links_without_stats(handle: Handle) -> Result<usize, rtnetlink::Error> {
+    handle
+        .link()
+        .get()
+        .set_filter_mask(AF_UNSPEC as u8, RTEXT_FILTER_SKIP_STATS)
+        .execute()
+        .try_fold(0, |count, _| async move { Ok(count + 1) })
+        .await
+}
+
+/// Dumps the addresses of `family`, [`AF_UNSPEC`] dumps the addresses of every family.
+#[cfg(target_os = "linux")]
+async fn addresses(handle: Handle, family: u16) -> Result<usize, rtnetlink::Error> {
+    let mut request = handle.address().get();
+    request.message_mut().header.family = family as u8;
+    request
+        .execute()
+        .try_fold(0, |count, _| async move { Ok(count + 1) })
+        .await
+}
+
+/// Runs `update` [`ITERATIONS`] times and prints the average duration.
+#[cfg(target_os = "linux")]
+fn measure_update<T>(name: &str, mut update: impl FnMut() -> T) -> Duration {
+    let started = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(update());
+    }
+    let average = started.elapsed() / ITERATIONS;
+    println!("{name:<40} {average:>12?}");
+    average
+}
+
+/// Creates a state with an interface which has [`ROUTES`] routes in the main table.
+#[cfg(target_os = "linux")]
+fn routed_state() -> Interfaces {
+    let mut state = Interfaces::new();
+    state.add_link(LinkInfo {
+        index: 2.into(),
+        loop_back: false,
+        carrier: true,
+        name: Some("eth0".to_owned()),
+        hardware_address: None,
+        mtu: None,
+    });
+    for route in 0..ROUTES {
+        state.add_route(RouteDetails {
+            index: 2.into(),
+            destination: IpAddr::V4(Ipv4Addr::from(route << 8)),
+            prefix_length: 24,
+            gateway: Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))),
+            priority: 100.into(),
+            table: Some(254),
+            protocol: None,
+        });
+    }
+    state
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (connection, handle, _) = rtnetlink::new_connection()?;
+    tokio::spawn(connection);
+
+    let unfiltered = measure("links", || links(handle.clone())).await?;
+    let filtered = measure("links without statistics", || {
+        links_without_stats(handle.clone())
+    })
+    .await?;
+    println!(
+        "{:<40} {:>12?}",
+        "links saved",
+        unfiltered.saturating_sub(filtered)
+    );
+
+    let unfiltered = measure("addresses of every family", || {
+        addresses(handle.clone(), AF_UNSPEC)
+    })
+    .await?;
+    let filtered =
+        measure("addresses of ipv4 and ipv6", || {
+            let handle = handle.clone();
+            async move {
+                Ok(addresses(handle.clone(), AF_INET).await? + addresses(handle, AF_INET6).await?)
+            }
+        })
+        .await?;
+    println!(
+        "{:<40} {:>12?}",
+        "addresses saved",
+        unfiltered.saturating_sub(filtered)
+    );
+
+    // a repeated route used to clone the state for a new snapshot, now it is recognized as unchanged and nothing is published
+    let mut state = routed_state();
+    let repeated = state.routes()[0];
+    let published = measure_update("repeated route publishing a snapshot", || {
+        state.add_route(repeated);
+        state.clone()
+    });
+    let recognized = measure_update("repeated route recognized unchanged", || {
+        state.add_route(repeated)
+    });
+    println!(
+        "{:<40} {:>12?}",
+        "update saved",
+        published.saturating_sub(recognized)
+    );
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    println!("the initial dump benchmark requires rtnetlink on linux");
+}
@@ -0,0 +1,240 @@
+// SPDX-License-Identifier: MIT
+
+//! Detects ipv4 address conflicts by watching the address resolution packets received on every interface.
+//!
+//! Linux does not perform duplicate address detection for ipv4 and does not learn a neighbor for one of its own addresses,
+//! so a gratuitous arp or arp reply of another host claiming an address of this system is only visible on a packet socket.
+//! Such a socket requires `CAP_NET_RAW`.
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    state::{HardwareAddress, InterfaceId, Interfaces},
+};
+use core::{
+    mem::size_of,
+    ptr::{addr_of, addr_of_mut},
+};
+use futures::Future;
+use hashbrown::{HashMap, HashSet};
+use log::{debug, warn};
+use std::{
+    error::Error,
+    io,
+    net::{IpAddr, Ipv4Addr},
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd},
+};
+use tokio::{io::unix::AsyncFd, sync::watch};
+
+/// The hardware type of ethernet in an arp packet
+const ETHERNET: u16 = 1;
+/// The protocol type of ipv4 in an arp packet
+const IPV4: u16 = 0x0800;
+
+/// The addresses of this system, a claim of another host for any of them is a conflict.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+struct Owned {
+    /// The ipv4 addresses with the interface they are assigned to
+    addresses: HashMap<Ipv4Addr, InterfaceId>,
+    /// The hardware addresses of every interface, this system claims its own addresses with any of them
+    hardware_addresses: HashSet<HardwareAddress>,
+}
+impl Owned {
+    /// Collects the addresses of this system from `state`.
+    fn of(state: &Interfaces) -> Self {
+        let mut owned = Self::default();
+        for interface in state.snapshot() {
+            for &(address, _) in &interface.addresses {
+                if let IpAddr::V4(address) = address {
+                    owned.addresses.insert(address, interface.index);
+                }
+            }
+            if let Some(hardware_address) = interface.hardware_address {
+                owned.hardware_addresses.insert(hardware_address);
+            }
+        }
+        owned
+    }
+}
+
+/// An ipv4 address claimed by a host in a received arp packet.
+struct Claim {
+    /// The claimed address
+    address: Ipv4Addr,
+    /// The hardware address of the claiming host
+    hardware_address: HardwareAddress,
+}
+
+/// Parses the sender of an ethernet arp packet for ipv4, [`None`] for any other packet.
+fn parse(packet: &[u8]) -> Option<Claim> {
+    let header = packet.get(..8)?;
+    let (hardware_type, protocol_type) = (
+        u16::from_be_bytes([*header.first()?, *header.get(1)?]),
+        u16::from_be_bytes([*header.get(2)?, *header.get(3)?]),
+    );
+    if hardware_type != ETHERNET || protocol_type != IPV4 || header.get(4..6)? != [6, 4] {
+        return None;
+    }
+    let hardware_address = packet.get(8..14)?.to_vec();
+    let address = Ipv4Addr::from(<[u8; 4]>::try_from(packet.get(14..18)?).ok()?);
+    Some(Claim {
+        address,
+        hardware_address,
+    })
+}
+
+/// A packet socket which receives the arp packets of every interface.
+struct Socket(AsyncFd<OwnedFd>);
+impl Socket {
+    /// Creates the socket.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the socket could not be created, usually because the process lacks `CAP_NET_RAW`.
+    fn new() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let protocol = i32::from(u16::try_from(libc::ETH_P_ARP)?.to_be());
+        // SAFETY:
+        // the descriptor is owned from here on and closed on drop
+        let descriptor = unsafe {
+            let descriptor = libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_DGRAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                protocol,
+            );
+            if descriptor < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            OwnedFd::from_raw_fd(descriptor)
+        };
+        Ok(Self(AsyncFd::new(descriptor)?))
+    }
+
+    /// Receives the next arp packet for ipv4 which was not sent by this system.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if receiving failed.
+    async fn recv(&self) -> Result<Claim, Box<dyn Error + Send + Sync>> {
+        let mut buffer = [0_u8; 64];
+        loop {
+            let mut guard = self.0.readable().await?;
+            let received = guard.try_io(|descriptor| {
+                // SAFETY:
+                // an all zero address is valid and overwritten by the kernel
+                let mut address: libc::sockaddr_ll = unsafe { core::mem::zeroed() };
+                let mut length = libc::socklen_t::try_from(size_of::<libc::sockaddr_ll>())
+                    .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+                // SAFETY:
+                // the buffer and the address are valid for their length
+                let read = unsafe {
+                    libc::recvfrom(
+                        descriptor.as_raw_fd(),
+                        buffer.as_mut_ptr().cast(),
+                        buffer.len(),
+                        0,
+                        addr_of_mut!(address).cast(),
+                        &mut length,
+                    )
+                };
+                let read = usize::try_from(read).map_err(|_| io::Error::last_os_error())?;
+                // SAFETY:
+                // the kernel wrote the address
+                let packet_type = unsafe { *addr_of!(address.sll_pkttype) };
+                Ok((read, packet_type))
+            });
+            match received {
+                Ok(Ok((read, packet_type))) => {
+                    if i32::from(packet_type) == i32::from(libc::PACKET_OUTGOING) {
+                        continue;
+                    }
+                    if let Some(claim) = buffer.get(..read).and_then(parse) {
+                        return Ok(claim);
+                    }
+                }
+                Ok(Err(error)) => return Err(error.into()),
+                Err(_) => continue,
+            }
+        }
+    }
+}
+
+/// The part of the conflict detection which lives in the emitter.
+pub(crate) struct Watch {
+    /// The addresses of this system
+    owned: watch::Sender<Owned>,
+}
+impl Watch {
+    /// Creates the [`Watch`] and the watcher which reports conflicts and its failure to `diagnostics`.
+    ///
+    /// The watcher completes when the [`Watch`] is dropped.
+    pub(crate) fn new(diagnostics: Diagnostics) -> (Self, impl Future<Output = ()>) {
+        let (owned, owned_rx) = watch::channel(Owned::default());
+        let watcher = async move {
+            if let Err(error) = run(owned_rx, &diagnostics).await {
+                warn!("watching for address conflicts failed {error}");
+                diagnostics.report(Diagnostic::ConflictWatchFailed {
+                    error: error.to_string(),
+                });
+            }
+        };
+        (Self { owned }, watcher)
+    }
+
+    /// Updates the addresses of this system from `state`.
+    pub(crate) fn update(&self, state: &Interfaces) {
+        let owned = Owned::of(state);
+        self.owned.send_if_modified(|known| {
+            let modified = *known != owned;
+            *known = owned;
+            modified
+        });
+    }
+}
+
+/// Reports every host which claims an address of this system, once per address and host while the address is assigned.
+///
+/// # Notes
+///
+/// This function completes when the [`Watch`] is dropped.
+///
+/// # Errors
+///
+/// This function will return an error if the packet socket could not be created or receiving failed.
+async fn run(
+    mut owned: watch::Receiver<Owned>,
+    diagnostics: &Diagnostics,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let socket = Socket::new()?;
+    let mut reported = HashSet::new();
+    loop {
+        #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+        {
+            tokio::select! {
+                claim = socket.recv() => {
+                    let claim = claim?;
+                    let owned = owned.borrow();
+                    let index = match owned.addresses.get(&claim.address) {
+                        Some(&index) if !owned.hardware_addresses.contains(&claim.hardware_address) => index,
+                        _ => continue,
+                    };
+                    if reported.insert((claim.address, claim.hardware_address.clone())) {
+                        warn!("{} of interface {index} is claimed by another host", claim.address);
+                        diagnostics.report(Diagnostic::AddressConflict {
+                            index,
+                            address: IpAddr::V4(claim.address),
+                            hardware_address: Some(claim.hardware_address),
+                        });
+                    }
+                },
+                changed = owned.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let owned = owned.borrow();
+                    reported.retain(|&(address, _)| owned.addresses.contains_key(&address));
+                },
+            }
+        }
+    }
+    debug!("no more address conflict watchers");
+    Ok(())
+}
@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: MIT
+
+//! Selects at runtime which platform listener the drivers of the process share.
+
+use crate::shared::{Listener, Platform};
+use core::fmt::Display;
+use log::debug;
+use std::{
+    error::Error,
+    io::{self, ErrorKind},
+    sync::{Mutex, PoisonError},
+};
+
+/// The backends in the order they are tried, the native backend of the system when empty
+static SELECTED: Mutex<Vec<Backend>> = Mutex::new(Vec::new());
+
+/// A platform listener which can be selected with [`select_backends`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum Backend {
+    /// Notified of changes through a netlink socket
+    #[cfg(target_os = "linux")]
+    Netlink,
+    /// Notified of changes through `NotifyIpInterfaceChange`
+    #[cfg(target_os = "windows")]
+    IpHelper,
+    /// Notified of changes through a `PF_ROUTE` routing socket, after which the system is sampled like [polling](Self::Polling) does
+    #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+    RoutingSocket,
+    /// Polls the system with `getifaddrs` and `netstat -rn`, changes are reported up to 5 seconds late
+    #[cfg(unix)]
+    Polling,
+    /// Notified of changes through the signals of NetworkManager on the system bus, after which the system is sampled like [polling](Self::Polling) does.
+    ///
+    /// The verdict of the connectivity check of NetworkManager limits the connectivity, so captive portals and networks without internet access are reported like NetworkManager sees them.
+    #[cfg(all(feature = "nm", target_os = "linux"))]
+    NetworkManager,
+}
+impl Backend {
+    /// The backend which is notified of changes by the system, it is used when no backends are selected.
+    #[allow(clippy::must_use_candidate)]
+    pub const fn native() -> Self {
+        cfg_if::cfg_if! {
+            if #[cfg(target_os = "linux")] {
+                Self::Netlink
+            } else if #[cfg(target_os = "windows")] {
+                Self::IpHelper
+            } else if #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))] {
+                Self::RoutingSocket
+            } else if #[cfg(unix)] {
+                Self::Polling
+            } else {
+                compile_error!("This crate has no implementation for this configuration.");
+            }
+        }
+    }
+
+    /// Checks whether the process may use this backend, so missing permissions are reported when a driver is built.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the system refused the resources of this backend.
+    fn preflight(self) -> io::Result<()> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Self::Netlink => crate::linux::preflight(),
+            #[cfg(all(feature = "nm", target_os = "linux"))]
+            Self::NetworkManager => crate::nm::preflight(),
+            #[allow(unreachable_patterns)]
+            _ => Ok(()),
+        }
+    }
+
+    /// Creates the platform listener of this backend.
+    pub(crate) fn listen(self, listener: &Listener) -> Platform<'_> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Self::Netlink => crate::linux::listen(listener),
+            #[cfg(target_os = "windows")]
+            Self::IpHelper => crate::windows::listen(listener),
+            #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+            Self::RoutingSocket => crate::bsd::listen(listener),
+            #[cfg(unix)]
+            Self::Polling => crate::unix::listen(listener),
+            #[cfg(all(feature = "nm", target_os = "linux"))]
+            Self::NetworkManager => crate::nm::listen(listener),
+        }
+    }
+}
+impl Display for Backend {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            #[cfg(target_os = "linux")]
+            Self::Netlink => write!(f, "netlink"),
+            #[cfg(target_os = "windows")]
+            Self::IpHelper => write!(f, "ip helper"),
+            #[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+            Self::RoutingSocket => write!(f, "routing socket"),
+            #[cfg(unix)]
+            Self::Polling => write!(f, "polling"),
+            #[cfg(all(feature = "nm", target_os = "linux"))]
+            Self::NetworkManager => write!(f, "networkmanager"),
+        }
+    }
+}
+
+/// The error returned when building a driver while none of the [selected backends](select_backends) can be used by the process.
+///
+/// On linux this happens when netlink is restricted, for example by a seccomp profile of a container runtime or within a user namespace without network access.
+/// Grant the process access to `NETLINK_ROUTE` sockets, for example by allowing the `socket` and `bind` system calls for `AF_NETLINK` in the seccomp profile.
+/// It can be told apart from other errors of a build with `downcast_ref`.
+#[derive(Debug)]
+pub struct BackendUnavailable {
+    /// The backend which was tried first
+    backend: Backend,
+    /// Why the system refused it
+    error: io::Error,
+}
+impl BackendUnavailable {
+    /// Get the backend which was tried first
+    #[allow(clippy::must_use_candidate)]
+    pub const fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Whether the system refused the backend for lack of permissions rather than lack of support
+    #[allow(clippy::must_use_candidate)]
+    pub fn is_permission_denied(&self) -> bool {
+        self.error.kind() == ErrorKind::PermissionDenied
+    }
+}
+impl Display for BackendUnavailable {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "the {} backend is not available: {}",
+            self.backend, self.error
+        )?;
+        if self.is_permission_denied() {
+            write!(
+                f,
+                ", the process is not permitted to use it, which is usually caused by a seccomp profile or a user namespace"
+            )?;
+        }
+        Ok(())
+    }
+}
+impl Error for BackendUnavailable {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Checks whether any of the selected backends can be used by the process.
+///
+/// # Errors
+///
+/// This function will return an error for the first backend if none of them can be used.
+pub(crate) fn preflight() -> Result<(), BackendUnavailable> {
+    preflight_of(&selected())
+}
+
+/// Checks whether any of `backends` can be used by the process.
+///
+/// The backends after the first usable one are only fallbacks, so they are not checked.
+///
+/// # Errors
+///
+/// This function will return an error for the first backend if none of them can be used.
+fn preflight_of(backends: &[Backend]) -> Result<(), BackendUnavailable> {
+    let mut unavailable = None;
+    for &backend in backends {
+        match backend.preflight() {
+            Ok(()) => return Ok(()),
+            Err(error) => {
+                debug!("the {backend} backend is not available {error}");
+                unavailable.get_or_insert(BackendUnavailable { backend, error });
+            }
+        }
+    }
+    unavailable.map_or(Ok(()), Err)
+}
+
+/// Selects the backends of the platform listener shared by all drivers of the process, in the order they are tried.
+///
+/// When a backend fails the next one is used, so a preferred integration can fall back to one that is always available.
+/// A running platform listener is restarted with the first backend, the drivers keep running and report
+/// [`Diagnostic::Resynchronized`](crate::Diagnostic::Resynchronized).
+/// Selecting no backends uses the [native](Backend::native) one.
+///
+/// # Errors
+///
+/// This function will return a [`BackendUnavailable`] error if the process may not use any of `backends`,
+/// the selected backends and the running platform listener are left as they are then.
+pub fn select_backends(backends: &[Backend]) -> Result<(), BackendUnavailable> {
+    let native = [Backend::native()];
+    preflight_of(if backends.is_empty() {
+        &native
+    } else {
+        backends
+    })?;
+    *SELECTED.lock().unwrap_or_else(PoisonError::into_inner) = backends.to_vec();
+    Listener::restart_shared();
+    Ok(())
+}
+
+/// The selected backends in the order they are tried, never empty
+pub(crate) fn selected() -> Vec<Backend> {
+    let selected = SELECTED.lock().unwrap_or_else(PoisonError::into_inner);
+    if selected.is_empty() {
+        vec![Backend::native()]
+    } else {
+        selected.clone()
+    }
+}
@@ -0,0 +1,330 @@
+// SPDX-License-Identifier: MIT
+
+//! Shares the connectivity with local processes.
+//!
+//! Usage: `connectivityd [--config FILE] [--dispatch COMMAND]... [--service] [ENDPOINT]`
+//! or `connectivityd [--config FILE] debug capture FILE`
+//!
+//! The endpoint is the path of a unix socket on unix or the name of a named pipe on windows.
+//! With the `service` feature `--service` runs the daemon as the windows service `connectivityd`, see `network_connectivity::service`.
+//! Every `--dispatch` adds a command to run when the connectivity, the network or the mtu changes or a captive portal is detected.
+//!
+//! `debug capture` does not share the events but records the snapshots of the system and the resulting events to a file until it is terminated,
+//! which can be attached to a report of a misdetection, see `Builder::capture`.
+//!
+//! With the `config` feature `--config` loads a toml configuration file, see `network_connectivity::config`.
+//! The driver is further configured by the `CONNECTIVITY_*` environment variables, see `Builder::env`.
+//! The command line arguments take precedence over the environment which takes precedence over the configuration file.
+//! `CONNECTIVITY_LOG` sets the log filter in the syntax of `env_logger`, see `Builder::log_filter`, `RUST_LOG` is used when it is not set.
+
+use env_logger::Env;
+use log::info;
+use network_connectivity::{Builder, Dispatcher};
+use std::{env, error::Error, ffi::OsString};
+
+/// The path of the unix socket when none is given
+#[cfg(unix)]
+const DEFAULT_SOCKET_PATH: &str = "/run/connectivityd.sock";
+
+/// The name of the named pipe when none is given
+#[cfg(windows)]
+const DEFAULT_PIPE_NAME: &str = r"\\.\pipe\connectivityd";
+
+/// The name of the windows service
+#[cfg(all(windows, feature = "service"))]
+const SERVICE_NAME: &str = "connectivityd";
+
+/// Initializes the logger with `filter` or otherwise the one from `RUST_LOG`, logging info and above when neither is set.
+fn init_logger(filter: Option<&str>) {
+    match filter {
+        Some(filter) => env_logger::Builder::new().parse_filters(filter).init(),
+        None => env_logger::Builder::from_env(Env::default().default_filter_or("info")).init(),
+    }
+}
+
+/// The parsed command line arguments
+struct Arguments {
+    /// The endpoint to listen on
+    endpoint: Option<OsString>,
+    /// The commands to run on events
+    dispatch: Vec<OsString>,
+    /// The path of the configuration file
+    config: Option<OsString>,
+    /// The path of the file to capture to instead of sharing the events
+    capture: Option<OsString>,
+    /// Whether to run as a windows service
+    #[cfg(all(windows, feature = "service"))]
+    service: bool,
+}
+
+/// Parses the command line arguments.
+///
+/// # Errors
+///
+/// This function will return an error if the arguments are invalid.
+fn arguments() -> Result<Arguments, Box<dyn Error + Send + Sync>> {
+    let mut arguments = Arguments {
+        endpoint: None,
+        dispatch: Vec::new(),
+        config: None,
+        capture: None,
+        #[cfg(all(windows, feature = "service"))]
+        service: false,
+    };
+    let mut args = env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        #[cfg(all(windows, feature = "service"))]
+        if arg == "--service" {
+            arguments.service = true;
+            continue;
+        }
+        if arg == "--dispatch" {
+            let command = args.next().ok_or("--dispatch requires a command")?;
+            arguments.dispatch.push(command);
+        } else if arg == "--config" {
+            let path = args.next().ok_or("--config requires a file")?;
+            arguments.config = Some(path);
+        } else if arg == "debug" {
+            if args.next().map_or(true, |command| command != "capture") {
+                return Err("debug requires the capture command".into());
+            }
+            let path = args.next().ok_or("debug capture requires a file")?;
+            arguments.capture = Some(path);
+        } else if arguments.endpoint.is_none() {
+            arguments.endpoint = Some(arg);
+        } else {
+            return Err(format!("unexpected argument {arg:?}").into());
+        }
+    }
+    Ok(arguments)
+}
+
+/// Loads the configuration file when given and applies the environment and the command line arguments on top of it.
+///
+/// The logger is initialized with the log filter of the environment.
+/// Returns the configured endpoint and the builder of the driver.
+///
+/// # Errors
+///
+/// This function will return an error if the configuration file or the environment is invalid.
+fn configure(
+    arguments: Arguments,
+) -> Result<(Option<OsString>, Builder), Box<dyn Error + Send + Sync>> {
+    let (mut endpoint, mut commands, builder) = match arguments.config {
+        #[cfg(feature = "config")]
+        Some(path) => {
+            let config = network_connectivity::config::Config::load(path)?;
+            let commands = config.dispatch.iter().map(OsString::from).collect();
+            (
+                config.endpoint.clone().map(OsString::from),
+                commands,
+                config.builder()?,
+            )
+        }
+        #[cfg(not(feature = "config"))]
+        Some(_) => return Err("--config requires the config feature".into()),
+        None => (None, Vec::new(), Builder::new()),
+    };
+    if arguments.endpoint.is_some() {
+        endpoint = arguments.endpoint;
+    }
+    commands.extend(arguments.dispatch);
+    let mut builder = builder.env()?;
+    init_logger(builder.log_filter());
+    if !commands.is_empty() {
+        builder = builder.dispatcher(
+            commands
+                .into_iter()
+                .fold(Dispatcher::new(), Dispatcher::command),
+        );
+    }
+    Ok((endpoint, builder))
+}
+
+/// Records the snapshots of the system and the events of the driver configured by `builder` to the file at `path` until the process is asked to terminate.
+///
+/// The events are logged as well, so the capture can be followed while reproducing the problem.
+///
+/// # Errors
+///
+/// This function will return an error if the file could not be created or the driver failed.
+#[cfg(any(unix, windows))]
+async fn capture(path: OsString, builder: Builder) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let file = std::fs::File::create(&path)
+        .map_err(|error| format!("creating capture {path:?} failed: {error}"))?;
+    info!("capturing to {path:?}");
+    let (driver, mut events) = builder
+        .capture(std::io::LineWriter::new(file))
+        .build_detailed()?;
+    let log = async {
+        while let Some(event) = events.recv().await {
+            info!("{event:?}");
+        }
+        Ok(())
+    };
+    let result = tokio::select! {
+        result = driver => result,
+        result = log => result,
+        result = terminated() => result,
+    };
+    info!("capture stopped");
+    result
+}
+
+/// Completes when the process is asked to terminate.
+#[cfg(windows)]
+async fn terminated() -> Result<(), Box<dyn Error + Send + Sync>> {
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+/// Completes when the process is asked to terminate.
+#[cfg(unix)]
+async fn terminated() -> Result<(), Box<dyn Error + Send + Sync>> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => (),
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    use std::{fs, io::ErrorKind, path::PathBuf};
+    use tokio::net::UnixListener;
+
+    let mut arguments = arguments()?;
+    let capture_path = arguments.capture.take();
+    let (endpoint, builder) = configure(arguments)?;
+    if let Some(path) = capture_path {
+        return capture(path, builder).await;
+    }
+    let path = endpoint.map_or_else(|| PathBuf::from(DEFAULT_SOCKET_PATH), PathBuf::from);
+    match fs::remove_file(&path) {
+        Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+        _ => {}
+    }
+    let listener = UnixListener::bind(&path)?;
+    info!("listening on {}", path.display());
+
+    let (driver, events) = builder.build_detailed()?;
+    let result = tokio::select! {
+        result = driver => result,
+        result = network_connectivity::daemon::serve_unix(listener, events) => result,
+        result = terminated() => result,
+    };
+    info!("shutting down");
+    fs::remove_file(&path)?;
+    result
+}
+
+/// Gets the name of the named pipe from the configured `endpoint`.
+///
+/// # Errors
+///
+/// This function will return an error if the endpoint is not valid unicode.
+#[cfg(windows)]
+fn pipe_name(endpoint: Option<OsString>) -> Result<String, Box<dyn Error + Send + Sync>> {
+    match endpoint {
+        Some(endpoint) => Ok(endpoint
+            .into_string()
+            .map_err(|endpoint| format!("invalid pipe name {endpoint:?}"))?),
+        None => Ok(DEFAULT_PIPE_NAME.to_owned()),
+    }
+}
+
+/// Creates the runtime the daemon runs in.
+///
+/// # Errors
+///
+/// This function will return an error if the runtime could not be created.
+#[cfg(windows)]
+fn runtime() -> Result<tokio::runtime::Runtime, Box<dyn Error + Send + Sync>> {
+    Ok(tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?)
+}
+
+/// Runs the daemon as a windows service until the service control manager stops it.
+///
+/// While the service is paused the driver keeps running but its events are only shared once the service is continued.
+///
+/// # Errors
+///
+/// This function will return an error if the configuration is invalid or the driver or the named pipe failed.
+#[cfg(all(windows, feature = "service"))]
+async fn serve_service(
+    arguments: Arguments,
+    mut controls: tokio::sync::watch::Receiver<network_connectivity::service::Control>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    use network_connectivity::service::Control;
+
+    let (endpoint, builder) = configure(arguments)?;
+    let name = pipe_name(endpoint)?;
+    info!("listening on {name} as service {SERVICE_NAME}");
+
+    let (driver, events) = builder.build_detailed()?;
+    let serve = network_connectivity::daemon::serve_named_pipe(&name, events);
+    futures::pin_mut!(driver, serve);
+    let mut paused = false;
+    let result = loop {
+        tokio::select! {
+            result = &mut driver => break result,
+            result = &mut serve, if !paused => break result,
+            changed = controls.changed() => {
+                if changed.is_err() {
+                    break Ok(());
+                }
+                let control = *controls.borrow_and_update();
+                match control {
+                    Control::Stopped => break Ok(()),
+                    Control::Paused => info!("pausing"),
+                    _ => info!("continuing"),
+                }
+                paused = control == Control::Paused;
+            }
+        }
+    };
+    info!("shutting down");
+    result
+}
+
+#[cfg(windows)]
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut arguments = arguments()?;
+    #[cfg(feature = "service")]
+    if arguments.service {
+        return network_connectivity::service::run(SERVICE_NAME, move |controls| {
+            runtime()?.block_on(serve_service(arguments, controls))
+        });
+    }
+    let capture_path = arguments.capture.take();
+    let (endpoint, builder) = configure(arguments)?;
+    if let Some(path) = capture_path {
+        return runtime()?.block_on(capture(path, builder));
+    }
+    let name = pipe_name(endpoint)?;
+    info!("listening on {name}");
+
+    runtime()?.block_on(async {
+        let (driver, events) = builder.build_detailed()?;
+        let result = tokio::select! {
+            result = driver => result,
+            result = network_connectivity::daemon::serve_named_pipe(&name, events) => result,
+            result = terminated() => result,
+        };
+        info!("shutting down");
+        result
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    init_logger(None);
+    info!("connectivityd is not supported on this platform");
+    Err("connectivityd is not supported on this platform".into())
+}
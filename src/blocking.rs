@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: MIT
+
+//! A driver running in a thread of its own with a blocking receiver of its connectivity updates.
+
+use crate::{Builder, Connectivity};
+use core::time::Duration;
+use std::{
+    error::Error,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread::{self, JoinHandle},
+};
+use tokio::sync::oneshot;
+
+/// The blocking receive end of the connectivity updates of a driver which runs in a thread of its own, created with [`Builder::build_blocking`].
+///
+/// It is an [`Iterator`] over the connectivity updates, so simple programs can use `for connectivity in receiver { … }`.
+/// The iteration ends when the driver completed or, with a [timeout](Self::timeout), when no update is received in time.
+///
+/// Dropping the receiver stops the driver.
+pub struct BlockingReceiver {
+    /// The thread running the driver
+    thread: JoinHandle<Result<(), Box<dyn Error + Send + Sync>>>,
+    /// The connectivity updates of the driver
+    updates: Receiver<Connectivity>,
+    /// Stops the driver when sent to or dropped
+    shutdown: oneshot::Sender<()>,
+    /// How long to wait for an update while iterating, [`None`] to wait until the driver completed
+    timeout: Option<Duration>,
+}
+impl BlockingReceiver {
+    /// Spawns the driver configured by `builder` in a thread with a runtime of its own.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the driver could not be created or the thread could not be spawned.
+    pub(crate) fn spawn(builder: Builder) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let (updates_tx, updates) = mpsc::channel();
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let (ready_tx, ready) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("network-connectivity".to_owned())
+            .spawn(move || {
+                runtime.block_on(async move {
+                    // the driver must be created within the runtime it runs on.
+                    let (driver, mut driver_updates) = match builder.build() {
+                        Ok(built) => {
+                            ready_tx.send(Ok(())).ok();
+                            built
+                        }
+                        Err(error) => {
+                            ready_tx.send(Err(error.to_string())).ok();
+                            return Err(error);
+                        }
+                    };
+                    let forward = async move {
+                        while let Some(connectivity) = driver_updates.recv().await {
+                            if updates_tx.send(connectivity).is_err() {
+                                break;
+                            }
+                        }
+                    };
+                    #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+                    let result = tokio::select! {
+                        (result, ()) = futures::future::join(driver, forward) => result,
+                        _ = shutdown_rx => Ok(()),
+                    };
+                    result
+                })
+            })?;
+        ready
+            .recv()
+            .map_err(|_| "the driver thread stopped unexpectedly")??;
+        Ok(Self {
+            thread,
+            updates,
+            shutdown,
+            timeout: None,
+        })
+    }
+
+    /// Only wait `timeout` for every update while iterating, the iteration ends when no update is received in time.
+    ///
+    /// The driver keeps running, iterating again continues with the next update.
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Blocks until the next connectivity update is received, [`None`] when the driver completed.
+    #[allow(clippy::must_use_candidate)]
+    pub fn recv(&self) -> Option<Connectivity> {
+        self.updates.recv().ok()
+    }
+
+    /// Blocks until the next connectivity update is received or `timeout` elapsed, [`None`] when the driver completed or the timeout elapsed.
+    #[allow(clippy::must_use_candidate)]
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Connectivity> {
+        match self.updates.recv_timeout(timeout) {
+            Ok(connectivity) => Some(connectivity),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    /// Whether the driver completed, after which no more updates are received
+    #[allow(clippy::must_use_candidate)]
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stops the driver and waits until its thread completed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the driver failed before it was stopped or its thread panicked.
+    pub fn shutdown(self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.shutdown.send(()).ok();
+        self.thread
+            .join()
+            .map_err(|_| "the driver thread panicked")?
+    }
+}
+impl Iterator for BlockingReceiver {
+    type Item = Connectivity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.timeout {
+            Some(timeout) => self.recv_timeout(timeout),
+            None => self.recv(),
+        }
+    }
+}
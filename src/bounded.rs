@@ -0,0 +1,248 @@
+// SPDX-License-Identifier: MIT
+
+//! A channel of detailed events with a limited capacity and a policy for events which do not fit.
+
+use crate::{Connectivity, NetworkEvent};
+use core::{
+    mem::discriminant,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, PoisonError},
+};
+use tokio::sync::Notify;
+
+/// What happens to an event which is sent while the bounded channel is full, see [`Builder::build_bounded`](crate::Builder::build_bounded).
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum OverflowPolicy {
+    /// The oldest queued event is dropped to make room for the new event
+    DropOldest,
+    /// The new event is dropped
+    DropNewest,
+    /// The queued event of the same kind is dropped and the new event is queued at the end,
+    /// the oldest queued event is dropped when there is none of the same kind
+    Conflate,
+}
+
+/// The state shared by both ends of the channel.
+struct Queue {
+    /// The queued events
+    events: Mutex<VecDeque<NetworkEvent>>,
+    /// The amount of events that can be queued
+    capacity: usize,
+    /// What happens to an event which is sent while the queue is full
+    policy: OverflowPolicy,
+    /// The amount of events which were dropped because the queue was full
+    dropped: AtomicU64,
+    /// The amount of events which were dropped since the receive end was told with [`NetworkEvent::Lagged`]
+    lagged: AtomicU64,
+    /// The latest connectivity which was sent, also when its event was dropped
+    connectivity: Mutex<Option<Connectivity>>,
+    /// The amount of transmit ends
+    senders: AtomicUsize,
+    /// Whether all transmit ends are dropped
+    sender_dropped: AtomicBool,
+    /// Whether the receive end is dropped
+    receiver_dropped: AtomicBool,
+    /// Notified when an event is queued or the transmit end is dropped
+    sent: Notify,
+    /// Notified when the receive end is dropped
+    closed: Notify,
+}
+
+/// Creates a bounded channel which queues at most `capacity` events and handles events that do not fit according to `policy`.
+pub(crate) fn channel(capacity: usize, policy: OverflowPolicy) -> (BoundedSender, BoundedReceiver) {
+    let queue = Arc::new(Queue {
+        events: Mutex::new(VecDeque::with_capacity(capacity)),
+        capacity,
+        policy,
+        dropped: AtomicU64::new(0),
+        lagged: AtomicU64::new(0),
+        connectivity: Mutex::new(None),
+        senders: AtomicUsize::new(1),
+        sender_dropped: AtomicBool::new(false),
+        receiver_dropped: AtomicBool::new(false),
+        sent: Notify::new(),
+        closed: Notify::new(),
+    });
+    (BoundedSender(Arc::clone(&queue)), BoundedReceiver(queue))
+}
+
+/// The transmit end of a bounded channel.
+pub(crate) struct BoundedSender(Arc<Queue>);
+impl BoundedSender {
+    /// Queues `event`, dropping an event according to the overflow policy when the channel is full.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the receive end of the channel was dropped.
+    pub(crate) fn send(&self, event: NetworkEvent) -> Result<(), &'static str> {
+        let queue = &self.0;
+        if queue.receiver_dropped.load(Ordering::Acquire) {
+            return Err("the receive end of the bounded channel was dropped");
+        }
+        if let NetworkEvent::ConnectivityChanged { connectivity, .. } = event {
+            *queue
+                .connectivity
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(connectivity);
+        }
+        let mut events = queue.events.lock().unwrap_or_else(PoisonError::into_inner);
+        if events.len() >= queue.capacity {
+            match queue.policy {
+                OverflowPolicy::DropOldest => {
+                    events.pop_front();
+                }
+                OverflowPolicy::DropNewest => {
+                    queue.dropped.fetch_add(1, Ordering::Relaxed);
+                    queue.lagged.fetch_add(1, Ordering::Relaxed);
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!("network_connectivity_dropped_events_total", 1);
+                    return Ok(());
+                }
+                OverflowPolicy::Conflate => {
+                    let kind = discriminant(&event);
+                    match events
+                        .iter()
+                        .position(|queued| discriminant(queued) == kind)
+                    {
+                        Some(position) => {
+                            events.remove(position);
+                        }
+                        None => {
+                            events.pop_front();
+                        }
+                    }
+                }
+            }
+            queue.dropped.fetch_add(1, Ordering::Relaxed);
+            queue.lagged.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "metrics")]
+            metrics::counter!("network_connectivity_dropped_events_total", 1);
+        }
+        events.push_back(event);
+        drop(events);
+        queue.sent.notify_one();
+        Ok(())
+    }
+
+    /// Completes when the receive end of the channel is dropped.
+    pub(crate) async fn closed(&self) {
+        while !self.0.receiver_dropped.load(Ordering::Acquire) {
+            self.0.closed.notified().await;
+        }
+    }
+}
+impl Clone for BoundedSender {
+    fn clone(&self) -> Self {
+        self.0.senders.fetch_add(1, Ordering::Relaxed);
+        Self(Arc::clone(&self.0))
+    }
+}
+impl Drop for BoundedSender {
+    fn drop(&mut self) {
+        // the receive end only completes when the last transmit end is dropped.
+        if self.0.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.sender_dropped.store(true, Ordering::Release);
+            self.0.sent.notify_one();
+        }
+    }
+}
+
+/// The receive end of a bounded channel of detailed events, created with [`Builder::build_bounded`](crate::Builder::build_bounded).
+pub struct BoundedReceiver(Arc<Queue>);
+impl BoundedReceiver {
+    /// Receives the next detailed event, [`None`] when the driver completed and all queued events are received.
+    ///
+    /// When events were dropped since the previous event was received [`NetworkEvent::Lagged`] is received first,
+    /// carrying the amount of dropped events and the latest connectivity to resynchronize with.
+    pub async fn recv(&mut self) -> Option<NetworkEvent> {
+        loop {
+            {
+                let mut events = self.0.events.lock().unwrap_or_else(PoisonError::into_inner);
+                // the events are dropped while the queue is locked, so the amount matches the queued events.
+                let missed = self.0.lagged.swap(0, Ordering::Relaxed);
+                if missed != 0 {
+                    let connectivity = *self
+                        .0
+                        .connectivity
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner);
+                    return Some(NetworkEvent::Lagged {
+                        missed,
+                        connectivity,
+                    });
+                }
+                if let Some(event) = events.pop_front() {
+                    return Some(event);
+                }
+            }
+            if self.0.sender_dropped.load(Ordering::Acquire) {
+                return None;
+            }
+            self.0.sent.notified().await;
+        }
+    }
+
+    /// The amount of events which were dropped because the channel was full
+    #[allow(clippy::must_use_candidate)]
+    pub fn dropped(&self) -> u64 {
+        self.0.dropped.load(Ordering::Relaxed)
+    }
+}
+impl Drop for BoundedReceiver {
+    fn drop(&mut self) {
+        self.0.receiver_dropped.store(true, Ordering::Release);
+        self.0.closed.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{channel, OverflowPolicy};
+    use crate::{Connectivity, ConnectivityState, NetworkEvent, Source};
+    use futures::FutureExt;
+
+    /// Creates the event of a change to `ipv4` connectivity.
+    fn changed(ipv4: ConnectivityState) -> NetworkEvent {
+        NetworkEvent::ConnectivityChanged {
+            connectivity: Connectivity {
+                ipv4,
+                ipv6: ConnectivityState::None,
+            },
+            previous: None,
+            duration_in_previous_state: None,
+            verified: false,
+            source: Source::PassiveRouting,
+            link_local_only: false,
+        }
+    }
+
+    #[test]
+    fn dropped_events_are_received_as_lagged() {
+        let (sender, mut receiver) = channel(1, OverflowPolicy::DropOldest);
+        sender.send(changed(ConnectivityState::None)).unwrap();
+        sender.send(changed(ConnectivityState::Network)).unwrap();
+        sender.send(changed(ConnectivityState::Internet)).unwrap();
+
+        assert_eq!(
+            receiver.recv().now_or_never().flatten(),
+            Some(NetworkEvent::Lagged {
+                missed: 2,
+                connectivity: Some(Connectivity {
+                    ipv4: ConnectivityState::Internet,
+                    ipv6: ConnectivityState::None,
+                }),
+            })
+        );
+        assert_eq!(
+            receiver.recv().now_or_never().flatten(),
+            Some(changed(ConnectivityState::Internet))
+        );
+        assert_eq!(receiver.dropped(), 2);
+        drop(sender);
+        assert_eq!(receiver.recv().now_or_never().flatten(), None);
+    }
+}
@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: MIT
+
+//! Forwards the updates of a receiver to the channel types of other ecosystems, so applications standardized on them need no forwarding task of their own.
+
+use futures::{channel::mpsc, Future, SinkExt};
+use std::error::Error;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// Forwards the connectivity updates or detailed events received from `receiver` to a [`futures::channel::mpsc`] channel,
+/// together with the future that forwards them and must be awaited.
+///
+/// Created from the receive end returned by [`Builder::build`](crate::Builder::build) or [`Builder::build_detailed`](crate::Builder::build_detailed).
+/// The channel buffers `capacity` updates, the forwarding waits while it is full and `receiver` queues the updates meanwhile.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/// use futures::StreamExt;
+/// use network_connectivity::{forward_to_futures, Builder};
+///
+/// let (driver, receiver) = Builder::new().build()?;
+/// let (forward, mut updates) = forward_to_futures(receiver, 16)?;
+/// tokio::spawn(async move {
+///     while let Some(connectivity) = updates.next().await {
+///         println!("{connectivity:?}");
+///     }
+/// });
+/// futures::future::join(driver, forward).await.0?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Notes
+///
+/// The future completes when `receiver` closed, after which the channel is closed as well.
+/// It also completes at the next update once the returned receiver is dropped, which drops `receiver` so the driver runs to completion.
+///
+/// # Errors
+///
+/// This function will return an error if `capacity` is zero.
+pub fn forward_to_futures<T>(
+    mut receiver: UnboundedReceiver<T>,
+    capacity: usize,
+) -> Result<(impl Future<Output = ()>, mpsc::Receiver<T>), Box<dyn Error + Send + Sync>> {
+    if capacity == 0 {
+        return Err("the capacity of a futures channel must be at least one".into());
+    }
+    // the channel buffers one message per sender on top of its buffer
+    let (mut tx, rx) = mpsc::channel(capacity.saturating_sub(1));
+    let forward = async move {
+        while let Some(update) = receiver.recv().await {
+            if tx.send(update).await.is_err() {
+                break;
+            }
+        }
+    };
+    Ok((forward, rx))
+}
+
+/// Forwards the connectivity updates or detailed events received from `receiver` to an unbounded [`crossbeam_channel`] channel,
+/// together with the future that forwards them and must be awaited.
+///
+/// Created from the receive end returned by [`Builder::build`](crate::Builder::build) or [`Builder::build_detailed`](crate::Builder::build_detailed).
+/// The receiver can be cloned and used from threads which do not run async code, for example in a `select!` over other crossbeam channels.
+/// The channel is unbounded like `receiver`, since the forwarding can not wait for room without blocking the runtime.
+///
+/// # Notes
+///
+/// The future completes when `receiver` closed, after which the channel is disconnected as well.
+/// It also completes at the next update once every clone of the returned receiver is dropped, which drops `receiver` so the driver runs to completion.
+#[cfg(feature = "crossbeam")]
+pub fn forward_to_crossbeam<T>(
+    mut receiver: UnboundedReceiver<T>,
+) -> (impl Future<Output = ()>, crossbeam_channel::Receiver<T>) {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let forward = async move {
+        while let Some(update) = receiver.recv().await {
+            if tx.send(update).is_err() {
+                break;
+            }
+        }
+    };
+    (forward, rx)
+}
@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: MIT
+
+//! The bsd implementation for this crate using a `PF_ROUTE` routing socket.
+//!
+//! The kernel sends a message on the routing socket for every change of the interfaces, addresses and routes.
+//! Whenever such a message arrives the system is sampled like the [polling](crate::unix) backend does,
+//! so changes are reported right away instead of up to five seconds late.
+
+use crate::shared::{Listener, Platform};
+use log::debug;
+use std::{
+    error::Error,
+    io,
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd},
+};
+use tokio::io::unix::AsyncFd;
+
+/// A routing socket which receives the messages about changes of the system.
+struct RoutingSocket(AsyncFd<OwnedFd>);
+impl RoutingSocket {
+    /// Opens the routing socket for the messages of every address family.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the socket could not be opened.
+    fn new() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        // SAFETY:
+        // the descriptor is owned from here on and closed on drop
+        let descriptor = unsafe {
+            let descriptor = libc::socket(
+                libc::PF_ROUTE,
+                libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                libc::AF_UNSPEC,
+            );
+            if descriptor < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            OwnedFd::from_raw_fd(descriptor)
+        };
+        Ok(Self(AsyncFd::new(descriptor)?))
+    }
+
+    /// Completes when a message about a change of the system was received.
+    ///
+    /// The messages which are received already are read as well, so a burst of changes is sampled once.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the messages could not be read.
+    async fn changed(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut buffer = [0_u8; 2048];
+        let mut changed = false;
+        loop {
+            let mut guard = self.0.readable().await?;
+            while let Ok(result) = guard.try_io(|descriptor| {
+                // SAFETY:
+                // the buffer is valid for its length
+                let read = unsafe {
+                    libc::read(
+                        descriptor.as_raw_fd(),
+                        buffer.as_mut_ptr().cast(),
+                        buffer.len(),
+                    )
+                };
+                usize::try_from(read).map_err(|_| io::Error::last_os_error())
+            }) {
+                changed |= is_change(buffer.get(..result?).unwrap_or_default());
+            }
+            if changed {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Whether a routing message is about a change of the interfaces, addresses or routes.
+///
+/// Every routing message starts with its length in two bytes, its version and its type.
+fn is_change(message: &[u8]) -> bool {
+    message.get(3).map_or(false, |&kind| {
+        matches!(
+            libc::c_int::from(kind),
+            libc::RTM_ADD
+                | libc::RTM_DELETE
+                | libc::RTM_CHANGE
+                | libc::RTM_NEWADDR
+                | libc::RTM_DELADDR
+                | libc::RTM_IFINFO
+                | libc::RTM_IFANNOUNCE
+        )
+    })
+}
+
+/// Creates the platform listener which publishes snapshots of the system to the shared [listener](Listener).
+///
+/// # Notes
+///
+/// The platform listener never completes by itself.
+///
+/// # Errors
+///
+/// The platform listener fails when the routing socket could not be opened or read or the system could not be sampled.
+pub fn listen(listener: &Listener) -> Platform<'_> {
+    Box::pin(run(listener))
+}
+
+/// Samples the system and publishes a snapshot to the shared [listener](Listener) whenever the routing socket received a change.
+///
+/// # Errors
+///
+/// This function will return an error if the routing socket could not be opened or read or the system could not be sampled.
+async fn run(listener: &Listener) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // the socket is opened before the initial sample, so no change in between is missed.
+    debug!("opening routing socket");
+    let socket = RoutingSocket::new()?;
+    crate::unix::publish_on(listener, || socket.changed()).await
+}
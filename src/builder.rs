@@ -0,0 +1,1038 @@
+// SPDX-License-Identifier: MIT
+
+//! The builder to configure the driver of this crate.
+
+use crate::{
+    bounded::{BoundedReceiver, OverflowPolicy},
+    diagnostic::Diagnostics,
+    driver,
+    emitter::{Emitter, Sender},
+    state::{DormantPolicy, HardwareAddress, InterfaceType},
+    Connectivity, ConnectivityState, Diagnostic, Dispatcher, InterfaceNames, Inventory, IpFamily,
+    NetworkEvent, RoutingInfo, Store,
+};
+#[cfg(feature = "probe")]
+use crate::{
+    probe::{Probes, Verification, PROBE_TIMEOUT},
+    HttpProbe, Preset, Probe,
+};
+use core::pin::Pin;
+use core::time::Duration;
+use futures::{future::join_all, Future};
+use std::{env, error::Error, net::IpAddr};
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    watch,
+};
+
+/// The environment variable with the comma separated urls of endpoints which respond with status 204 to probe
+const PROBE_URL_VARIABLE: &str = "CONNECTIVITY_PROBE_URL";
+/// The environment variable with the debounce interval in milliseconds
+const DEBOUNCE_VARIABLE: &str = "CONNECTIVITY_DEBOUNCE";
+/// The environment variable with the comma separated names of the interfaces to ignore
+const IGNORE_INTERFACES_VARIABLE: &str = "CONNECTIVITY_IGNORE_INTERFACES";
+/// The environment variable with the log filter
+const LOG_VARIABLE: &str = "CONNECTIVITY_LOG";
+
+/// Get the value of the environment variable `name`, [`None`] when it is not set.
+///
+/// # Errors
+///
+/// This function will return an error if the variable is not valid unicode.
+fn variable(name: &str) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    match env::var(name) {
+        Ok(value) => Ok(Some(value)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(error) => Err(format!("invalid {name}: {error}").into()),
+    }
+}
+
+/// Get the comma separated values of the environment variable `name`, empty when it is not set.
+///
+/// # Errors
+///
+/// This function will return an error if the variable is not valid unicode.
+fn variable_list(name: &str) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+    Ok(variable(name)?.map_or_else(Vec::new, |value| {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }))
+}
+
+/// A task which runs alongside the driver and completes after the driver drops the [`Emitter`], with the name it is spawned by.
+type Task = (&'static str, Pin<Box<dyn Future<Output = ()> + Send>>);
+
+/// Runs the driver together with the tasks.
+///
+/// With the `tokio-console` feature the driver and every task are spawned as named tokio tasks, otherwise they are polled by the calling task.
+///
+/// # Errors
+///
+/// This function will return an error if the driver returned an error or could not be spawned.
+async fn run(
+    driver: impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send + 'static,
+    tasks: Vec<Task>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    #[cfg(feature = "tokio-console")]
+    let result = {
+        let tasks = tasks
+            .into_iter()
+            .map(|(name, task)| crate::task::spawn(name, task))
+            .collect::<Result<Vec<_>, _>>()?;
+        let result = crate::task::spawn("network_connectivity::driver", driver)?.await;
+        join_all(tasks).await;
+        result
+    };
+    #[cfg(not(feature = "tokio-console"))]
+    let (result, _) = futures::join!(driver, join_all(tasks.into_iter().map(|(_, task)| task)));
+    #[cfg(feature = "metrics")]
+    if result.is_err() {
+        metrics::counter!("network_connectivity_errors_total", 1, "kind" => "driver_failed");
+    }
+    result
+}
+
+/// Configures and creates a driver that sends connectivity updates to a channel.
+///
+/// Any amount of drivers with different configurations can run concurrently in one process.
+/// They share one platform listener and apply their own filters and policies to its snapshots,
+/// only the [backends](crate::select_backends) are selected for the whole process.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/// use network_connectivity::{Builder, IpFamily};
+///
+/// let (wired, mut wired_updates) = Builder::new().ignore_interface("wlan0").build()?;
+/// let (ipv6, mut ipv6_updates) = Builder::new().ignore_family(IpFamily::V4).build_detailed()?;
+/// tokio::spawn(wired);
+/// tokio::spawn(ipv6);
+/// loop {
+///     tokio::select! {
+///         Some(connectivity) = wired_updates.recv() => println!("wired {connectivity:?}"),
+///         Some(event) = ipv6_updates.recv() => println!("ipv6 {event:?}"),
+///         else => break,
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct Builder {
+    /// The store to persist the last reported connectivity in
+    store: Option<Box<dyn Store + Send>>,
+    /// The dispatcher to run commands on events
+    dispatcher: Option<Dispatcher>,
+    /// Whether only gateways within the subnet of an address of their interface are counted
+    on_link_gateways: bool,
+    /// Whether the hardware addresses of the gateways are reported and fingerprinted
+    gateway_hardware_addresses: bool,
+    /// The destinations to look up a route for
+    destinations: Vec<IpAddr>,
+    /// The prefixes to report the reachability of
+    prefixes: Vec<(IpAddr, u8)>,
+    /// The amount of carrier changes and the window in which they must occur to suppress an interface
+    flap_suppression: Option<(usize, Duration)>,
+    /// The names of the interfaces which are ignored
+    ignored_interfaces: Vec<String>,
+    /// The prefixes of the hardware addresses of the interfaces which are allowed
+    allowed_hardware_addresses: Vec<HardwareAddress>,
+    /// How the connectivity of a dormant interface is reported
+    dormant_policy: DormantPolicy,
+    /// Whether the loopback interfaces are reported like any other interface
+    keep_loop_backs: bool,
+    /// The ip types which are ignored
+    ignored_families: Vec<IpFamily>,
+    /// The priorities of the kinds of interfaces which differ from the default ones
+    interface_priorities: Vec<(InterfaceType, u8)>,
+    /// How long the system must be stable before its changes are emitted
+    debounce: Option<Duration>,
+    /// How long the changes of the system are batched before they are emitted
+    coalesce: Option<Duration>,
+    /// The interval between heartbeats
+    heartbeat: Option<Duration>,
+    /// Receives the non-fatal problems of the driver
+    diagnostics: Diagnostics,
+    /// The names of the interfaces shared with the application
+    interface_names: Option<InterfaceNames>,
+    /// The inventory of the interfaces shared with the application
+    inventory: Option<Inventory>,
+    /// The preferred default routes shared with the application
+    routing_info: Option<RoutingInfo>,
+    /// Whether a failed platform listener is restarted
+    restart_on_error: bool,
+    /// The log filter for the logger of the application in the syntax of `env_logger`
+    log_filter: Option<String>,
+    /// Whether the connectivity transitions are exported to OpenTelemetry
+    #[cfg(feature = "otel")]
+    otel: bool,
+    /// The writer to record the snapshots and the emitted events to
+    #[cfg(feature = "json")]
+    capture: Option<Box<dyn std::io::Write + Send>>,
+    /// Whether the dns configuration is watched
+    #[cfg(all(feature = "dns", any(target_os = "linux", windows)))]
+    watch_dns: bool,
+    /// Whether the address resolution is watched for conflicts
+    #[cfg(all(feature = "arp", target_os = "linux"))]
+    detect_address_conflicts: bool,
+    /// Whether the hostname and search domains are watched
+    #[cfg(all(feature = "hostname", any(unix, windows)))]
+    watch_hostname: bool,
+    /// How ipv4 is reported on networks with NAT64, [`None`] to not detect NAT64
+    #[cfg(feature = "nat64")]
+    nat64: Option<crate::Nat64Policy>,
+    /// Notified when the platform listener must be restarted
+    #[cfg(feature = "monitor")]
+    restart: std::sync::Arc<tokio::sync::Notify>,
+    /// The probes to verify internet connectivity with and their quorum
+    #[cfg(feature = "probe")]
+    probes: Probes,
+    /// The urls of the webhooks to post connectivity transitions to
+    #[cfg(feature = "webhook")]
+    webhooks: Vec<String>,
+    /// The urls of the mqtt brokers and topics to publish connectivity transitions to
+    #[cfg(feature = "mqtt")]
+    mqtt: Vec<String>,
+}
+impl Builder {
+    /// Create a new [`Builder`] instance with the default configuration
+    #[allow(clippy::must_use_candidate)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Persist the last reported connectivity in `store`.
+    ///
+    /// At startup the persisted connectivity is sent as a provisional value before the state of the system is known,
+    /// detailed receivers get it as [`NetworkEvent::ConnectivityRestored`].
+    /// The actual connectivity is always sent afterwards, also when it equals the provisional value.
+    #[must_use]
+    pub fn store(mut self, store: impl Store + Send + 'static) -> Self {
+        self.store = Some(Box::new(store));
+        self
+    }
+
+    /// Run commands with `dispatcher` when the connectivity, the network or the mtu changes or a captive portal is detected.
+    #[must_use]
+    pub fn dispatcher(mut self, dispatcher: Dispatcher) -> Self {
+        self.dispatcher = Some(dispatcher);
+        self
+    }
+
+    /// Only report [internet](crate::ConnectivityState::Internet) connectivity when the gateway of a default route falls within the subnet of an address of its interface.
+    ///
+    /// This prevents leftover default routes to unreachable gateways from producing false reports.
+    /// Networks which reach their gateway through an on-link route outside of the subnet, as some hosting providers do, should not enable this.
+    #[must_use]
+    pub const fn on_link_gateways(mut self, required: bool) -> Self {
+        self.on_link_gateways = required;
+        self
+    }
+
+    /// Report the hardware address of the gateway of the preferred default route in [`NetworkEvent::DefaultRouteChanged`] events
+    /// and mix the hardware addresses of the gateways into the [fingerprints](crate::NetworkFingerprint) of the networks.
+    ///
+    /// The hardware addresses are taken from the neighbor table of the system, so they are only known once the system resolved the gateway.
+    /// A different hardware address for the same gateway reveals that another device took over the gateway address or that the system moved to another network with the same addresses,
+    /// at the cost of the fingerprint changing once when the gateway is resolved.
+    #[must_use]
+    pub const fn gateway_hardware_addresses(mut self, enabled: bool) -> Self {
+        self.gateway_hardware_addresses = enabled;
+        self
+    }
+
+    /// Determine [internet](crate::ConnectivityState::Internet) connectivity of the ip type of `destination` by asking the system whether it can select a route to it.
+    ///
+    /// This takes routing tables and rules into account which the default routes alone do not.
+    /// Without a route to `destination` the ip type has at most [network](crate::ConnectivityState::Network) connectivity.
+    /// Only one destination per ip type is used, a later one replaces an earlier one.
+    #[must_use]
+    pub fn route_lookup(mut self, destination: impl Into<IpAddr>) -> Self {
+        self.destinations.push(destination.into());
+        self
+    }
+
+    /// Report whether the prefix `prefix` with `prefix_length` is reachable as [`NetworkEvent::PrefixReachabilityChanged`],
+    /// for example a corporate subnet which is only reachable over a vpn.
+    ///
+    /// The prefix is reachable when a route covers it through an interface that is up, the most specific of them is reported with its interface.
+    /// A default route covers every prefix, the interface tells whether the prefix is reached through the vpn.
+    /// The prefix length must not exceed the length of an address of the ip type of `prefix`.
+    #[must_use]
+    pub fn watch_prefix(mut self, prefix: impl Into<IpAddr>, prefix_length: u8) -> Self {
+        self.prefixes.push((prefix.into(), prefix_length));
+        self
+    }
+
+    /// Report an interface as down while its carrier changed more than `transitions` times within `window`.
+    ///
+    /// The carrier changes are counted within `window` as well, see [`NetworkEvent::CarrierChanged`].
+    /// Without flap suppression they are counted within a minute.
+    #[must_use]
+    pub const fn flap_suppression(mut self, transitions: usize, window: Duration) -> Self {
+        self.flap_suppression = Some((transitions, window));
+        self
+    }
+
+    /// Ignore the interface with `name` as if it does not exist, for example a virtual interface of a container runtime.
+    #[must_use]
+    pub fn ignore_interface(mut self, name: impl Into<String>) -> Self {
+        self.ignored_interfaces.push(name.into());
+        self
+    }
+
+    /// Only consider the interfaces whose hardware address starts with `prefix`, the others are ignored as if they do not exist.
+    ///
+    /// A complete mac address allows a single interface and its first three bytes, the OUI, allow the interfaces of a vendor,
+    /// which is more robust than names on systems with unpredictable interface names such as usb adapters.
+    /// Calling it again allows the interfaces matching any of the prefixes, interfaces without a hardware address like tunnels are ignored.
+    #[must_use]
+    pub fn allow_hardware_address(mut self, prefix: impl Into<HardwareAddress>) -> Self {
+        self.allowed_hardware_addresses.push(prefix.into());
+        self
+    }
+
+    /// Report the connectivity of an interface which waits for an authentication like 802.1X according to `policy`,
+    /// [`Disconnected`](DormantPolicy::Disconnected) by default.
+    ///
+    /// Such an interface is reported with [`NetworkEvent::AuthenticationChanged`] on platforms that expose it.
+    #[must_use]
+    pub const fn dormant_policy(mut self, policy: DormantPolicy) -> Self {
+        self.dormant_policy = policy;
+        self
+    }
+
+    /// Ignore the loopback interfaces as if they do not exist, which is the default.
+    ///
+    /// When not ignored they are reported in the detailed events and the inventory like any other interface on platforms which expose them,
+    /// their addresses never provide connectivity.
+    #[must_use]
+    pub const fn ignore_loopback(mut self, ignore: bool) -> Self {
+        self.keep_loop_backs = !ignore;
+        self
+    }
+
+    /// Ignore `family` as if the system does not support it, for example to only monitor ipv4.
+    ///
+    /// Its connectivity is always reported as [`None`](crate::ConnectivityState::None),
+    /// its addresses, routes and route lookups are not reported and it is not probed.
+    /// An ip type left out by the `ipv4-only` or `ipv6-only` feature is always ignored.
+    #[must_use]
+    pub fn ignore_family(mut self, family: IpFamily) -> Self {
+        self.ignored_families.push(family);
+        self
+    }
+
+    /// Prefer interfaces of `interface_type` with `priority` when reporting which interface provides internet connectivity, higher is preferred.
+    ///
+    /// By default ethernet is preferred over wireless, point to point links, cellular, tunnels like vpns and other interfaces, in that order.
+    /// The priorities only determine the interface reported with [`NetworkEvent::InternetInterfaceChanged`], the connectivity is provided by any interface.
+    #[must_use]
+    pub fn interface_priority(mut self, interface_type: InterfaceType, priority: u8) -> Self {
+        self.interface_priorities.push((interface_type, priority));
+        self
+    }
+
+    /// Only emit the changes of the system once it did not change for `interval`.
+    ///
+    /// This prevents reporting every intermediate state while an interface is being configured,
+    /// but delays every report by at least `interval` and postpones it for as long as the system keeps changing.
+    /// The initial state is emitted without delay.
+    #[must_use]
+    pub const fn debounce(mut self, interval: Duration) -> Self {
+        self.debounce = Some(interval);
+        self
+    }
+
+    /// Batch the changes of the system for `tick` after the first of them and emit the state at the end of the tick.
+    ///
+    /// Unlike [`debounce`](Self::debounce) the tick is not postponed while the system keeps changing, so bursts of changes are reported at most once per tick
+    /// and the same on every platform, regardless of how the platform notifies the changes.
+    /// Together with a debounce the tick bounds how long the debounce can postpone a report.
+    /// The initial state is emitted without delay.
+    #[must_use]
+    pub const fn coalesce(mut self, tick: Duration) -> Self {
+        self.coalesce = Some(tick);
+        self
+    }
+
+    /// Emit a [`NetworkEvent::Heartbeat`] with the current connectivity every `interval`,
+    /// so consumers which forward the detailed events over lossy transports can detect a driver that stopped instead of a quiet network.
+    ///
+    /// Heartbeats are only sent to detailed receivers, the connectivity updates are not repeated.
+    #[must_use]
+    pub const fn heartbeat(mut self, interval: Duration) -> Self {
+        self.heartbeat = Some(interval);
+        self
+    }
+
+    /// Send the non-fatal problems the driver recovered from as [diagnostics](Diagnostic) to `sender`, so applications can surface them instead of losing them in the logs.
+    ///
+    /// The diagnostics are still logged as well.
+    /// When the receive end of `sender` is dropped the diagnostics are discarded, the driver keeps running.
+    #[must_use]
+    pub fn diagnostics(mut self, sender: UnboundedSender<Diagnostic>) -> Self {
+        self.diagnostics = Diagnostics::new(sender);
+        self
+    }
+
+    /// Record the names of the interfaces by their index in `names`, so the application can name the interfaces of events which only carry an index,
+    /// like [`NetworkEvent::RouteAdded`], even when they disappeared moments ago.
+    ///
+    /// The names are known to the driver without this as well, detailed events about an interface carry its name.
+    #[must_use]
+    pub fn interface_names(mut self, names: InterfaceNames) -> Self {
+        self.interface_names = Some(names);
+        self
+    }
+
+    /// Maintain an [`Inventory`] of the interfaces in `inventory` which retains the interfaces that are down or were removed with their last known addresses,
+    /// so inventory and debugging consumers can show every interface the driver has seen.
+    #[must_use]
+    pub fn inventory(mut self, inventory: Inventory) -> Self {
+        self.inventory = Some(inventory);
+        self
+    }
+
+    /// Maintain the preferred default route and gateway of every ip type in `routing`,
+    /// so latency probes or UPnP and NAT-PMP clients can find the gateway without parsing the routing table themselves.
+    #[must_use]
+    pub fn routing_info(mut self, routing: RoutingInfo) -> Self {
+        self.routing_info = Some(routing);
+        self
+    }
+
+    /// Watch the dns configuration and emit [`NetworkEvent::DnsChanged`](crate::NetworkEvent::DnsChanged) when it changed.
+    ///
+    /// Resolver changes often explain why a system is connected while names do not resolve.
+    /// On linux `/etc/resolv.conf` is watched with inotify,
+    /// when systemd-resolved is used its upstream nameservers, the nameservers of every link and its dnssec and dns over tls settings are reported.
+    /// On windows the nameservers and dns suffixes of the adapters that are up are polled every few seconds and the nameservers are reported per interface.
+    #[cfg(all(feature = "dns", any(target_os = "linux", windows)))]
+    #[must_use]
+    pub const fn watch_dns(mut self, watch: bool) -> Self {
+        self.watch_dns = watch;
+        self
+    }
+
+    /// Watch the hostname and search domains of the system and emit [`NetworkEvent::HostnameChanged`](crate::NetworkEvent::HostnameChanged) when they changed.
+    ///
+    /// Dhcp often provides the domains with the network, so they change with it and affect service discovery.
+    /// They are read every five seconds, on unix from the kernel and `/etc/resolv.conf` and on windows with `GetComputerNameExW` and the dns suffixes of the adapters.
+    #[cfg(all(feature = "hostname", any(unix, windows)))]
+    #[must_use]
+    pub const fn watch_hostname(mut self, watch: bool) -> Self {
+        self.watch_hostname = watch;
+        self
+    }
+
+    /// Watch the arp packets received on every interface and report [`Diagnostic::AddressConflict`] when another host claims an ipv4 address of the system.
+    ///
+    /// Linux performs no duplicate address detection for ipv4, so a host whose address is used by another host seems connected while its traffic silently fails.
+    /// Watching requires `CAP_NET_RAW`, without it [`Diagnostic::ConflictWatchFailed`] is reported.
+    /// Conflicts found by the duplicate address detection of the platform are reported without it.
+    #[cfg(all(feature = "arp", target_os = "linux"))]
+    #[must_use]
+    pub const fn detect_address_conflicts(mut self, detect: bool) -> Self {
+        self.detect_address_conflicts = detect;
+        self
+    }
+
+    /// Detect NAT64 on networks with internet connectivity over ipv6 and report ipv4 on them according to `policy`.
+    ///
+    /// NAT64 is detected by looking up `ipv4only.arpa` as described in RFC 7050, a dns64 resolver synthesizes an ipv6 address for it.
+    /// The detection runs again whenever the networks change.
+    /// With [`Nat64Policy::Synthesized`](crate::Nat64Policy::Synthesized) ipv4 is reported as internet connectivity while ipv6 has internet connectivity and NAT64 is detected,
+    /// with [`Nat64Policy::Native`](crate::Nat64Policy::Native) ipv4 is reported as the system has it natively.
+    #[cfg(feature = "nat64")]
+    #[must_use]
+    pub const fn nat64(mut self, policy: crate::Nat64Policy) -> Self {
+        self.nat64 = Some(policy);
+        self
+    }
+
+    /// Export the connectivity transitions through the global OpenTelemetry providers, so an existing OTLP pipeline of the application picks them up.
+    ///
+    /// Every transition increments the `network_connectivity.transitions` counter, records how long the previous connectivity lasted in the
+    /// `network_connectivity.state.duration` histogram and is added as an event to a `connectivity transition` span.
+    /// They carry the new and previous connectivity, the `reason` of the transition which is the kind of event that caused it,
+    /// and the index and name of the interface it happened on when known.
+    /// The providers must be installed before the driver is built.
+    #[cfg(feature = "otel")]
+    #[must_use]
+    pub const fn otel(mut self, export: bool) -> Self {
+        self.otel = export;
+        self
+    }
+
+    /// Record every snapshot of the system published by the platform listener and every emitted event as lines of json to `writer`.
+    ///
+    /// The capture contains the complete [state](crate::state::Interfaces) the connectivity was derived from,
+    /// so a misdetection can be reproduced without access to the system it happened on.
+    /// Every line is flushed when it is written, a failed write is reported as [`Diagnostic::CaptureFailed`] and stops the capture.
+    #[cfg(feature = "json")]
+    #[must_use]
+    pub fn capture(mut self, writer: impl std::io::Write + Send + 'static) -> Self {
+        self.capture = Some(Box::new(writer));
+        self
+    }
+
+    /// Restart the platform listener when it fails instead of completing the driver with the error.
+    ///
+    /// The error is sent as [`Diagnostic::PlatformFailed`] and the platform listener is restarted after a second,
+    /// so a momentary rtnetlink error does not require the application to create a new driver.
+    /// Errors of the driver itself, like a dropped receive end of the channel, still complete it.
+    #[must_use]
+    pub const fn restart_on_error(mut self, restart: bool) -> Self {
+        self.restart_on_error = restart;
+        self
+    }
+
+    /// Verify internet connectivity with `probe` before reporting it, for example a [`Preset`], a [`DnsProbe`](crate::DnsProbe) or a user defined [`Probe`].
+    ///
+    /// Internet connectivity of an ip type is reported as network connectivity until the [quorum](Builder::probe_quorum) of the probes verified it over that ip type,
+    /// which also detects captive portals and broken upstreams.
+    /// The probes run again whenever the connectivity or the fingerprint of the networks changes.
+    /// When they detect a captive portal [`NetworkEvent::CaptivePortalDetected`] is sent with the url of its login page if they were redirected to it.
+    /// A captive portal api advertised through dhcp or router advertisements (RFC 8908) is not known to this crate.
+    /// The probe is considered failed when it takes longer than five seconds.
+    #[cfg(feature = "probe")]
+    #[must_use]
+    pub fn probe(self, probe: impl Probe + Send + Sync + 'static) -> Self {
+        self.probe_with_timeout(probe, PROBE_TIMEOUT)
+    }
+
+    /// Verify internet connectivity with `probe` which is considered failed when it takes longer than `timeout`, see [`Builder::probe`].
+    #[cfg(feature = "probe")]
+    #[must_use]
+    pub fn probe_with_timeout(
+        mut self,
+        probe: impl Probe + Send + Sync + 'static,
+        timeout: Duration,
+    ) -> Self {
+        self.probes.push(Box::new(probe), timeout);
+        self
+    }
+
+    /// Require `quorum` of the probes to verify internet connectivity, so a single flaky probe can not change the reported connectivity.
+    ///
+    /// Without a quorum any of the probes can verify internet connectivity.
+    /// A quorum of zero or larger than the amount of probes results in an error when building.
+    #[cfg(feature = "probe")]
+    #[must_use]
+    pub fn probe_quorum(mut self, quorum: usize) -> Self {
+        self.probes.set_quorum(quorum);
+        self
+    }
+
+    /// Probe ip types which were not verified again every `interval`, ten seconds when not given.
+    ///
+    /// While the system reports internet connectivity but the probes detected a captive portal or failed, the connectivity is reported as network connectivity.
+    /// Probing again detects when the user logged in to the captive portal or the upstream recovered and reports the internet connectivity right away,
+    /// without waiting for the connectivity or the networks to change.
+    #[cfg(feature = "probe")]
+    #[must_use]
+    pub fn probe_interval(mut self, interval: Duration) -> Self {
+        self.probes.set_reprobe_interval(interval);
+        self
+    }
+
+    /// Verify internet connectivity by probing the [default presets](Preset::DEFAULT), see [`Builder::probe`].
+    #[cfg(feature = "probe")]
+    #[must_use]
+    pub fn default_probes(mut self) -> Self {
+        for preset in Preset::DEFAULT {
+            self = self.probe(preset);
+        }
+        self
+    }
+
+    /// Post every connectivity transition as json to the webhook at `url`.
+    ///
+    /// Only `http://` urls are supported, an invalid url results in an error when building.
+    /// The payloads are posted without tls, so an `https://` webhook must be reached through a local relay like a reverse proxy.
+    /// Transitions are only delivered while there is internet connectivity and are retried when it returns.
+    #[cfg(feature = "webhook")]
+    #[must_use]
+    pub fn webhook(mut self, url: impl Into<String>) -> Self {
+        self.webhooks.push(url.into());
+        self
+    }
+
+    /// Publish every connectivity transition as json to the mqtt broker and topic at `url`, for example `mqtt://broker:1883/connectivity`.
+    ///
+    /// The transitions are published as retained messages, so new subscribers receive the latest one.
+    /// They are delivered like the transitions posted to webhooks, see [`Builder::webhook`].
+    #[cfg(feature = "mqtt")]
+    #[must_use]
+    pub fn mqtt(mut self, url: impl Into<String>) -> Self {
+        self.mqtt.push(url.into());
+        self
+    }
+
+    /// Apply the configuration from the environment on top of the current configuration, so deployments can tune it without code changes.
+    ///
+    /// The following variables are used when set:
+    /// - `CONNECTIVITY_PROBE_URL`: comma separated urls of endpoints which respond with status 204 to verify internet connectivity with, see [`Builder::probe`], requires the `probe` feature
+    /// - `CONNECTIVITY_DEBOUNCE`: the debounce interval in milliseconds, see [`Builder::debounce`]
+    /// - `CONNECTIVITY_IGNORE_INTERFACES`: comma separated names of the interfaces to ignore, see [`Builder::ignore_interface`]
+    /// - `CONNECTIVITY_LOG`: the log filter in the syntax of `env_logger`, see [`Builder::log_filter`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a variable is invalid.
+    pub fn env(mut self) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let urls = variable_list(PROBE_URL_VARIABLE)?;
+        #[cfg(feature = "probe")]
+        for url in urls {
+            let probe = HttpProbe::new(&url)
+                .map_err(|error| format!("invalid {PROBE_URL_VARIABLE}: {error}"))?;
+            self = self.probe(probe);
+        }
+        #[cfg(not(feature = "probe"))]
+        if !urls.is_empty() {
+            return Err(format!("{PROBE_URL_VARIABLE} requires the probe feature").into());
+        }
+        if let Some(debounce) = variable(DEBOUNCE_VARIABLE)? {
+            let milliseconds = debounce
+                .trim()
+                .parse()
+                .map_err(|error| format!("invalid {DEBOUNCE_VARIABLE}: {error}"))?;
+            self = self.debounce(Duration::from_millis(milliseconds));
+        }
+        for name in variable_list(IGNORE_INTERFACES_VARIABLE)? {
+            self = self.ignore_interface(name);
+        }
+        if let Some(filter) = variable(LOG_VARIABLE)? {
+            self.log_filter = Some(filter);
+        }
+        Ok(self)
+    }
+
+    /// Get the log filter taken from the environment by [`Builder::env`], [`None`] when it was not set.
+    ///
+    /// The crate only logs through the `log` facade, so the application passes the filter to the logger it initializes, like `connectivityd` does.
+    #[allow(clippy::must_use_candidate)]
+    pub fn log_filter(&self) -> Option<&str> {
+        self.log_filter.as_deref()
+    }
+
+    /// Creates the [`Emitter`] and the tasks for this configuration
+    fn emitter(self, sender: Sender) -> Result<(Emitter, Vec<Task>), Box<dyn Error + Send + Sync>> {
+        let mut emitter = Emitter::new(sender);
+        #[allow(unused_mut)]
+        let mut tasks = Vec::<Task>::new();
+        emitter.diagnose(self.diagnostics);
+        if self.restart_on_error {
+            emitter.restart_platform();
+        }
+        #[cfg(feature = "otel")]
+        if self.otel {
+            emitter.export(crate::otel::Exporter::new());
+        }
+        #[cfg(feature = "json")]
+        if let Some(writer) = self.capture {
+            emitter.capture(crate::capture::Capture::new(writer));
+        }
+        #[cfg(feature = "monitor")]
+        emitter.restart_on(self.restart);
+        if let Some(dispatcher) = self.dispatcher {
+            emitter.dispatch(dispatcher);
+        }
+        if let Some(names) = self.interface_names {
+            emitter.name_interfaces(names);
+        }
+        if let Some(inventory) = self.inventory {
+            emitter.take_inventory(inventory);
+        }
+        if let Some(routing) = self.routing_info {
+            emitter.take_routing_info(routing);
+        }
+        if self.on_link_gateways {
+            emitter.require_on_link_gateways();
+        }
+        if self.gateway_hardware_addresses {
+            emitter.report_gateway_hardware_addresses();
+        }
+        for destination in self.destinations {
+            emitter.look_up_route(destination);
+        }
+        for (prefix, prefix_length) in self.prefixes {
+            let maximum = if prefix.is_ipv4() { 32 } else { 128 };
+            if prefix_length > maximum {
+                return Err(format!("invalid prefix length {prefix_length} for {prefix}").into());
+            }
+            emitter.watch_prefix(prefix, prefix_length);
+        }
+        if let Some((transitions, window)) = self.flap_suppression {
+            emitter.suppress_flapping(transitions, window);
+        }
+        for name in self.ignored_interfaces {
+            emitter.ignore_interface(name);
+        }
+        for prefix in self.allowed_hardware_addresses {
+            emitter.allow_hardware_address(prefix);
+        }
+        emitter.set_ignore_loop_backs(!self.keep_loop_backs);
+        emitter.set_dormant_policy(self.dormant_policy);
+        for family in self.ignored_families {
+            emitter.ignore_family(family);
+        }
+        for family in [IpFamily::V4, IpFamily::V6] {
+            if !family.is_compiled() {
+                emitter.ignore_family(family);
+            }
+        }
+        for (interface_type, priority) in self.interface_priorities {
+            emitter.prioritize(interface_type, priority);
+        }
+        if let Some(interval) = self.debounce {
+            emitter.debounce(interval);
+        }
+        if let Some(tick) = self.coalesce {
+            emitter.coalesce(tick);
+        }
+        if let Some(interval) = self.heartbeat {
+            emitter.heartbeat(interval);
+        }
+        #[cfg(feature = "probe")]
+        if !self.probes.is_empty() {
+            self.probes.validate()?;
+            let (verification, prober) =
+                Verification::new(self.probes, emitter.wake(), emitter.diagnostics().clone());
+            emitter.verify(verification);
+            tasks.push(("network_connectivity::prober", Box::pin(prober)));
+        }
+        #[cfg(all(feature = "dns", any(target_os = "linux", windows)))]
+        if self.watch_dns {
+            let (dns, watcher) =
+                crate::dns::Watch::new(emitter.wake(), emitter.diagnostics().clone());
+            emitter.watch_dns(dns);
+            tasks.push(("network_connectivity::dns", Box::pin(watcher)));
+        }
+        #[cfg(all(feature = "arp", target_os = "linux"))]
+        if self.detect_address_conflicts {
+            let (arp, watcher) = crate::arp::Watch::new(emitter.diagnostics().clone());
+            emitter.detect_address_conflicts(arp);
+            tasks.push(("network_connectivity::arp", Box::pin(watcher)));
+        }
+        #[cfg(all(feature = "hostname", any(unix, windows)))]
+        if self.watch_hostname {
+            let (hostname, watcher) = crate::hostname::Watch::new(emitter.wake());
+            emitter.watch_hostname(hostname);
+            tasks.push(("network_connectivity::hostname", Box::pin(watcher)));
+        }
+        #[cfg(feature = "nat64")]
+        if let Some(policy) = self.nat64 {
+            let (nat64, detector) = crate::nat64::Detection::new(policy, emitter.wake());
+            emitter.detect_nat64(nat64);
+            tasks.push(("network_connectivity::nat64", Box::pin(detector)));
+        }
+        #[cfg(feature = "webhook")]
+        {
+            #[allow(unused_mut)]
+            let mut endpoints = crate::webhook::parse(&self.webhooks)?;
+            #[cfg(feature = "mqtt")]
+            for url in &self.mqtt {
+                endpoints.push(crate::webhook::Endpoint::Mqtt(crate::mqtt::parse(url)?));
+            }
+            if !endpoints.is_empty() {
+                let (tx, rx) = unbounded_channel();
+                emitter.listen(tx);
+                tasks.push((
+                    "network_connectivity::webhook",
+                    Box::pin(crate::webhook::notify(
+                        endpoints,
+                        rx,
+                        emitter.diagnostics().clone(),
+                    )),
+                ));
+            }
+        }
+        if let Some(store) = self.store {
+            emitter.restore(store)?;
+        }
+        Ok((emitter, tasks))
+    }
+
+    /// Get the notify which restarts the platform listener of the driver when notified
+    #[cfg(feature = "monitor")]
+    pub(crate) fn restart(&self) -> std::sync::Arc<tokio::sync::Notify> {
+        std::sync::Arc::clone(&self.restart)
+    }
+
+    /// Creates a [`Monitor`](crate::Monitor) which runs the driver in a task of the current tokio runtime,
+    /// bundling its detailed events, its latest connectivity and its shutdown in one type.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it is not called within a tokio runtime or the underlying driver failed in some way.
+    #[cfg(feature = "monitor")]
+    pub fn monitor(self) -> Result<crate::Monitor, Box<dyn Error + Send + Sync>> {
+        crate::Monitor::spawn(self)
+    }
+
+    /// Creates a driver which runs in a thread of its own with a runtime of its own and returns the blocking receive end of its connectivity updates,
+    /// for programs which do not use async.
+    ///
+    /// The receiver is an iterator over the connectivity updates, dropping it stops the driver.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the underlying driver could not be created or its thread could not be spawned.
+    #[cfg(feature = "blocking")]
+    pub fn build_blocking(self) -> Result<crate::BlockingReceiver, Box<dyn Error + Send + Sync>> {
+        crate::BlockingReceiver::spawn(self)
+    }
+
+    /// Creates a driver that sends connectivity updates to a channel.
+    ///
+    /// # Returns
+    ///
+    /// The return value consists of a future that must be awaited and the receive end of a channel through which connectivity updates are received.
+    ///
+    /// # Notes
+    ///
+    /// When the receive end of the channel is dropped, the future will run to completion.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the underlying driver failed in some way,
+    /// a [`BackendUnavailable`](crate::BackendUnavailable) error when the process may not use any of the [selected backends](crate::select_backends).
+    /// The returned future can fail when the underlying driver received an error.
+    pub fn build(
+        self,
+    ) -> Result<
+        (
+            impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+            UnboundedReceiver<Connectivity>,
+        ),
+        Box<dyn Error + Send + Sync>,
+    > {
+        let (tx, rx) = unbounded_channel();
+        let (emitter, tasks) = self.emitter(Sender::Connectivity(tx))?;
+        Ok((run(driver(emitter)?, tasks), rx))
+    }
+
+    /// Queries the current connectivity once, for command line tools and health checks which only need the answer now.
+    ///
+    /// The driver stops as soon as it reported the initial connectivity, so no long running watcher is left behind.
+    /// When another driver of the process is running its latest snapshot of the system is used instead of querying the system again.
+    /// The provisional connectivity of a [store](Self::store) is skipped, only the connectivity of the system is returned.
+    /// A [`Monitor`](crate::Monitor) has the latest connectivity at hand with [`Monitor::connectivity`](crate::Monitor::connectivity).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the underlying driver failed in some way or completed without reporting the connectivity.
+    pub async fn current(self) -> Result<Connectivity, Box<dyn Error + Send + Sync>> {
+        let (driver, mut receiver) = self.build_detailed()?;
+        let reported = async {
+            while let Some(event) = receiver.recv().await {
+                if let NetworkEvent::ConnectivityChanged { connectivity, .. } = event {
+                    return Some(connectivity);
+                }
+            }
+            None
+        };
+        #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+        let current = tokio::select! {
+            result = driver => {
+                result?;
+                None
+            },
+            connectivity = reported => connectivity,
+        };
+        current.ok_or_else(|| "the driver completed without reporting the connectivity".into())
+    }
+
+    /// Creates a driver that only keeps the latest connectivity in a [`watch`] channel.
+    ///
+    /// # Returns
+    ///
+    /// The return value consists of a future that must be awaited and the receive end of a watch channel which holds the latest connectivity.
+    /// It holds no connectivity on any ip type until the driver reported the initial connectivity.
+    ///
+    /// # Notes
+    ///
+    /// Consumers which only care about the current connectivity do not have to drain a queue, and the receiver can be cloned cheaply to share it across tasks.
+    /// When every clone of the receive end is dropped, the future will run to completion.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the underlying driver failed in some way,
+    /// a [`BackendUnavailable`](crate::BackendUnavailable) error when the process may not use any of the [selected backends](crate::select_backends).
+    /// The returned future can fail when the underlying driver received an error.
+    pub fn build_watch(
+        self,
+    ) -> Result<
+        (
+            impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+            watch::Receiver<Connectivity>,
+        ),
+        Box<dyn Error + Send + Sync>,
+    > {
+        let (tx, rx) = watch::channel(Connectivity {
+            ipv4: ConnectivityState::None,
+            ipv6: ConnectivityState::None,
+        });
+        let (emitter, tasks) = self.emitter(Sender::Watch(tx))?;
+        Ok((run(driver(emitter)?, tasks), rx))
+    }
+
+    /// Creates a driver that sends detailed [events](NetworkEvent) to a channel.
+    ///
+    /// # Returns
+    ///
+    /// The return value consists of a future that must be awaited and the receive end of a channel through which detailed events are received.
+    ///
+    /// # Notes
+    ///
+    /// When the receive end of the channel is dropped, the future will run to completion.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the underlying driver failed in some way,
+    /// a [`BackendUnavailable`](crate::BackendUnavailable) error when the process may not use any of the [selected backends](crate::select_backends).
+    /// The returned future can fail when the underlying driver received an error.
+    pub fn build_detailed(
+        self,
+    ) -> Result<
+        (
+            impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+            UnboundedReceiver<NetworkEvent>,
+        ),
+        Box<dyn Error + Send + Sync>,
+    > {
+        let (tx, rx) = unbounded_channel();
+        let (emitter, tasks) = self.emitter(Sender::Detailed(tx))?;
+        Ok((run(driver(emitter)?, tasks), rx))
+    }
+
+    /// Creates a driver that sends detailed [events](NetworkEvent) to a channel which queues at most `capacity` events.
+    ///
+    /// # Returns
+    ///
+    /// The return value consists of a future that must be awaited and the receive end of a channel through which detailed events are received.
+    ///
+    /// # Notes
+    ///
+    /// When the channel is full, an event is dropped according to `policy` and counted in [`BoundedReceiver::dropped`],
+    /// so a slow consumer can not make the driver queue an unlimited amount of events.
+    /// The receiver is told about the dropped events with [`NetworkEvent::Lagged`] before the next queued event.
+    /// When the receive end of the channel is dropped, the future will run to completion.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `capacity` is zero or the underlying driver failed in some way.
+    /// The returned future can fail when the underlying driver received an error.
+    pub fn build_bounded(
+        self,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> Result<
+        (
+            impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+            BoundedReceiver,
+        ),
+        Box<dyn Error + Send + Sync>,
+    > {
+        if capacity == 0 {
+            return Err("the capacity of a bounded channel must be at least one".into());
+        }
+        let (tx, rx) = crate::bounded::channel(capacity, policy);
+        let (emitter, tasks) = self.emitter(Sender::Bounded(tx))?;
+        Ok((run(driver(emitter)?, tasks), rx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Builder, DEBOUNCE_VARIABLE, IGNORE_INTERFACES_VARIABLE, LOG_VARIABLE};
+    use crate::emitter::Sender;
+    use core::time::Duration;
+    use std::{
+        env,
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        sync::{Mutex, PoisonError},
+    };
+    use tokio::sync::mpsc::unbounded_channel;
+
+    /// Serializes the tests which change the environment of the process
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    /// Creates the emitter of a [`Builder`] which watches `prefix` with `prefix_length`, returns the error when it is rejected.
+    fn watch_prefix(prefix: impl Into<IpAddr>, prefix_length: u8) -> Result<(), String> {
+        let (sender, _) = unbounded_channel();
+        Builder::new()
+            .watch_prefix(prefix, prefix_length)
+            .emitter(Sender::Detailed(sender))
+            .map(|_| ())
+            .map_err(|error| error.to_string())
+    }
+
+    /// Applies the environment with the variables in `variables` set to a new [`Builder`], the other variables are removed.
+    fn with_environment(variables: &[(&str, &str)]) -> Result<Builder, String> {
+        let _serial = SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+        for name in [DEBOUNCE_VARIABLE, IGNORE_INTERFACES_VARIABLE, LOG_VARIABLE] {
+            env::remove_var(name);
+        }
+        for &(name, value) in variables {
+            env::set_var(name, value);
+        }
+        let builder = Builder::new().env().map_err(|error| error.to_string());
+        for &(name, _) in variables {
+            env::remove_var(name);
+        }
+        builder
+    }
+
+    #[test]
+    fn environment_configures_the_builder() {
+        let builder = with_environment(&[
+            (DEBOUNCE_VARIABLE, " 250 "),
+            (IGNORE_INTERFACES_VARIABLE, "docker0, ,veth0"),
+            (LOG_VARIABLE, "network_connectivity=debug"),
+        ])
+        .unwrap();
+        assert_eq!(builder.debounce, Some(Duration::from_millis(250)));
+        assert_eq!(builder.ignored_interfaces, ["docker0", "veth0"]);
+        assert_eq!(builder.log_filter(), Some("network_connectivity=debug"));
+    }
+
+    #[test]
+    fn empty_environment_keeps_the_configuration() {
+        let builder = with_environment(&[]).unwrap();
+        assert_eq!(builder.debounce, None);
+        assert!(builder.ignored_interfaces.is_empty());
+        assert_eq!(builder.log_filter(), None);
+    }
+
+    #[test]
+    fn invalid_debounce_is_an_error() {
+        let error = with_environment(&[(DEBOUNCE_VARIABLE, "soon")])
+            .err()
+            .unwrap();
+        assert!(error.contains(DEBOUNCE_VARIABLE));
+    }
+
+    #[test]
+    fn prefix_lengths_up_to_the_address_length_are_watched() {
+        assert!(watch_prefix(Ipv4Addr::new(10, 0, 0, 0), 8).is_ok());
+        assert!(watch_prefix(Ipv4Addr::new(10, 0, 0, 1), 32).is_ok());
+        assert!(watch_prefix(Ipv4Addr::UNSPECIFIED, 0).is_ok());
+        assert!(watch_prefix(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 128).is_ok());
+    }
+
+    #[test]
+    fn prefix_lengths_beyond_the_address_length_are_rejected() {
+        let error = watch_prefix(Ipv4Addr::new(10, 0, 0, 0), 33).unwrap_err();
+        assert!(error.contains("33"));
+        assert!(error.contains("10.0.0.0"));
+        // an ipv6 prefix length does not make an ipv4 prefix valid
+        assert!(watch_prefix(Ipv4Addr::new(10, 0, 0, 0), 64).is_err());
+        assert!(watch_prefix(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), 129).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: MIT
+
+//! Records the snapshots of the platform and the events derived from them as lines of json.
+//!
+//! A capture contains everything needed to reproduce a misdetection without access to the system it happened on:
+//! every snapshot of the [state](Interfaces) published by the platform listener with the destinations a route was found for,
+//! followed by the events the driver emitted, in the order they happened.
+
+use crate::{state::Interfaces, NetworkEvent};
+use serde::Serialize;
+use std::{error::Error, io::Write, net::IpAddr, time::Instant};
+
+/// A record of a capture.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Record<'a> {
+    /// A snapshot of the system published by the platform listener
+    Snapshot {
+        /// The state of the system
+        state: &'a Interfaces,
+        /// The destinations a route could be selected for
+        routed: &'a [IpAddr],
+    },
+    /// An event emitted by the driver
+    Event(&'a NetworkEvent),
+}
+
+/// A single line of a capture.
+#[derive(Serialize)]
+struct Line<'a> {
+    /// The milliseconds since the capture started
+    elapsed: u128,
+    /// What happened
+    #[serde(flatten)]
+    record: Record<'a>,
+}
+
+/// Writes the records of a capture to a writer.
+pub(crate) struct Capture {
+    /// The writer the lines are written to
+    writer: Box<dyn Write + Send>,
+    /// When the capture started
+    started: Instant,
+}
+impl Capture {
+    /// Creates a [`Capture`] which writes to `writer`.
+    pub(crate) fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer,
+            started: Instant::now(),
+        }
+    }
+
+    /// Writes `record` as a single line of json and flushes it, so a capture is complete up to the last record when the process is killed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if serializing or writing the record failed.
+    fn write(&mut self, record: Record<'_>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let line = Line {
+            elapsed: self.started.elapsed().as_millis(),
+            record,
+        };
+        serde_json::to_writer(&mut self.writer, &line)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Records a snapshot of the system.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if serializing or writing the snapshot failed.
+    pub(crate) fn snapshot(
+        &mut self,
+        state: &Interfaces,
+        routed: &[IpAddr],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.write(Record::Snapshot { state, routed })
+    }
+
+    /// Records an emitted event.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if serializing or writing the event failed.
+    pub(crate) fn event(
+        &mut self,
+        event: &NetworkEvent,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.write(Record::Event(event))
+    }
+}
@@ -0,0 +1,279 @@
+// SPDX-License-Identifier: MIT
+
+//! Loads the configuration of a driver from a toml file, so operators can manage it like other system services.
+//!
+//! ```toml
+//! endpoint = "/run/connectivityd.sock"
+//! debounce = 500
+//! coalesce = 2000
+//! heartbeat = 30000
+//! on-link-gateways = true
+//! route-lookup = ["1.1.1.1", "2606:4700:4700::1111"]
+//! ignore-interfaces = ["docker0"]
+//! allow-hardware-addresses = ["00:1a:2b", "00:1a:2b:3c:4d:5e"]
+//! ignore-loopback = true
+//! ignore-families = ["ipv6"]
+//! restart-on-error = true
+//! dispatch = ["/etc/connectivity/dispatch.sh"]
+//! webhooks = ["http://localhost:8080/connectivity"]
+//! mqtt = ["mqtt://localhost/connectivity"]
+//!
+//! [probe]
+//! presets = ["cloudflare", "google"]
+//! urls = ["http://example.com/generate_204"]
+//! dns = ["cloudflare", "quad9"]
+//! quorum = 2
+//! timeout = 3000
+//! ```
+
+use crate::{state::HardwareAddress, Builder, Dispatcher, IpFamily};
+#[cfg(feature = "probe")]
+use crate::{DnsProbe, HttpProbe, Preset};
+use core::{str::FromStr, time::Duration};
+use serde::Deserialize;
+use std::{error::Error, fs, net::IpAddr, path::Path};
+
+/// Parses a hardware address or a prefix of it written as hexadecimal bytes separated by colons or dashes, like `00:1a:2b`.
+///
+/// # Errors
+///
+/// This function will return an error if a byte is not written as two hexadecimal digits.
+fn hardware_address(text: &str) -> Result<HardwareAddress, Box<dyn Error + Send + Sync>> {
+    text.split(|separator| separator == ':' || separator == '-')
+        .map(|byte| match byte.len() {
+            2 => u8::from_str_radix(byte, 16).ok(),
+            _ => None,
+        })
+        .collect::<Option<_>>()
+        .ok_or_else(|| {
+            format!("invalid hardware address {text:?}, expected hexadecimal bytes like 00:1a:2b")
+                .into()
+        })
+}
+
+/// The configuration of the probes which verify internet connectivity, see [`Builder::probe`].
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct ProbeConfig {
+    /// The names of the [presets](crate::Preset) to probe: `cloudflare`, `google`, `microsoft` or `apple`
+    pub presets: Vec<String>,
+    /// The urls of own endpoints which respond with status 204 to probe
+    pub urls: Vec<String>,
+    /// The [dns resolvers](crate::DnsProbe) to probe: `cloudflare`, `google` or `quad9`
+    pub dns: Vec<String>,
+    /// The amount of probes that must verify internet connectivity, see [`Builder::probe_quorum`]
+    pub quorum: Option<usize>,
+    /// The time in milliseconds after which a probe is considered failed
+    pub timeout: Option<u64>,
+    /// The time in milliseconds between probes of ip types which were not verified, see [`Builder::probe_interval`]
+    pub interval: Option<u64>,
+}
+
+/// The configuration of a driver.
+///
+/// Every field is optional, an empty file results in the default configuration.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct Config {
+    /// The path of the unix socket or the name of the named pipe on which the daemon shares the events as json lines
+    pub endpoint: Option<String>,
+    /// The debounce interval in milliseconds, see [`Builder::debounce`]
+    pub debounce: Option<u64>,
+    /// The coalescing tick in milliseconds, see [`Builder::coalesce`]
+    pub coalesce: Option<u64>,
+    /// The interval between heartbeats in milliseconds, see [`Builder::heartbeat`]
+    pub heartbeat: Option<u64>,
+    /// Whether only gateways within the subnet of an address of their interface are counted, see [`Builder::on_link_gateways`]
+    pub on_link_gateways: bool,
+    /// Whether the hardware addresses of the gateways are reported and fingerprinted, see [`Builder::gateway_hardware_addresses`]
+    pub gateway_hardware_addresses: bool,
+    /// The destinations to look up a route for, see [`Builder::route_lookup`]
+    pub route_lookup: Vec<IpAddr>,
+    /// The names of the interfaces to ignore, see [`Builder::ignore_interface`]
+    pub ignore_interfaces: Vec<String>,
+    /// The prefixes of the hardware addresses of the interfaces to allow, hexadecimal bytes separated by colons or dashes, see [`Builder::allow_hardware_address`]
+    pub allow_hardware_addresses: Vec<String>,
+    /// Whether the loopback interfaces are ignored, see [`Builder::ignore_loopback`]
+    pub ignore_loopback: Option<bool>,
+    /// The ip types to ignore, `ipv4` or `ipv6`, see [`Builder::ignore_family`]
+    pub ignore_families: Vec<String>,
+    /// Whether a failed platform listener is restarted, see [`Builder::restart_on_error`]
+    pub restart_on_error: bool,
+    /// The commands to run on events, see [`Dispatcher`]
+    pub dispatch: Vec<String>,
+    /// The probes which verify internet connectivity, requires the `probe` feature
+    pub probe: Option<ProbeConfig>,
+    /// The `http://` urls of the webhooks to post connectivity transitions to, requires the `webhook` feature
+    pub webhooks: Vec<String>,
+    /// The urls of the mqtt brokers and topics to publish connectivity transitions to, requires the `mqtt` feature
+    pub mqtt: Vec<String>,
+}
+impl Config {
+    /// Loads the configuration from the toml file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file could not be read or the configuration is invalid.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let path = path.as_ref();
+        fs::read_to_string(path)
+            .map_err(|error| format!("reading {} failed: {error}", path.display()))?
+            .parse()
+            .map_err(|error| format!("invalid configuration {}: {error}", path.display()).into())
+    }
+
+    /// Creates a [`Builder`] with this configuration.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the configuration is invalid or requires a feature that is not enabled.
+    pub fn builder(&self) -> Result<Builder, Box<dyn Error + Send + Sync>> {
+        let mut builder = Builder::new()
+            .on_link_gateways(self.on_link_gateways)
+            .gateway_hardware_addresses(self.gateway_hardware_addresses)
+            .restart_on_error(self.restart_on_error);
+        if let Some(debounce) = self.debounce {
+            builder = builder.debounce(Duration::from_millis(debounce));
+        }
+        if let Some(coalesce) = self.coalesce {
+            builder = builder.coalesce(Duration::from_millis(coalesce));
+        }
+        if let Some(heartbeat) = self.heartbeat {
+            builder = builder.heartbeat(Duration::from_millis(heartbeat));
+        }
+        for &destination in &self.route_lookup {
+            builder = builder.route_lookup(destination);
+        }
+        for name in &self.ignore_interfaces {
+            builder = builder.ignore_interface(name.clone());
+        }
+        for prefix in &self.allow_hardware_addresses {
+            builder = builder.allow_hardware_address(hardware_address(prefix)?);
+        }
+        if let Some(ignore) = self.ignore_loopback {
+            builder = builder.ignore_loopback(ignore);
+        }
+        for name in &self.ignore_families {
+            let family = match name.as_str() {
+                "ipv4" => IpFamily::V4,
+                "ipv6" => IpFamily::V6,
+                _ => return Err(format!("unknown ip type {name:?}, expected ipv4 or ipv6").into()),
+            };
+            builder = builder.ignore_family(family);
+        }
+        if !self.dispatch.is_empty() {
+            let dispatcher = self
+                .dispatch
+                .iter()
+                .fold(Dispatcher::new(), |dispatcher, command| {
+                    dispatcher.command(command)
+                });
+            builder = builder.dispatcher(dispatcher);
+        }
+        if let Some(ref probe) = self.probe {
+            builder = probe.apply(builder)?;
+        }
+        #[cfg(feature = "webhook")]
+        for url in &self.webhooks {
+            crate::webhook::parse(core::slice::from_ref(url))
+                .map_err(|error| format!("invalid webhook: {error}"))?;
+            builder = builder.webhook(url.clone());
+        }
+        #[cfg(not(feature = "webhook"))]
+        if !self.webhooks.is_empty() {
+            return Err("webhooks require the webhook feature".into());
+        }
+        #[cfg(feature = "mqtt")]
+        for url in &self.mqtt {
+            crate::mqtt::parse(url).map_err(|error| format!("invalid mqtt: {error}"))?;
+            builder = builder.mqtt(url.clone());
+        }
+        #[cfg(not(feature = "mqtt"))]
+        if !self.mqtt.is_empty() {
+            return Err("mqtt requires the mqtt feature".into());
+        }
+        Ok(builder)
+    }
+}
+impl FromStr for Config {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    /// Parses and validates a toml configuration.
+    fn from_str(config: &str) -> Result<Self, Self::Err> {
+        let config: Self = toml::from_str(config)?;
+        config.builder()?;
+        Ok(config)
+    }
+}
+impl ProbeConfig {
+    /// Adds the configured probes to `builder`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a preset, url or dns resolver is invalid or the quorum can not be reached.
+    #[cfg(feature = "probe")]
+    fn apply(&self, mut builder: Builder) -> Result<Builder, Box<dyn Error + Send + Sync>> {
+        let timeout = self
+            .timeout
+            .map_or(crate::probe::PROBE_TIMEOUT, Duration::from_millis);
+        for name in &self.presets {
+            let preset = match name.as_str() {
+                "cloudflare" => Preset::Cloudflare,
+                "google" => Preset::Google,
+                "microsoft" => Preset::Microsoft,
+                "apple" => Preset::Apple,
+                _ => return Err(format!("unknown probe preset {name:?}, expected cloudflare, google, microsoft or apple").into()),
+            };
+            builder = builder.probe_with_timeout(preset, timeout);
+        }
+        for url in &self.urls {
+            let probe = HttpProbe::new(url).map_err(|error| format!("invalid probe: {error}"))?;
+            builder = builder.probe_with_timeout(probe, timeout);
+        }
+        for name in &self.dns {
+            let probe = match name.as_str() {
+                "cloudflare" => DnsProbe::CLOUDFLARE,
+                "google" => DnsProbe::GOOGLE,
+                "quad9" => DnsProbe::QUAD9,
+                _ => {
+                    return Err(format!(
+                        "unknown dns probe {name:?}, expected cloudflare, google or quad9"
+                    )
+                    .into())
+                }
+            };
+            builder = builder.probe_with_timeout(probe, timeout);
+        }
+        if let Some(quorum) = self.quorum {
+            let probes = self
+                .presets
+                .len()
+                .saturating_add(self.urls.len())
+                .saturating_add(self.dns.len());
+            if quorum == 0 || quorum > probes {
+                return Err(format!(
+                    "the probe quorum {quorum} must be between 1 and the amount of probes {probes}"
+                )
+                .into());
+            }
+            builder = builder.probe_quorum(quorum);
+        }
+        if let Some(interval) = self.interval {
+            builder = builder.probe_interval(Duration::from_millis(interval));
+        }
+        Ok(builder)
+    }
+
+    /// Fails because probes require the `probe` feature.
+    ///
+    /// # Errors
+    ///
+    /// This function always returns an error.
+    #[cfg(not(feature = "probe"))]
+    #[allow(clippy::unused_self)]
+    fn apply(&self, _: Builder) -> Result<Builder, Box<dyn Error + Send + Sync>> {
+        Err("probes require the probe feature".into())
+    }
+}
@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: MIT
+
+//! The daemon which shares the connectivity with local processes over a unix socket or a windows named pipe.
+//!
+//! Every connected client receives all detailed [events](NetworkEvent) as single lines of json.
+//! A client can send a line containing `GET` to receive the current connectivity as a single line of json.
+//! A client which does not keep up with the events receives the amount of events it missed followed by the current connectivity,
+//! so it can resynchronize.
+
+use crate::{Connectivity, NetworkEvent};
+use futures::{pin_mut, Stream, StreamExt};
+use log::{debug, warn};
+use serde::Serialize;
+use std::{error::Error, sync::Arc};
+#[cfg(windows)]
+use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::{broadcast, mpsc::UnboundedReceiver, watch},
+};
+
+/// The amount of serialized events that are buffered for a client which is not reading
+const CLIENT_BUFFER: usize = 64;
+
+/// A message sent to clients.
+#[derive(Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+enum Message<'a> {
+    /// A detailed event
+    Event(&'a NetworkEvent),
+    /// The current connectivity as response to `GET` or after [`Message::Lagged`], [`None`] when it is not known yet
+    State(Option<Connectivity>),
+    /// The amount of events the client missed because it did not read them in time
+    Lagged(u64),
+    /// The response to a request that is not understood
+    Error(&'a str),
+}
+impl Message<'_> {
+    /// Serialize to a single line of json
+    fn to_line(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let mut line = serde_json::to_string(self)?;
+        line.push('\n');
+        Ok(line)
+    }
+}
+
+/// Get the json schema of the lines sent to clients.
+///
+/// It can be used to validate the output of the daemon or to generate bindings for other languages.
+#[cfg(feature = "schemars")]
+#[allow(clippy::must_use_candidate)]
+pub fn schema() -> schemars::schema::RootSchema {
+    schemars::schema_for!(Message<'_>)
+}
+
+/// Shares the events received from `events` with the clients from `clients`.
+///
+/// # Notes
+///
+/// This function completes when `events` or `clients` is closed.
+///
+/// # Errors
+///
+/// This function will return an error if accepting a client or serializing an event failed.
+async fn serve<S>(
+    clients: impl Stream<Item = Result<S, Box<dyn Error + Send + Sync>>>,
+    mut events: UnboundedReceiver<NetworkEvent>,
+) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    pin_mut!(clients);
+    let (events_tx, _) = broadcast::channel::<Arc<str>>(CLIENT_BUFFER);
+    let (state_tx, state_rx) = watch::channel(None);
+
+    loop {
+        tokio::select! {
+            biased;
+            event = events.recv() => {
+                let event = match event {
+                    Some(event) => event,
+                    None => {
+                        debug!("no more events");
+                        break;
+                    }
+                };
+                if let NetworkEvent::ConnectivityChanged { connectivity, .. } = event {
+                    state_tx.send_replace(Some(connectivity));
+                }
+                // there being no clients is not an error
+                drop(events_tx.send(Message::Event(&event).to_line()?.into()));
+            },
+            stream = clients.next() => {
+                let stream = match stream {
+                    Some(stream) => stream?,
+                    None => {
+                        debug!("no more clients");
+                        break;
+                    }
+                };
+                debug!("accepted client");
+                let client = client(stream, events_tx.subscribe(), state_rx.clone());
+                tokio::spawn(async {
+                    if let Err(error) = client.await {
+                        warn!("client failed {error}");
+                    }
+                });
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Serves a single client.
+///
+/// # Errors
+///
+/// This function will return an error if reading from or writing to the client failed.
+async fn client(
+    stream: impl AsyncRead + AsyncWrite,
+    mut events: broadcast::Receiver<Arc<str>>,
+    state: watch::Receiver<Option<Connectivity>>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (read, mut write) = tokio::io::split(stream);
+    let mut lines = BufReader::new(read).lines();
+
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let line = match line? {
+                    Some(line) => line,
+                    None => break,
+                };
+                let response = if line.trim() == "GET" {
+                    Message::State(*state.borrow())
+                } else {
+                    Message::Error("unknown request")
+                };
+                write.write_all(response.to_line()?.as_bytes()).await?;
+            },
+            event = events.recv() => match event {
+                Ok(line) => write.write_all(line.as_bytes()).await?,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("client missed {skipped} events");
+                    write.write_all(Message::Lagged(skipped).to_line()?.as_bytes()).await?;
+                    let current = Message::State(*state.borrow());
+                    write.write_all(current.to_line()?.as_bytes()).await?;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+
+    debug!("client disconnected");
+    Ok(())
+}
+
+/// Shares the events received from `events` with the clients connecting to `listener`.
+///
+/// # Notes
+///
+/// This function completes when `events` is closed.
+///
+/// # Errors
+///
+/// This function will return an error if accepting a client or serializing an event failed.
+#[cfg(unix)]
+pub async fn serve_unix(
+    listener: UnixListener,
+    events: UnboundedReceiver<NetworkEvent>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let clients = futures::stream::unfold(listener, |listener| async {
+        let client = listener
+            .accept()
+            .await
+            .map(|(stream, _)| stream)
+            .map_err(Into::into);
+        Some((client, listener))
+    });
+    serve(clients, events).await
+}
+
+/// Creates the next instance of the named pipe and waits for a client to connect to the current instance.
+///
+/// # Errors
+///
+/// This function will return an error if creating or connecting the named pipe failed.
+#[cfg(windows)]
+async fn connect_named_pipe(
+    name: &str,
+    server: NamedPipeServer,
+) -> Result<(NamedPipeServer, NamedPipeServer), Box<dyn Error + Send + Sync>> {
+    server.connect().await?;
+    let next = ServerOptions::new().create(name)?;
+    Ok((server, next))
+}
+
+/// Shares the events received from `events` with the clients connecting to the named pipe `name`.
+///
+/// # Notes
+///
+/// This function completes when `events` is closed.
+///
+/// # Errors
+///
+/// This function will return an error if creating the named pipe, accepting a client or serializing an event failed.
+#[cfg(windows)]
+pub async fn serve_named_pipe(
+    name: &str,
+    events: UnboundedReceiver<NetworkEvent>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let first = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(name)?;
+    let clients = futures::stream::unfold(Some(first), |server| async {
+        let result = connect_named_pipe(name, server?).await;
+        match result {
+            Ok((connected, next)) => Some((Ok(connected), Some(next))),
+            Err(error) => Some((Err(error), None)),
+        }
+    });
+    serve(clients, events).await
+}
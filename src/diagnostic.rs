@@ -0,0 +1,188 @@
+// SPDX-License-Identifier: MIT
+
+//! Non-fatal problems of the driver which would otherwise only be logged.
+
+use crate::{
+    state::{HardwareAddress, InterfaceId},
+    IpFamily,
+};
+use core::fmt::Display;
+use std::net::IpAddr;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// A non-fatal problem the driver recovered from, see [`Builder::diagnostics`](crate::Builder::diagnostics).
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[non_exhaustive]
+pub enum Diagnostic {
+    /// Looking up a route to a destination failed, the destination is considered unreachable
+    RouteLookupFailed {
+        /// The destination of the route lookup
+        destination: IpAddr,
+        /// The description of the error
+        error: String,
+    },
+    /// Handling a change notification of the platform failed, the change is picked up with the next notification
+    NotificationFailed {
+        /// The description of the error
+        error: String,
+    },
+    /// The platform listener failed and is restarted, see [`Builder::restart_on_error`](crate::Builder::restart_on_error)
+    PlatformFailed {
+        /// The description of the error
+        error: String,
+    },
+    /// The platform listener was started anew after the previous one completed or failed, the state of the system is taken anew
+    Resynchronized,
+    /// A probe could not verify internet connectivity over an ip type
+    ProbeFailed {
+        /// The ip type the probe ran over
+        family: IpFamily,
+        /// Whether the probe took longer than its timeout
+        timed_out: bool,
+    },
+    /// Loading or persisting the connectivity in the [store](crate::Store) failed
+    StoreFailed {
+        /// The description of the error
+        error: String,
+    },
+    /// Running or waiting on a command of the [dispatcher](crate::Dispatcher) failed
+    DispatchFailed {
+        /// The command that was run
+        command: String,
+        /// The description of the error
+        error: String,
+    },
+    /// Delivering a transition to a webhook or mqtt broker failed, it is retried later
+    DeliveryFailed {
+        /// The url of the endpoint
+        endpoint: String,
+        /// The description of the error
+        error: String,
+    },
+    /// Watching the dns configuration failed, no more dns changes are reported
+    DnsWatchFailed {
+        /// The description of the error
+        error: String,
+    },
+    /// Writing the capture failed, nothing more is captured
+    CaptureFailed {
+        /// The description of the error
+        error: String,
+    },
+    /// Another host uses an address of this system, which seems connected while its traffic silently fails.
+    ///
+    /// Is reported once when duplicate address detection of the platform flags an address as [duplicate](crate::state::AddressFlags::duplicate)
+    /// or, with the `arp` feature on linux, when another host claims it in an arp packet.
+    AddressConflict {
+        /// The index of the interface with the address
+        index: InterfaceId,
+        /// The address which is used by another host
+        address: IpAddr,
+        /// The hardware address of the other host, [`None`] when duplicate address detection of the platform found the conflict
+        hardware_address: Option<HardwareAddress>,
+    },
+    /// Watching the address resolution for conflicts failed, no more conflicts are detected that way
+    ConflictWatchFailed {
+        /// The description of the error
+        error: String,
+    },
+}
+impl Diagnostic {
+    /// The name of the kind of this diagnostic as used in metric labels
+    #[cfg(feature = "metrics")]
+    pub(crate) const fn name(&self) -> &'static str {
+        match *self {
+            Self::RouteLookupFailed { .. } => "route_lookup_failed",
+            Self::NotificationFailed { .. } => "notification_failed",
+            Self::PlatformFailed { .. } => "platform_failed",
+            Self::Resynchronized => "resynchronized",
+            Self::ProbeFailed { .. } => "probe_failed",
+            Self::StoreFailed { .. } => "store_failed",
+            Self::DispatchFailed { .. } => "dispatch_failed",
+            Self::DeliveryFailed { .. } => "delivery_failed",
+            Self::DnsWatchFailed { .. } => "dns_watch_failed",
+            Self::CaptureFailed { .. } => "capture_failed",
+            Self::AddressConflict { .. } => "address_conflict",
+            Self::ConflictWatchFailed { .. } => "conflict_watch_failed",
+        }
+    }
+}
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Self::RouteLookupFailed {
+                destination,
+                ref error,
+            } => write!(f, "route lookup for {destination} failed {error}"),
+            Self::NotificationFailed { ref error } => {
+                write!(f, "handling a change notification failed {error}")
+            }
+            Self::PlatformFailed { ref error } => {
+                write!(f, "platform listener failed {error}")
+            }
+            Self::Resynchronized => write!(f, "restarted the platform listener"),
+            Self::ProbeFailed { family, timed_out } => {
+                if timed_out {
+                    write!(f, "probe over {family} timed out")
+                } else {
+                    write!(f, "probe over {family} failed")
+                }
+            }
+            Self::StoreFailed { ref error } => write!(f, "store failed {error}"),
+            Self::DispatchFailed {
+                ref command,
+                ref error,
+            } => write!(f, "dispatching to {command} failed {error}"),
+            Self::DeliveryFailed {
+                ref endpoint,
+                ref error,
+            } => write!(f, "delivering to {endpoint} failed {error}"),
+            Self::DnsWatchFailed { ref error } => {
+                write!(f, "watching the dns configuration failed {error}")
+            }
+            Self::CaptureFailed { ref error } => write!(f, "writing the capture failed {error}"),
+            Self::AddressConflict {
+                index,
+                address,
+                ref hardware_address,
+            } => {
+                write!(f, "{address} of interface {index} is used by another host")?;
+                if let Some(ref hardware_address) = *hardware_address {
+                    let hardware_address: Vec<String> = hardware_address
+                        .iter()
+                        .map(|byte| format!("{byte:02x}"))
+                        .collect();
+                    write!(f, " with {}", hardware_address.join(":"))?;
+                }
+                Ok(())
+            }
+            Self::ConflictWatchFailed { ref error } => {
+                write!(f, "watching for address conflicts failed {error}")
+            }
+        }
+    }
+}
+
+/// The transmit end of the diagnostics channel, diagnostics are dropped when none is configured.
+#[derive(Clone, Default)]
+pub struct Diagnostics(Option<UnboundedSender<Diagnostic>>);
+impl Diagnostics {
+    /// Sends diagnostics to `sender`.
+    pub(crate) const fn new(sender: UnboundedSender<Diagnostic>) -> Self {
+        Self(Some(sender))
+    }
+
+    /// The transmit end of the channel when one is configured
+    pub(crate) const fn sender(&self) -> Option<&UnboundedSender<Diagnostic>> {
+        self.0.as_ref()
+    }
+
+    /// Counts `diagnostic` and sends it when a channel is configured, a closed channel is ignored.
+    pub(crate) fn report(&self, diagnostic: Diagnostic) {
+        #[cfg(feature = "metrics")]
+        metrics::counter!("network_connectivity_errors_total", 1, "kind" => diagnostic.name());
+        if let Some(ref sender) = self.0 {
+            sender.send(diagnostic).ok();
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// SPDX-License-Identifier: MIT
+
+//! Runs commands when events are emitted, similar to NetworkManager dispatcher scripts.
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    NetworkEvent,
+};
+use log::{debug, warn};
+use std::{ffi::OsString, process::Command, thread};
+
+/// Runs commands when the connectivity, the network or the mtu changes or a captive portal is detected.
+///
+/// Every command is run without arguments and with environment variables describing the event.
+/// `CONNECTIVITY_EVENT` is `connectivity`, `network`, `mtu` or `portal`.
+///
+/// For `connectivity` every command is run once for each ip family of which the state changed with:
+/// - `CONNECTIVITY_FAMILY`: `ipv4` or `ipv6`
+/// - `CONNECTIVITY_OLD`: the previous state, absent for the initial connectivity
+/// - `CONNECTIVITY_NEW`: the new state
+/// - `CONNECTIVITY_VERIFIED`: `true` when active probes verified the internet connectivity, otherwise `false`
+///
+/// For `network` every command is run once with:
+/// - `CONNECTIVITY_INTERFACE`: the index of the interface
+/// - `CONNECTIVITY_INTERFACE_NAME`: the name of the interface, absent when it is not known
+/// - `CONNECTIVITY_FINGERPRINT`: the fingerprint of the new network
+///
+/// For `mtu` every command is run once with:
+/// - `CONNECTIVITY_INTERFACE`: the index of the interface
+/// - `CONNECTIVITY_INTERFACE_NAME`: the name of the interface, absent when it is not known
+/// - `CONNECTIVITY_OLD`: the previous mtu
+/// - `CONNECTIVITY_NEW`: the new mtu
+///
+/// For `portal` every command is run once with:
+/// - `CONNECTIVITY_PORTAL_URL`: the url of the login page, absent when the portal did not redirect
+///
+/// The states are `none`, `network` or `internet`.
+#[derive(Clone, Debug, Default)]
+pub struct Dispatcher {
+    /// The commands to run
+    commands: Vec<OsString>,
+}
+impl Dispatcher {
+    /// Create a new [`Dispatcher`] instance without commands
+    #[allow(clippy::must_use_candidate)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a command to run
+    #[must_use]
+    pub fn command(mut self, program: impl Into<OsString>) -> Self {
+        self.commands.push(program.into());
+        self
+    }
+
+    /// Run the commands for an event, failures are reported to `diagnostics`
+    pub(crate) fn dispatch(&self, event: &NetworkEvent, diagnostics: &Diagnostics) {
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *event {
+            NetworkEvent::ConnectivityChanged {
+                connectivity,
+                previous,
+                verified,
+                ..
+            } => {
+                let families = [
+                    ("ipv4", connectivity.ipv4, previous.map(|old| old.ipv4)),
+                    ("ipv6", connectivity.ipv6, previous.map(|old| old.ipv6)),
+                ];
+                for (family, new, old) in families {
+                    if old != Some(new) {
+                        let mut variables = vec![
+                            ("CONNECTIVITY_EVENT", "connectivity".to_owned()),
+                            ("CONNECTIVITY_FAMILY", family.to_owned()),
+                            ("CONNECTIVITY_NEW", new.to_string()),
+                            ("CONNECTIVITY_VERIFIED", verified.to_string()),
+                        ];
+                        if let Some(old) = old {
+                            variables.push(("CONNECTIVITY_OLD", old.to_string()));
+                        }
+                        self.run(&variables, diagnostics);
+                    }
+                }
+            }
+            NetworkEvent::NetworkChanged {
+                index,
+                ref name,
+                fingerprint,
+            } => {
+                let mut variables = vec![
+                    ("CONNECTIVITY_EVENT", "network".to_owned()),
+                    ("CONNECTIVITY_INTERFACE", index.to_string()),
+                    ("CONNECTIVITY_FINGERPRINT", fingerprint.to_string()),
+                ];
+                if let Some(ref name) = *name {
+                    variables.push(("CONNECTIVITY_INTERFACE_NAME", name.clone()));
+                }
+                self.run(&variables, diagnostics);
+            }
+            NetworkEvent::MtuChanged {
+                index,
+                ref name,
+                mtu,
+                previous,
+            } => {
+                let mut variables = vec![
+                    ("CONNECTIVITY_EVENT", "mtu".to_owned()),
+                    ("CONNECTIVITY_INTERFACE", index.to_string()),
+                    ("CONNECTIVITY_OLD", previous.to_string()),
+                    ("CONNECTIVITY_NEW", mtu.to_string()),
+                ];
+                if let Some(ref name) = *name {
+                    variables.push(("CONNECTIVITY_INTERFACE_NAME", name.clone()));
+                }
+                self.run(&variables, diagnostics);
+            }
+            NetworkEvent::CaptivePortalDetected { ref url } => {
+                let mut variables = vec![("CONNECTIVITY_EVENT", "portal".to_owned())];
+                if let Some(ref url) = *url {
+                    variables.push(("CONNECTIVITY_PORTAL_URL", url.clone()));
+                }
+                self.run(&variables, diagnostics);
+            }
+            _ => {}
+        }
+    }
+
+    /// Run the commands with the environment variables, failures are reported to `diagnostics`
+    fn run(&self, variables: &[(&str, String)], diagnostics: &Diagnostics) {
+        for program in &self.commands {
+            debug!("dispatching to {program:?}");
+            let mut command = Command::new(program);
+            command.envs(variables.iter().map(|&(key, ref value)| (key, value)));
+            match command.spawn() {
+                Ok(mut child) => {
+                    // wait on the child in the background so it does not linger after exiting
+                    let diagnostics = diagnostics.clone();
+                    let program = program.clone();
+                    thread::spawn(move || {
+                        if let Err(error) = child.wait() {
+                            warn!("waiting on dispatched command failed {error}");
+                            diagnostics.report(Diagnostic::DispatchFailed {
+                                command: program.to_string_lossy().into_owned(),
+                                error: error.to_string(),
+                            });
+                        }
+                    });
+                }
+                Err(error) => {
+                    warn!("dispatching to {program:?} failed {error}");
+                    diagnostics.report(Diagnostic::DispatchFailed {
+                        command: program.to_string_lossy().into_owned(),
+                        error: error.to_string(),
+                    });
+                }
+            }
+        }
+    }
+}
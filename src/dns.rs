@@ -0,0 +1,566 @@
+// SPDX-License-Identifier: MIT
+
+//! Watches the dns configuration of the system.
+//!
+//! On linux `/etc/resolv.conf` is watched with inotify.
+//! The directories of the configuration are watched instead of the files because resolvers replace the file instead of writing to it.
+//! When the configuration points at the stub resolver of systemd-resolved the upstream configuration of systemd-resolved is reported instead.
+//! The nameservers of every link and the dnssec and dns over tls settings of systemd-resolved are queried over d-bus with `busctl`
+//! because resolv.conf can not express them.
+//! On windows the nameservers and dns suffixes of the adapters that are up are read with `GetAdaptersAddresses`,
+//! they are not notified in a way that is practical to receive so they are polled.
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    state::InterfaceId,
+};
+use futures::Future;
+use log::warn;
+use std::{net::IpAddr, sync::Arc};
+use tokio::sync::{watch, Notify};
+
+/// The dns configuration of the system.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub(crate) struct Configuration {
+    /// The nameservers in the order they are used
+    pub(crate) nameservers: Vec<IpAddr>,
+    /// The domains appended to names which are not fully qualified
+    pub(crate) search: Vec<String>,
+    /// Whether the stub resolver of systemd-resolved is used
+    pub(crate) resolved: bool,
+    /// The nameservers systemd-resolved or windows uses for every link, index 0 for the global nameservers of systemd-resolved
+    pub(crate) link_nameservers: Vec<(InterfaceId, IpAddr)>,
+    /// The dnssec setting of systemd-resolved
+    pub(crate) dnssec: Option<String>,
+    /// The dns over tls setting of systemd-resolved
+    pub(crate) dns_over_tls: Option<String>,
+}
+
+/// Reads and watches the configuration on linux.
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::Configuration;
+    use crate::state::InterfaceId;
+    use log::debug;
+    use std::{
+        error::Error,
+        ffi::CString,
+        fs, io,
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        os::unix::io::{AsRawFd, FromRawFd, OwnedFd},
+        path::Path,
+        process::Command,
+    };
+    use tokio::{
+        io::unix::AsyncFd,
+        sync::{watch, Notify},
+    };
+
+    /// The configuration of the resolver of the system
+    const RESOLV_CONF: &str = "/etc/resolv.conf";
+    /// The upstream configuration of systemd-resolved
+    const RESOLVED_CONF: &str = "/run/systemd/resolve/resolv.conf";
+    /// The addresses of the stub resolvers of systemd-resolved
+    const RESOLVED_STUBS: [IpAddr; 2] = [
+        IpAddr::V4(Ipv4Addr::new(127, 0, 0, 53)),
+        IpAddr::V4(Ipv4Addr::new(127, 0, 0, 54)),
+    ];
+    /// The directory of the files managed by systemd-resolved
+    const RESOLVED_DIRECTORY: &str = "/run/systemd/resolve";
+    /// The changes of a directory after which the configuration is read again
+    const WATCHED_CHANGES: u32 = libc::IN_CLOSE_WRITE
+        | libc::IN_CREATE
+        | libc::IN_DELETE
+        | libc::IN_MOVED_FROM
+        | libc::IN_MOVED_TO;
+
+    /// Reads the configuration of the system, a missing file is an empty configuration.
+    ///
+    /// systemd-resolved is queried when resolv.conf points at its stub resolver or is one of its files.
+    pub(super) fn read() -> Configuration {
+        let (mut nameservers, mut search) =
+            parse(&fs::read_to_string(RESOLV_CONF).unwrap_or_default());
+        let resolved = !nameservers.is_empty()
+            && nameservers
+                .iter()
+                .all(|nameserver| RESOLVED_STUBS.contains(nameserver));
+        if resolved {
+            if let Ok(upstream) = fs::read_to_string(RESOLVED_CONF) {
+                (nameservers, search) = parse(&upstream);
+            }
+        }
+        let mut configuration = Configuration {
+            nameservers,
+            search,
+            resolved,
+            ..Configuration::default()
+        };
+        let managed = fs::canonicalize(RESOLV_CONF)
+            .map_or(false, |target| target.starts_with(RESOLVED_DIRECTORY));
+        if resolved || managed {
+            configuration.link_nameservers = resolved_property("DNS")
+                .and_then(|property| {
+                    parse_link_nameservers(&property)
+                        .ok_or_else(|| format!("unexpected nameservers {property}").into())
+                })
+                .unwrap_or_else(|error| {
+                    debug!("querying the nameservers of systemd-resolved failed {error}");
+                    Vec::new()
+                });
+            configuration.dnssec = resolved_string("DNSSEC");
+            configuration.dns_over_tls = resolved_string("DNSOverTLS");
+        }
+        configuration
+    }
+
+    /// Parses the nameservers and search domains of a resolv.conf.
+    ///
+    /// The `domain` keyword is used as search domain when there is no `search` keyword.
+    fn parse(contents: &str) -> (Vec<IpAddr>, Vec<String>) {
+        let mut nameservers = Vec::new();
+        let mut search = None;
+        let mut domain = None;
+        for line in contents.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("nameserver") => {
+                    // link local nameservers are scoped to their interface with a suffix
+                    if let Some(nameserver) = words
+                        .next()
+                        .and_then(|nameserver| nameserver.split('%').next())
+                        .and_then(|nameserver| nameserver.parse().ok())
+                    {
+                        nameservers.push(nameserver);
+                    }
+                }
+                Some("search") => search = Some(words.map(str::to_owned).collect()),
+                Some("domain") => domain = words.next().map(str::to_owned),
+                _ => {}
+            }
+        }
+        (
+            nameservers,
+            search.unwrap_or_else(|| domain.into_iter().collect()),
+        )
+    }
+
+    /// Gets a property of the manager of systemd-resolved over d-bus, in the text format of `busctl`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `busctl` could not be run or systemd-resolved did not answer.
+    fn resolved_property(name: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let output = Command::new("busctl")
+            .args([
+                "get-property",
+                "org.freedesktop.resolve1",
+                "/org/freedesktop/resolve1",
+                "org.freedesktop.resolve1.Manager",
+                name,
+            ])
+            .output()?;
+        if !output.status.success() {
+            return Err(format!(
+                "busctl failed with {} {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Gets a string property of the manager of systemd-resolved, [`None`] when it could not be queried.
+    fn resolved_string(name: &str) -> Option<String> {
+        match resolved_property(name) {
+            Ok(property) => property
+                .trim()
+                .strip_prefix("s ")
+                .map(|value| value.trim_matches('"').to_owned()),
+            Err(error) => {
+                debug!("querying {name} of systemd-resolved failed {error}");
+                None
+            }
+        }
+    }
+
+    /// Parses the `DNS` property of systemd-resolved.
+    ///
+    /// `busctl` prints it as `a(iiay)` followed by the amount of nameservers
+    /// and for each of them the interface index, the address family, the amount of bytes and the bytes of the address.
+    fn parse_link_nameservers(property: &str) -> Option<Vec<(InterfaceId, IpAddr)>> {
+        let mut words = property.split_whitespace();
+        if words.next()? != "a(iiay)" {
+            return None;
+        }
+        let count: usize = words.next()?.parse().ok()?;
+        let mut nameservers = Vec::new();
+        for _ in 0..count {
+            let index = InterfaceId::new(words.next()?.parse().ok()?);
+            let family: i32 = words.next()?.parse().ok()?;
+            let length: usize = words.next()?.parse().ok()?;
+            let bytes = (0..length)
+                .map(|_| words.next()?.parse().ok())
+                .collect::<Option<Vec<u8>>>()?;
+            let address = match family {
+                libc::AF_INET => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+                libc::AF_INET6 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+                _ => continue,
+            };
+            nameservers.push((index, address));
+        }
+        Some(nameservers)
+    }
+
+    /// An inotify instance which watches directories for changes.
+    struct Inotify(AsyncFd<OwnedFd>);
+    impl Inotify {
+        /// Creates the instance watching `directories`, directories which do not exist are skipped.
+        ///
+        /// # Errors
+        ///
+        /// This function will return an error if the instance could not be created or none of the directories could be watched.
+        fn new(directories: &[&Path]) -> Result<Self, Box<dyn Error + Send + Sync>> {
+            // SAFETY:
+            // the descriptor is owned from here on and closed on drop
+            let descriptor = unsafe {
+                let descriptor = libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC);
+                if descriptor < 0 {
+                    return Err(io::Error::last_os_error().into());
+                }
+                OwnedFd::from_raw_fd(descriptor)
+            };
+            let mut watched = false;
+            for directory in directories {
+                let path = CString::new(directory.as_os_str().to_string_lossy().as_bytes())?;
+                // SAFETY:
+                // the path is a nul terminated string that lives during the call
+                if unsafe {
+                    libc::inotify_add_watch(descriptor.as_raw_fd(), path.as_ptr(), WATCHED_CHANGES)
+                } < 0
+                {
+                    debug!(
+                        "not watching {}: {}",
+                        directory.display(),
+                        io::Error::last_os_error()
+                    );
+                } else {
+                    watched = true;
+                }
+            }
+            if !watched {
+                return Err(
+                    "none of the directories of the dns configuration could be watched".into(),
+                );
+            }
+            Ok(Self(AsyncFd::new(descriptor)?))
+        }
+
+        /// Completes when a watched directory changed.
+        ///
+        /// # Errors
+        ///
+        /// This function will return an error if the changes could not be read.
+        async fn changed(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+            let mut buffer = [0_u8; 4096];
+            loop {
+                let mut guard = self.0.readable().await?;
+                // the changes themselves are not needed, the configuration is read again anyway
+                if let Ok(result) = guard.try_io(|descriptor| {
+                    // SAFETY:
+                    // the buffer is valid for its length
+                    let read = unsafe {
+                        libc::read(
+                            descriptor.as_raw_fd(),
+                            buffer.as_mut_ptr().cast(),
+                            buffer.len(),
+                        )
+                    };
+                    if read < 0 {
+                        Err(io::Error::last_os_error())
+                    } else {
+                        Ok(())
+                    }
+                }) {
+                    return result.map_err(Into::into);
+                }
+            }
+        }
+    }
+
+    /// Reads the configuration whenever its directories changed and sends it when it differs from the previous one.
+    ///
+    /// # Notes
+    ///
+    /// This function completes when the receive end is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the directories could not be watched.
+    pub(super) async fn run(
+        configurations: &watch::Sender<Option<Configuration>>,
+        wake: &Notify,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut directories = vec![Path::new("/etc").to_path_buf()];
+        // resolv.conf is usually a link to a file managed by a resolver
+        if let Some(target) = fs::canonicalize(RESOLV_CONF)
+            .ok()
+            .and_then(|target| target.parent().map(Path::to_path_buf))
+        {
+            directories.push(target);
+        }
+        directories.push(Path::new(RESOLVED_DIRECTORY).to_path_buf());
+        directories.dedup();
+        let directories: Vec<&Path> = directories
+            .iter()
+            .map(|directory| directory.as_path())
+            .collect();
+        let inotify = Inotify::new(&directories)?;
+        loop {
+            let configuration = read();
+            if configurations.borrow().as_ref() != Some(&configuration) {
+                debug!("dns configuration changed {configuration:?}");
+                configurations.send_replace(Some(configuration));
+                wake.notify_one();
+            }
+            #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+            {
+                tokio::select! {
+                    result = inotify.changed() => result?,
+                    _ = configurations.closed() => return Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// Reads the configuration on windows.
+#[cfg(windows)]
+mod platform {
+    use super::Configuration;
+    use crate::state::InterfaceId;
+    use core::{mem::size_of, ptr};
+    use log::debug;
+    use std::{
+        error::Error,
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        time::Duration,
+    };
+    use tokio::{
+        sync::{watch, Notify},
+        time::{interval, MissedTickBehavior},
+    };
+    use windows::Win32::{
+        Foundation::{ERROR_BUFFER_OVERFLOW, NO_ERROR},
+        NetworkManagement::{
+            IpHelper::{
+                GetAdaptersAddresses, GAA_FLAG_SKIP_ANYCAST, GAA_FLAG_SKIP_FRIENDLY_NAME,
+                GAA_FLAG_SKIP_MULTICAST, GAA_FLAG_SKIP_UNICAST, IP_ADAPTER_ADDRESSES_LH,
+            },
+            Ndis::IfOperStatusUp,
+        },
+        Networking::WinSock::{
+            ADDRESS_FAMILY, AF_INET, AF_INET6, AF_UNSPEC, SOCKADDR_IN, SOCKADDR_IN6, SOCKET_ADDRESS,
+        },
+    };
+
+    /// How often the configuration is read
+    const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Converts the socket address of a dns server to an [`IpAddr`], [`None`] when it is not an ip address.
+    fn ip_address(address: &SOCKET_ADDRESS) -> Option<IpAddr> {
+        if address.lpSockaddr.is_null() {
+            return None;
+        }
+        let length = usize::try_from(address.iSockaddrLength).ok()?;
+        // SAFETY:
+        // the address is valid for its length and its family determines its layout, it is read unaligned since it is only aligned for SOCKADDR
+        unsafe {
+            match ADDRESS_FAMILY(u32::from((*address.lpSockaddr).sa_family)) {
+                AF_INET if length >= size_of::<SOCKADDR_IN>() => {
+                    let address = address.lpSockaddr.cast::<SOCKADDR_IN>().read_unaligned();
+                    Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                        address.sin_addr.S_un.S_addr,
+                    ))))
+                }
+                AF_INET6 if length >= size_of::<SOCKADDR_IN6>() => {
+                    let address = address.lpSockaddr.cast::<SOCKADDR_IN6>().read_unaligned();
+                    Some(IpAddr::V6(Ipv6Addr::from(address.sin6_addr.u.Byte)))
+                }
+                _ => None,
+            }
+        }
+    }
+
+    /// Gets the adapters with their dns servers and suffixes, allocated as u64 to align them.
+    fn adapters() -> Vec<u64> {
+        let flags = GAA_FLAG_SKIP_UNICAST
+            | GAA_FLAG_SKIP_ANYCAST
+            | GAA_FLAG_SKIP_MULTICAST
+            | GAA_FLAG_SKIP_FRIENDLY_NAME;
+        let mut size = 16 * 1024_u32;
+        loop {
+            let mut buffer = vec![0; usize::try_from(size).unwrap_or(0) / size_of::<u64>() + 1];
+            // SAFETY:
+            // Invoking an unsafe windows api
+            // the buffer is valid for at least size bytes
+            let result = unsafe {
+                GetAdaptersAddresses(
+                    AF_UNSPEC,
+                    flags,
+                    None,
+                    Some(buffer.as_mut_ptr().cast()),
+                    &mut size,
+                )
+            };
+            if result == ERROR_BUFFER_OVERFLOW.0 {
+                continue;
+            }
+            if result != NO_ERROR.0 {
+                debug!("reading the adapters failed with {result}");
+                return Vec::new();
+            }
+            return buffer;
+        }
+    }
+
+    /// Reads the configuration of the adapters that are up.
+    ///
+    /// The nameservers are ordered by adapter like windows queries them, the search domains are the connection specific dns suffixes.
+    pub(super) fn read() -> Configuration {
+        let mut configuration = Configuration::default();
+        let buffer = adapters();
+        let mut adapter = if buffer.is_empty() {
+            ptr::null()
+        } else {
+            buffer.as_ptr().cast::<IP_ADAPTER_ADDRESSES_LH>()
+        };
+        while !adapter.is_null() {
+            // SAFETY:
+            // the adapters are a linked list within the buffer which ends with a null pointer
+            let current = unsafe { &*adapter };
+            adapter = current.Next;
+            if current.OperStatus != IfOperStatusUp {
+                continue;
+            }
+            // SAFETY:
+            // every variant of the union starts with the index
+            let index = match unsafe { current.Anonymous1.Anonymous.IfIndex } {
+                0 => current.Ipv6IfIndex,
+                index => index,
+            };
+            let index = InterfaceId::new(index);
+            let mut server = current.FirstDnsServerAddress;
+            while !server.is_null() {
+                // SAFETY:
+                // the dns servers are a linked list within the buffer which ends with a null pointer
+                let current = unsafe { &*server };
+                server = current.Next;
+                if let Some(address) = ip_address(&current.Address) {
+                    if !configuration.nameservers.contains(&address) {
+                        configuration.nameservers.push(address);
+                    }
+                    configuration.link_nameservers.push((index, address));
+                }
+            }
+            let mut suffixes = Vec::new();
+            if !current.DnsSuffix.is_null() {
+                // SAFETY:
+                // the suffix is a nul terminated string within the buffer
+                if let Ok(suffix) = unsafe { current.DnsSuffix.to_string() } {
+                    suffixes.push(suffix);
+                }
+            }
+            let mut suffix = current.FirstDnsSuffix;
+            while !suffix.is_null() {
+                // SAFETY:
+                // the suffixes are a linked list within the buffer which ends with a null pointer
+                let current = unsafe { &*suffix };
+                suffix = current.Next;
+                let length = current
+                    .String
+                    .iter()
+                    .position(|&character| character == 0)
+                    .unwrap_or(current.String.len());
+                if let Some(characters) = current.String.get(..length) {
+                    suffixes.push(String::from_utf16_lossy(characters));
+                }
+            }
+            for suffix in suffixes {
+                if !suffix.is_empty() && !configuration.search.contains(&suffix) {
+                    configuration.search.push(suffix);
+                }
+            }
+        }
+        configuration
+    }
+
+    /// Reads the configuration periodically and sends it when it differs from the previous one.
+    ///
+    /// # Notes
+    ///
+    /// This function completes when the receive end is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function does not fail on windows, a configuration that can not be read is reported as empty.
+    #[allow(clippy::unnecessary_wraps)]
+    pub(super) async fn run(
+        configurations: &watch::Sender<Option<Configuration>>,
+        wake: &Notify,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut poll = interval(POLL_INTERVAL);
+        poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+            {
+                tokio::select! {
+                    _ = poll.tick() => {},
+                    _ = configurations.closed() => return Ok(()),
+                }
+            }
+            let configuration = read();
+            if configurations.borrow().as_ref() != Some(&configuration) {
+                debug!("dns configuration changed {configuration:?}");
+                configurations.send_replace(Some(configuration));
+                wake.notify_one();
+            }
+        }
+    }
+}
+
+/// The dns configuration received from the watcher.
+pub(crate) struct Watch {
+    /// The latest configuration, [`None`] until it was read the first time
+    configurations: watch::Receiver<Option<Configuration>>,
+}
+impl Watch {
+    /// Creates the [`Watch`] and the watcher which notifies `wake` of every change of the configuration.
+    ///
+    /// When the configuration can not be watched the error is reported to `diagnostics`.
+    /// The watcher completes when the [`Watch`] is dropped.
+    pub(crate) fn new(
+        wake: Arc<Notify>,
+        diagnostics: Diagnostics,
+    ) -> (Self, impl Future<Output = ()>) {
+        let (configurations_tx, configurations) = watch::channel(None);
+        let watcher = async move {
+            if let Err(error) = platform::run(&configurations_tx, &wake).await {
+                warn!("watching the dns configuration failed {error}");
+                diagnostics.report(Diagnostic::DnsWatchFailed {
+                    error: error.to_string(),
+                });
+            }
+        };
+        (Self { configurations }, watcher)
+    }
+
+    /// Get the configuration when it changed since the last call.
+    pub(crate) fn changed(&mut self) -> Option<Configuration> {
+        if self.configurations.has_changed().unwrap_or(false) {
+            self.configurations.borrow_and_update().clone()
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,1732 @@
+// SPDX-License-Identifier: MIT
+
+//! The platform independent emission of connectivity updates and detailed events.
+
+#[cfg(feature = "json")]
+use crate::capture::Capture;
+#[cfg(feature = "probe")]
+use crate::probe::Verification;
+use crate::{
+    bounded::BoundedSender,
+    diagnostic::{Diagnostic, Diagnostics},
+    state::{
+        subnet, AddressFlags, AddressLease, AddressScope, DormantPolicy, Duplex, HardwareAddress,
+        InterfaceId, InterfaceType, Interfaces, LinkDetails, NetworkIdentity, PrefixLength,
+        RouteDetails, RouterAdvertisement, SystemConnectivity,
+    },
+    Connectivity, ConnectivityState, Dispatcher, InterfaceNames, Inventory, IpFamily, NetworkEvent,
+    NetworkFingerprint, ReachabilityState, RoutingInfo, Source, Store,
+};
+use alloc::borrow::Cow;
+use core::{
+    cmp::{min, Reverse},
+    time::Duration,
+};
+use log::{debug, warn};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    error::Error,
+    net::IpAddr,
+    sync::Arc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{mpsc::UnboundedSender, watch, Notify};
+
+/// The window in which carrier changes are counted when flap suppression is not configured
+const FLAP_WINDOW: Duration = Duration::from_secs(60);
+
+/// The transmit end of a channel to send notifications to.
+#[derive(Clone)]
+pub enum Sender {
+    /// Only sends aggregated connectivity updates
+    Connectivity(UnboundedSender<Connectivity>),
+    /// Sends detailed events
+    Detailed(UnboundedSender<NetworkEvent>),
+    /// Sends detailed events to a channel with a limited capacity
+    Bounded(BoundedSender),
+    /// Only replaces the latest connectivity
+    Watch(watch::Sender<Connectivity>),
+}
+impl Sender {
+    /// Completes when the receive end of the channel is dropped.
+    pub(crate) async fn closed(&self) {
+        match *self {
+            Self::Connectivity(ref tx) => tx.closed().await,
+            Self::Detailed(ref tx) => tx.closed().await,
+            Self::Bounded(ref tx) => tx.closed().await,
+            Self::Watch(ref tx) => tx.closed().await,
+        }
+    }
+}
+
+/// Checks whether a new gateway or subnet appeared on a network that was already known.
+///
+/// The remembered identity is updated with the non empty parts of the current identity.
+fn network_changed(remembered: &mut NetworkIdentity, current: NetworkIdentity) -> bool {
+    let new_gateway =
+        !remembered.gateways.is_empty() && !current.gateways.is_subset(&remembered.gateways);
+    let new_subnet =
+        !remembered.subnets.is_empty() && !current.subnets.is_subset(&remembered.subnets);
+    if !current.gateways.is_empty() {
+        remembered.gateways = current.gateways;
+    }
+    if !current.subnets.is_empty() {
+        remembered.subnets = current.subnets;
+    }
+    new_gateway || new_subnet
+}
+
+/// Checks whether the interface with the `current` details is a different one than the interface with the `known` details.
+///
+/// Details that were not known before or are no longer known do not make it a different interface.
+fn interface_replaced(known: &LinkDetails, current: &LinkDetails) -> bool {
+    matches!((&known.name, &current.name), (&Some(ref known), &Some(ref new)) if known != new)
+        || matches!(
+            (&known.hardware_address, &current.hardware_address),
+            (&Some(ref known), &Some(ref new)) if known != new
+        )
+}
+
+/// The default priorities of the kinds of interfaces, higher is preferred
+const INTERFACE_PRIORITIES: [(InterfaceType, u8); 6] = [
+    (InterfaceType::Ethernet, 50),
+    (InterfaceType::Wireless, 40),
+    (InterfaceType::Ppp, 30),
+    (InterfaceType::Cellular, 20),
+    (InterfaceType::Tunnel, 10),
+    (InterfaceType::Other, 0),
+];
+
+/// Finds the most specific route in `routes` which covers the prefix `prefix` with `prefix_length` through an interface that is up in `carriers`.
+///
+/// Among routes of the same length the one with the lowest metric is preferred.
+fn covering_route<'a>(
+    routes: &'a [RouteDetails],
+    carriers: &HashMap<InterfaceId, bool>,
+    prefix: IpAddr,
+    prefix_length: PrefixLength,
+) -> Option<&'a RouteDetails> {
+    routes
+        .iter()
+        .filter(|route| {
+            route.destination.is_ipv4() == prefix.is_ipv4()
+                && route.prefix_length <= prefix_length
+                && subnet(route.destination, route.prefix_length)
+                    == subnet(prefix, route.prefix_length)
+                && carriers.get(&route.index).copied().unwrap_or(false)
+        })
+        .min_by_key(|route| (Reverse(route.prefix_length), route.priority))
+}
+
+/// Completes at `deadline`, never completes without a deadline.
+pub(crate) async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => futures::future::pending().await,
+    }
+}
+
+/// Derives connectivity updates and detailed events from the [state](Interfaces) and sends them.
+pub struct Emitter {
+    /// The transmit end of a channel to send notifications to
+    sender: Sender,
+    /// The last emitted connectivity
+    connectivity: Option<Connectivity>,
+    /// When the last connectivity was emitted
+    since: Option<Instant>,
+    /// The last known details of every interface
+    interfaces: HashMap<InterfaceId, LinkDetails>,
+    /// The names of the interfaces, including the ones that were removed moments ago
+    names: InterfaceNames,
+    /// The inventory of the interfaces including the ones that are down or were removed
+    inventory: Option<Inventory>,
+    /// The preferred default routes shared with the application
+    routing_info: Option<RoutingInfo>,
+    /// The last known prefix length and flags of every address by its interface
+    addresses: HashMap<(InterfaceId, IpAddr), (u8, AddressFlags)>,
+    /// The last known ipv4 leases of every interface, kept while the interface holds no lease
+    leases: HashMap<InterfaceId, BTreeMap<IpAddr, AddressLease>>,
+    /// The last known routes
+    routes: BTreeSet<RouteDetails>,
+    /// The last known ipv4 and ipv6 [`NetworkIdentity`] of every interface that is up
+    networks: HashMap<InterfaceId, (NetworkIdentity, NetworkIdentity)>,
+    /// The last emitted fingerprint of all networks
+    fingerprint: Option<NetworkFingerprint>,
+    /// The last known mtu of every interface
+    mtus: HashMap<InterfaceId, u32>,
+    /// The last emitted speed and duplex mode of every interface
+    link_speeds: HashMap<InterfaceId, (Option<u64>, Option<Duplex>)>,
+    /// The last emitted router advertisement parameters of every interface
+    router_advertisements: HashMap<InterfaceId, RouterAdvertisement>,
+    /// The last known carrier of every interface and when it changed within the flap window
+    carriers: HashMap<InterfaceId, (bool, VecDeque<Instant>)>,
+    /// The interfaces which were dormant, waiting for an authentication
+    dormant: HashSet<InterfaceId>,
+    /// How the connectivity of a dormant interface is reported
+    dormant_policy: DormantPolicy,
+    /// The window in which carrier changes are counted
+    flap_window: Duration,
+    /// The amount of carrier changes within the flap window above which an interface is reported as down
+    flap_threshold: Option<usize>,
+    /// The verification of internet connectivity by active probes
+    #[cfg(feature = "probe")]
+    verification: Option<Verification>,
+    /// Whether the connectivity check of the system detected a captive portal
+    system_portal: bool,
+    /// The dns configuration of the system
+    #[cfg(all(feature = "dns", any(target_os = "linux", windows)))]
+    dns: Option<crate::dns::Watch>,
+    /// The hostname and search domains of the system
+    #[cfg(all(feature = "hostname", any(unix, windows)))]
+    hostname: Option<crate::hostname::Watch>,
+    /// Detects conflicts of the ipv4 addresses of the system
+    #[cfg(all(feature = "arp", target_os = "linux"))]
+    arp: Option<crate::arp::Watch>,
+    /// The detection of NAT64 and how ipv4 is reported with it
+    #[cfg(feature = "nat64")]
+    nat64: Option<crate::nat64::Detection>,
+    /// Notified when the state must be updated again without a change of the system
+    wake: Arc<Notify>,
+    /// Notified when the platform listener must be restarted
+    restart: Arc<Notify>,
+    /// The store to persist the last emitted connectivity in
+    store: Option<Box<dyn Store + Send>>,
+    /// The dispatcher to run commands on events
+    dispatcher: Option<Dispatcher>,
+    /// Internal consumers which receive every emitted event
+    listeners: Vec<UnboundedSender<NetworkEvent>>,
+    /// Records the snapshots and the emitted events
+    #[cfg(feature = "json")]
+    capture: Option<Capture>,
+    /// Exports the connectivity transitions to OpenTelemetry
+    #[cfg(feature = "otel")]
+    exporter: Option<crate::otel::Exporter>,
+    /// Whether only gateways within the subnet of an address of their interface are counted
+    on_link_gateways: bool,
+    /// Whether the hardware addresses of the gateways are reported and fingerprinted
+    gateway_hardware_addresses: bool,
+    /// The destinations to look up a route for to determine internet connectivity of their ip type
+    destinations: Vec<IpAddr>,
+    /// The watched prefixes with the last emitted reachability and interface, [`None`] before the first emission
+    prefixes: Vec<(
+        IpAddr,
+        PrefixLength,
+        Option<(ReachabilityState, Option<InterfaceId>)>,
+    )>,
+    /// The last emitted preferred default route, the other default routes with the same metric and the hardware address of the preferred gateway of every ip type
+    default_routes: HashMap<
+        IpFamily,
+        (
+            Option<RouteDetails>,
+            Vec<RouteDetails>,
+            Option<HardwareAddress>,
+        ),
+    >,
+    /// The names of the interfaces which are ignored
+    ignored_interfaces: Vec<String>,
+    /// The prefixes of the hardware addresses of the interfaces which are allowed, every interface is allowed when empty
+    allowed_hardware_addresses: Vec<HardwareAddress>,
+    /// Whether the loopback interfaces are ignored
+    ignore_loop_backs: bool,
+    /// The ip types which are ignored
+    ignored_families: Vec<IpFamily>,
+    /// The priority of every kind of interface, higher is preferred
+    interface_priorities: Vec<(InterfaceType, u8)>,
+    /// The last emitted interface which provides internet connectivity with its kind, [`None`] before the first emission
+    internet_interface: Option<Option<(InterfaceId, Option<InterfaceType>)>>,
+    /// The last emitted connectivity of every interface
+    interface_connectivity: HashMap<InterfaceId, Connectivity>,
+    /// How long the system must be stable before its changes are emitted
+    debounce: Option<Duration>,
+    /// How long the changes of the system are batched before they are emitted
+    coalesce: Option<Duration>,
+    /// The interval between heartbeats
+    heartbeat: Option<Duration>,
+    /// Receives the non-fatal problems
+    diagnostics: Diagnostics,
+    /// Whether a failed platform listener is restarted
+    restart_platform: bool,
+}
+impl Emitter {
+    /// Create a new [`Emitter`] instance
+    pub(crate) fn new(sender: Sender) -> Self {
+        Self {
+            sender,
+            connectivity: None,
+            since: None,
+            interfaces: HashMap::new(),
+            names: InterfaceNames::new(),
+            inventory: None,
+            routing_info: None,
+            addresses: HashMap::new(),
+            leases: HashMap::new(),
+            routes: BTreeSet::new(),
+            networks: HashMap::new(),
+            fingerprint: None,
+            mtus: HashMap::new(),
+            link_speeds: HashMap::new(),
+            router_advertisements: HashMap::new(),
+            carriers: HashMap::new(),
+            dormant: HashSet::new(),
+            dormant_policy: DormantPolicy::Disconnected,
+            flap_window: FLAP_WINDOW,
+            flap_threshold: None,
+            #[cfg(feature = "probe")]
+            verification: None,
+            system_portal: false,
+            #[cfg(all(feature = "dns", any(target_os = "linux", windows)))]
+            dns: None,
+            #[cfg(all(feature = "hostname", any(unix, windows)))]
+            hostname: None,
+            #[cfg(all(feature = "arp", target_os = "linux"))]
+            arp: None,
+            #[cfg(feature = "nat64")]
+            nat64: None,
+            wake: Arc::new(Notify::new()),
+            restart: Arc::new(Notify::new()),
+            store: None,
+            dispatcher: None,
+            listeners: Vec::new(),
+            #[cfg(feature = "json")]
+            capture: None,
+            #[cfg(feature = "otel")]
+            exporter: None,
+            on_link_gateways: false,
+            gateway_hardware_addresses: false,
+            destinations: Vec::new(),
+            prefixes: Vec::new(),
+            default_routes: HashMap::new(),
+            ignored_interfaces: Vec::new(),
+            allowed_hardware_addresses: Vec::new(),
+            ignore_loop_backs: true,
+            ignored_families: Vec::new(),
+            interface_priorities: INTERFACE_PRIORITIES.to_vec(),
+            internet_interface: None,
+            interface_connectivity: HashMap::new(),
+            debounce: None,
+            coalesce: None,
+            heartbeat: None,
+            diagnostics: Diagnostics::default(),
+            restart_platform: false,
+        }
+    }
+
+    /// Sends the connectivity persisted in `store` as a provisional value and persists future connectivity in `store`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the receive end of the channel was dropped.
+    pub(crate) fn restore(
+        &mut self,
+        mut store: Box<dyn Store + Send>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match store.load() {
+            Ok(Some(connectivity)) => {
+                debug!("emit provisional connectivity {:?}", connectivity);
+                self.send(NetworkEvent::ConnectivityRestored { connectivity })?;
+            }
+            Ok(None) => {}
+            Err(error) => {
+                warn!("loading persisted connectivity failed {error}");
+                self.diagnostics.report(Diagnostic::StoreFailed {
+                    error: error.to_string(),
+                });
+            }
+        }
+        self.store = Some(store);
+        Ok(())
+    }
+
+    /// Run commands with `dispatcher` on every emitted event
+    pub(crate) fn dispatch(&mut self, dispatcher: Dispatcher) {
+        self.dispatcher = Some(dispatcher);
+    }
+
+    /// Send every emitted event to `listener` as well
+    #[cfg(feature = "webhook")]
+    pub(crate) fn listen(&mut self, listener: UnboundedSender<NetworkEvent>) {
+        self.listeners.push(listener);
+    }
+
+    /// Export the connectivity transitions with `exporter`
+    #[cfg(feature = "otel")]
+    pub(crate) fn export(&mut self, exporter: crate::otel::Exporter) {
+        self.exporter = Some(exporter);
+    }
+
+    /// Record every snapshot and emitted event with `capture`
+    #[cfg(feature = "json")]
+    pub(crate) fn capture(&mut self, capture: Capture) {
+        self.capture = Some(capture);
+    }
+
+    /// Records a snapshot of the system published by the platform listener when capturing.
+    ///
+    /// A failure is reported to the diagnostics and stops the capture.
+    #[allow(clippy::unused_self, unused_variables)]
+    pub(crate) fn capture_snapshot(&mut self, state: &Interfaces, routed: &[IpAddr]) {
+        #[cfg(feature = "json")]
+        if let Some(ref mut capture) = self.capture {
+            if let Err(error) = capture.snapshot(state, routed) {
+                self.capture_failed(&*error);
+            }
+        }
+    }
+
+    /// Reports that writing the capture failed and stops capturing.
+    #[cfg(feature = "json")]
+    fn capture_failed(&mut self, error: &(dyn Error + Send + Sync)) {
+        warn!("writing the capture failed {error}");
+        self.diagnostics.report(Diagnostic::CaptureFailed {
+            error: error.to_string(),
+        });
+        self.capture = None;
+    }
+
+    /// Record the names of the interfaces in `names`
+    pub(crate) fn name_interfaces(&mut self, names: InterfaceNames) {
+        self.names = names;
+    }
+
+    /// Record the interfaces including the ones that are down or were removed in `inventory`
+    pub(crate) fn take_inventory(&mut self, inventory: Inventory) {
+        self.inventory = Some(inventory);
+    }
+
+    /// Record the preferred default route of every ip type in `routing`
+    pub(crate) fn take_routing_info(&mut self, routing: RoutingInfo) {
+        self.routing_info = Some(routing);
+    }
+
+    /// Only count gateways within the subnet of an address of their interface
+    pub(crate) fn require_on_link_gateways(&mut self) {
+        self.on_link_gateways = true;
+    }
+
+    /// Report the hardware addresses of the gateways and mix them into the fingerprints
+    pub(crate) fn report_gateway_hardware_addresses(&mut self) {
+        self.gateway_hardware_addresses = true;
+    }
+
+    /// Determine internet connectivity of the ip type of `destination` by whether a route to it can be selected
+    pub(crate) fn look_up_route(&mut self, destination: IpAddr) {
+        self.destinations
+            .retain(|known| known.is_ipv4() != destination.is_ipv4());
+        self.destinations.push(destination);
+    }
+
+    /// Report the reachability of the prefix `prefix` with `prefix_length`
+    pub(crate) fn watch_prefix(&mut self, prefix: IpAddr, prefix_length: PrefixLength) {
+        self.prefixes.push((prefix, prefix_length, None));
+    }
+
+    /// Report an interface as down while its carrier changed more than `threshold` times within `window`
+    pub(crate) fn suppress_flapping(&mut self, threshold: usize, window: Duration) {
+        self.flap_threshold = Some(threshold);
+        self.flap_window = window;
+    }
+
+    /// Get when the suppression of a flapping interface ends and the state must be updated again
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        let threshold = self.flap_threshold?;
+        self.carriers
+            .values()
+            .filter(|&&(_, ref transitions)| transitions.len() > threshold)
+            .filter_map(|&(_, ref transitions)| transitions.front()?.checked_add(self.flap_window))
+            .min()
+    }
+
+    /// Ignore the interface with `name` as if it does not exist
+    pub(crate) fn ignore_interface(&mut self, name: String) {
+        self.ignored_interfaces.push(name);
+    }
+
+    /// Only allow the interfaces whose hardware address starts with `prefix` or any other allowed prefix
+    pub(crate) fn allow_hardware_address(&mut self, prefix: HardwareAddress) {
+        self.allowed_hardware_addresses.push(prefix);
+    }
+
+    /// Sets how the connectivity of a dormant interface is reported
+    pub(crate) fn set_dormant_policy(&mut self, policy: DormantPolicy) {
+        self.dormant_policy = policy;
+    }
+
+    /// Sets whether the loopback interfaces are ignored as if they do not exist
+    pub(crate) fn set_ignore_loop_backs(&mut self, ignore: bool) {
+        self.ignore_loop_backs = ignore;
+    }
+
+    /// Ignore `family` as if the system does not support it
+    pub(crate) fn ignore_family(&mut self, family: IpFamily) {
+        if !self.ignored_families.contains(&family) {
+            self.ignored_families.push(family);
+        }
+    }
+
+    /// Prefer interfaces of `interface_type` with `priority` when reporting which interface provides internet connectivity
+    pub(crate) fn prioritize(&mut self, interface_type: InterfaceType, priority: u8) {
+        match self
+            .interface_priorities
+            .iter_mut()
+            .find(|&&mut (known, _)| known == interface_type)
+        {
+            Some(&mut (_, ref mut known)) => *known = priority,
+            None => self.interface_priorities.push((interface_type, priority)),
+        }
+    }
+
+    /// Finds the interface which provides the internet connectivity of the ip types in `connectivity` with the kind of the highest priority.
+    ///
+    /// Among interfaces of the same priority the one with the lowest index is preferred.
+    fn internet_interface(
+        &self,
+        state: &Interfaces,
+        connectivity: Connectivity,
+    ) -> Option<(InterfaceId, Option<InterfaceType>)> {
+        let details = state.link_details();
+        state
+            .interface_connectivity(self.on_link_gateways)
+            .into_iter()
+            .filter(|&(_, interface)| {
+                [IpFamily::V4, IpFamily::V6].into_iter().any(|family| {
+                    interface.get(family) == ConnectivityState::Internet
+                        && connectivity.get(family) == ConnectivityState::Internet
+                })
+            })
+            .map(|(index, _)| {
+                let interface_type = details
+                    .get(&index)
+                    .and_then(|details| details.interface_type);
+                (index, interface_type)
+            })
+            .max_by_key(|&(index, interface_type)| {
+                let priority = interface_type
+                    .and_then(|kind| {
+                        self.interface_priorities
+                            .iter()
+                            .find(|&&(known, _)| known == kind)
+                    })
+                    .map_or(0, |&(_, priority)| priority);
+                (priority, Reverse(index))
+            })
+    }
+
+    /// Only emit the changes of the system once it did not change for `interval`
+    pub(crate) fn debounce(&mut self, interval: Duration) {
+        self.debounce = Some(interval);
+    }
+
+    /// Get how long the system must be stable before its changes are emitted
+    pub(crate) const fn debounce_interval(&self) -> Option<Duration> {
+        self.debounce
+    }
+
+    /// Batch the changes of the system for `tick` before they are emitted
+    pub(crate) fn coalesce(&mut self, tick: Duration) {
+        self.coalesce = Some(tick);
+    }
+
+    /// Get how long the changes of the system are batched before they are emitted
+    pub(crate) const fn coalesce_tick(&self) -> Option<Duration> {
+        self.coalesce
+    }
+
+    /// Emit a heartbeat every `interval`
+    pub(crate) fn heartbeat(&mut self, interval: Duration) {
+        self.heartbeat = Some(interval);
+    }
+
+    /// Get the interval between heartbeats
+    pub(crate) const fn heartbeat_interval(&self) -> Option<Duration> {
+        self.heartbeat
+    }
+
+    /// Report the non-fatal problems to `diagnostics`
+    pub(crate) fn diagnose(&mut self, diagnostics: Diagnostics) {
+        self.diagnostics = diagnostics;
+    }
+
+    /// Get the [`Diagnostics`] the non-fatal problems are reported to
+    pub(crate) const fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    /// Restart a failed platform listener instead of completing with its error
+    pub(crate) fn restart_platform(&mut self) {
+        self.restart_platform = true;
+    }
+
+    /// Get whether a failed platform listener is restarted
+    pub(crate) const fn restarts_platform(&self) -> bool {
+        self.restart_platform
+    }
+
+    /// Verify internet connectivity with `verification` before reporting it
+    #[cfg(feature = "probe")]
+    pub(crate) fn verify(&mut self, verification: Verification) {
+        self.verification = Some(verification);
+    }
+
+    /// Report the changes of the dns configuration received from `dns`
+    #[cfg(all(feature = "dns", any(target_os = "linux", windows)))]
+    pub(crate) fn watch_dns(&mut self, dns: crate::dns::Watch) {
+        self.dns = Some(dns);
+    }
+
+    /// Share the addresses of the system with `arp` to detect conflicts of them
+    #[cfg(all(feature = "arp", target_os = "linux"))]
+    pub(crate) fn detect_address_conflicts(&mut self, arp: crate::arp::Watch) {
+        self.arp = Some(arp);
+    }
+
+    /// Report the changes of the hostname and search domains received from `hostname`
+    #[cfg(all(feature = "hostname", any(unix, windows)))]
+    pub(crate) fn watch_hostname(&mut self, hostname: crate::hostname::Watch) {
+        self.hostname = Some(hostname);
+    }
+
+    /// Report ipv4 on networks with NAT64 according to the policy of `nat64`
+    #[cfg(feature = "nat64")]
+    pub(crate) fn detect_nat64(&mut self, nat64: crate::nat64::Detection) {
+        self.nat64 = Some(nat64);
+    }
+
+    /// Restart the platform listener whenever `restart` is notified
+    #[cfg(feature = "monitor")]
+    pub(crate) fn restart_on(&mut self, restart: Arc<Notify>) {
+        self.restart = restart;
+    }
+
+    /// Get the notify which is notified when the platform listener must be restarted
+    pub(crate) fn restart(&self) -> Arc<Notify> {
+        Arc::clone(&self.restart)
+    }
+
+    /// Get the notify which is notified when the state must be updated again without a change of the system
+    pub(crate) fn wake(&self) -> Arc<Notify> {
+        Arc::clone(&self.wake)
+    }
+
+    /// Get the destinations to look up a route for before every update
+    pub(crate) fn destinations(&self) -> &[IpAddr] {
+        &self.destinations
+    }
+
+    /// Get the [`Sender`] of this [`Emitter`]
+    pub(crate) const fn sender(&self) -> &Sender {
+        &self.sender
+    }
+
+    /// Records `connectivity` as the last emitted connectivity and creates the event for it with how it was determined
+    /// and whether ipv6 is held at network connectivity by link local addresses.
+    fn connectivity_changed(
+        &mut self,
+        connectivity: Connectivity,
+        verified: bool,
+        source: Source,
+        link_local_only: bool,
+    ) -> NetworkEvent {
+        let now = Instant::now();
+        let duration_in_previous_state = self.since.map(|since| now.duration_since(since));
+        let previous = self.connectivity.replace(connectivity);
+        self.since = Some(now);
+        NetworkEvent::ConnectivityChanged {
+            connectivity,
+            previous,
+            duration_in_previous_state,
+            verified,
+            source,
+            link_local_only,
+        }
+    }
+
+    /// Derives the [Connectivity] from the [state](Interfaces) and the `routed` destinations.
+    ///
+    /// The ip type of every destination has internet connectivity when it was routed and at most network connectivity otherwise.
+    /// With verification internet connectivity is only reported once it is verified for the current fingerprint.
+    ///
+    /// Returns the connectivity with whether probes verified its internet connectivity and how it was determined.
+    fn connectivity(
+        &mut self,
+        state: &Interfaces,
+        routed: &[IpAddr],
+    ) -> (Connectivity, bool, Source) {
+        let mut connectivity = if self.on_link_gateways {
+            state.on_link_connectivity()
+        } else {
+            state.connectivity()
+        };
+        for destination in &self.destinations {
+            let family = match *destination {
+                IpAddr::V4(_) if !self.ignored_families.contains(&IpFamily::V4) => {
+                    &mut connectivity.ipv4
+                }
+                IpAddr::V6(_) if !self.ignored_families.contains(&IpFamily::V6) => {
+                    &mut connectivity.ipv6
+                }
+                _ => continue,
+            };
+            *family = if routed.contains(destination) {
+                ConnectivityState::Internet
+            } else {
+                min(*family, ConnectivityState::Network)
+            };
+        }
+        let internet = connectivity.any() == ConnectivityState::Internet;
+        #[allow(unused_mut)]
+        let mut verified = false;
+        #[cfg(feature = "probe")]
+        if let Some(ref mut verification) = self.verification {
+            connectivity = verification.verify(connectivity, self.fingerprint);
+            // unverified internet connectivity is reported as network connectivity.
+            verified = connectivity.any() == ConnectivityState::Internet;
+        }
+        #[cfg(feature = "nat64")]
+        if let Some(ref mut nat64) = self.nat64 {
+            connectivity = nat64.apply(connectivity, self.fingerprint);
+        }
+        for &family in &self.ignored_families {
+            match family {
+                IpFamily::V4 => connectivity.ipv4 = ConnectivityState::None,
+                IpFamily::V6 => connectivity.ipv6 = ConnectivityState::None,
+            }
+        }
+        let looked_up = self
+            .destinations
+            .iter()
+            .any(|destination| routed.contains(destination));
+        let source = if verified {
+            Source::ActiveProbe
+        } else if internet && state.system_connectivity() == Some(SystemConnectivity::Full) {
+            Source::SystemCheck
+        } else if internet && looked_up {
+            Source::PlatformApi
+        } else {
+            Source::PassiveRouting
+        };
+        (connectivity, verified, source)
+    }
+
+    /// Records the interfaces in the [state](Interfaces) and creates the events for the ones that appeared or disappeared.
+    fn interfaces_changed(&mut self, state: &Interfaces, events: &mut Vec<NetworkEvent>) {
+        let interfaces: HashMap<InterfaceId, LinkDetails> =
+            state.link_details().into_iter().collect();
+        let mut removed: Vec<_> = self
+            .interfaces
+            .iter()
+            .filter(|&(index, known)| {
+                interfaces
+                    .get(index)
+                    .map_or(true, |current| interface_replaced(known, current))
+            })
+            .map(|(&index, known)| (index, known.name.clone().or_else(|| self.names.get(index))))
+            .collect();
+        removed.sort_unstable();
+        for (index, name) in removed {
+            events.push(NetworkEvent::InterfaceRemoved { index, name });
+        }
+        let mut added: Vec<_> = interfaces
+            .iter()
+            .filter(|&(index, current)| {
+                self.interfaces
+                    .get(index)
+                    .map_or(true, |known| interface_replaced(known, current))
+            })
+            .collect();
+        added.sort_unstable_by_key(|&(&index, _)| index);
+        let mtus = state.mtus();
+        #[cfg(feature = "unstable-raw")]
+        let raw_flags = state.raw_flags();
+        for (&index, details) in added {
+            events.push(NetworkEvent::InterfaceAdded {
+                index,
+                name: details.name.clone(),
+                interface_type: details.interface_type,
+                hardware_address: details.hardware_address.clone(),
+                mtu: mtus.get(&index).copied(),
+                #[cfg(feature = "unstable-raw")]
+                raw_flags: raw_flags.get(&index).copied(),
+            });
+        }
+        self.names.update(&interfaces);
+        self.interfaces = interfaces;
+    }
+
+    /// Records the addresses in the [state](Interfaces) and creates the events for the ones that were added, changed or removed.
+    fn addresses_changed(&mut self, state: &Interfaces, events: &mut Vec<NetworkEvent>) {
+        let addresses: HashMap<(InterfaceId, IpAddr), (u8, AddressFlags)> = state
+            .address_details()
+            .into_iter()
+            .map(|details| {
+                (
+                    (details.index, details.address),
+                    (details.prefix_length, details.flags),
+                )
+            })
+            .collect();
+        let mut removed: Vec<_> = self
+            .addresses
+            .iter()
+            .filter(|&(key, _)| !addresses.contains_key(key))
+            .map(|(&(index, address), &(prefix_length, _))| (index, address, prefix_length))
+            .collect();
+        removed.sort_unstable();
+        for (index, address, prefix_length) in removed {
+            events.push(NetworkEvent::AddressRemoved {
+                index,
+                name: self.names.get(index),
+                address,
+                prefix_length,
+            });
+        }
+        let mut current: Vec<_> = addresses
+            .iter()
+            .filter(|&(key, details)| self.addresses.get(key) != Some(details))
+            .collect();
+        current.sort_unstable_by_key(|&(&key, _)| key);
+        for (&(index, address), &(prefix_length, flags)) in current {
+            let known = self.addresses.get(&(index, address));
+            if flags.duplicate && !known.map_or(false, |&(_, known)| known.duplicate) {
+                warn!("{address} of interface {index} is used by another host");
+                self.diagnostics.report(Diagnostic::AddressConflict {
+                    index,
+                    address,
+                    hardware_address: None,
+                });
+            }
+            let scope = AddressScope::of(address);
+            let name = self.names.get(index);
+            events.push(if self.addresses.contains_key(&(index, address)) {
+                NetworkEvent::AddressChanged {
+                    index,
+                    name,
+                    address,
+                    prefix_length,
+                    scope,
+                    flags,
+                }
+            } else {
+                NetworkEvent::AddressAdded {
+                    index,
+                    name,
+                    address,
+                    prefix_length,
+                    scope,
+                    flags,
+                }
+            });
+        }
+        self.addresses = addresses;
+    }
+
+    /// Records the ipv4 leases in the [state](Interfaces) and creates the events for the ones that were renewed or obtained.
+    ///
+    /// The leases of an interface which holds none at the moment are kept, so obtaining one for the same address again is a renewal.
+    fn leases_changed(&mut self, state: &Interfaces, events: &mut Vec<NetworkEvent>) {
+        let carriers = state.carriers();
+        self.leases.retain(|index, _| carriers.contains_key(index));
+        let mut current: BTreeMap<InterfaceId, BTreeMap<IpAddr, AddressLease>> = BTreeMap::new();
+        for (index, address, lease) in state.address_leases() {
+            if address.is_ipv4() {
+                current.entry(index).or_default().insert(address, lease);
+            }
+        }
+        for (index, leases) in current {
+            let known = self.leases.remove(&index).unwrap_or_default();
+            for (&address, &lease) in &leases {
+                match known.get(&address) {
+                    Some(renewed) if renewed.renewed == lease.renewed => {}
+                    Some(_) => events.push(NetworkEvent::LeaseRenewed {
+                        index,
+                        name: self.names.get(index),
+                        address,
+                        valid_lifetime: lease.valid_lifetime,
+                        preferred_lifetime: lease.preferred_lifetime,
+                    }),
+                    None => events.push(NetworkEvent::LeaseChanged {
+                        index,
+                        name: self.names.get(index),
+                        address,
+                        previous: known
+                            .keys()
+                            .find(|&previous| !leases.contains_key(previous))
+                            .copied(),
+                        valid_lifetime: lease.valid_lifetime,
+                        preferred_lifetime: lease.preferred_lifetime,
+                    }),
+                }
+            }
+            self.leases.insert(index, leases);
+        }
+    }
+
+    /// Records the routes in the [state](Interfaces) and creates the events for the ones that were added or removed.
+    fn routes_changed(&mut self, state: &Interfaces, events: &mut Vec<NetworkEvent>) {
+        let routes: BTreeSet<RouteDetails> = state.routes().into_iter().collect();
+        for &route in self.routes.difference(&routes) {
+            events.push(NetworkEvent::RouteRemoved(route));
+        }
+        for &route in routes.difference(&self.routes) {
+            events.push(NetworkEvent::RouteAdded(route));
+        }
+        self.routes = routes;
+    }
+
+    /// Records the carrier changes in the [state](Interfaces) and creates the events for them.
+    ///
+    /// Returns the indices of the interfaces which must be reported as down because they are flapping.
+    fn carriers_changed(
+        &mut self,
+        state: &Interfaces,
+        events: &mut Vec<NetworkEvent>,
+    ) -> Vec<InterfaceId> {
+        let now = Instant::now();
+        let window = self.flap_window;
+        let carriers = state.carriers();
+        #[cfg(feature = "unstable-raw")]
+        let raw_flags = state.raw_flags();
+        self.carriers
+            .retain(|index, _| carriers.contains_key(index));
+        for (index, carrier) in carriers {
+            let &mut (ref mut known, ref mut transitions) = self
+                .carriers
+                .entry(index)
+                .or_insert_with(|| (carrier, VecDeque::new()));
+            while transitions
+                .front()
+                .and_then(|&changed| changed.checked_add(window))
+                .map_or(false, |expires| expires <= now)
+            {
+                transitions.pop_front();
+            }
+            if *known != carrier {
+                *known = carrier;
+                transitions.push_back(now);
+                events.push(NetworkEvent::CarrierChanged {
+                    index,
+                    name: self.names.get(index),
+                    carrier,
+                    transitions: transitions.len(),
+                    #[cfg(feature = "unstable-raw")]
+                    raw_flags: raw_flags.get(&index).copied(),
+                });
+            }
+        }
+        match self.flap_threshold {
+            Some(threshold) => self
+                .carriers
+                .iter()
+                .filter(|&(_, &(_, ref transitions))| transitions.len() > threshold)
+                .map(|(&index, _)| index)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Records the dormant interfaces in the [state](Interfaces) and creates the events for the ones that started or finished authenticating.
+    ///
+    /// An interface which is removed while authenticating is forgotten without an event.
+    fn dormant_changed(&mut self, state: &Interfaces, events: &mut Vec<NetworkEvent>) {
+        let carriers = state.carriers();
+        let dormant: HashSet<InterfaceId> = state.dormant().into_iter().collect();
+        let mut changed: Vec<_> = dormant
+            .symmetric_difference(&self.dormant)
+            .filter(|&index| carriers.contains_key(index))
+            .copied()
+            .collect();
+        changed.sort_unstable();
+        for index in changed {
+            events.push(NetworkEvent::AuthenticationChanged {
+                index,
+                name: self.names.get(index),
+                authenticating: dormant.contains(&index),
+            });
+        }
+        self.dormant = dormant;
+    }
+
+    /// Removes the ignored interfaces and ip types from the [state](Interfaces), it is only cloned when anything is ignored.
+    fn filtered<'a>(&self, state: &'a Interfaces) -> Cow<'a, Interfaces> {
+        if self.ignored_interfaces.is_empty()
+            && self.allowed_hardware_addresses.is_empty()
+            && !self.ignore_loop_backs
+            && self.ignored_families.is_empty()
+        {
+            return Cow::Borrowed(state);
+        }
+        let mut remaining = state.clone();
+        remaining.retain_links(|_, name| {
+            name.map_or(true, |name| {
+                !self
+                    .ignored_interfaces
+                    .iter()
+                    .any(|ignored| ignored == name)
+            })
+        });
+        if !self.allowed_hardware_addresses.is_empty() {
+            remaining.retain_hardware_addresses(|_, hardware_address| {
+                hardware_address.map_or(false, |hardware_address| {
+                    self.allowed_hardware_addresses
+                        .iter()
+                        .any(|prefix| hardware_address.starts_with(prefix))
+                })
+            });
+        }
+        if self.ignore_loop_backs {
+            remaining.remove_loop_backs();
+        }
+        for &family in &self.ignored_families {
+            remaining.remove_family(family);
+        }
+        Cow::Owned(remaining)
+    }
+
+    /// Reports the `flapping` and dormant interfaces of the [state](Interfaces) as down or without internet connectivity, it is only cloned when there are any.
+    fn suppressed<'a>(
+        &self,
+        state: &'a Interfaces,
+        flapping: Vec<InterfaceId>,
+    ) -> Cow<'a, Interfaces> {
+        if flapping.is_empty() && self.dormant.is_empty() {
+            return Cow::Borrowed(state);
+        }
+        if !flapping.is_empty() {
+            debug!("suppressing flapping interfaces {:?}", flapping);
+        }
+        let mut suppressed = state.clone();
+        // a dormant interface does not pass traffic until it authenticated.
+        for &index in &self.dormant {
+            match self.dormant_policy {
+                DormantPolicy::Disconnected => suppressed.set_carrier(index, false),
+                DormantPolicy::Network => {
+                    suppressed.set_carrier(index, true);
+                    suppressed.remove_default_routes(index);
+                }
+            }
+        }
+        for index in flapping {
+            suppressed.set_carrier(index, false);
+        }
+        Cow::Owned(suppressed)
+    }
+
+    /// Records the networks of the interfaces in the [state](Interfaces) and creates the events for the ones that changed and for the combined fingerprint.
+    fn networks_changed(&mut self, state: &Interfaces, events: &mut Vec<NetworkEvent>) {
+        let identities = state.network_identities();
+        self.networks
+            .retain(|index, _| identities.contains_key(index));
+        let mut fingerprints = Vec::new();
+        for (index, (ipv4, ipv6)) in identities {
+            let mut fingerprint = NetworkFingerprint::from_identities([&ipv4, &ipv6]);
+            if self.gateway_hardware_addresses {
+                let hardware_addresses: BTreeSet<&HardwareAddress> = ipv4
+                    .gateways
+                    .iter()
+                    .chain(&ipv6.gateways)
+                    .filter_map(|&gateway| state.neighbor(index, gateway))
+                    .collect();
+                fingerprint = fingerprint.with_gateway_hardware_addresses(
+                    hardware_addresses.into_iter().map(Vec::as_slice),
+                );
+            }
+            if !ipv4.is_empty() || !ipv6.is_empty() {
+                fingerprints.push(fingerprint);
+            }
+            match self.networks.entry(index) {
+                Entry::Occupied(mut entry) => {
+                    let (ref mut remembered_ipv4, ref mut remembered_ipv6) = *entry.get_mut();
+                    let ipv4_changed = network_changed(remembered_ipv4, ipv4);
+                    let ipv6_changed = network_changed(remembered_ipv6, ipv6);
+                    if ipv4_changed || ipv6_changed {
+                        events.push(NetworkEvent::NetworkChanged {
+                            index,
+                            name: self.names.get(index),
+                            fingerprint,
+                        });
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert((ipv4, ipv6));
+                }
+            }
+        }
+
+        let fingerprint = NetworkFingerprint::combine(fingerprints);
+        if self.fingerprint != fingerprint {
+            self.fingerprint = fingerprint;
+            events.push(NetworkEvent::FingerprintChanged(fingerprint));
+        }
+    }
+
+    /// Records the connectivity of the [state](Interfaces) and its interfaces and creates the events for the changes.
+    ///
+    /// The event of the aggregated connectivity is inserted at `position`, so it comes before the events it depends on.
+    /// Returns the aggregated connectivity.
+    fn connectivity_events(
+        &mut self,
+        state: &Interfaces,
+        routed: &[IpAddr],
+        position: usize,
+        events: &mut Vec<NetworkEvent>,
+    ) -> Connectivity {
+        let (connectivity, verified, source) = self.connectivity(state, routed);
+        if self.connectivity != Some(connectivity) {
+            let link_local_only =
+                connectivity.ipv6 != ConnectivityState::Internet && state.ipv6_link_local_only();
+            let event = self.connectivity_changed(connectivity, verified, source, link_local_only);
+            events.insert(position, event);
+        }
+        let interface_connectivity: HashMap<InterfaceId, Connectivity> = state
+            .interface_connectivity(self.on_link_gateways)
+            .into_iter()
+            .collect();
+        let mut changed: Vec<_> = interface_connectivity
+            .iter()
+            .filter(|&(index, current)| self.interface_connectivity.get(index) != Some(current))
+            .map(|(&index, &connectivity)| (index, connectivity))
+            .collect();
+        changed.sort_unstable_by_key(|&(index, _)| index);
+        for (index, connectivity) in changed {
+            events.push(NetworkEvent::InterfaceConnectivityChanged {
+                index,
+                name: self.names.get(index),
+                connectivity,
+            });
+        }
+        self.interface_connectivity = interface_connectivity;
+        connectivity
+    }
+
+    /// Records the interface which provides the internet `connectivity` of the [state](Interfaces) and creates the events when it or its kind changed.
+    fn internet_interface_changed(
+        &mut self,
+        state: &Interfaces,
+        connectivity: Connectivity,
+        events: &mut Vec<NetworkEvent>,
+    ) {
+        let internet_interface = self.internet_interface(state, connectivity);
+        if self.internet_interface == Some(internet_interface) {
+            return;
+        }
+        let previous = self.internet_interface.replace(internet_interface);
+        let index = internet_interface.map(|(index, _)| index);
+        let connection_type = internet_interface.and_then(|(_, interface_type)| interface_type);
+        events.push(NetworkEvent::InternetInterfaceChanged {
+            index,
+            name: index.and_then(|index| self.names.get(index)),
+            interface_type: connection_type,
+        });
+        // the kind is only reported as a change after the initial one
+        if let Some(previous) = previous {
+            let previous = previous.and_then(|(_, interface_type)| interface_type);
+            if previous != connection_type {
+                events.push(NetworkEvent::ConnectionTypeChanged {
+                    connection_type,
+                    previous,
+                });
+            }
+        }
+    }
+
+    /// Creates the events for the captive portals, the dns configuration and the hostname which were detected since the last update.
+    fn system_changed(&mut self, state: &Interfaces, events: &mut Vec<NetworkEvent>) {
+        #[cfg(feature = "probe")]
+        if let Some(url) = self
+            .verification
+            .as_mut()
+            .and_then(Verification::captive_portal)
+        {
+            events.push(NetworkEvent::CaptivePortalDetected { url });
+        }
+        // the connectivity check of the system does not tell the url of the login page
+        let system_portal = state.system_connectivity() == Some(SystemConnectivity::Portal);
+        if system_portal && !self.system_portal {
+            events.push(NetworkEvent::CaptivePortalDetected { url: None });
+        }
+        self.system_portal = system_portal;
+        #[cfg(all(feature = "dns", any(target_os = "linux", windows)))]
+        if let Some(configuration) = self.dns.as_mut().and_then(crate::dns::Watch::changed) {
+            events.push(NetworkEvent::DnsChanged {
+                nameservers: configuration.nameservers,
+                search: configuration.search,
+                resolved: configuration.resolved,
+                link_nameservers: configuration.link_nameservers,
+                dnssec: configuration.dnssec,
+                dns_over_tls: configuration.dns_over_tls,
+            });
+        }
+        #[cfg(all(feature = "hostname", any(unix, windows)))]
+        if let Some(names) = self
+            .hostname
+            .as_mut()
+            .and_then(crate::hostname::Watch::changed)
+        {
+            events.push(NetworkEvent::HostnameChanged {
+                hostname: names.hostname,
+                search: names.search,
+            });
+        }
+    }
+
+    /// Records the reachability of the watched prefixes through the routes of the [state](Interfaces) and creates the events for the ones that changed.
+    fn prefixes_changed(&mut self, state: &Interfaces, events: &mut Vec<NetworkEvent>) {
+        if self.prefixes.is_empty() {
+            return;
+        }
+        let routes = state.routes();
+        let carriers: HashMap<InterfaceId, bool> = state.carriers().into_iter().collect();
+        for &mut (prefix, prefix_length, ref mut known) in &mut self.prefixes {
+            let current = covering_route(&routes, &carriers, prefix, prefix_length).map_or(
+                (ReachabilityState::Unreachable, None),
+                |route| {
+                    let reachability = if route.gateway.is_some() {
+                        ReachabilityState::Gateway
+                    } else {
+                        ReachabilityState::Direct
+                    };
+                    (reachability, Some(route.index))
+                },
+            );
+            if *known != Some(current) {
+                *known = Some(current);
+                let (reachability, index) = current;
+                events.push(NetworkEvent::PrefixReachabilityChanged {
+                    prefix,
+                    prefix_length,
+                    reachability,
+                    index,
+                    name: index.and_then(|index| self.names.get(index)),
+                });
+            }
+        }
+    }
+
+    /// Records the preferred default routes of every ip type in the [state](Interfaces) and creates the events for the ip types whose routes changed.
+    fn default_routes_changed(&mut self, state: &Interfaces, events: &mut Vec<NetworkEvent>) {
+        for family in [IpFamily::V4, IpFamily::V6] {
+            if self.ignored_families.contains(&family) {
+                continue;
+            }
+            let mut routes = state.default_routes(family).into_iter();
+            let primary = routes.next();
+            if let Some(ref routing) = self.routing_info {
+                routing.update(family, primary);
+            }
+            let equal_cost: Vec<RouteDetails> = routes
+                .take_while(|route| {
+                    primary.map_or(false, |primary| route.priority == primary.priority)
+                })
+                .collect();
+            let gateway_hardware_address = primary
+                .and_then(|route| Some((route.index, route.gateway?)))
+                .filter(|_| self.gateway_hardware_addresses)
+                .and_then(|(index, gateway)| state.neighbor(index, gateway).cloned());
+            let current = (primary, equal_cost, gateway_hardware_address);
+            if self.default_routes.get(&family) != Some(&current) {
+                let (primary, equal_cost, gateway_hardware_address) = current.clone();
+                self.default_routes.insert(family, current);
+                events.push(NetworkEvent::DefaultRouteChanged {
+                    family,
+                    primary,
+                    equal_cost,
+                    gateway_hardware_address,
+                });
+            }
+        }
+    }
+
+    /// Records the mtu, speed and router advertisements of the links in the [state](Interfaces) and creates the events for the ones that changed.
+    fn links_changed(&mut self, state: &Interfaces, events: &mut Vec<NetworkEvent>) {
+        let mtus: HashMap<InterfaceId, u32> = state.mtus().into_iter().collect();
+        for (&index, &mtu) in &mtus {
+            match self.mtus.get(&index) {
+                Some(&previous) if previous != mtu => {
+                    events.push(NetworkEvent::MtuChanged {
+                        index,
+                        name: self.names.get(index),
+                        mtu,
+                        previous,
+                    });
+                }
+                _ => {}
+            }
+        }
+        self.mtus = mtus;
+
+        let link_speeds: HashMap<InterfaceId, (Option<u64>, Option<Duplex>)> =
+            state.link_speeds().into_iter().collect();
+        for (&index, &(speed, duplex)) in &link_speeds {
+            let previous = self.link_speeds.get(&index).copied().unwrap_or_default();
+            if previous != (speed, duplex) {
+                events.push(NetworkEvent::LinkSpeedChanged {
+                    index,
+                    name: self.names.get(index),
+                    speed,
+                    duplex,
+                });
+            }
+        }
+        self.link_speeds = link_speeds;
+
+        let router_advertisements = state.router_advertisements();
+        for (&index, advertisement) in &router_advertisements {
+            let previous = self
+                .router_advertisements
+                .get(&index)
+                .cloned()
+                .unwrap_or_default();
+            if previous != *advertisement {
+                events.push(NetworkEvent::RouterAdvertisementChanged {
+                    index,
+                    name: self.names.get(index),
+                    advertisement: advertisement.clone(),
+                });
+            }
+        }
+        self.router_advertisements = router_advertisements.into_iter().collect();
+    }
+
+    /// Derives the events from the differences between the [state](Interfaces) and the previously seen state.
+    fn events(&mut self, state: &Interfaces, routed: &[IpAddr]) -> Vec<NetworkEvent> {
+        let mut events = Vec::new();
+
+        let filtered = self.filtered(state);
+        let state = filtered.as_ref();
+        #[cfg(all(feature = "arp", target_os = "linux"))]
+        if let Some(ref arp) = self.arp {
+            arp.update(state);
+        }
+        self.interfaces_changed(state, &mut events);
+        if let Some(ref inventory) = self.inventory {
+            inventory.update(state.snapshot());
+        }
+        self.addresses_changed(state, &mut events);
+        self.leases_changed(state, &mut events);
+        self.routes_changed(state, &mut events);
+        let flapping = self.carriers_changed(state, &mut events);
+        self.dormant_changed(state, &mut events);
+
+        let suppressed = self.suppressed(state, flapping);
+        let state = suppressed.as_ref();
+        // the connectivity depends on the fingerprint but its event comes first
+        let connectivity_position = events.len();
+        self.networks_changed(state, &mut events);
+        let connectivity =
+            self.connectivity_events(state, routed, connectivity_position, &mut events);
+        self.internet_interface_changed(state, connectivity, &mut events);
+        self.system_changed(state, &mut events);
+        self.prefixes_changed(state, &mut events);
+        self.default_routes_changed(state, &mut events);
+        self.links_changed(state, &mut events);
+
+        events
+    }
+
+    /// Sends an event through the channel.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the receive end of the channel was dropped.
+    fn send(&self, event: NetworkEvent) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match self.sender {
+            Sender::Connectivity(ref tx) => {
+                if let NetworkEvent::ConnectivityChanged { connectivity, .. }
+                | NetworkEvent::ConnectivityRestored { connectivity } = event
+                {
+                    tx.send(connectivity)?;
+                }
+            }
+            Sender::Detailed(ref tx) => {
+                tx.send(event)?;
+            }
+            Sender::Bounded(ref tx) => {
+                tx.send(event)?;
+            }
+            Sender::Watch(ref tx) => {
+                if let NetworkEvent::ConnectivityChanged { connectivity, .. }
+                | NetworkEvent::ConnectivityRestored { connectivity } = event
+                {
+                    tx.send(connectivity)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compares the [state](Interfaces) with the previously seen state and sends the differences.
+    ///
+    /// `routed` contains the [destinations](Self::destinations) a route could be selected for.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the receive end of the channel was dropped.
+    pub(crate) fn update(
+        &mut self,
+        state: &Interfaces,
+        routed: &[IpAddr],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let events = self.events(state, routed);
+        self.emit(events)
+    }
+
+    /// Sends a heartbeat with the last emitted connectivity and fingerprint.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the receive end of the channel was dropped.
+    pub(crate) fn beat(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.emit(vec![NetworkEvent::Heartbeat {
+            connectivity: self.connectivity,
+            fingerprint: self.fingerprint,
+            timestamp,
+        }])
+    }
+
+    /// Records, exports, dispatches and sends the `events`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the receive end of the channel was dropped.
+    fn emit(&mut self, events: Vec<NetworkEvent>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        #[cfg(feature = "otel")]
+        if let Some(ref exporter) = self.exporter {
+            exporter.export(&events, &self.names);
+        }
+        #[cfg(feature = "json-log")]
+        crate::json_log::log(&events, &self.names);
+        for event in events {
+            #[cfg(feature = "json-log")]
+            crate::json_log::log_event(&event);
+            #[cfg(not(feature = "json-log"))]
+            debug!("emit {:?}", event);
+            #[cfg(feature = "json")]
+            if let Some(ref mut capture) = self.capture {
+                if let Err(error) = capture.event(&event) {
+                    self.capture_failed(&*error);
+                }
+            }
+            #[cfg(feature = "metrics")]
+            metrics::counter!("network_connectivity_events_total", 1, "event" => event.name());
+            if let NetworkEvent::ConnectivityChanged { connectivity, .. } = event {
+                if let Some(ref mut store) = self.store {
+                    if let Err(error) = store.save(connectivity) {
+                        warn!("persisting connectivity failed {error}");
+                        self.diagnostics.report(Diagnostic::StoreFailed {
+                            error: error.to_string(),
+                        });
+                    }
+                }
+            }
+            if let Some(ref dispatcher) = self.dispatcher {
+                dispatcher.dispatch(&event, &self.diagnostics);
+            }
+            self.listeners
+                .retain(|listener| listener.send(event.clone()).is_ok());
+            self.send(event)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Emitter, Sender};
+    use crate::{
+        state::{
+            AddressInfo, AddressLease, InterfaceId, InterfaceType, Interfaces, LinkInfo, RouteInfo,
+            RouteMetric,
+        },
+        Connectivity, ConnectivityState, NetworkEvent,
+    };
+    use std::{
+        net::Ipv4Addr,
+        thread::sleep,
+        time::{Duration, Instant},
+    };
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+    /// The index of the interface
+    const INDEX: InterfaceId = InterfaceId::new(2);
+
+    /// The ipv4 internet connectivity of the interface
+    const INTERNET: Connectivity = Connectivity {
+        ipv4: ConnectivityState::Internet,
+        ipv6: ConnectivityState::None,
+    };
+
+    /// Adds the interface with `index` and `name` of `interface_type` to `state` which has ipv4 internet connectivity while it has a `carrier`.
+    fn add_interface(
+        state: &mut Interfaces,
+        index: InterfaceId,
+        name: &str,
+        interface_type: InterfaceType,
+        carrier: bool,
+    ) {
+        state.add_link(LinkInfo {
+            index,
+            loop_back: false,
+            carrier,
+            name: Some(name.into()),
+            hardware_address: None,
+            mtu: None,
+        });
+        state.set_interface_type(index, interface_type);
+        state.add_address(AddressInfo {
+            index,
+            address: Ipv4Addr::new(192, 0, 2, 2).into(),
+            prefix_length: 24,
+        });
+        state.add_default_route(RouteInfo {
+            index,
+            gateway: Ipv4Addr::new(192, 0, 2, 1).into(),
+            priority: RouteMetric::new(100),
+        });
+    }
+
+    /// Creates the state of an ethernet interface with ipv4 internet connectivity while it has a `carrier`.
+    fn interface(carrier: bool) -> Interfaces {
+        let mut state = Interfaces::new();
+        add_interface(&mut state, INDEX, "eth0", InterfaceType::Ethernet, carrier);
+        state
+    }
+
+    /// Creates an emitter which sends detailed events to the returned receiver.
+    fn emitter() -> (Emitter, UnboundedReceiver<NetworkEvent>) {
+        let (sender, receiver) = unbounded_channel();
+        (Emitter::new(Sender::Detailed(sender)), receiver)
+    }
+
+    /// Receives the events that were sent.
+    fn received(receiver: &mut UnboundedReceiver<NetworkEvent>) -> Vec<NetworkEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+
+    /// The last connectivity among the `events`.
+    fn connectivity(events: &[NetworkEvent]) -> Option<Connectivity> {
+        events.iter().rev().find_map(|event| match *event {
+            NetworkEvent::ConnectivityChanged { connectivity, .. } => Some(connectivity),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn flapping_carrier_is_suppressed_until_the_window_passed() {
+        let window = Duration::from_millis(100);
+        let (mut emitter, mut events) = emitter();
+        emitter.suppress_flapping(1, window);
+        emitter.update(&interface(true), &[]).unwrap();
+        assert_eq!(connectivity(&received(&mut events)), Some(INTERNET));
+        emitter.update(&interface(false), &[]).unwrap();
+        assert_eq!(
+            connectivity(&received(&mut events)).map(|connectivity| connectivity.any()),
+            Some(ConnectivityState::None)
+        );
+        assert_eq!(emitter.deadline(), None);
+
+        // the second change within the window exceeds the threshold, the interface stays down
+        let toggled = Instant::now();
+        emitter.update(&interface(true), &[]).unwrap();
+        let suppressed = received(&mut events);
+        assert_eq!(connectivity(&suppressed), None);
+        assert!(suppressed.iter().any(|event| matches!(
+            *event,
+            NetworkEvent::CarrierChanged {
+                index: INDEX,
+                carrier: true,
+                transitions: 2,
+                ..
+            }
+        )));
+        let deadline = emitter.deadline().unwrap();
+        assert!(deadline <= toggled + window);
+
+        sleep(deadline.saturating_duration_since(Instant::now()));
+        emitter.update(&interface(true), &[]).unwrap();
+        assert_eq!(connectivity(&received(&mut events)), Some(INTERNET));
+        assert_eq!(emitter.deadline(), None);
+    }
+
+    /// The interface the last [`NetworkEvent::InternetInterfaceChanged`] among the `events` reported, [`None`] when there is none.
+    fn internet_interface(
+        events: &[NetworkEvent],
+    ) -> Option<(Option<InterfaceId>, Option<InterfaceType>)> {
+        events.iter().rev().find_map(|event| match *event {
+            NetworkEvent::InternetInterfaceChanged {
+                index,
+                interface_type,
+                ..
+            } => Some((index, interface_type)),
+            _ => None,
+        })
+    }
+
+    /// Creates the state of a wireless, an ethernet and a second wireless interface which all have ipv4 internet connectivity.
+    fn several_interfaces() -> Interfaces {
+        let mut state = Interfaces::new();
+        add_interface(&mut state, INDEX, "wlan0", InterfaceType::Wireless, true);
+        add_interface(
+            &mut state,
+            InterfaceId::new(3),
+            "eth0",
+            InterfaceType::Ethernet,
+            true,
+        );
+        add_interface(
+            &mut state,
+            InterfaceId::new(4),
+            "wlan1",
+            InterfaceType::Wireless,
+            true,
+        );
+        state
+    }
+
+    #[test]
+    fn ethernet_is_preferred_by_default() {
+        let (mut emitter, mut events) = emitter();
+        emitter.update(&several_interfaces(), &[]).unwrap();
+        assert_eq!(
+            internet_interface(&received(&mut events)),
+            Some((Some(InterfaceId::new(3)), Some(InterfaceType::Ethernet)))
+        );
+    }
+
+    #[test]
+    fn configured_priority_overrides_the_default() {
+        let (mut emitter, mut events) = emitter();
+        emitter.prioritize(InterfaceType::Wireless, 60);
+        emitter.update(&several_interfaces(), &[]).unwrap();
+        // among interfaces of the same priority the lowest index wins
+        assert_eq!(
+            internet_interface(&received(&mut events)),
+            Some((Some(INDEX), Some(InterfaceType::Wireless)))
+        );
+    }
+
+    #[test]
+    fn losing_the_preferred_interface_changes_the_connection_type() {
+        let (mut emitter, mut events) = emitter();
+        let mut state = several_interfaces();
+        emitter.update(&state, &[]).unwrap();
+        received(&mut events);
+
+        state.set_carrier(InterfaceId::new(3), false);
+        emitter.update(&state, &[]).unwrap();
+        let changed = received(&mut events);
+        assert_eq!(
+            internet_interface(&changed),
+            Some((Some(INDEX), Some(InterfaceType::Wireless)))
+        );
+        assert!(changed.contains(&NetworkEvent::ConnectionTypeChanged {
+            connection_type: Some(InterfaceType::Wireless),
+            previous: Some(InterfaceType::Ethernet),
+        }));
+    }
+
+    /// The lease events among the `events`.
+    fn lease_events(events: Vec<NetworkEvent>) -> Vec<NetworkEvent> {
+        events
+            .into_iter()
+            .filter(|event| {
+                matches!(
+                    *event,
+                    NetworkEvent::LeaseChanged { .. } | NetworkEvent::LeaseRenewed { .. }
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expired_lease_is_reported_as_the_previous_one() {
+        let (mut emitter, mut events) = emitter();
+        let mut state = interface(true);
+        let first = AddressInfo {
+            index: INDEX,
+            address: Ipv4Addr::new(192, 0, 2, 2).into(),
+            prefix_length: 24,
+        };
+        let lease = |renewed| AddressLease {
+            valid_lifetime: Duration::from_secs(3600),
+            preferred_lifetime: Duration::from_secs(3600),
+            renewed: Duration::from_secs(renewed),
+        };
+        state.set_address_lease(first, lease(10));
+        emitter.update(&state, &[]).unwrap();
+        assert_eq!(
+            lease_events(received(&mut events)),
+            [NetworkEvent::LeaseChanged {
+                index: INDEX,
+                name: Some("eth0".into()),
+                address: first.address,
+                previous: None,
+                valid_lifetime: Duration::from_secs(3600),
+                preferred_lifetime: Duration::from_secs(3600),
+            }]
+        );
+
+        // the lease expired, the address is removed without a lease event
+        state.remove_address(first);
+        emitter.update(&state, &[]).unwrap();
+        let expired = received(&mut events);
+        assert!(lease_events(expired.clone()).is_empty());
+        assert!(expired.iter().any(|event| matches!(
+            *event,
+            NetworkEvent::AddressRemoved { address, .. } if address == first.address
+        )));
+
+        // a lease for another address is a lease change from the expired one
+        let second = AddressInfo {
+            address: Ipv4Addr::new(198, 51, 100, 2).into(),
+            ..first
+        };
+        state.add_address(second);
+        state.set_address_lease(second, lease(20));
+        emitter.update(&state, &[]).unwrap();
+        assert!(matches!(
+            *lease_events(received(&mut events)),
+            [NetworkEvent::LeaseChanged { address, previous, .. }]
+                if address == second.address && previous == Some(first.address)
+        ));
+    }
+
+    #[test]
+    fn lease_obtained_again_for_the_same_address_is_a_renewal() {
+        let (mut emitter, mut events) = emitter();
+        let mut state = interface(true);
+        let address = AddressInfo {
+            index: INDEX,
+            address: Ipv4Addr::new(192, 0, 2, 2).into(),
+            prefix_length: 24,
+        };
+        let lease = |renewed| AddressLease {
+            valid_lifetime: Duration::from_secs(60),
+            preferred_lifetime: Duration::from_secs(30),
+            renewed: Duration::from_secs(renewed),
+        };
+        state.set_address_lease(address, lease(10));
+        emitter.update(&state, &[]).unwrap();
+        received(&mut events);
+        // reporting the same lease again is not a renewal
+        emitter.update(&state, &[]).unwrap();
+        assert!(lease_events(received(&mut events)).is_empty());
+
+        state.remove_address(address);
+        emitter.update(&state, &[]).unwrap();
+        received(&mut events);
+        state.add_address(address);
+        state.set_address_lease(address, lease(80));
+        emitter.update(&state, &[]).unwrap();
+        assert!(matches!(
+            *lease_events(received(&mut events)),
+            [NetworkEvent::LeaseRenewed { address: renewed, .. }] if renewed == address.address
+        ));
+    }
+}
@@ -0,0 +1,525 @@
+// SPDX-License-Identifier: MIT
+
+//! The detailed events for this crate.
+
+#[cfg(feature = "unstable-raw")]
+use crate::state::RawFlags;
+use crate::{
+    state::{
+        AddressFlags, AddressScope, Duplex, InterfaceId, InterfaceType, RouteDetails,
+        RouterAdvertisement,
+    },
+    Connectivity, IpFamily, NetworkFingerprint, ReachabilityState,
+};
+use alloc::{string::String, vec::Vec};
+#[cfg(not(feature = "std"))]
+use core::net::IpAddr;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::net::IpAddr;
+
+/// How the reported connectivity was determined.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum Source {
+    /// Derived from the addresses and default routes of the interfaces
+    #[default]
+    PassiveRouting,
+    /// Active probes verified the internet connectivity, see [`Builder::probe`](crate::Builder::probe)
+    ActiveProbe,
+    /// The system selected a route to a destination, see [`Builder::route_lookup`](crate::Builder::route_lookup)
+    PlatformApi,
+    /// The connectivity check of the system reported full connectivity, see [`SystemConnectivity`](crate::state::SystemConnectivity)
+    SystemCheck,
+}
+
+/// Represents a detailed network event.
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum NetworkEvent {
+    /// The aggregated connectivity changed
+    ConnectivityChanged {
+        /// The new connectivity
+        connectivity: Connectivity,
+        /// The previous connectivity.
+        ///
+        /// Is [`None`] for the initial connectivity.
+        previous: Option<Connectivity>,
+        /// How long the previous connectivity lasted, measured with a monotonic clock.
+        ///
+        /// Is [`None`] for the initial connectivity.
+        duration_in_previous_state: Option<Duration>,
+        /// Whether active probes verified the internet connectivity, always `false` without probes or internet connectivity
+        #[cfg_attr(feature = "serde", serde(default))]
+        verified: bool,
+        /// How the internet connectivity was determined, [`Source::PassiveRouting`] without internet connectivity
+        #[cfg_attr(feature = "serde", serde(default))]
+        source: Source,
+        /// Whether ipv6 only has [network](crate::ConnectivityState::Network) connectivity because an interface has a default route but only link local addresses,
+        /// see [`Interfaces::ipv6_link_local_only`](crate::state::Interfaces::ipv6_link_local_only)
+        #[cfg_attr(feature = "serde", serde(default))]
+        link_local_only: bool,
+    },
+    /// The connectivity persisted in the [store](crate::Builder::store) was restored when the driver started.
+    ///
+    /// It is provisional until the state of the system is known, the actual connectivity is always sent afterwards as [`NetworkEvent::ConnectivityChanged`].
+    ConnectivityRestored {
+        /// The persisted connectivity
+        connectivity: Connectivity,
+    },
+    /// An interface appeared.
+    ///
+    /// Is emitted for every interface that exists when the driver starts as well, so no separate enumeration is needed.
+    InterfaceAdded {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name of the interface, [`None`] when the platform does not expose it
+        name: Option<String>,
+        /// The kind of the interface, [`None`] when the platform does not expose it
+        interface_type: Option<InterfaceType>,
+        /// The hardware address of the interface, [`None`] when the platform does not expose it or the interface has none
+        hardware_address: Option<Vec<u8>>,
+        /// The mtu of the interface, [`None`] when the platform does not expose it.
+        ///
+        /// Later changes are reported with [`MtuChanged`](Self::MtuChanged).
+        #[cfg_attr(feature = "serde", serde(default))]
+        mtu: Option<u32>,
+        /// The raw flags of the interface, [`None`] when the platform does not expose them
+        #[cfg(feature = "unstable-raw")]
+        #[cfg_attr(feature = "serde", serde(default))]
+        raw_flags: Option<RawFlags>,
+    },
+    /// An interface disappeared.
+    ///
+    /// Is also emitted before an [added](Self::InterfaceAdded) event when the index of the interface was reused for a different interface.
+    InterfaceRemoved {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name the interface was known by, [`None`] when the platform never exposed it
+        name: Option<String>,
+    },
+    /// An address was added to an interface.
+    ///
+    /// Is emitted for every address that exists when the driver starts as well.
+    AddressAdded {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name of the interface, also of one that was removed moments ago, [`None`] when the platform does not expose it
+        #[cfg_attr(feature = "serde", serde(default))]
+        name: Option<String>,
+        /// The address
+        address: IpAddr,
+        /// The length of the prefix of the subnet the address is in
+        prefix_length: u8,
+        /// The scope of the address
+        scope: AddressScope,
+        /// The flags of the address, all unset when the platform does not expose them
+        flags: AddressFlags,
+    },
+    /// The prefix length or the flags of an address changed, for example when duplicate address detection completed.
+    AddressChanged {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name of the interface, also of one that was removed moments ago, [`None`] when the platform does not expose it
+        #[cfg_attr(feature = "serde", serde(default))]
+        name: Option<String>,
+        /// The address
+        address: IpAddr,
+        /// The length of the prefix of the subnet the address is in
+        prefix_length: u8,
+        /// The scope of the address
+        scope: AddressScope,
+        /// The flags of the address, all unset when the platform does not expose them
+        flags: AddressFlags,
+    },
+    /// An address was removed from an interface.
+    AddressRemoved {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name of the interface, also of one that was removed moments ago, [`None`] when the platform does not expose it
+        #[cfg_attr(feature = "serde", serde(default))]
+        name: Option<String>,
+        /// The address
+        address: IpAddr,
+        /// The length of the prefix the address had
+        prefix_length: u8,
+    },
+    /// The lease of an ipv4 address was renewed without changing the address, for example by a dhcp client.
+    ///
+    /// This is informational, a renewal does not change the network.
+    /// Leases are only exposed on linux, as the lifetimes of addresses which are not permanent.
+    LeaseRenewed {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name of the interface, also of one that was removed moments ago, [`None`] when the platform does not expose it
+        name: Option<String>,
+        /// The leased address
+        address: IpAddr,
+        /// How long the address is valid after the renewal
+        valid_lifetime: Duration,
+        /// How long the address is preferred for new connections after the renewal
+        preferred_lifetime: Duration,
+    },
+    /// An interface obtained a lease for an ipv4 address it did not hold a lease for before, for example from the dhcp server of another network.
+    ///
+    /// A lease that is lost and obtained again for the same address, as during a short loss of the link, is reported as [`LeaseRenewed`](Self::LeaseRenewed) instead.
+    /// Is emitted for the leases when the driver starts as well.
+    LeaseChanged {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name of the interface, also of one that was removed moments ago, [`None`] when the platform does not expose it
+        name: Option<String>,
+        /// The leased address
+        address: IpAddr,
+        /// The address the interface held the lease for before, [`None`] when it did not hold a lease before
+        previous: Option<IpAddr>,
+        /// How long the address is valid
+        valid_lifetime: Duration,
+        /// How long the address is preferred for new connections
+        preferred_lifetime: Duration,
+    },
+    /// A route was added.
+    ///
+    /// Is emitted for every route that exists when the driver starts as well.
+    RouteAdded(RouteDetails),
+    /// A route was removed.
+    RouteRemoved(RouteDetails),
+    /// The network behind an interface changed while the interface stayed up.
+    ///
+    /// This happens when a new gateway or a new subnet appears on an interface that was already configured for another network,
+    /// for example when roaming between networks without losing the carrier.
+    NetworkChanged {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name of the interface, also of one that was removed moments ago, [`None`] when the platform does not expose it
+        #[cfg_attr(feature = "serde", serde(default))]
+        name: Option<String>,
+        /// The fingerprint of the new network
+        fingerprint: NetworkFingerprint,
+    },
+    /// An interface started or finished waiting for an authentication like 802.1X before it passes traffic, on platforms that expose it.
+    ///
+    /// Its connectivity meanwhile is reported according to [`Builder::dormant_policy`](crate::Builder::dormant_policy).
+    AuthenticationChanged {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name of the interface, also of one that was removed moments ago, [`None`] when the platform does not expose it
+        #[cfg_attr(feature = "serde", serde(default))]
+        name: Option<String>,
+        /// Whether the interface is authenticating now
+        authenticating: bool,
+    },
+    /// The carrier of an interface changed.
+    CarrierChanged {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name of the interface, also of one that was removed moments ago, [`None`] when the platform does not expose it
+        #[cfg_attr(feature = "serde", serde(default))]
+        name: Option<String>,
+        /// Whether the interface has a carrier now
+        carrier: bool,
+        /// The amount of carrier changes of the interface within the flap window, including this one
+        transitions: usize,
+        /// The raw flags of the interface after the change, [`None`] when the platform does not expose them
+        #[cfg(feature = "unstable-raw")]
+        #[cfg_attr(feature = "serde", serde(default))]
+        raw_flags: Option<RawFlags>,
+    },
+    /// The mtu of an interface changed.
+    ///
+    /// This happens for example when a vpn or pppoe connection is established and can break long lived connections.
+    MtuChanged {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name of the interface, also of one that was removed moments ago, [`None`] when the platform does not expose it
+        #[cfg_attr(feature = "serde", serde(default))]
+        name: Option<String>,
+        /// The new mtu
+        mtu: u32,
+        /// The previous mtu
+        previous: u32,
+    },
+    /// The negotiated speed or duplex mode of an interface changed.
+    ///
+    /// This is informational and does not influence the connectivity.
+    LinkSpeedChanged {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name of the interface, also of one that was removed moments ago, [`None`] when the platform does not expose it
+        #[cfg_attr(feature = "serde", serde(default))]
+        name: Option<String>,
+        /// The speed in bits per second, [`None`] when the platform does not expose it or the link is down
+        speed: Option<u64>,
+        /// The duplex mode, [`None`] when the platform does not expose it or the link is down
+        duplex: Option<Duplex>,
+    },
+    /// The parameters advertised by the ipv6 routers on the link of an interface changed.
+    ///
+    /// This is informational and does not influence the connectivity, only linux exposes the parameters.
+    /// Is emitted when the driver starts as well for the interfaces on which a router advertised any.
+    RouterAdvertisementChanged {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name of the interface, also of one that was removed moments ago, [`None`] when the platform does not expose it
+        #[cfg_attr(feature = "serde", serde(default))]
+        name: Option<String>,
+        /// The advertised parameters
+        advertisement: RouterAdvertisement,
+    },
+    /// A captive portal intercepted the probes which verify internet connectivity.
+    ///
+    /// Is emitted once every time the connectivity or the networks change while the portal is still detected.
+    /// With the NetworkManager backend it is also emitted when the connectivity check of NetworkManager detected a portal.
+    CaptivePortalDetected {
+        /// The url of the login page the probes were redirected to, [`None`] when the portal did not redirect
+        url: Option<String>,
+    },
+    /// The kind of the interface which provides the [internet](crate::ConnectivityState::Internet) connectivity changed, for example from ethernet to wireless.
+    ///
+    /// Applications often adapt the quality or prefetching to the kind of link instead of the connectivity, which may stay the same.
+    /// Switching between interfaces of the same kind is only reported with [`InternetInterfaceChanged`](Self::InternetInterfaceChanged).
+    ConnectionTypeChanged {
+        /// The kind of the interface, [`None`] when no interface provides internet connectivity or the platform does not expose its kind
+        connection_type: Option<InterfaceType>,
+        /// The kind of the interface which provided the internet connectivity before, [`None`] when there was none or its kind was not exposed
+        previous: Option<InterfaceType>,
+    },
+    /// The connectivity of a single interface changed, derived from its own addresses and default routes.
+    ///
+    /// Unlike the aggregated connectivity it is not verified by probes or route lookups.
+    /// Is emitted for every interface when the driver starts as well.
+    InterfaceConnectivityChanged {
+        /// The index of the interface
+        index: InterfaceId,
+        /// The name of the interface, [`None`] when the platform does not expose it
+        name: Option<String>,
+        /// The connectivity of the interface
+        connectivity: Connectivity,
+    },
+    /// The dns configuration of the system changed, see [`Builder::watch_dns`](crate::Builder::watch_dns).
+    ///
+    /// Is emitted for the configuration when the driver starts as well.
+    DnsChanged {
+        /// The nameservers in the order they are used
+        nameservers: Vec<IpAddr>,
+        /// The domains appended to names which are not fully qualified
+        search: Vec<String>,
+        /// Whether the stub resolver of systemd-resolved is used, the nameservers are its upstream nameservers then
+        resolved: bool,
+        /// The nameservers systemd-resolved uses for every interface index, index 0 for its global nameservers.
+        ///
+        /// On windows the nameservers of every adapter that is up.
+        /// Is empty on linux when systemd-resolved is not used.
+        link_nameservers: Vec<(InterfaceId, IpAddr)>,
+        /// The dnssec setting of systemd-resolved, like `allow-downgrade`, [`None`] when systemd-resolved is not used
+        dnssec: Option<String>,
+        /// The dns over tls setting of systemd-resolved, like `opportunistic`, [`None`] when systemd-resolved is not used
+        dns_over_tls: Option<String>,
+    },
+    /// The hostname or the search domains of the system changed, see [`Builder::watch_hostname`](crate::Builder::watch_hostname).
+    ///
+    /// Dhcp often provides the domains with the network, so they change with it and affect service discovery.
+    /// Is emitted for the names when the driver starts as well.
+    HostnameChanged {
+        /// The hostname of the system, [`None`] when it could not be read
+        hostname: Option<String>,
+        /// The domains appended to names which are not fully qualified
+        search: Vec<String>,
+    },
+    /// The reachability of a prefix watched with [`Builder::watch_prefix`](crate::Builder::watch_prefix) changed.
+    ///
+    /// Is emitted for every watched prefix when the driver starts as well.
+    PrefixReachabilityChanged {
+        /// The address of the watched prefix
+        prefix: IpAddr,
+        /// The length of the watched prefix
+        prefix_length: u8,
+        /// Whether the most specific route covering the prefix goes through a gateway or is on-link,
+        /// [`Unreachable`](ReachabilityState::Unreachable) when no route covers it through an interface that is up
+        reachability: ReachabilityState,
+        /// The index of the interface of that route, [`None`] when the prefix is unreachable
+        index: Option<InterfaceId>,
+        /// The name of that interface, [`None`] when the prefix is unreachable or the platform does not expose it
+        name: Option<String>,
+    },
+    /// The preferred default route of an ip type or the other default routes with the same metric changed.
+    ///
+    /// Is emitted for both ip types when the driver starts as well.
+    /// Is also emitted when only the [metric](RouteDetails::priority) of the preferred route changed,
+    /// which reveals a metric based failover that does not change the connectivity.
+    /// The order of preference is described by [`Interfaces::default_routes`](crate::state::Interfaces::default_routes).
+    DefaultRouteChanged {
+        /// The ip type of the default routes
+        family: IpFamily,
+        /// The preferred default route through an interface that is up, [`None`] when there is none
+        primary: Option<RouteDetails>,
+        /// The other default routes with the same metric as the preferred one in the order they are preferred,
+        /// the system may balance connections over them or fall back to them
+        equal_cost: Vec<RouteDetails>,
+        /// The hardware address last resolved for the gateway of the preferred route, [`None`] when it is not resolved, the route has no gateway,
+        /// the platform does not expose it or it is not enabled with [`Builder::gateway_hardware_addresses`](crate::Builder::gateway_hardware_addresses)
+        #[cfg_attr(feature = "serde", serde(default))]
+        gateway_hardware_address: Option<Vec<u8>>,
+    },
+    /// The interface which provides the [internet](crate::ConnectivityState::Internet) connectivity changed.
+    ///
+    /// When several interfaces provide it the one with the kind of the highest [priority](crate::Builder::interface_priority) is reported.
+    /// Is emitted when the driver starts as well.
+    InternetInterfaceChanged {
+        /// The index of the interface, [`None`] when no interface provides internet connectivity
+        index: Option<InterfaceId>,
+        /// The name of the interface, [`None`] when no interface provides internet connectivity or the platform does not expose it
+        name: Option<String>,
+        /// The kind of the interface, [`None`] when no interface provides internet connectivity or the platform does not expose it
+        interface_type: Option<InterfaceType>,
+    },
+    /// The driver is alive, emitted periodically when enabled with [`Builder::heartbeat`](crate::Builder::heartbeat).
+    ///
+    /// Consumers which forward the events over lossy transports can tell a dead driver from a quiet network by its absence.
+    Heartbeat {
+        /// The last emitted connectivity, [`None`] before the initial connectivity was emitted
+        connectivity: Option<Connectivity>,
+        /// The last emitted fingerprint of all networks the system is connected to
+        fingerprint: Option<NetworkFingerprint>,
+        /// The time since the unix epoch at which the heartbeat was emitted
+        timestamp: Duration,
+    },
+    /// The receiver fell behind and events were dropped before it received them.
+    ///
+    /// Is not emitted by the driver but by receivers with a limited capacity, like a [bounded receiver](crate::BoundedReceiver) or [`Monitor::poll_changes`](crate::Monitor::poll_changes),
+    /// in place of the events that were dropped.
+    Lagged {
+        /// The amount of events that were dropped
+        missed: u64,
+        /// The latest connectivity to resynchronize with, [`None`] before the initial connectivity was emitted
+        connectivity: Option<Connectivity>,
+    },
+    /// The fingerprint of all networks the system is connected to changed.
+    ///
+    /// Is [`None`] when the system is not connected to any network.
+    FingerprintChanged(Option<NetworkFingerprint>),
+}
+impl NetworkEvent {
+    /// The name of the kind of this event as used in metric labels
+    #[cfg(any(feature = "metrics", feature = "otel", feature = "json-log"))]
+    pub(crate) const fn name(&self) -> &'static str {
+        match *self {
+            Self::ConnectivityChanged { .. } => "connectivity_changed",
+            Self::ConnectivityRestored { .. } => "connectivity_restored",
+            Self::InterfaceAdded { .. } => "interface_added",
+            Self::InterfaceRemoved { .. } => "interface_removed",
+            Self::AddressAdded { .. } => "address_added",
+            Self::AddressChanged { .. } => "address_changed",
+            Self::AddressRemoved { .. } => "address_removed",
+            Self::LeaseRenewed { .. } => "lease_renewed",
+            Self::LeaseChanged { .. } => "lease_changed",
+            Self::RouteAdded(_) => "route_added",
+            Self::RouteRemoved(_) => "route_removed",
+            Self::NetworkChanged { .. } => "network_changed",
+            Self::AuthenticationChanged { .. } => "authentication_changed",
+            Self::CarrierChanged { .. } => "carrier_changed",
+            Self::MtuChanged { .. } => "mtu_changed",
+            Self::LinkSpeedChanged { .. } => "link_speed_changed",
+            Self::RouterAdvertisementChanged { .. } => "router_advertisement_changed",
+            Self::CaptivePortalDetected { .. } => "captive_portal_detected",
+            Self::InterfaceConnectivityChanged { .. } => "interface_connectivity_changed",
+            Self::ConnectionTypeChanged { .. } => "connection_type_changed",
+            Self::DnsChanged { .. } => "dns_changed",
+            Self::HostnameChanged { .. } => "hostname_changed",
+            Self::PrefixReachabilityChanged { .. } => "prefix_reachability_changed",
+            Self::DefaultRouteChanged { .. } => "default_route_changed",
+            Self::InternetInterfaceChanged { .. } => "internet_interface_changed",
+            Self::Heartbeat { .. } => "heartbeat",
+            Self::Lagged { .. } => "lagged",
+            Self::FingerprintChanged(_) => "fingerprint_changed",
+        }
+    }
+
+    /// The index of the interface this event is about, [`None`] when it is not about a single interface
+    #[cfg(any(feature = "otel", feature = "json-log"))]
+    pub(crate) const fn index(&self) -> Option<InterfaceId> {
+        match *self {
+            Self::InterfaceAdded { index, .. }
+            | Self::InterfaceRemoved { index, .. }
+            | Self::AddressAdded { index, .. }
+            | Self::AddressChanged { index, .. }
+            | Self::AddressRemoved { index, .. }
+            | Self::LeaseRenewed { index, .. }
+            | Self::LeaseChanged { index, .. }
+            | Self::NetworkChanged { index, .. }
+            | Self::AuthenticationChanged { index, .. }
+            | Self::CarrierChanged { index, .. }
+            | Self::MtuChanged { index, .. }
+            | Self::LinkSpeedChanged { index, .. }
+            | Self::RouterAdvertisementChanged { index, .. }
+            | Self::InterfaceConnectivityChanged { index, .. } => Some(index),
+            Self::RouteAdded(ref route) | Self::RouteRemoved(ref route) => Some(route.index),
+            Self::PrefixReachabilityChanged { index, .. }
+            | Self::InternetInterfaceChanged { index, .. } => index,
+            Self::DefaultRouteChanged { ref primary, .. } => match *primary {
+                Some(ref route) => Some(route.index),
+                None => None,
+            },
+            Self::ConnectivityChanged { .. }
+            | Self::ConnectivityRestored { .. }
+            | Self::CaptivePortalDetected { .. }
+            | Self::DnsChanged { .. }
+            | Self::HostnameChanged { .. }
+            | Self::ConnectionTypeChanged { .. }
+            | Self::Heartbeat { .. }
+            | Self::Lagged { .. }
+            | Self::FingerprintChanged(_) => None,
+        }
+    }
+
+    /// The event among `events`, which were emitted for the same change of the system, that caused the connectivity transitions among them.
+    ///
+    /// It is the first event which is not a connectivity or fingerprint change.
+    #[cfg(any(feature = "otel", feature = "json-log"))]
+    pub(crate) fn cause(events: &[Self]) -> Option<&Self> {
+        events.iter().find(|event| {
+            !matches!(
+                **event,
+                Self::ConnectivityChanged { .. } | Self::FingerprintChanged(_)
+            )
+        })
+    }
+
+    /// The reason of a connectivity transition from `previous` which was caused by `cause`, the kind of the causing event.
+    ///
+    /// The reason of the initial connectivity is `initial`, without a cause the reason is `reevaluated`,
+    /// for example when a probe verified the connectivity or a flapping interface settled.
+    #[cfg(any(feature = "otel", feature = "json-log"))]
+    pub(crate) const fn reason(
+        cause: Option<&Self>,
+        previous: Option<Connectivity>,
+    ) -> &'static str {
+        match (cause, previous) {
+            (_, None) => "initial",
+            (Some(cause), Some(_)) => cause.name(),
+            (None, Some(_)) => "reevaluated",
+        }
+    }
+
+    /// The index and name of the interface this event is about, `names` provides the name when the event does not contain it.
+    #[cfg(any(feature = "otel", feature = "json-log"))]
+    pub(crate) fn interface(
+        &self,
+        names: &crate::InterfaceNames,
+    ) -> Option<(InterfaceId, Option<String>)> {
+        let index = self.index()?;
+        let name = match *self {
+            Self::InterfaceAdded { ref name, .. } | Self::InterfaceRemoved { ref name, .. } => {
+                name.clone()
+            }
+            _ => names.get(index),
+        };
+        Some((index, name))
+    }
+}
@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: MIT
+
+//! Distributes the updates of a single receiver to any amount of subscribers.
+
+use futures::Future;
+use std::{
+    error::Error,
+    sync::{Arc, Mutex, PoisonError},
+};
+use tokio::sync::{broadcast, mpsc::UnboundedReceiver, watch};
+
+/// Distributes the connectivity updates or detailed events of a driver to any amount of subscribers, so every part of an application can receive them.
+///
+/// Created with [`Fanout::new`] from the receive end returned by [`Builder::build`](crate::Builder::build) or [`Builder::build_detailed`](crate::Builder::build_detailed).
+/// The fanout is cheap to clone, every clone subscribes to the same updates.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/// use network_connectivity::{Builder, Fanout};
+///
+/// let (driver, receiver) = Builder::new().build()?;
+/// let (fanout, forward) = Fanout::new(receiver, 16)?;
+/// let mut updates = fanout.subscribe();
+/// let mut latest = fanout.watch();
+/// tokio::spawn(async move {
+///     while let Ok(connectivity) = updates.recv().await {
+///         println!("{connectivity:?}");
+///     }
+/// });
+/// tokio::spawn(async move {
+///     while latest.changed().await.is_ok() {
+///         println!("latest {:?}", *latest.borrow());
+///     }
+/// });
+/// futures::future::join(driver, forward).await.0?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Fanout<T> {
+    /// Sends every update to the subscribers, [`None`] once the receive end closed
+    updates: Arc<Mutex<Option<broadcast::Sender<T>>>>,
+    /// The latest update, [`None`] until the first update is received
+    latest: watch::Receiver<Option<T>>,
+}
+impl<T: Clone + Send + 'static> Fanout<T> {
+    /// Create a new [`Fanout`] of the updates received from `receiver`, together with the future that forwards them and must be awaited.
+    ///
+    /// A subscriber which falls more than `capacity` updates behind misses the oldest ones and is told so with [`RecvError::Lagged`](broadcast::error::RecvError::Lagged).
+    ///
+    /// # Notes
+    ///
+    /// The future completes when `receiver` closed, after which the subscribers are closed as well.
+    /// It also completes at the next update once every fanout and every subscriber is dropped,
+    /// which drops `receiver` so the driver runs to completion.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `capacity` is zero.
+    pub fn new(
+        receiver: UnboundedReceiver<T>,
+        capacity: usize,
+    ) -> Result<(Self, impl Future<Output = ()>), Box<dyn Error + Send + Sync>> {
+        if capacity == 0 {
+            return Err("the capacity of a fanout must be at least one".into());
+        }
+        let (updates, _) = broadcast::channel(capacity);
+        let updates = Arc::new(Mutex::new(Some(updates)));
+        let (latest_tx, latest) = watch::channel(None);
+        let fanout = Self {
+            updates: Arc::clone(&updates),
+            latest,
+        };
+        Ok((fanout, forward(receiver, updates, latest_tx)))
+    }
+
+    /// Subscribe to every update received after this call.
+    ///
+    /// The subscriber is closed once the receive end of the fanout closed.
+    #[allow(clippy::must_use_candidate)]
+    pub fn subscribe(&self) -> broadcast::Receiver<T> {
+        match *self.updates.lock().unwrap_or_else(PoisonError::into_inner) {
+            Some(ref updates) => updates.subscribe(),
+            None => broadcast::channel(1).1,
+        }
+    }
+
+    /// Subscribe to the latest update, [`None`] until the first update is received.
+    ///
+    /// The subscriber only sees the latest update when it falls behind, which suits consumers that only care about the current connectivity.
+    /// It can be cloned and is closed once the receive end of the fanout closed.
+    #[allow(clippy::must_use_candidate)]
+    pub fn watch(&self) -> watch::Receiver<Option<T>> {
+        self.latest.clone()
+    }
+
+    /// Get the latest update, [`None`] until the first update is received
+    #[allow(clippy::must_use_candidate)]
+    pub fn latest(&self) -> Option<T> {
+        self.latest.borrow().clone()
+    }
+}
+
+/// Forwards every update of `receiver` to the subscribers of `updates` and to `latest`.
+///
+/// # Notes
+///
+/// This function completes when `receiver` closed or, at the next update, when nothing subscribes to either anymore.
+async fn forward<T: Clone + Send + 'static>(
+    mut receiver: UnboundedReceiver<T>,
+    updates: Arc<Mutex<Option<broadcast::Sender<T>>>>,
+    latest: watch::Sender<Option<T>>,
+) {
+    while let Some(update) = receiver.recv().await {
+        latest.send_replace(Some(update.clone()));
+        let subscribed = updates
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .map_or(false, |updates| updates.send(update).is_ok());
+        if !subscribed && latest.is_closed() {
+            break;
+        }
+    }
+    *updates.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
@@ -0,0 +1,125 @@
+// SPDX-License-Identifier: MIT
+
+//! Stable fingerprints identifying networks.
+
+use crate::state::NetworkIdentity;
+use alloc::vec::Vec;
+use core::fmt::Display;
+#[cfg(not(feature = "std"))]
+use core::net::IpAddr;
+#[cfg(feature = "std")]
+use std::net::IpAddr;
+
+/// The FNV-1a 64 bit offset basis
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+/// The FNV-1a 64 bit prime
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// A FNV-1a hasher which, unlike the std hashers, is stable across builds and platforms.
+struct Fnv(u64);
+impl Fnv {
+    /// Create a new [`Fnv`] instance
+    const fn new() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+
+    /// Feed bytes to the hasher
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// Feed an ip address to the hasher
+    fn write_ip(&mut self, address: IpAddr) {
+        match address {
+            IpAddr::V4(ipv4_address) => {
+                self.write(&[4]);
+                self.write(&ipv4_address.octets());
+            }
+            IpAddr::V6(ipv6_address) => {
+                self.write(&[6]);
+                self.write(&ipv6_address.octets());
+            }
+        }
+    }
+}
+
+/// A stable fingerprint of a network.
+///
+/// The fingerprint is derived from the gateways and subnets of a network and does not depend on interface indices,
+/// so the same network results in the same fingerprint across restarts and machines.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct NetworkFingerprint(u64);
+impl NetworkFingerprint {
+    /// Create the fingerprint of an interface from its [identities](NetworkIdentity)
+    #[allow(clippy::must_use_candidate)]
+    pub fn from_identities<'a>(identities: impl IntoIterator<Item = &'a NetworkIdentity>) -> Self {
+        let mut hasher = Fnv::new();
+        for identity in identities {
+            hasher.write(b"g");
+            for &gateway in &identity.gateways {
+                hasher.write_ip(gateway);
+            }
+            hasher.write(b"s");
+            for &(subnet, prefix_length) in &identity.subnets {
+                hasher.write_ip(subnet);
+                hasher.write(&[prefix_length]);
+            }
+        }
+        Self(hasher.0)
+    }
+
+    /// Mix the hardware addresses of the gateways of a network into this fingerprint, which tells apart networks that use the same addresses.
+    ///
+    /// The hardware addresses must be given in a stable order.
+    #[allow(clippy::must_use_candidate)]
+    pub fn with_gateway_hardware_addresses<'a>(
+        self,
+        hardware_addresses: impl IntoIterator<Item = &'a [u8]>,
+    ) -> Self {
+        let mut hasher = Fnv::new();
+        hasher.write(&self.0.to_be_bytes());
+        hasher.write(b"m");
+        for hardware_address in hardware_addresses {
+            hasher.write(&hardware_address.len().to_be_bytes());
+            hasher.write(hardware_address);
+        }
+        Self(hasher.0)
+    }
+
+    /// Combine the fingerprints of multiple interfaces into one fingerprint.
+    ///
+    /// Has a valid result if there is at least one fingerprint.
+    #[allow(clippy::must_use_candidate)]
+    pub fn combine(fingerprints: impl IntoIterator<Item = Self>) -> Option<Self> {
+        let mut sorted = fingerprints.into_iter().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        sorted.dedup();
+        match *sorted.as_slice() {
+            [] => None,
+            [fingerprint] => Some(fingerprint),
+            _ => {
+                let mut hasher = Fnv::new();
+                for fingerprint in sorted {
+                    hasher.write(&fingerprint.0.to_be_bytes());
+                }
+                Some(Self(hasher.0))
+            }
+        }
+    }
+
+    /// Get the raw value of this fingerprint
+    #[allow(clippy::must_use_candidate)]
+    pub const fn value(self) -> u64 {
+        self.0
+    }
+}
+impl Display for NetworkFingerprint {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
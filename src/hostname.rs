@@ -0,0 +1,251 @@
+// SPDX-License-Identifier: MIT
+
+//! Watches the hostname and the search domains of the system.
+//!
+//! Neither is notified in a way that is practical to receive, so they are polled.
+//! On unix the hostname is the one of the kernel, which systemd-hostnamed sets on linux when it changes over d-bus,
+//! and the search domains are read from `/etc/resolv.conf` where dhcp clients write the domains of the network.
+//! On windows the dns hostname and the primary dns suffix are read with `GetComputerNameExW`,
+//! followed by the connection specific dns suffixes of the adapters that are up.
+
+use futures::Future;
+use log::debug;
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::{watch, Notify},
+    time::{interval, MissedTickBehavior},
+};
+
+/// How often the hostname and the search domains are read
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The names of the system.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub(crate) struct Names {
+    /// The hostname of the system, [`None`] when it could not be read
+    pub(crate) hostname: Option<String>,
+    /// The domains appended to names which are not fully qualified
+    pub(crate) search: Vec<String>,
+}
+
+/// Reads the names on unix systems.
+#[cfg(unix)]
+mod platform {
+    use super::Names;
+    use log::debug;
+    use std::{fs, io};
+
+    /// The configuration of the resolver of the system
+    const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+    /// Reads the names of the system, a missing resolv.conf has no search domains.
+    pub(super) fn read() -> Names {
+        Names {
+            hostname: hostname(),
+            search: search(&fs::read_to_string(RESOLV_CONF).unwrap_or_default()),
+        }
+    }
+
+    /// Gets the hostname of the kernel, [`None`] when it could not be read or is empty.
+    fn hostname() -> Option<String> {
+        let mut buffer = [0_u8; 256];
+        // SAFETY:
+        // the buffer is valid for its length
+        if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+            debug!("reading the hostname failed {}", io::Error::last_os_error());
+            return None;
+        }
+        // the name is not nul terminated when it was truncated
+        let length = buffer
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(buffer.len());
+        let hostname = String::from_utf8_lossy(buffer.get(..length)?).into_owned();
+        (!hostname.is_empty()).then_some(hostname)
+    }
+
+    /// Parses the search domains of a resolv.conf.
+    ///
+    /// The `domain` keyword is used as search domain when there is no `search` keyword.
+    fn search(contents: &str) -> Vec<String> {
+        let mut search = None;
+        let mut domain = None;
+        for line in contents.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("search") => search = Some(words.map(str::to_owned).collect()),
+                Some("domain") => domain = words.next().map(str::to_owned),
+                _ => {}
+            }
+        }
+        search.unwrap_or_else(|| domain.into_iter().collect())
+    }
+}
+
+/// Reads the names on windows.
+#[cfg(windows)]
+mod platform {
+    use super::Names;
+    use core::mem::size_of;
+    use log::debug;
+    use windows::{
+        core::PWSTR,
+        Win32::{
+            Foundation::{ERROR_BUFFER_OVERFLOW, NO_ERROR},
+            NetworkManagement::{
+                IpHelper::{
+                    GetAdaptersAddresses, GAA_FLAG_SKIP_ANYCAST, GAA_FLAG_SKIP_DNS_SERVER,
+                    GAA_FLAG_SKIP_FRIENDLY_NAME, GAA_FLAG_SKIP_MULTICAST, GAA_FLAG_SKIP_UNICAST,
+                    IP_ADAPTER_ADDRESSES_LH,
+                },
+                Ndis::IfOperStatusUp,
+            },
+            Networking::WinSock::AF_UNSPEC,
+            System::SystemInformation::{
+                ComputerNameDnsDomain, ComputerNameDnsHostname, GetComputerNameExW,
+                COMPUTER_NAME_FORMAT,
+            },
+        },
+    };
+
+    /// Reads the names of the system, the primary dns suffix is searched before the connection specific ones.
+    pub(super) fn read() -> Names {
+        let mut search: Vec<String> = computer_name(ComputerNameDnsDomain).into_iter().collect();
+        for suffix in suffixes() {
+            if !search.contains(&suffix) {
+                search.push(suffix);
+            }
+        }
+        Names {
+            hostname: computer_name(ComputerNameDnsHostname),
+            search,
+        }
+    }
+
+    /// Gets the name of the computer in `format`, [`None`] when it could not be read or is empty.
+    fn computer_name(format: COMPUTER_NAME_FORMAT) -> Option<String> {
+        let mut size = 0;
+        // SAFETY:
+        // Invoking an unsafe windows api
+        // without a buffer the required size including the nul terminator is stored in size
+        unsafe { GetComputerNameExW(format, PWSTR::null(), &mut size) };
+        let mut buffer = vec![0_u16; usize::try_from(size).ok()?];
+        // SAFETY:
+        // Invoking an unsafe windows api
+        // the buffer is valid for size characters
+        if !unsafe { GetComputerNameExW(format, PWSTR(buffer.as_mut_ptr()), &mut size) }.as_bool() {
+            debug!(
+                "reading the computer name failed {}",
+                std::io::Error::last_os_error()
+            );
+            return None;
+        }
+        // on success size is the length without the nul terminator
+        buffer.truncate(usize::try_from(size).ok()?);
+        let name = String::from_utf16_lossy(&buffer);
+        (!name.is_empty()).then_some(name)
+    }
+
+    /// Gets the connection specific dns suffixes of the adapters that are up.
+    fn suffixes() -> Vec<String> {
+        let flags = GAA_FLAG_SKIP_UNICAST
+            | GAA_FLAG_SKIP_ANYCAST
+            | GAA_FLAG_SKIP_MULTICAST
+            | GAA_FLAG_SKIP_DNS_SERVER
+            | GAA_FLAG_SKIP_FRIENDLY_NAME;
+        let mut size = 16 * 1024_u32;
+        // the adapters are allocated as u64 to align them
+        let mut buffer: Vec<u64>;
+        loop {
+            buffer = vec![0; usize::try_from(size).unwrap_or(0) / size_of::<u64>() + 1];
+            // SAFETY:
+            // Invoking an unsafe windows api
+            // the buffer is valid for at least size bytes
+            let result = unsafe {
+                GetAdaptersAddresses(
+                    AF_UNSPEC,
+                    flags,
+                    None,
+                    Some(buffer.as_mut_ptr().cast()),
+                    &mut size,
+                )
+            };
+            if result == ERROR_BUFFER_OVERFLOW.0 {
+                continue;
+            }
+            if result != NO_ERROR.0 {
+                debug!("reading the adapters failed with {result}");
+                return Vec::new();
+            }
+            break;
+        }
+        let mut suffixes = Vec::new();
+        let mut adapter = buffer.as_ptr().cast::<IP_ADAPTER_ADDRESSES_LH>();
+        while !adapter.is_null() {
+            // SAFETY:
+            // the adapters are a linked list within the buffer which ends with a null pointer
+            let current = unsafe { &*adapter };
+            if current.OperStatus == IfOperStatusUp && !current.DnsSuffix.is_null() {
+                // SAFETY:
+                // the suffix is a nul terminated string within the buffer
+                if let Ok(suffix) = unsafe { current.DnsSuffix.to_string() } {
+                    if !suffix.is_empty() && !suffixes.contains(&suffix) {
+                        suffixes.push(suffix);
+                    }
+                }
+            }
+            adapter = current.Next;
+        }
+        suffixes
+    }
+}
+
+/// The names of the system received from the watcher.
+pub(crate) struct Watch {
+    /// The latest names, [`None`] until they were read the first time
+    names: watch::Receiver<Option<Names>>,
+}
+impl Watch {
+    /// Creates the [`Watch`] and the watcher which notifies `wake` of every change of the names.
+    ///
+    /// The watcher completes when the [`Watch`] is dropped.
+    pub(crate) fn new(wake: Arc<Notify>) -> (Self, impl Future<Output = ()>) {
+        let (names_tx, names) = watch::channel(None);
+        (Self { names }, run(names_tx, wake))
+    }
+
+    /// Get the names when they changed since the last call.
+    pub(crate) fn changed(&mut self) -> Option<Names> {
+        if self.names.has_changed().unwrap_or(false) {
+            self.names.borrow_and_update().clone()
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads the names periodically and sends them when they differ from the previous ones.
+///
+/// # Notes
+///
+/// This function completes when the receive end is dropped.
+async fn run(names: watch::Sender<Option<Names>>, wake: Arc<Notify>) {
+    let mut poll = interval(POLL_INTERVAL);
+    poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+        {
+            tokio::select! {
+                _ = poll.tick() => {},
+                _ = names.closed() => break,
+            }
+        }
+        let current = platform::read();
+        if names.borrow().as_ref() != Some(&current) {
+            debug!("names of the system changed {current:?}");
+            names.send_replace(Some(current));
+            wake.notify_one();
+        }
+    }
+    debug!("no more hostname watchers");
+}
@@ -0,0 +1,225 @@
+// SPDX-License-Identifier: MIT
+
+//! A minimal http/1.1 client for plain http urls.
+
+use crate::IpFamily;
+use core::{fmt::Display, str::FromStr};
+use std::error::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{lookup_host, TcpStream},
+};
+
+/// The maximum size of the status line and headers of a response
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+/// The maximum size of the body of a response, the rest is ignored
+const MAX_BODY_SIZE: usize = 16 * 1024;
+
+/// A parsed `http://` or `mqtt://` url.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Url {
+    /// The host name or ip address, ipv6 addresses without brackets
+    host: String,
+    /// The port
+    port: u16,
+    /// The path including the query
+    path: String,
+}
+impl Url {
+    /// Parses `url` which must start with `scheme`, using `default_port` when it has no port.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `url` has another scheme or is invalid.
+    pub fn parse(
+        url: &str,
+        scheme: &str,
+        default_port: u16,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let rest = url
+            .strip_prefix(scheme)
+            .ok_or_else(|| format!("only {scheme} urls are supported: {url}"))?;
+        let (authority, path) = rest
+            .find('/')
+            .map_or((rest, "/"), |index| rest.split_at(index));
+        let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+            let (host, port) = bracketed
+                .split_once(']')
+                .ok_or_else(|| format!("invalid ipv6 host: {url}"))?;
+            (host, port.strip_prefix(':'))
+        } else {
+            authority
+                .split_once(':')
+                .map_or((authority, None), |(host, port)| (host, Some(port)))
+        };
+        if host.is_empty() {
+            return Err(format!("missing host: {url}").into());
+        }
+        Ok(Self {
+            host: host.to_owned(),
+            port: port.map_or(Ok(default_port), str::parse)?,
+            path: path.to_owned(),
+        })
+    }
+
+    /// The path including the query
+    #[cfg(feature = "mqtt")]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+impl FromStr for Url {
+    type Err = Box<dyn Error + Send + Sync>;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        Self::parse(url, "http://", 80)
+    }
+}
+
+impl Display for Url {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}{}", self.host, self.port, self.path)
+        } else {
+            write!(f, "{}:{}{}", self.host, self.port, self.path)
+        }
+    }
+}
+
+/// The status and the body of a response.
+#[derive(Debug)]
+// only the probes look at the location and the body
+#[cfg_attr(not(feature = "probe"), allow(dead_code))]
+pub struct Response {
+    /// The status code
+    pub status: u16,
+    /// The value of the location header
+    pub location: Option<String>,
+    /// The start of the body
+    pub body: Vec<u8>,
+}
+impl Response {
+    /// Whether the status code indicates success
+    #[cfg(feature = "webhook")]
+    pub const fn is_success(&self) -> bool {
+        matches!(self.status, 200..=299)
+    }
+}
+
+/// Parses the status code and the location header from the status line and the headers of a response.
+fn parse_head(head: &str) -> Result<(u16, Option<String>), Box<dyn Error + Send + Sync>> {
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split(' ').nth(1))
+        .ok_or("missing status line")?
+        .parse()?;
+    let location = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|&(name, _)| name.trim().eq_ignore_ascii_case("location"))
+        .map(|(_, value)| value.trim().to_owned());
+    Ok((status, location))
+}
+
+/// Connects to the host of `url`, only using addresses of `family` when given.
+///
+/// # Errors
+///
+/// This function will return an error if the host could not be resolved or none of its addresses could be connected to.
+pub async fn connect(
+    url: &Url,
+    family: Option<IpFamily>,
+) -> Result<TcpStream, Box<dyn Error + Send + Sync>> {
+    let mut last_error = None;
+    for address in lookup_host((url.host.as_str(), url.port)).await? {
+        let matches = match family {
+            Some(IpFamily::V4) => address.is_ipv4(),
+            Some(IpFamily::V6) => address.is_ipv6(),
+            None => true,
+        };
+        if matches {
+            match TcpStream::connect(address).await {
+                Ok(stream) => return Ok(stream),
+                Err(error) => last_error = Some(error),
+            }
+        }
+    }
+    Err(last_error.map_or_else(
+        || format!("no address for {} of the requested family", url.host).into(),
+        Into::into,
+    ))
+}
+
+/// Sends a request and reads the response.
+///
+/// The request is sent over `family` when given.
+/// The content type and length are only sent when `body` is not empty.
+///
+/// # Errors
+///
+/// This function will return an error if the connection failed or the response is invalid.
+pub async fn request(
+    method: &str,
+    url: &Url,
+    family: Option<IpFamily>,
+    content_type: &str,
+    body: &[u8],
+) -> Result<Response, Box<dyn Error + Send + Sync>> {
+    let mut stream = connect(url, family).await?;
+    let host = if url.host.contains(':') {
+        format!("[{}]:{}", url.host, url.port)
+    } else {
+        format!("{}:{}", url.host, url.port)
+    };
+    let content = if body.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "Content-Type: {content_type}\r\nContent-Length: {}\r\n",
+            body.len()
+        )
+    };
+    let head = format!(
+        "{method} {} HTTP/1.1\r\nHost: {host}\r\nUser-Agent: network_connectivity\r\nConnection: close\r\n{content}\r\n",
+        url.path,
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+
+    let mut received = Vec::new();
+    let mut buffer = [0; 1024];
+    let end = loop {
+        if let Some(end) = received.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end;
+        }
+        if received.len() > MAX_HEAD_SIZE {
+            return Err("response head too large".into());
+        }
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break received.len();
+        }
+        received.extend_from_slice(buffer.get(..read).ok_or("read out of bounds")?);
+    };
+    let (status, location) = parse_head(&String::from_utf8_lossy(
+        received.get(..end).ok_or("head out of bounds")?,
+    ))?;
+
+    // the connection is closed by the server after the body
+    let mut body = received.split_off(end);
+    // the empty line between the head and the body
+    body.drain(..body.len().min(4));
+    while body.len() < MAX_BODY_SIZE {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(buffer.get(..read).ok_or("read out of bounds")?);
+    }
+    body.truncate(MAX_BODY_SIZE);
+    Ok(Response {
+        status,
+        location,
+        body,
+    })
+}
@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: MIT
+
+//! An inventory of the interfaces which retains the ones that are down or were removed.
+
+use crate::state::{InterfaceId, InterfaceSnapshot};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+/// The interfaces of the system by their index, shared between the driver and the application.
+///
+/// Unlike the connectivity, which only counts interfaces that are up, the inventory contains every interface the driver has seen:
+/// interfaces that are down keep the addresses they had before they went down when the system removed them,
+/// and removed interfaces are kept with their last known details and marked as [removed](InterfaceSnapshot::removed) until the system reuses their index.
+/// This is meant for inventory and debugging consumers which want to show what the system looked like.
+///
+/// Pass a clone to [`Builder::inventory`](crate::Builder::inventory) to let the driver maintain it.
+#[derive(Clone, Debug, Default)]
+pub struct Inventory(Arc<RwLock<BTreeMap<InterfaceId, InterfaceSnapshot>>>);
+impl Inventory {
+    /// Create a new [`Inventory`] instance without interfaces
+    #[allow(clippy::must_use_candidate)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the interface with `index`, [`None`] when it is not known
+    #[allow(clippy::must_use_candidate)]
+    pub fn get(&self, index: InterfaceId) -> Option<InterfaceSnapshot> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&index)
+            .cloned()
+    }
+
+    /// Get every known interface sorted by its index
+    #[allow(clippy::must_use_candidate)]
+    pub fn interfaces(&self) -> Vec<InterfaceSnapshot> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Forget the interfaces which were removed
+    pub fn clear_removed(&self) {
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|_, interface| !interface.removed);
+    }
+
+    /// Records the `current` interfaces.
+    ///
+    /// An interface that is down without addresses keeps the addresses it had before,
+    /// the interfaces which are no longer among them are marked as removed.
+    pub(crate) fn update(&self, current: Vec<InterfaceSnapshot>) {
+        let mut interfaces = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        let mut previous = core::mem::take(&mut *interfaces);
+        for mut interface in current {
+            if let Some(known) = previous.remove(&interface.index) {
+                if !interface.up && interface.addresses.is_empty() && !known.removed {
+                    interface.addresses = known.addresses;
+                }
+            }
+            interfaces.insert(interface.index, interface);
+        }
+        for (index, mut interface) in previous {
+            interface.removed = true;
+            interface.up = false;
+            interfaces.insert(index, interface);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: MIT
+
+//! Logs every connectivity transition as a single structured json record, so log pipelines such as journald or ELK can ingest them.
+//!
+//! Every emitted event is logged as a json record at the debug level as well, in place of the debug lines of the emitter.
+
+use crate::{state::InterfaceId, Connectivity, InterfaceNames, NetworkEvent, Source};
+use log::{debug, info, log_enabled, warn, Level};
+use serde::Serialize;
+
+/// The target of the logged transitions, so they can be filtered independently of the other log lines
+const TARGET: &str = "network_connectivity::transition";
+/// The target of the logged events
+const EVENT_TARGET: &str = "network_connectivity::event";
+
+/// The interface a transition happened on.
+#[derive(Serialize)]
+struct Interface {
+    /// The index of the interface
+    index: InterfaceId,
+    /// The name of the interface, [`None`] when it is not known
+    name: Option<String>,
+}
+
+/// A connectivity transition as it is logged.
+#[derive(Serialize)]
+struct Transition {
+    /// The connectivity before the transition, [`None`] for the initial connectivity
+    old: Option<Connectivity>,
+    /// The connectivity after the transition
+    new: Connectivity,
+    /// The kind of event that caused the transition, `initial` or `reevaluated`
+    reason: &'static str,
+    /// How many seconds the previous connectivity lasted, [`None`] for the initial connectivity
+    seconds_in_previous_state: Option<f64>,
+    /// Whether a probe verified the internet connectivity
+    verified: bool,
+    /// How the internet connectivity was determined
+    source: Source,
+    /// The interface the causing event happened on, [`None`] when it is not about a single interface
+    interface: Option<Interface>,
+}
+
+/// Logs the connectivity transitions among `events` which were emitted for the same change of the system at the info level.
+///
+/// The reason and interface of a transition are derived like the ones exported to OpenTelemetry,
+/// `names` provides the name of the interface when the causing event does not contain it.
+pub(crate) fn log(events: &[NetworkEvent], names: &InterfaceNames) {
+    let cause = NetworkEvent::cause(events);
+    for event in events {
+        if let NetworkEvent::ConnectivityChanged {
+            connectivity,
+            previous,
+            duration_in_previous_state,
+            verified,
+            source,
+            ..
+        } = *event
+        {
+            // the events of the initial state did not cause the initial connectivity.
+            let cause = previous.and(cause);
+            let transition = Transition {
+                old: previous,
+                new: connectivity,
+                reason: NetworkEvent::reason(cause, previous),
+                seconds_in_previous_state: duration_in_previous_state
+                    .map(|duration| duration.as_secs_f64()),
+                verified,
+                source,
+                interface: cause
+                    .and_then(|cause| cause.interface(names))
+                    .map(|(index, name)| Interface { index, name }),
+            };
+            match serde_json::to_string(&transition) {
+                Ok(record) => info!(target: TARGET, "{record}"),
+                Err(error) => warn!("serializing a connectivity transition failed {error}"),
+            }
+        }
+    }
+}
+
+/// Logs `event` as a json record at the debug level.
+pub(crate) fn log_event(event: &NetworkEvent) {
+    if !log_enabled!(target: EVENT_TARGET, Level::Debug) {
+        return;
+    }
+    match serde_json::to_string(event) {
+        Ok(record) => debug!(target: EVENT_TARGET, "{record}"),
+        Err(error) => warn!("serializing an event failed {error}"),
+    }
+}
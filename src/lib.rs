@@ -1,6 +1,15 @@
 // SPDX-License-Identifier: MIT
 
 //! This crate allows you to receive network connectivity updates through a channel.
+//!
+//! Without the `std` feature only the platform independent [state] is available.
+//! It takes its ip addresses from `core::net` then, while with `std` they are taken from `std::net`.
+//! The minimum supported rust version is 1.77 either way.
+//!
+//! Linux and windows are notified of changes, FreeBSD, OpenBSD and NetBSD through a `PF_ROUTE` routing socket after which the system is sampled with `getifaddrs` and `netstat -rn`.
+//! Other unix systems are polled with `getifaddrs` and `netstat -rn`.
+//!
+//! All drivers created in a process share one netlink socket or windows notification, so several libraries using this crate do not multiply the resources of the system.
 
 #![warn(clippy::cargo, clippy::nursery, clippy::pedantic, clippy::restriction)]
 #![allow(
@@ -10,19 +19,142 @@
     clippy::missing_trait_methods,
     clippy::single_char_lifetime_names
 )]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-#[cfg(target_os = "linux")]
+extern crate alloc;
+
+#[cfg(all(feature = "arp", target_os = "linux"))]
+mod arp;
+#[cfg(feature = "std")]
+mod backend;
+#[cfg(feature = "blocking")]
+mod blocking;
+#[cfg(feature = "std")]
+mod bounded;
+#[cfg(feature = "std")]
+mod bridge;
+#[cfg(all(
+    feature = "std",
+    any(target_os = "freebsd", target_os = "openbsd", target_os = "netbsd")
+))]
+mod bsd;
+#[cfg(feature = "std")]
+mod builder;
+#[cfg(feature = "json")]
+mod capture;
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(all(feature = "daemon", any(unix, windows)))]
+pub mod daemon;
+#[cfg(feature = "std")]
+mod diagnostic;
+#[cfg(feature = "std")]
+mod dispatcher;
+#[cfg(all(feature = "dns", any(target_os = "linux", windows)))]
+mod dns;
+#[cfg(feature = "std")]
+mod emitter;
+mod event;
+#[cfg(feature = "std")]
+mod fanout;
+mod fingerprint;
+#[cfg(all(feature = "hostname", any(unix, windows)))]
+mod hostname;
+#[cfg(any(feature = "probe", feature = "webhook"))]
+mod http;
+#[cfg(feature = "std")]
+mod inventory;
+#[cfg(feature = "json-log")]
+mod json_log;
+#[cfg(all(feature = "std", target_os = "linux"))]
 mod linux;
-#[cfg(any(target_os = "linux"))]
-mod state;
-#[cfg(target_os = "windows")]
+#[cfg(feature = "monitor")]
+mod monitor;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "std")]
+mod names;
+#[cfg(feature = "nat64")]
+mod nat64;
+#[cfg(all(feature = "nm", target_os = "linux"))]
+mod nm;
+#[cfg(feature = "otel")]
+mod otel;
+#[cfg(feature = "probe")]
+mod probe;
+#[cfg(feature = "std")]
+mod routing;
+#[cfg(all(feature = "service", windows))]
+pub mod service;
+#[cfg(feature = "std")]
+mod shared;
+pub mod state;
+#[cfg(feature = "std")]
+mod store;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+#[cfg(feature = "tokio-console")]
+mod task;
+#[cfg(all(feature = "std", unix))]
+mod unix;
+#[cfg(feature = "webhook")]
+mod webhook;
+#[cfg(all(feature = "std", target_os = "windows"))]
 mod windows;
 
-use futures::Future;
-use std::error::Error;
+#[cfg(feature = "std")]
+pub use backend::{select_backends, Backend, BackendUnavailable};
+#[cfg(feature = "blocking")]
+pub use blocking::BlockingReceiver;
+#[cfg(feature = "std")]
+pub use bounded::{BoundedReceiver, OverflowPolicy};
+#[cfg(feature = "crossbeam")]
+pub use bridge::forward_to_crossbeam;
+#[cfg(feature = "std")]
+pub use bridge::forward_to_futures;
+#[cfg(feature = "std")]
+pub use builder::Builder;
+#[cfg(not(feature = "std"))]
+use core::net::IpAddr;
+use core::{cmp::max, fmt::Display, iter::Sum};
+#[cfg(feature = "std")]
+pub use diagnostic::Diagnostic;
+#[cfg(feature = "std")]
+pub use dispatcher::Dispatcher;
+#[cfg(feature = "std")]
+use emitter::Emitter;
+pub use event::{NetworkEvent, Source};
+#[cfg(feature = "std")]
+pub use fanout::Fanout;
+pub use fingerprint::NetworkFingerprint;
+#[cfg(feature = "std")]
+use futures::{Future, Stream};
+#[cfg(feature = "std")]
+pub use inventory::Inventory;
+#[cfg(feature = "monitor")]
+pub use monitor::{Monitor, NetworkDetails, SequenceNumber};
+#[cfg(feature = "std")]
+pub use names::InterfaceNames;
+#[cfg(feature = "nat64")]
+pub use nat64::Nat64Policy;
+#[cfg(feature = "probe")]
+pub use probe::{DnsProbe, HttpProbe, Preset, Probe, ProbeFuture, ProbeResult};
+#[cfg(feature = "std")]
+pub use routing::RoutingInfo;
+#[cfg(feature = "std")]
+use std::{error::Error, net::IpAddr};
+#[cfg(feature = "json")]
+pub use store::JsonFileStore;
+#[cfg(feature = "std")]
+pub use store::Store;
+#[cfg(feature = "std")]
+use tokio::sync::mpsc::UnboundedReceiver;
 
 /// Represents connectivity to the internet.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[non_exhaustive]
 pub enum ConnectivityState {
     /// No connectivity
@@ -33,8 +165,62 @@ pub enum ConnectivityState {
     Internet,
 }
 
+impl Display for ConnectivityState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Self::None => write!(f, "none"),
+            Self::Network => write!(f, "network"),
+            Self::Internet => write!(f, "internet"),
+        }
+    }
+}
+
+/// Represents an ip type.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum IpFamily {
+    /// Ipv4
+    V4,
+    /// Ipv6
+    V6,
+}
+
+impl IpFamily {
+    /// Whether the handling of this ip type is compiled in, see the `ipv4-only` and `ipv6-only` features.
+    ///
+    /// Enabling both features compiles in both ip types, so building with all features keeps working.
+    pub(crate) const fn is_compiled(self) -> bool {
+        match self {
+            Self::V4 => !cfg!(all(feature = "ipv6-only", not(feature = "ipv4-only"))),
+            Self::V6 => !cfg!(all(feature = "ipv4-only", not(feature = "ipv6-only"))),
+        }
+    }
+
+    /// Get the ip type of `address`
+    pub(crate) const fn of(address: &IpAddr) -> Self {
+        match *address {
+            IpAddr::V4(_) => Self::V4,
+            IpAddr::V6(_) => Self::V6,
+        }
+    }
+}
+
+impl Display for IpFamily {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Self::V4 => write!(f, "ipv4"),
+            Self::V6 => write!(f, "ipv6"),
+        }
+    }
+}
+
 /// Represents connectivity to the internet separated by ipv4 and ipv6.
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[non_exhaustive]
 pub struct Connectivity {
     /// Ipv4 connectivity
@@ -63,6 +249,124 @@ impl Connectivity {
             self.ipv6
         }
     }
+
+    /// Get the connectivity state of `family`
+    #[allow(clippy::must_use_candidate)]
+    pub const fn get(&self, family: IpFamily) -> ConnectivityState {
+        match family {
+            IpFamily::V4 => self.ipv4,
+            IpFamily::V6 => self.ipv6,
+        }
+    }
+
+    /// Merge with `other` taking the highest connectivity state per ip type
+    #[must_use]
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            ipv4: max(self.ipv4, other.ipv4),
+            ipv6: max(self.ipv6, other.ipv6),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Connectivity {
+    /// Queries the current connectivity once without setting up a long running watcher, see [`Builder::current`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the underlying driver failed in some way.
+    pub async fn current() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Builder::new().current().await
+    }
+}
+
+impl FromIterator<Self> for Connectivity {
+    /// Merges all connectivity values, without any values there is no connectivity.
+    fn from_iter<T: IntoIterator<Item = Self>>(iter: T) -> Self {
+        iter.into_iter().fold(
+            Self {
+                ipv4: ConnectivityState::None,
+                ipv6: ConnectivityState::None,
+            },
+            Self::merge,
+        )
+    }
+}
+
+impl Sum for Connectivity {
+    /// Merges all connectivity values, without any values there is no connectivity.
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.collect()
+    }
+}
+
+/// Represents the reachability of a specific destination.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum ReachabilityState {
+    /// No route to the destination can be selected
+    Unreachable,
+    /// The destination is directly reachable on the link of the selected route
+    Direct,
+    /// The destination is reachable through the gateway of the selected route
+    Gateway,
+}
+
+impl Display for ReachabilityState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Self::Unreachable => write!(f, "unreachable"),
+            Self::Direct => write!(f, "direct"),
+            Self::Gateway => write!(f, "gateway"),
+        }
+    }
+}
+
+/// Creates the driver which sends through the [emitter](Emitter).
+///
+/// All drivers of the process share one platform specific listener instead of each creating their own,
+/// it is created by the first available of the [selected backends](select_backends).
+///
+/// # Errors
+///
+/// This function will return a [`BackendUnavailable`] error if the process may not use any of the selected backends.
+#[cfg(feature = "std")]
+fn driver(
+    emitter: Emitter,
+) -> Result<
+    impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+    Box<dyn Error + Send + Sync>,
+> {
+    backend::preflight()?;
+    Ok(shared::drive(emitter))
+}
+
+/// Determines the [reachability](ReachabilityState) of `destination` by asking the system which route it would select for it.
+///
+/// Unlike the connectivity this takes the routing tables and rules into account, so split tunnel vpns are handled correctly.
+///
+/// # Errors
+///
+/// This function will return an error if the system could not be asked.
+#[cfg(feature = "std")]
+pub async fn can_reach(
+    destination: IpAddr,
+) -> Result<ReachabilityState, Box<dyn Error + Send + Sync>> {
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "linux")] {
+            linux::can_reach(destination).await
+        } else if #[cfg(target_os = "windows")] {
+            Ok(windows::can_reach(destination))
+        } else if #[cfg(unix)] {
+            Ok(unix::can_reach(destination))
+        } else {
+            compile_error!("This crate has no implementation for this configuration.");
+        }
+    }
 }
 
 /// Creates a driver that sends connectivity updates to a channel.
@@ -79,20 +383,103 @@ impl Connectivity {
 ///
 /// This function will return an error if the underlying driver failed in some way.
 /// The returned future can fail when the underlying driver received an error.
+#[cfg(feature = "std")]
 pub fn new() -> Result<
     (
         impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
-        tokio::sync::mpsc::UnboundedReceiver<Connectivity>,
+        UnboundedReceiver<Connectivity>,
     ),
     Box<dyn Error + Send + Sync>,
 > {
-    cfg_if::cfg_if! {
-        if #[cfg(target_os = "linux")] {
-            linux::new()
-        } else if #[cfg(target_os = "windows")] {
-            windows::new()
-        } else {
-            compile_error!("This crate has no implementation for this configuration.");
-        }
-    }
+    Builder::new().build()
+}
+
+/// Creates a driver that yields connectivity updates as a [`Stream`].
+///
+/// # Returns
+///
+/// The return value consists of a future that must be awaited and a stream of connectivity updates wrapping the receive end of the channel.
+///
+/// # Notes
+///
+/// When the stream is dropped, the future will run to completion.
+/// The stream ends when the future completed.
+///
+/// # Errors
+///
+/// This function will return an error if the underlying driver failed in some way.
+/// The returned future can fail when the underlying driver received an error.
+#[cfg(feature = "std")]
+pub fn new_stream() -> Result<
+    (
+        impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+        impl Stream<Item = Connectivity>,
+    ),
+    Box<dyn Error + Send + Sync>,
+> {
+    let (driver, mut receiver) = new()?;
+    Ok((
+        driver,
+        futures::stream::poll_fn(move |context| receiver.poll_recv(context)),
+    ))
+}
+
+/// Creates a driver that only keeps the latest connectivity in a [`watch`](tokio::sync::watch) channel.
+///
+/// # Returns
+///
+/// The return value consists of a future that must be awaited and the receive end of a watch channel which holds the latest connectivity.
+///
+/// # Notes
+///
+/// When every clone of the receive end is dropped, the future will run to completion.
+///
+/// # Errors
+///
+/// This function will return an error if the underlying driver failed in some way.
+/// The returned future can fail when the underlying driver received an error.
+#[cfg(feature = "std")]
+pub fn new_watch() -> Result<
+    (
+        impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+        tokio::sync::watch::Receiver<Connectivity>,
+    ),
+    Box<dyn Error + Send + Sync>,
+> {
+    Builder::new().build_watch()
+}
+
+/// Creates a driver that sends detailed [events](NetworkEvent) to a channel.
+///
+/// # Returns
+///
+/// The return value consists of a future that must be awaited and the receive end of a channel through which detailed events are received.
+///
+/// # Notes
+///
+/// When the receive end of the channel is dropped, the future will run to completion.
+///
+/// # Errors
+///
+/// This function will return an error if the underlying driver failed in some way.
+/// The returned future can fail when the underlying driver received an error.
+#[cfg(feature = "std")]
+pub fn new_detailed() -> Result<
+    (
+        impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+        UnboundedReceiver<NetworkEvent>,
+    ),
+    Box<dyn Error + Send + Sync>,
+> {
+    Builder::new().build_detailed()
+}
+
+/// Creates a [`Monitor`] running a driver configured by `config` in a task of the current tokio runtime.
+///
+/// # Errors
+///
+/// This function will return an error if the configuration is invalid, it is not called within a tokio runtime or the driver could not be created.
+#[cfg(all(feature = "monitor", feature = "config"))]
+pub fn new_with_config(config: &config::Config) -> Result<Monitor, Box<dyn Error + Send + Sync>> {
+    config.builder()?.monitor()
 }
@@ -3,122 +3,544 @@
 //! The linux implementation for this crate using rt-netlink.
 
 use crate::{
-    state::{AddressInfo, Interfaces, LinkInfo, RouteInfo},
-    Connectivity,
+    diagnostic::Diagnostic,
+    shared::{Listener, Platform},
+    state::{
+        AddressFlags, AddressInfo, AddressLease, Duplex, InterfaceId, InterfaceType, Interfaces,
+        LinkInfo, LinkSpeedInfo, RouteDetails, RouteInfo, RouteProtocol,
+    },
+    IpFamily, ReachabilityState,
 };
-use core::fmt::Display;
-use futures::{channel::mpsc::UnboundedReceiver, stream::StreamExt, Future, TryStreamExt};
-use log::debug;
+use core::{fmt::Display, ptr::addr_of, time::Duration};
+use futures::{channel::mpsc::UnboundedReceiver, future::pending, stream::StreamExt, TryStreamExt};
+use log::{debug, warn};
 use rtnetlink::{
     new_connection,
     packet::{
         constants::{
-            self, AF_INET, AF_INET6, IFF_LOOPBACK, IFF_LOWER_UP, RTNLGRP_IPV4_IFADDR,
-            RTNLGRP_IPV4_ROUTE, RTNLGRP_IPV6_IFADDR, RTNLGRP_IPV6_ROUTE, RTNLGRP_LINK,
+            self, AF_INET, AF_INET6, AF_UNSPEC, ARPHRD_ETHER, ARPHRD_IPGRE, ARPHRD_NONE,
+            ARPHRD_PPP, ARPHRD_SIT, ARPHRD_TUNNEL, ARPHRD_TUNNEL6, IFF_DORMANT, IFF_LOOPBACK,
+            IFF_LOWER_UP, NLM_F_REQUEST, NUD_FAILED, NUD_INCOMPLETE, RTEXT_FILTER_SKIP_STATS,
+            RTNLGRP_LINK, RTNLGRP_NEIGH, RTN_LOCAL, RTN_UNICAST, RTPROT_BOOT, RTPROT_DHCP,
+            RTPROT_KERNEL, RTPROT_RA, RTPROT_STATIC, RT_TABLE_MAIN,
         },
-        nlas, AddressMessage, LinkMessage, RouteMessage, RtnlMessage,
+        nlas, AddressMessage, LinkMessage, NeighbourMessage, RouteMessage, RtnlMessage,
     },
     proto::{NetlinkMessage, NetlinkPayload},
     sys::{AsyncSocket, SocketAddr},
     Handle, IpVersion,
 };
 use std::{
+    collections::HashSet,
     error::Error,
+    fs, io,
+    mem::size_of,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::unix::{
+        fs::MetadataExt,
+        io::{AsRawFd, FromRawFd, OwnedFd},
+    },
 };
+use tokio::io::unix::AsyncFd;
+
+/// The file which identifies the network namespace of the process
+const NAMESPACE: &str = "/proc/self/ns/net";
+/// How often the network namespace of the process is checked
+const NAMESPACE_INTERVAL: Duration = Duration::from_secs(2);
+/// The lifetime of an address that does not expire
+const INFINITY_LIFE_TIME: u32 = u32::MAX;
+/// The rtnetlink groups the platform listener is notified of changes through
+///
+/// The groups of an ip type which is not compiled in are left out, see [`IpFamily::is_compiled`].
+const GROUPS: &[u32] = &[
+    RTNLGRP_LINK,
+    #[cfg(not(all(feature = "ipv6-only", not(feature = "ipv4-only"))))]
+    constants::RTNLGRP_IPV4_IFADDR,
+    #[cfg(not(all(feature = "ipv4-only", not(feature = "ipv6-only"))))]
+    constants::RTNLGRP_IPV6_IFADDR,
+    #[cfg(not(all(feature = "ipv6-only", not(feature = "ipv4-only"))))]
+    constants::RTNLGRP_IPV4_ROUTE,
+    #[cfg(not(all(feature = "ipv4-only", not(feature = "ipv6-only"))))]
+    constants::RTNLGRP_IPV6_ROUTE,
+    #[cfg(not(all(feature = "ipv4-only", not(feature = "ipv6-only"))))]
+    constants::RTNLGRP_IPV6_IFINFO,
+    RTNLGRP_NEIGH,
+];
+/// The flag of an ipv6 interface which is set when the last router advertisement had the managed flag
+const IF_RA_MANAGED: u32 = 0x40;
+/// The flag of an ipv6 interface which is set when the last router advertisement had the other configuration flag
+const IF_RA_OTHERCONF: u32 = 0x80;
+/// The type of the recursive dns server option of a router advertisement, see RFC 8106
+const ND_OPT_RDNSS: u8 = 25;
+/// The size of a netlink message header
+const NLMSG_HEADER: usize = 16;
+/// The size of the header of a router advertisement option message, `struct nduseroptmsg`
+const NDUSEROPT_HEADER: usize = 16;
 
 /// Converts a vector to an array.
 fn vec_to_array<T, const N: usize>(v: Vec<T>) -> Result<[T; N], Vec<T>> {
     v.try_into()
 }
 
-/// assigns to assign from assignee only when they are different,
+/// Creates the platform listener which publishes snapshots of the system to the shared [listener](Listener).
 ///
-/// # Returns
+/// # Notes
 ///
-/// true if the arguments were different and false otherwise
-fn diff_assign<T>(assign: &mut T, assignee: T) -> bool
-where
-    T: Eq,
-{
-    if *assign == assignee {
-        false
-    } else {
-        *assign = assignee;
-        true
-    }
-}
-
-/// Creates a connection with rtnetlink and sends connectivity updates.
+/// The platform listener completes when rtnetlink stops sending messages.
 ///
-/// # Returns
+/// # Errors
 ///
-/// The return value consists of a future that must be awaited and the receive end of a channel through which connectivity updates are received.
+/// The platform listener fails when the rtnetlink connection failed, memberships couldn't be added or a rtnetlink error was received.
+pub fn listen(listener: &Listener) -> Platform<'_> {
+    Box::pin(run(listener))
+}
+
+/// Checks whether a rtnetlink socket can be opened and join the groups of the platform listener.
 ///
-/// # Notes
+/// # Errors
 ///
-/// When the receive end of the channel is dropped, the future will run to completion.
+/// This function will return an error if netlink is not available to the process, for example because seccomp or a user namespace restricts it.
+pub(crate) fn preflight() -> io::Result<()> {
+    // SAFETY:
+    // the descriptor is owned from here on and closed on drop
+    let socket = unsafe {
+        let descriptor = libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        );
+        if descriptor < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        OwnedFd::from_raw_fd(descriptor)
+    };
+    let length = |size: usize| {
+        libc::socklen_t::try_from(size).map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+    };
+    // SAFETY:
+    // an all zero address is valid and lets the kernel assign the port id
+    let mut address: libc::sockaddr_nl = unsafe { core::mem::zeroed() };
+    address.nl_family = libc::sa_family_t::try_from(libc::AF_NETLINK)
+        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+    let address_length = length(size_of::<libc::sockaddr_nl>())?;
+    // SAFETY:
+    // the address is valid for its size
+    if unsafe { libc::bind(socket.as_raw_fd(), addr_of!(address).cast(), address_length) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let group_length = length(size_of::<u32>())?;
+    for &group in GROUPS {
+        // SAFETY:
+        // the group is valid for its size
+        if unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_NETLINK,
+                libc::NETLINK_ADD_MEMBERSHIP,
+                addr_of!(group).cast(),
+                group_length,
+            )
+        } != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Creates a connection with rtnetlink and publishes snapshots of the system to the shared [listener](Listener).
 ///
 /// # Errors
 ///
-/// This function will return an error if the rtnetlink connection failed or memberships couldn't be added.
-/// The returned future can fail when a rtnetlink error was received.
-pub fn new() -> Result<
-    (
-        impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
-        tokio::sync::mpsc::UnboundedReceiver<Connectivity>,
-    ),
-    Box<dyn Error + Send + Sync>,
-> {
+/// This function will return an error if the rtnetlink connection failed, memberships couldn't be added or a rtnetlink error was received.
+async fn run(listener: &Listener) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // the rtnetlink socket keeps listening to the namespace it was created in, so it is recorded first.
+    let namespace = namespace();
     debug!("creating rtnetlink connection");
     let (mut conn, handle, messages) = new_connection()?;
 
     debug!("add group membership for rtnetlink");
-    let groups = vec![
-        RTNLGRP_LINK,
-        RTNLGRP_IPV4_IFADDR,
-        RTNLGRP_IPV6_IFADDR,
-        RTNLGRP_IPV4_ROUTE,
-        RTNLGRP_IPV6_ROUTE,
-    ];
-    for group in groups {
+    for &group in GROUPS {
         conn.socket_mut().socket_mut().add_membership(group)?;
     }
 
-    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-
-    let checker = check_internet_connectivity(handle, messages, tx);
+    let checker = check_internet_connectivity(handle, messages, listener);
 
+    debug!("waiting on rtnetlink connection or connectivity checker");
+    // waiting for both of these futures can be done with a select because when one finishes the other one will not do anymore meaningful work and can be dropped.
     #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
-    let driver = async {
-        debug!("waiting on rtnetlink connection or connectivity checker");
-        // waiting for both of these futures can be done with a select because when one finishes the other one will not do anymore meaningful work and can be dropped.
+    {
         tokio::select! {
             biased;
             r_check = checker => {
                 r_check?;
             },
             _ = conn => (),
+            _ = namespace_changed(namespace) => {
+                warn!("the process moved to another network namespace, resynchronizing");
+                listener.resync();
+                pending::<()>().await;
+            },
         };
-        debug!("done waiting on rtnetlink connection or connectivity checker");
+    }
+    debug!("done waiting on rtnetlink connection or connectivity checker");
 
-        Ok(())
-    };
+    Ok(())
+}
+
+/// The recursive dns servers of a router advertisement, with the interface it was received on and whether the router withdrew them.
+type DnsServers = (InterfaceId, Vec<Ipv6Addr>, bool);
+
+/// A netlink socket which receives the options of router advertisements the kernel does not process itself, like the recursive dns servers.
+///
+/// The options are only notified to the group `RTNLGRP_ND_USEROPT` in messages which rtnetlink does not parse, so they are received on a socket of their own.
+struct UserOptions(AsyncFd<OwnedFd>);
+impl UserOptions {
+    /// Creates the socket and joins the group of the options.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the socket could not be created or could not join the group.
+    fn new() -> io::Result<Self> {
+        // SAFETY:
+        // the descriptor is owned from here on and closed on drop
+        let socket = unsafe {
+            let descriptor = libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            );
+            if descriptor < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            OwnedFd::from_raw_fd(descriptor)
+        };
+        let length = |size: usize| {
+            libc::socklen_t::try_from(size)
+                .map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+        };
+        // the kernel only delivers notifications to a socket with a port id, so it is bound first.
+        // SAFETY:
+        // an all zero address is valid and lets the kernel assign the port id
+        let mut address: libc::sockaddr_nl = unsafe { core::mem::zeroed() };
+        address.nl_family = libc::sa_family_t::try_from(libc::AF_NETLINK)
+            .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+        let address_length = length(size_of::<libc::sockaddr_nl>())?;
+        // SAFETY:
+        // the address is valid for its size
+        if unsafe { libc::bind(socket.as_raw_fd(), addr_of!(address).cast(), address_length) } != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        let group = constants::RTNLGRP_ND_USEROPT;
+        let group_length = length(size_of::<u32>())?;
+        // SAFETY:
+        // the group is valid for its size
+        if unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_NETLINK,
+                libc::NETLINK_ADD_MEMBERSHIP,
+                addr_of!(group).cast(),
+                group_length,
+            )
+        } != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self(AsyncFd::new(socket)?))
+    }
 
-    Ok((driver, rx))
+    /// Receives the recursive dns servers of the next router advertisements which have any.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if receiving failed.
+    async fn recv(&self) -> io::Result<Vec<DnsServers>> {
+        let mut buffer = vec![0_u8; 8192];
+        loop {
+            let mut guard = self.0.readable().await?;
+            let received = guard.try_io(|socket| {
+                // SAFETY:
+                // the buffer is valid for its length
+                let read = unsafe {
+                    libc::recv(
+                        socket.as_raw_fd(),
+                        buffer.as_mut_ptr().cast(),
+                        buffer.len(),
+                        0,
+                    )
+                };
+                usize::try_from(read).map_err(|_| io::Error::last_os_error())
+            });
+            match received {
+                Ok(Ok(read)) => {
+                    let servers = parse_user_options(buffer.get(..read).unwrap_or_default());
+                    if !servers.is_empty() {
+                        return Ok(servers);
+                    }
+                }
+                Ok(Err(error)) => return Err(error),
+                Err(_) => continue,
+            }
+        }
+    }
+}
+/// Receives the recursive dns servers of the next router advertisements from `options`, never completes without a socket.
+async fn next_dns_servers(options: Option<&UserOptions>) -> io::Result<Vec<DnsServers>> {
+    match options {
+        Some(options) => options.recv().await,
+        None => pending().await,
+    }
+}
+/// Extract the recursive dns servers of the router advertisement options in the netlink messages of a `datagram`.
+fn parse_user_options(mut datagram: &[u8]) -> Vec<DnsServers> {
+    let mut servers = Vec::new();
+    while let Some(header) = datagram.get(..NLMSG_HEADER) {
+        let length = header
+            .get(..4)
+            .and_then(|length| length.try_into().ok())
+            .map_or(0, u32::from_ne_bytes);
+        let length = usize::try_from(length).unwrap_or(0);
+        let kind = header
+            .get(4..6)
+            .and_then(|kind| kind.try_into().ok())
+            .map_or(0, u16::from_ne_bytes);
+        if length < NLMSG_HEADER {
+            break;
+        }
+        if kind == constants::RTM_NEWNDUSEROPT {
+            if let Some(message) = datagram.get(NLMSG_HEADER..length) {
+                servers.extend(parse_user_option(message));
+            }
+        }
+        // messages are padded to a multiple of four bytes
+        datagram = length
+            .checked_add(3)
+            .and_then(|length| datagram.get(length & !3..))
+            .unwrap_or_default();
+    }
+    servers
+}
+/// Extract the recursive dns servers of the options of a single `struct nduseroptmsg`.
+fn parse_user_option(message: &[u8]) -> Option<DnsServers> {
+    // struct nduseroptmsg { unsigned char family; unsigned char pad1; unsigned short opts_len; int ifindex; __u8 icmp_type; __u8 icmp_code; ... }
+    let options_length = usize::from(u16::from_ne_bytes(message.get(2..4)?.try_into().ok()?));
+    let index = u32::try_from(i32::from_ne_bytes(message.get(4..8)?.try_into().ok()?)).ok()?;
+    let mut options =
+        message.get(NDUSEROPT_HEADER..NDUSEROPT_HEADER.checked_add(options_length)?)?;
+    let mut servers = Vec::new();
+    let mut withdrawn = false;
+    // every option has its type and its length in units of eight bytes followed by its value
+    while let (Some(&kind), Some(&units)) = (options.first(), options.get(1)) {
+        let length = usize::from(units).checked_mul(8)?;
+        let option = options.get(..length)?;
+        if kind == ND_OPT_RDNSS {
+            withdrawn = u32::from_be_bytes(option.get(4..8)?.try_into().ok()?) == 0;
+            servers.extend(
+                option.get(8..)?.chunks_exact(16).filter_map(|address| {
+                    Some(Ipv6Addr::from(<[u8; 16]>::try_from(address).ok()?))
+                }),
+            );
+        }
+        if length == 0 {
+            break;
+        }
+        options = options.get(length..)?;
+    }
+    (!servers.is_empty()).then(|| (InterfaceId::new(index), servers, withdrawn))
+}
+
+/// Identifies the network namespace of the process by the device and inode of its namespace file, [`None`] when it can't be read.
+fn namespace() -> Option<(u64, u64)> {
+    fs::metadata(NAMESPACE)
+        .ok()
+        .map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+/// Completes when the process was moved to another network namespace than `initial`.
+///
+/// Never completes when the network namespace of the process can't be identified.
+async fn namespace_changed(initial: Option<(u64, u64)>) {
+    if initial.is_none() {
+        debug!("the network namespace of the process is unknown");
+        return pending().await;
+    }
+    let mut interval = tokio::time::interval(NAMESPACE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if namespace() != initial {
+            return;
+        }
+    }
 }
 
 /// Extract useful information from a [`LinkMessage`].
-const fn parse_link(link: &LinkMessage) -> LinkInfo {
-    (
-        link.header.index,
-        link.header.flags & IFF_LOOPBACK != 0,
-        link.header.flags & IFF_LOWER_UP != 0,
-    )
+///
+/// Has a valid result if the message describes the interface itself.
+/// Messages of other families, like the bridge port information of an interface, are ignored because removing them does not remove the interface.
+fn parse_link(link: &LinkMessage) -> Option<LinkInfo> {
+    if u16::from(link.header.interface_family) != AF_UNSPEC {
+        return None;
+    }
+    let name = link.nlas.iter().find_map(|nla| {
+        if let nlas::link::Nla::IfName(ref name) = *nla {
+            Some(name.clone())
+        } else {
+            None
+        }
+    });
+    let hardware_address = link.nlas.iter().find_map(|nla| {
+        if let nlas::link::Nla::Address(ref address) = *nla {
+            Some(address.clone())
+        } else {
+            None
+        }
+    });
+    let mtu = link.nlas.iter().find_map(|nla| {
+        if let nlas::link::Nla::Mtu(mtu) = *nla {
+            Some(mtu)
+        } else {
+            None
+        }
+    });
+    Some(LinkInfo {
+        index: link.header.index.into(),
+        loop_back: link.header.flags & IFF_LOOPBACK != 0,
+        carrier: link.header.flags & IFF_LOWER_UP != 0,
+        name,
+        hardware_address,
+        mtu,
+    })
+}
+/// Reads the negotiated speed and duplex mode of the interface named `name` from sysfs.
+///
+/// Both are unknown when the interface does not expose them, for example when the link is down or it is not an ethernet device.
+fn read_link_speed(index: InterfaceId, name: &str) -> LinkSpeedInfo {
+    let read =
+        |attribute: &str| fs::read_to_string(format!("/sys/class/net/{name}/{attribute}")).ok();
+    // sysfs reports the speed in megabits per second and -1 when it is unknown
+    let speed = read("speed")
+        .and_then(|speed| speed.trim().parse::<u64>().ok())
+        .and_then(|speed| speed.checked_mul(1_000_000));
+    let duplex = match read("duplex").as_deref().map(str::trim) {
+        Some("half") => Some(Duplex::Half),
+        Some("full") => Some(Duplex::Full),
+        _ => None,
+    };
+    LinkSpeedInfo {
+        index,
+        speed,
+        duplex,
+    }
+}
+/// Determines the kind of an interface from the `link_layer_type` of its [`LinkMessage`].
+///
+/// Wireless devices are ethernet devices as well, they are told apart by the wireless directory sysfs exposes for the interface named `name`.
+/// Mobile broadband devices are ethernet devices or have no link layer, they are told apart by the device type sysfs exposes.
+fn interface_type(link_layer_type: u16, name: Option<&str>) -> InterfaceType {
+    let cellular = name.map_or(false, |name| {
+        fs::read_to_string(format!("/sys/class/net/{name}/uevent")).map_or(false, |uevent| {
+            uevent.lines().any(|line| line == "DEVTYPE=wwan")
+        })
+    });
+    if cellular {
+        return InterfaceType::Cellular;
+    }
+    match link_layer_type {
+        ARPHRD_ETHER => {
+            let wireless = name.map_or(false, |name| {
+                fs::metadata(format!("/sys/class/net/{name}/wireless")).is_ok()
+            });
+            if wireless {
+                InterfaceType::Wireless
+            } else {
+                InterfaceType::Ethernet
+            }
+        }
+        ARPHRD_PPP => InterfaceType::Ppp,
+        // tun devices like wireguard or openvpn have no link layer
+        ARPHRD_NONE | ARPHRD_TUNNEL | ARPHRD_TUNNEL6 | ARPHRD_SIT | ARPHRD_IPGRE => {
+            InterfaceType::Tunnel
+        }
+        _ => InterfaceType::Other,
+    }
+}
+/// Records a link together with its speed and the kind of interface given by the link layer type of its `message` in the [state](Interfaces).
+fn add_link(state: &mut Interfaces, link: LinkInfo, message: &LinkMessage) {
+    let LinkInfo {
+        index, ref name, ..
+    } = link;
+    let link_speed = name.as_deref().map(|name| read_link_speed(index, name));
+    let kind = interface_type(message.header.link_layer_type, name.as_deref());
+    state.add_link(link);
+    state.set_interface_type(index, kind);
+    // a supplicant keeps an interface in the dormant link mode while it authenticates, which only shows in the operational state
+    let dormant = message.header.flags & IFF_DORMANT != 0
+        || message
+            .nlas
+            .iter()
+            .any(|nla| matches!(*nla, nlas::link::Nla::OperState(nlas::link::State::Dormant)));
+    state.set_dormant(index, dormant);
+    #[cfg(feature = "unstable-raw")]
+    state.set_raw_flags(index, message.header.flags);
+    if let Some(link_speed) = link_speed {
+        state.set_link_speed(link_speed);
+    }
+}
+/// Extract the managed and other configuration flags of the last router advertisement from a [`LinkMessage`].
+///
+/// The interface itself has them among the ipv6 specific attributes, the ipv6 information notified when they change has them in the protocol information.
+fn parse_router_flags(link: &LinkMessage) -> Option<(InterfaceId, bool, bool)> {
+    let flags = link.nlas.iter().find_map(|nla| {
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *nla {
+            nlas::link::Nla::AfSpecInet(ref families) => families.iter().find_map(|family| {
+                if let nlas::link::AfSpecInet::Inet6(ref attributes) = *family {
+                    attributes.iter().find_map(|attribute| {
+                        if let nlas::link::Inet6::Flags(flags) = *attribute {
+                            Some(flags)
+                        } else {
+                            None
+                        }
+                    })
+                } else {
+                    None
+                }
+            }),
+            nlas::link::Nla::ProtoInfo(ref bytes)
+                if u16::from(link.header.interface_family) == AF_INET6 =>
+            {
+                inet6_flags(bytes)
+            }
+            _ => None,
+        }
+    })?;
+    Some((
+        link.header.index.into(),
+        flags & IF_RA_MANAGED != 0,
+        flags & IF_RA_OTHERCONF != 0,
+    ))
+}
+/// Finds the `IFLA_INET6_FLAGS` attribute among the nested attributes of the ipv6 protocol information.
+fn inet6_flags(mut bytes: &[u8]) -> Option<u32> {
+    while let Some(header) = bytes.get(..4) {
+        let length = usize::from(u16::from_ne_bytes(header.get(..2)?.try_into().ok()?));
+        let kind = u16::from_ne_bytes(header.get(2..4)?.try_into().ok()?);
+        if kind == constants::IFLA_INET6_FLAGS {
+            return Some(u32::from_ne_bytes(bytes.get(4..8)?.try_into().ok()?));
+        }
+        if length < 4 {
+            return None;
+        }
+        // attributes are padded to a multiple of four bytes
+        bytes = bytes.get(length.checked_add(3)? & !3..)?;
+    }
+    None
 }
 /// Extract useful information from an [`AddressMessage`].
 ///
-/// Has a valid result if the address is not permanent and actually has an address.
+/// Has a valid result if the address is not permanent and actually has an ipv4 or ipv6 address, other families are ignored.
 fn parse_address(addr: &AddressMessage) -> Option<AddressInfo> {
     let address = addr.nlas.iter().find_map(|nla| {
         if let nlas::address::Nla::Address(ref address) = *nla {
@@ -127,8 +549,25 @@ fn parse_address(addr: &AddressMessage) -> Option<AddressInfo> {
             None
         }
     })?;
-    let flags = addr
-        .nlas
+    let flags = raw_address_flags(addr);
+    let ip_address = match u16::from(addr.header.family) {
+        AF_INET => Some(IpAddr::V4(Ipv4Addr::from(
+            vec_to_array(address.clone()).ok()?,
+        ))),
+        AF_INET6 => Some(IpAddr::V6(Ipv6Addr::from(
+            vec_to_array(address.clone()).ok()?,
+        ))),
+        _ => None,
+    }?;
+    (flags & constants::IFA_F_PERMANENT == 0).then_some(AddressInfo {
+        index: addr.header.index.into(),
+        address: ip_address,
+        prefix_length: addr.header.prefix_len,
+    })
+}
+/// Get the flags of an [`AddressMessage`], the extended flags attribute holds the flags which do not fit in the header.
+fn raw_address_flags(addr: &AddressMessage) -> u32 {
+    addr.nlas
         .iter()
         .find_map(|nla| {
             if let nlas::address::Nla::Flags(flags) = *nla {
@@ -137,8 +576,76 @@ fn parse_address(addr: &AddressMessage) -> Option<AddressInfo> {
                 None
             }
         })
-        .unwrap_or_else(|| u32::from(addr.header.flags));
-    let ip_address = match u16::from(addr.header.family) {
+        .unwrap_or_else(|| u32::from(addr.header.flags))
+}
+/// Extract the [`AddressFlags`] from an [`AddressMessage`].
+fn parse_address_flags(addr: &AddressMessage) -> AddressFlags {
+    let flags = raw_address_flags(addr);
+    AddressFlags {
+        temporary: flags & constants::IFA_F_TEMPORARY != 0,
+        deprecated: flags & constants::IFA_F_DEPRECATED != 0,
+        tentative: flags & constants::IFA_F_TENTATIVE != 0,
+        duplicate: flags & constants::IFA_F_DADFAILED != 0,
+    }
+}
+/// Extract the [`AddressLease`] from the cache info of an [`AddressMessage`].
+///
+/// Has a valid result if the address has a finite valid lifetime.
+fn parse_address_lease(addr: &AddressMessage) -> Option<AddressLease> {
+    let cache_info = addr.nlas.iter().find_map(|nla| {
+        if let nlas::address::Nla::CacheInfo(ref cache_info) = *nla {
+            Some(cache_info)
+        } else {
+            None
+        }
+    })?;
+    // struct ifa_cacheinfo { __u32 ifa_prefered; __u32 ifa_valid; __u32 cstamp; __u32 tstamp; }
+    let field = |offset: usize| -> Option<u32> {
+        Some(u32::from_ne_bytes(
+            cache_info
+                .get(offset..offset.checked_add(4)?)?
+                .try_into()
+                .ok()?,
+        ))
+    };
+    let (preferred, valid, updated) = (field(0)?, field(4)?, field(12)?);
+    // the lifetimes are in seconds and the timestamp in hundredths of a second since boot
+    (valid != INFINITY_LIFE_TIME).then(|| AddressLease {
+        valid_lifetime: Duration::from_secs(valid.into()),
+        preferred_lifetime: Duration::from_secs(preferred.into()),
+        renewed: Duration::from_millis(u64::from(updated).saturating_mul(10)),
+    })
+}
+/// Records an address together with its flags and lease in the [state](Interfaces).
+fn add_address(state: &mut Interfaces, address: AddressInfo, addr: &AddressMessage) {
+    state.add_address(address);
+    state.set_address_flags(address, parse_address_flags(addr));
+    if let Some(lease) = parse_address_lease(addr) {
+        state.set_address_lease(address, lease);
+    }
+}
+/// Extract the neighbor of a [`NeighbourMessage`] with the hardware address it was resolved to.
+///
+/// Has a valid result if the neighbor is resolved and has an ipv4 or ipv6 address, other families like the forwarding database of a bridge are ignored.
+fn parse_neighbor(neighbor: &NeighbourMessage) -> Option<(InterfaceId, IpAddr, Vec<u8>)> {
+    if neighbor.header.state & (NUD_INCOMPLETE | NUD_FAILED) != 0 {
+        return None;
+    }
+    let address = neighbor.nlas.iter().find_map(|nla| {
+        if let nlas::neighbour::Nla::Destination(ref address) = *nla {
+            Some(address)
+        } else {
+            None
+        }
+    })?;
+    let hardware_address = neighbor.nlas.iter().find_map(|nla| {
+        if let nlas::neighbour::Nla::LinkLocalAddress(ref hardware_address) = *nla {
+            Some(hardware_address.clone())
+        } else {
+            None
+        }
+    })?;
+    let ip_address = match u16::from(neighbor.header.family) {
         AF_INET => Some(IpAddr::V4(Ipv4Addr::from(
             vec_to_array(address.clone()).ok()?,
         ))),
@@ -147,11 +654,11 @@ fn parse_address(addr: &AddressMessage) -> Option<AddressInfo> {
         ))),
         _ => None,
     }?;
-    (flags & constants::IFA_F_PERMANENT == 0).then_some((addr.header.index, ip_address))
+    Some((neighbor.header.ifindex.into(), ip_address, hardware_address))
 }
 /// Extract useful information from a [`RouteMessage`].
 ///
-/// Has a valid result when the message has an Output Interface, Gateway, and priority.
+/// Has a valid result when the message has an Output Interface, Gateway, and priority of the ipv4 or ipv6 family, other families are ignored.
 fn parse_default_route(route: &RouteMessage) -> Option<RouteInfo> {
     let oif = route.nlas.iter().find_map(|nla| {
         if let nlas::route::Nla::Oif(oif) = *nla {
@@ -183,7 +690,98 @@ fn parse_default_route(route: &RouteMessage) -> Option<RouteInfo> {
         ))),
         _ => None,
     }?;
-    Some((oif, ip_address, priority))
+    Some(RouteInfo {
+        index: oif.into(),
+        gateway: ip_address,
+        priority: priority.into(),
+    })
+}
+/// Extract the lifetime of the default router of a [`RouteMessage`] from its cache info.
+///
+/// Has a valid result when the message describes an ipv6 default route learned from a router advertisement.
+/// The kernel only notifies the route when it is learned, so the lifetime is how long the router remained a default router then.
+fn parse_router_lifetime(route: &RouteMessage) -> Option<(InterfaceId, Duration)> {
+    if u16::from(route.header.address_family) != AF_INET6
+        || route.header.protocol != RTPROT_RA
+        || route.header.destination_prefix_length != 0
+    {
+        return None;
+    }
+    let mut index = None;
+    let mut cache_info = None;
+    for nla in &route.nlas {
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *nla {
+            nlas::route::Nla::Oif(oif) => index = Some(oif),
+            nlas::route::Nla::CacheInfo(ref bytes) => cache_info = Some(bytes),
+            _ => {}
+        }
+    }
+    // struct rta_cacheinfo { __u32 rta_clntref; __u32 rta_lastuse; __s32 rta_expires; ... }
+    let expires = i32::from_ne_bytes(cache_info?.get(8..12)?.try_into().ok()?);
+    // the expiry is in hundredths of a second, rounded to the seconds the router advertised
+    let lifetime = u64::try_from(expires).ok()?.checked_add(50)? / 100;
+    Some((index?.into(), Duration::from_secs(lifetime)))
+}
+/// Extract the complete information from a [`RouteMessage`].
+///
+/// Has a valid result when the message describes a unicast route with an output interface of the ipv4 or ipv6 family, other routes are ignored.
+/// Only the routes of the main table and the default routes of every table are kept, the other tables can hold thousands of routes on a router which the state does not use.
+fn parse_route(route: &RouteMessage) -> Option<RouteDetails> {
+    if route.header.kind != RTN_UNICAST {
+        return None;
+    }
+    let to_ip = |bytes: &Vec<u8>| match u16::from(route.header.address_family) {
+        AF_INET => Some(IpAddr::V4(Ipv4Addr::from(
+            vec_to_array(bytes.clone()).ok()?,
+        ))),
+        AF_INET6 => Some(IpAddr::V6(Ipv6Addr::from(
+            vec_to_array(bytes.clone()).ok()?,
+        ))),
+        _ => None,
+    };
+    let unspecified = match u16::from(route.header.address_family) {
+        AF_INET => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        AF_INET6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        _ => return None,
+    };
+    let mut index = None;
+    let mut destination = unspecified;
+    let mut gateway = None;
+    let mut priority = 0;
+    let mut table = u32::from(route.header.table);
+    for nla in &route.nlas {
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *nla {
+            nlas::route::Nla::Oif(oif) => index = Some(oif),
+            nlas::route::Nla::Destination(ref bytes) => destination = to_ip(bytes)?,
+            nlas::route::Nla::Gateway(ref bytes) => gateway = Some(to_ip(bytes)?),
+            nlas::route::Nla::Priority(metric) => priority = metric,
+            // tables above 255 do not fit in the header
+            nlas::route::Nla::Table(id) => table = id,
+            _ => {}
+        }
+    }
+    if table != u32::from(RT_TABLE_MAIN) && route.header.destination_prefix_length != 0 {
+        return None;
+    }
+    let protocol = match route.header.protocol {
+        RTPROT_KERNEL => RouteProtocol::Kernel,
+        RTPROT_BOOT => RouteProtocol::Boot,
+        RTPROT_STATIC => RouteProtocol::Static,
+        RTPROT_RA => RouteProtocol::RouterAdvertisement,
+        RTPROT_DHCP => RouteProtocol::Dhcp,
+        _ => RouteProtocol::Other,
+    };
+    Some(RouteDetails {
+        index: index?.into(),
+        destination,
+        prefix_length: route.header.destination_prefix_length,
+        gateway,
+        priority: priority.into(),
+        table: Some(table),
+        protocol: Some(protocol),
+    })
 }
 
 #[derive(Debug)]
@@ -205,97 +803,301 @@ impl Display for ConnectivityError {
 }
 impl Error for ConnectivityError {}
 
+/// Records the information of an rtnetlink message in the [state](Interfaces).
+///
+/// Returns whether the state may have changed, so a snapshot is only published for it then.
+/// Neighbor messages which only refresh a known neighbor do not change it,
+/// neither do route messages for routes the state does not keep or already knows, which a router receives many of.
+///
+/// # Errors
+///
+/// This function will return an error if the message is an error or an overrun.
+fn apply_message(
+    state: &mut Interfaces,
+    message: NetlinkMessage<RtnlMessage>,
+) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    #[allow(clippy::wildcard_enum_match_arm)]
+    match message.payload {
+        NetlinkPayload::Error(e) => {
+            return Err(Box::new(rtnetlink::Error::NetlinkError(e)));
+        }
+        NetlinkPayload::Overrun(e) => {
+            return Err(Box::new(ConnectivityError::Overrun(e)));
+        }
+        NetlinkPayload::InnerMessage(inner_message) => match inner_message {
+            RtnlMessage::NewLink(ref link) => {
+                if let Some(parsed_link) = parse_link(link) {
+                    add_link(state, parsed_link, link);
+                }
+                if let Some((index, managed, other)) = parse_router_flags(link) {
+                    state.set_router_flags(index, managed, other);
+                }
+            }
+            RtnlMessage::DelLink(ref link) => {
+                if let Some(parsed_link) = parse_link(link) {
+                    state.remove_link(parsed_link);
+                }
+            }
+            RtnlMessage::NewAddress(ref address) => {
+                if let Some(parsed_address) = parse_address(address) {
+                    add_address(state, parsed_address, address);
+                }
+            }
+            RtnlMessage::DelAddress(ref address) => {
+                if let Some(parsed_address) = parse_address(address) {
+                    state.remove_address(parsed_address);
+                }
+            }
+            RtnlMessage::NewRoute(ref route) => {
+                let mut changed = false;
+                if let Some(parsed_route) = parse_default_route(route) {
+                    state.add_default_route(parsed_route);
+                    changed = true;
+                }
+                if let Some((index, lifetime)) = parse_router_lifetime(route) {
+                    state.set_router_lifetime(index, Some(lifetime));
+                    changed = true;
+                }
+                if let Some(details) = parse_route(route) {
+                    changed |= state.add_route(details);
+                }
+                return Ok(changed);
+            }
+            RtnlMessage::DelRoute(ref route) => {
+                let mut changed = false;
+                if let Some(parsed_route) = parse_default_route(route) {
+                    state.remove_default_route(parsed_route);
+                    changed = true;
+                }
+                if let Some((index, _)) = parse_router_lifetime(route) {
+                    state.set_router_lifetime(index, None);
+                    changed = true;
+                }
+                if let Some(details) = parse_route(route) {
+                    changed |= state.remove_route(&details);
+                }
+                return Ok(changed);
+            }
+            RtnlMessage::NewNeighbour(ref neighbor) => {
+                return Ok(parse_neighbor(neighbor).map_or(
+                    false,
+                    |(index, address, hardware_address)| {
+                        state.set_neighbor(index, address, hardware_address)
+                    },
+                ));
+            }
+            // the last resolved hardware address of an expired neighbor is remembered
+            RtnlMessage::DelNeighbour(_) => return Ok(false),
+            _ => {}
+        },
+        _ => {}
+    }
+
+    Ok(true)
+}
+
+/// Asks the kernel which route it would select for `destination`.
+///
+/// The destination is only reachable when a unicast or local route was selected.
+///
+/// # Errors
+///
+/// This function will return an error if the request could not be sent.
+async fn look_up_route(
+    handle: &mut Handle,
+    destination: IpAddr,
+) -> Result<ReachabilityState, Box<dyn Error + Send + Sync>> {
+    let mut message = RouteMessage::default();
+    let (family, prefix_length, address) = match destination {
+        IpAddr::V4(address) => (AF_INET, 32, address.octets().to_vec()),
+        IpAddr::V6(address) => (AF_INET6, 128, address.octets().to_vec()),
+    };
+    message.header.address_family = u8::try_from(family)?;
+    message.header.destination_prefix_length = prefix_length;
+    message.nlas.push(nlas::route::Nla::Destination(address));
+    // without the dump flag the kernel performs an actual route lookup for the destination.
+    let mut request = NetlinkMessage::from(RtnlMessage::GetRoute(message));
+    request.header.flags = NLM_F_REQUEST;
+
+    let mut responses = handle.request(request)?;
+    while let Some(response) = responses.next().await {
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match response.payload {
+            NetlinkPayload::InnerMessage(RtnlMessage::NewRoute(route)) => {
+                let gateway = route
+                    .nlas
+                    .iter()
+                    .any(|nla| matches!(*nla, nlas::route::Nla::Gateway(_)));
+                return Ok(match (route.header.kind, gateway) {
+                    (RTN_UNICAST, true) => ReachabilityState::Gateway,
+                    (RTN_UNICAST | RTN_LOCAL, _) => ReachabilityState::Direct,
+                    _ => ReachabilityState::Unreachable,
+                });
+            }
+            NetlinkPayload::Error(error) => {
+                debug!("no route to {destination}: {error}");
+                return Ok(ReachabilityState::Unreachable);
+            }
+            _ => {}
+        }
+    }
+    Ok(ReachabilityState::Unreachable)
+}
+
+/// Determines the [reachability](ReachabilityState) of `destination` using a separate rtnetlink connection.
+///
+/// # Errors
+///
+/// This function will return an error if the rtnetlink connection failed or the request could not be sent.
+pub async fn can_reach(
+    destination: IpAddr,
+) -> Result<ReachabilityState, Box<dyn Error + Send + Sync>> {
+    let (conn, mut handle, _) = new_connection()?;
+    #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+    let reachability = tokio::select! {
+        biased;
+        reachability = look_up_route(&mut handle, destination) => reachability,
+        _ = conn => Err("rtnetlink connection closed".into()),
+    };
+    reachability
+}
+
+/// Looks up a route for the destinations of all drivers of `listener` and returns the ones that can be routed.
+///
+/// Failed route lookups are reported to the drivers.
+async fn routed_destinations(handle: &mut Handle, listener: &Listener) -> Vec<IpAddr> {
+    let mut routed = Vec::new();
+    for destination in listener.destinations() {
+        match look_up_route(handle, destination).await {
+            Ok(ReachabilityState::Unreachable) => {}
+            Ok(_) => routed.push(destination),
+            Err(error) => {
+                warn!("route lookup for {destination} failed {error}");
+                listener.report(&Diagnostic::RouteLookupFailed {
+                    destination,
+                    error: error.to_string(),
+                });
+            }
+        }
+    }
+    routed
+}
+
+/// The indices of the interfaces in `state` without the loopback interfaces, which are never replaced.
+fn replaceable_links(state: &Interfaces) -> HashSet<InterfaceId> {
+    let loop_backs = state.loop_backs();
+    state
+        .link_details()
+        .into_keys()
+        .filter(|index| !loop_backs.contains(index))
+        .collect()
+}
+
+/// Whether every interface of `known` was replaced by the interfaces of `state`, as container runtimes do when they replace all interfaces of a namespace at once.
+///
+/// `known` is updated to the interfaces of `state` unless it has none.
+fn replaced_all(known: &mut HashSet<InterfaceId>, state: &Interfaces) -> bool {
+    let current = replaceable_links(state);
+    if current.is_empty() {
+        return false;
+    }
+    let replaced = !known.is_empty() && known.is_disjoint(&current);
+    *known = current;
+    replaced
+}
+
 /// Builds and updates an internal state with a subset of the information provided by rtnetlink.
 ///
-/// From this state the internet connectivity with will be determined and send to tx.
+/// Every change of this state is published to the shared [listener](Listener) from which the internet connectivity will be determined.
 ///
-/// This function will compete when the receiving end of tx is dropped.
+/// This function will complete when rtnetlink stops sending messages.
 ///
 /// # Errors
 ///
 /// This function will return an error if any of the underlying rtnetlink requests return an error.
 async fn check_internet_connectivity(
-    handle: Handle,
+    mut handle: Handle,
     mut messages: UnboundedReceiver<(NetlinkMessage<RtnlMessage>, SocketAddr)>,
-    tx: tokio::sync::mpsc::UnboundedSender<Connectivity>,
+    listener: &Listener,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // joined before the dump so no router advertisement received in the meantime is missed
+    let user_options = if IpFamily::V6.is_compiled() {
+        UserOptions::new()
+            .map_err(|error| warn!("receiving router advertisement options failed {error}"))
+            .ok()
+    } else {
+        None
+    };
     debug!("getting initial state");
     let mut state = Interfaces::new();
     get_links(&handle, &mut state).await?;
-    get_addresses(&handle, &mut state).await?;
-    get_default_routes(&handle, IpVersion::V4, &mut state).await?;
-    get_default_routes(&handle, IpVersion::V6, &mut state).await?;
+    if IpFamily::V4.is_compiled() {
+        get_addresses(&handle, AF_INET, &mut state).await?;
+        get_default_routes(&handle, IpVersion::V4, &mut state).await?;
+    }
+    if IpFamily::V6.is_compiled() {
+        get_addresses(&handle, AF_INET6, &mut state).await?;
+        get_default_routes(&handle, IpVersion::V6, &mut state).await?;
+    }
+    get_neighbors(&handle, &mut state).await?;
+
+    // messages received during the dump can be older or newer than the dump itself.
+    // applying them in order converges to the actual state, so they are applied before emitting anything to prevent reporting a transient state.
+    debug!("applying rtnetlink messages received during the initial dump");
+    while let Ok((message, _)) = messages.try_recv() {
+        apply_message(&mut state, message)?;
+    }
     debug!("got initial state");
 
-    let mut connectivity = state.connectivity();
-    debug!("emit initial connectivity {:?}", connectivity);
-    tx.send(connectivity)?;
+    let routed = routed_destinations(&mut handle, listener).await;
+    // the interfaces of the last snapshot which had any besides the loopback interfaces
+    let mut known = replaceable_links(&state);
+    listener.publish(state.clone(), routed);
 
-    debug!("waiting for rtnetlink messages or transmit channel closed");
+    debug!("waiting for rtnetlink messages");
+    // a new snapshot is also published without a message when a driver added a destination.
     #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
-    while let Some((message, _)) = tokio::select! {
+    while let Some(message) = tokio::select! {
         biased;
-        _ = tx.closed() => {
-            debug!("transmit channel closed");
-            None
-        },
         message = messages.next() => {
             if message.is_none() {
                 debug!("no more rtnetlink messages");
             }
-            message
+            message.map(|(message, _)| Some(message))
         },
-    } {
-        #[allow(clippy::wildcard_enum_match_arm)]
-        match message.payload {
-            NetlinkPayload::Error(e) => {
-                return Err(Box::new(rtnetlink::Error::NetlinkError(e)));
+        _ = listener.refreshed() => {
+            debug!("destinations added");
+            Some(None)
+        },
+        servers = next_dns_servers(user_options.as_ref()) => {
+            for (index, servers, withdrawn) in servers? {
+                debug!("router advertised dns servers {servers:?} on {index}, withdrawn {withdrawn}");
+                state.set_dns_servers(index, &servers, withdrawn);
             }
-            NetlinkPayload::Overrun(e) => {
-                return Err(Box::new(ConnectivityError::Overrun(e)));
+            Some(None)
+        },
+    } {
+        if let Some(message) = message {
+            if !apply_message(&mut state, message)? {
+                continue;
             }
-            NetlinkPayload::InnerMessage(inner_message) => match inner_message {
-                RtnlMessage::NewLink(ref link) => {
-                    state.add_link(parse_link(link));
-                }
-                RtnlMessage::DelLink(ref link) => {
-                    state.remove_link(parse_link(link));
-                }
-                RtnlMessage::NewAddress(ref address) => {
-                    if let Some(parsed_address) = parse_address(address) {
-                        state.add_address(parsed_address);
-                    }
-                }
-                RtnlMessage::DelAddress(ref address) => {
-                    if let Some(parsed_address) = parse_address(address) {
-                        state.remove_address(parsed_address);
-                    }
-                }
-                RtnlMessage::NewRoute(ref route) => {
-                    if let Some(parsed_route) = parse_default_route(route) {
-                        state.add_default_route(parsed_route);
-                    }
-                }
-                RtnlMessage::DelRoute(ref route) => {
-                    if let Some(parsed_route) = parse_default_route(route) {
-                        state.remove_default_route(parsed_route);
-                    }
-                }
-                _ => {}
-            },
-            _ => {}
         }
-
-        if diff_assign(&mut connectivity, state.connectivity()) {
-            debug!("emit updated connectivity {:?}", connectivity);
-            tx.send(connectivity)?;
+        // container runtimes replace all interfaces of a namespace at once, the state is taken anew instead of carrying it over.
+        if replaced_all(&mut known, &state) {
+            warn!("all interfaces were replaced, resynchronizing");
+            listener.resync();
+            return pending().await;
         }
+        let routed = routed_destinations(&mut handle, listener).await;
+        listener.publish(state.clone(), routed);
     }
 
     Ok(())
 }
 
-/// Gets all interfaces from rtnetlink ignoring the loopback interfaces and records them in the [state](Interfaces).
+/// Gets all interfaces from rtnetlink and records them in the [state](Interfaces).
+///
+/// The statistics of the interfaces are not used, so the kernel is asked to skip them which keeps the dump small on systems with many interfaces.
 ///
 /// # Errors
 ///
@@ -304,34 +1106,48 @@ async fn get_links(
     handle: &Handle,
     state: &mut Interfaces,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let mut links = handle.link().get().execute();
+    let mut links = handle
+        .link()
+        .get()
+        .set_filter_mask(AF_UNSPEC.try_into()?, RTEXT_FILTER_SKIP_STATS)
+        .execute();
 
     while let Some(ref link) = links.try_next().await? {
-        state.add_link(parse_link(link));
+        if let Some(parsed_link) = parse_link(link) {
+            add_link(state, parsed_link, link);
+        }
+        if let Some((index, managed, other)) = parse_router_flags(link) {
+            state.set_router_flags(index, managed, other);
+        }
     }
 
     Ok(())
 }
-/// Gets all addresses from rtnetlink and records them in the [state](Interfaces).
+/// Gets all addresses of the address `family` from rtnetlink and records them in the [state](Interfaces).
+///
+/// The kernel only dumps the addresses of the requested family instead of those of every protocol family that has addresses.
 ///
 /// # Errors
 ///
 /// This function will return an error if the underlying request has an error.
 async fn get_addresses(
     handle: &Handle,
+    family: u16,
     state: &mut Interfaces,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let mut addresses = handle.address().get().execute();
+    let mut request = handle.address().get();
+    request.message_mut().header.family = family.try_into()?;
+    let mut addresses = request.execute();
 
     while let Some(ref address) = addresses.try_next().await? {
         if let Some(parsed_address) = parse_address(address) {
-            state.add_address(parsed_address);
+            add_address(state, parsed_address, address);
         }
     }
 
     Ok(())
 }
-/// Gets all default routes from rtnetlink for a specified [`IpVersion`] and records them in the [state](Interfaces).
+/// Gets all routes from rtnetlink for a specified [`IpVersion`] and records them and the default routes among them in the [state](Interfaces).
 ///
 /// # Errors
 ///
@@ -347,7 +1163,249 @@ async fn get_default_routes(
         if let Some(parsed_route) = parse_default_route(route) {
             state.add_default_route(parsed_route);
         }
+        if let Some((index, lifetime)) = parse_router_lifetime(route) {
+            state.set_router_lifetime(index, Some(lifetime));
+        }
+        if let Some(details) = parse_route(route) {
+            state.add_route(details);
+        }
     }
 
     Ok(())
 }
+/// Gets the neighbors of all interfaces from rtnetlink and records the hardware addresses they were resolved to in the [state](Interfaces).
+///
+/// # Errors
+///
+/// This function will return an error if the underlying request has an error.
+async fn get_neighbors(
+    handle: &Handle,
+    state: &mut Interfaces,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut neighbors = handle.neighbours().get().execute();
+
+    while let Some(ref neighbor) = neighbors.try_next().await? {
+        if let Some((index, address, hardware_address)) = parse_neighbor(neighbor) {
+            state.set_neighbor(index, address, hardware_address);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_message, parse_link, parse_route, replaceable_links, replaced_all};
+    use crate::state::Interfaces;
+    use rtnetlink::{
+        packet::{
+            constants::{
+                AF_BRIDGE, AF_INET, AF_UNSPEC, IFF_LOOPBACK, IFF_LOWER_UP, RTN_UNICAST, RTPROT_BOOT,
+            },
+            nlas, AddressMessage, LinkMessage, RouteMessage, RtnlMessage,
+        },
+        proto::NetlinkMessage,
+    };
+
+    /// The index of the interface the messages are about
+    const INDEX: u32 = 2;
+    /// The family of mpls, which the netlink constants do not define
+    const AF_MPLS: u16 = 28;
+
+    /// Creates a link message of `family` for the interface.
+    fn link(family: u16) -> LinkMessage {
+        let mut link = named_link(INDEX, "eth0", IFF_LOWER_UP);
+        link.header.interface_family = u8::try_from(family).unwrap();
+        link
+    }
+
+    /// Creates a link message for the interface with `index`, `name` and `flags`.
+    fn named_link(index: u32, name: &str, flags: u32) -> LinkMessage {
+        let mut link = LinkMessage::default();
+        link.header.index = index;
+        link.header.flags = flags;
+        link.nlas.push(nlas::link::Nla::IfName(name.to_owned()));
+        link.nlas.push(nlas::link::Nla::Mtu(1500));
+        link
+    }
+
+    /// Applies `message` to `state`.
+    fn apply(state: &mut Interfaces, message: RtnlMessage) {
+        apply_message(state, NetlinkMessage::from(message)).unwrap();
+    }
+
+    /// Creates an address message of `family` with four address bytes for the interface.
+    fn address(family: u16) -> AddressMessage {
+        let mut address = AddressMessage::default();
+        address.header.family = u8::try_from(family).unwrap();
+        address.header.index = INDEX;
+        address.header.prefix_len = 24;
+        address
+            .nlas
+            .push(nlas::address::Nla::Address(vec![192, 0, 2, 2]));
+        address
+    }
+
+    /// Creates a unicast default route message of `family` with a four byte gateway through the interface.
+    fn route(family: u16) -> RouteMessage {
+        let mut route = RouteMessage::default();
+        route.header.address_family = u8::try_from(family).unwrap();
+        route.header.kind = RTN_UNICAST;
+        route.header.protocol = RTPROT_BOOT;
+        route.header.table = 254;
+        route.nlas.push(nlas::route::Nla::Oif(INDEX));
+        route
+            .nlas
+            .push(nlas::route::Nla::Gateway(vec![192, 0, 2, 1]));
+        route.nlas.push(nlas::route::Nla::Priority(100));
+        route
+    }
+
+    /// Creates a unicast ipv4 route in the main table to the on-link subnet of the interface with `prefix_length`.
+    fn on_link_route(prefix_length: u8) -> RouteMessage {
+        let mut route = RouteMessage::default();
+        route.header.address_family = u8::try_from(AF_INET).unwrap();
+        route.header.kind = RTN_UNICAST;
+        route.header.protocol = RTPROT_BOOT;
+        route.header.table = 254;
+        route.header.destination_prefix_length = prefix_length;
+        route.nlas.push(nlas::route::Nla::Oif(INDEX));
+        route
+            .nlas
+            .push(nlas::route::Nla::Destination(vec![192, 0, 2, 0]));
+        route
+    }
+
+    /// Creates the state of an interface with an ipv4 address and default route.
+    fn connected() -> Interfaces {
+        let mut state = Interfaces::new();
+        for message in [
+            RtnlMessage::NewLink(link(AF_UNSPEC)),
+            RtnlMessage::NewAddress(address(AF_INET)),
+            RtnlMessage::NewRoute(route(AF_INET)),
+        ] {
+            assert!(apply_message(&mut state, NetlinkMessage::from(message)).unwrap());
+        }
+        state
+    }
+
+    /// Asserts that applying `message` to a connected state leaves it unchanged.
+    fn assert_ignored(message: RtnlMessage) {
+        let expected = connected();
+        let mut state = connected();
+        apply_message(&mut state, NetlinkMessage::from(message)).unwrap();
+        assert_eq!(state.snapshot(), expected.snapshot());
+        let mut routes = state.routes();
+        let mut expected_routes = expected.routes();
+        routes.sort_unstable();
+        expected_routes.sort_unstable();
+        assert_eq!(routes, expected_routes);
+        assert_eq!(state.connectivity(), expected.connectivity());
+    }
+
+    #[test]
+    fn links_of_other_families_are_not_interfaces() {
+        assert!(parse_link(&link(AF_UNSPEC)).is_some());
+        assert!(parse_link(&link(AF_BRIDGE)).is_none());
+        assert!(parse_link(&link(AF_MPLS)).is_none());
+    }
+
+    #[test]
+    fn bridge_messages_leave_the_state_unchanged() {
+        assert_ignored(RtnlMessage::NewLink(link(AF_BRIDGE)));
+        assert_ignored(RtnlMessage::DelLink(link(AF_BRIDGE)));
+        assert_ignored(RtnlMessage::NewAddress(address(AF_BRIDGE)));
+        assert_ignored(RtnlMessage::DelAddress(address(AF_BRIDGE)));
+        assert_ignored(RtnlMessage::NewRoute(route(AF_BRIDGE)));
+        assert_ignored(RtnlMessage::DelRoute(route(AF_BRIDGE)));
+    }
+
+    #[test]
+    fn mpls_messages_leave_the_state_unchanged() {
+        assert_ignored(RtnlMessage::NewLink(link(AF_MPLS)));
+        assert_ignored(RtnlMessage::DelLink(link(AF_MPLS)));
+        assert_ignored(RtnlMessage::NewAddress(address(AF_MPLS)));
+        assert_ignored(RtnlMessage::DelAddress(address(AF_MPLS)));
+        assert_ignored(RtnlMessage::NewRoute(route(AF_MPLS)));
+        assert_ignored(RtnlMessage::DelRoute(route(AF_MPLS)));
+    }
+
+    #[test]
+    fn replacing_every_interface_but_loopback_resynchronizes() {
+        let mut state = Interfaces::new();
+        apply(
+            &mut state,
+            RtnlMessage::NewLink(named_link(1, "lo", IFF_LOOPBACK | IFF_LOWER_UP)),
+        );
+        apply(
+            &mut state,
+            RtnlMessage::NewLink(named_link(2, "eth0", IFF_LOWER_UP)),
+        );
+        apply(
+            &mut state,
+            RtnlMessage::NewLink(named_link(3, "eth1", IFF_LOWER_UP)),
+        );
+        let mut known = replaceable_links(&state);
+        assert!(!known.contains(&1.into()));
+
+        apply(&mut state, RtnlMessage::DelLink(named_link(2, "eth0", 0)));
+        assert!(!replaced_all(&mut known, &state));
+        apply(&mut state, RtnlMessage::DelLink(named_link(3, "eth1", 0)));
+        // only the loopback interface is left, which is not a replacement yet
+        assert!(!replaced_all(&mut known, &state));
+        apply(
+            &mut state,
+            RtnlMessage::NewLink(named_link(7, "eth0", IFF_LOWER_UP)),
+        );
+        assert!(replaced_all(&mut known, &state));
+    }
+
+    #[test]
+    fn replacing_some_interfaces_does_not_resynchronize() {
+        let mut state = Interfaces::new();
+        apply(
+            &mut state,
+            RtnlMessage::NewLink(named_link(1, "lo", IFF_LOOPBACK | IFF_LOWER_UP)),
+        );
+        apply(
+            &mut state,
+            RtnlMessage::NewLink(named_link(2, "eth0", IFF_LOWER_UP)),
+        );
+        apply(
+            &mut state,
+            RtnlMessage::NewLink(named_link(3, "eth1", IFF_LOWER_UP)),
+        );
+        let mut known = replaceable_links(&state);
+
+        apply(&mut state, RtnlMessage::DelLink(named_link(2, "eth0", 0)));
+        apply(
+            &mut state,
+            RtnlMessage::NewLink(named_link(7, "veth0", IFF_LOWER_UP)),
+        );
+        assert!(!replaced_all(&mut known, &state));
+    }
+
+    #[test]
+    fn only_main_table_and_default_routes_are_stored() {
+        let mut local = on_link_route(32);
+        local.header.table = 255;
+        assert!(parse_route(&local).is_none());
+        assert_ignored(RtnlMessage::NewRoute(local.clone()));
+        assert!(!apply_message(
+            &mut connected(),
+            NetlinkMessage::from(RtnlMessage::NewRoute(local))
+        )
+        .unwrap());
+
+        let main = on_link_route(24);
+        assert!(parse_route(&main).is_some());
+        let mut state = connected();
+        let message = NetlinkMessage::from(RtnlMessage::NewRoute(main));
+        assert!(apply_message(&mut state, message.clone()).unwrap());
+        assert!(!apply_message(&mut state, message).unwrap());
+
+        let mut policy_default = route(AF_INET);
+        policy_default.header.table = 100;
+        assert_eq!(parse_route(&policy_default).unwrap().table, Some(100));
+    }
+}
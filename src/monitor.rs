@@ -0,0 +1,498 @@
+// SPDX-License-Identifier: MIT
+
+//! A driver running in a task of the tokio runtime together with its events, connectivity and shutdown.
+
+use crate::{
+    state::{InterfaceId, InterfaceType, RouteMetric},
+    Builder, Connectivity, ConnectivityState, IpFamily, NetworkEvent, NetworkFingerprint,
+};
+use alloc::collections::{BTreeMap, VecDeque};
+use core::cmp::max;
+use std::{
+    error::Error,
+    sync::{Arc, Mutex, PoisonError},
+};
+use tokio::{
+    runtime::Handle,
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        oneshot, watch, Notify,
+    },
+    task::JoinHandle,
+};
+
+/// The details of the networks tracked by a [`Monitor`] from the detailed events, see [`Monitor::wait_until_detailed`].
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct NetworkDetails {
+    /// Whether probes verified the internet connectivity of the latest connectivity
+    pub verified: bool,
+    /// The fingerprint of all networks the system is connected to
+    pub fingerprint: Option<NetworkFingerprint>,
+    /// The index of the interface which provides the internet connectivity, see [`NetworkEvent::InternetInterfaceChanged`]
+    pub internet_interface: Option<InterfaceId>,
+    /// The kind of the interface which provides the internet connectivity, for example [`Cellular`](InterfaceType::Cellular) on networks which are usually metered
+    pub internet_interface_type: Option<InterfaceType>,
+    /// The name and connectivity of every interface, see [`NetworkEvent::InterfaceConnectivityChanged`]
+    pub interfaces: BTreeMap<InterfaceId, (Option<String>, Connectivity)>,
+    /// The metric of the preferred ipv4 default route, see [`NetworkEvent::DefaultRouteChanged`]
+    ///
+    /// Changes when a route with another metric takes over without changing the connectivity, for example when a cellular fallback is activated.
+    pub ipv4_default_route_metric: Option<RouteMetric>,
+    /// The metric of the preferred ipv6 default route, see [`NetworkEvent::DefaultRouteChanged`]
+    pub ipv6_default_route_metric: Option<RouteMetric>,
+}
+impl NetworkDetails {
+    /// Records the details carried by `event`.
+    fn apply(&mut self, event: &NetworkEvent) {
+        match *event {
+            NetworkEvent::ConnectivityChanged { verified, .. } => self.verified = verified,
+            NetworkEvent::FingerprintChanged(fingerprint) => self.fingerprint = fingerprint,
+            NetworkEvent::InternetInterfaceChanged {
+                index,
+                interface_type,
+                ..
+            } => {
+                self.internet_interface = index;
+                self.internet_interface_type = interface_type;
+            }
+            NetworkEvent::InterfaceConnectivityChanged {
+                index,
+                ref name,
+                connectivity,
+            } => {
+                self.interfaces.insert(index, (name.clone(), connectivity));
+            }
+            NetworkEvent::InterfaceRemoved { index, .. } => {
+                self.interfaces.remove(&index);
+            }
+            NetworkEvent::DefaultRouteChanged {
+                family,
+                ref primary,
+                ..
+            } => {
+                let metric = primary.as_ref().map(|primary| primary.priority);
+                match family {
+                    IpFamily::V4 => self.ipv4_default_route_metric = metric,
+                    IpFamily::V6 => self.ipv6_default_route_metric = metric,
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// How many of the latest events a [`Monitor`] keeps for [`Monitor::poll_changes`]
+const HISTORY_CAPACITY: usize = 256;
+
+/// Identifies an event emitted by a [`Monitor`], later events have higher numbers, see [`Monitor::poll_changes`].
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SequenceNumber(u64);
+impl SequenceNumber {
+    /// The sequence number before the first event, polling since it returns every event which is still kept
+    pub const START: Self = Self(0);
+
+    /// Create a [`SequenceNumber`] from its value, for example one received back from a client
+    #[allow(clippy::must_use_candidate)]
+    pub const fn new(value: u64) -> Self {
+        Self(value)
+    }
+
+    /// Get the value of the sequence number
+    #[allow(clippy::must_use_candidate)]
+    pub const fn value(self) -> u64 {
+        self.0
+    }
+
+    /// Get the sequence number of the event after this one
+    const fn next(self) -> Self {
+        Self(self.0.saturating_add(1))
+    }
+}
+impl From<u64> for SequenceNumber {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+/// The latest events emitted by a [`Monitor`].
+#[derive(Default)]
+struct History {
+    /// The kept events with their sequence numbers, oldest first
+    events: VecDeque<(SequenceNumber, NetworkEvent)>,
+    /// The sequence number of the latest event
+    latest: SequenceNumber,
+}
+impl History {
+    /// Records `event` with the next sequence number, forgetting the oldest event when the history is full.
+    fn record(&mut self, event: NetworkEvent) {
+        self.latest = self.latest.next();
+        if self.events.len() >= HISTORY_CAPACITY {
+            self.events.pop_front();
+        }
+        self.events.push_back((self.latest, event));
+    }
+}
+
+/// A driver which runs in its own task, created with [`Builder::monitor`].
+///
+/// Any number of components can [subscribe](Self::subscribe) to one monitor, each with a receive end of its own,
+/// and every driver of the process shares one platform listener, so large applications do not need a netlink socket or windows notification per consumer.
+/// Dropping the monitor stops the driver.
+pub struct Monitor {
+    /// The task running the driver
+    task: JoinHandle<Result<(), Box<dyn Error + Send + Sync>>>,
+    /// The detailed events of the driver
+    events: UnboundedReceiver<NetworkEvent>,
+    /// The latest connectivity, [`None`] until the initial connectivity is known
+    state: watch::Receiver<Option<Connectivity>>,
+    /// The latest connectivity with the details of the networks, marked changed whenever the driver emitted an event
+    changed: watch::Receiver<(Option<Connectivity>, NetworkDetails)>,
+    /// The transmit ends of the subscribers to the connectivity, [`None`] once the driver completed
+    subscribers: Arc<Mutex<Option<Vec<UnboundedSender<Connectivity>>>>>,
+    /// The latest events for [`poll_changes`](Self::poll_changes)
+    history: Arc<Mutex<History>>,
+    /// Stops the driver when sent to or dropped
+    shutdown: oneshot::Sender<()>,
+    /// Restarts the platform listener of the driver when notified
+    restart: Arc<Notify>,
+}
+impl Monitor {
+    /// Create a new [`Builder`] with the default configuration, the monitor is created from it with [`Builder::monitor`].
+    ///
+    /// ```no_run
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// use network_connectivity::Monitor;
+    ///
+    /// let monitor = Monitor::builder().monitor()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[allow(clippy::must_use_candidate)]
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// Spawns the driver configured by `builder` on the current tokio runtime.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it is not called within a tokio runtime or the driver could not be created.
+    pub(crate) fn spawn(builder: Builder) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let runtime = Handle::try_current().map_err(|error| {
+            format!("a monitor must be created within a tokio runtime: {error}")
+        })?;
+        let restart = builder.restart();
+        let (driver, mut driver_events) = builder.build_detailed()?;
+        let (events_tx, events) = unbounded_channel();
+        let (state_tx, state) = watch::channel(None);
+        let (changed_tx, changed) = watch::channel((None, NetworkDetails::default()));
+        let subscribers = Arc::new(Mutex::new(
+            Some(Vec::<UnboundedSender<Connectivity>>::new()),
+        ));
+        let forward_subscribers = Arc::clone(&subscribers);
+        let history = Arc::new(Mutex::new(History::default()));
+        let forward_history = Arc::clone(&history);
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        // the connectivity is still tracked after the events are no longer received.
+        let forward = async move {
+            let mut details = NetworkDetails::default();
+            while let Some(event) = driver_events.recv().await {
+                details.apply(&event);
+                if let NetworkEvent::ConnectivityChanged { connectivity, .. } = event {
+                    // subscribing waits for the update, so a subscriber receives every connectivity after its first once.
+                    let mut subscribers = forward_subscribers
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner);
+                    state_tx.send_replace(Some(connectivity));
+                    if let Some(ref mut subscribers) = *subscribers {
+                        subscribers.retain(|subscriber| subscriber.send(connectivity).is_ok());
+                    }
+                }
+                changed_tx.send_replace((*state_tx.borrow(), details.clone()));
+                forward_history
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .record(event.clone());
+                events_tx.send(event).ok();
+            }
+            *forward_subscribers
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = None;
+        };
+        let task = runtime.spawn(async move {
+            #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+            let result = tokio::select! {
+                (result, ()) = futures::future::join(driver, forward) => result,
+                _ = shutdown_rx => Ok(()),
+            };
+            result
+        });
+        Ok(Self {
+            task,
+            events,
+            state,
+            changed,
+            subscribers,
+            history,
+            shutdown,
+            restart,
+        })
+    }
+
+    /// Receives the next detailed event, [`None`] when the driver completed.
+    pub async fn recv(&mut self) -> Option<NetworkEvent> {
+        self.events.recv().await
+    }
+
+    /// The latest connectivity, [`None`] until the initial connectivity is known
+    #[allow(clippy::must_use_candidate)]
+    pub fn connectivity(&self) -> Option<Connectivity> {
+        *self.state.borrow()
+    }
+
+    /// A handle to the latest connectivity which can be shared and waited on independently of the events.
+    #[allow(clippy::must_use_candidate)]
+    pub fn state(&self) -> watch::Receiver<Option<Connectivity>> {
+        self.state.clone()
+    }
+
+    /// Creates a new receive end of the connectivity whose first item is the latest connectivity, followed by every change of it.
+    ///
+    /// Components which are created after the monitor, or dropped their previous receive end, can subscribe at any time without missing the current state.
+    /// Before the initial connectivity is known the first item is the initial connectivity.
+    /// The receive end completes when the driver completed.
+    #[allow(clippy::must_use_candidate)]
+    pub fn subscribe(&self) -> UnboundedReceiver<Connectivity> {
+        let (subscriber, connectivity) = unbounded_channel();
+        let mut subscribers = self
+            .subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(current) = *self.state.borrow() {
+            subscriber.send(current).ok();
+        }
+        if let Some(ref mut subscribers) = *subscribers {
+            subscribers.push(subscriber);
+        }
+        connectivity
+    }
+
+    /// Get the events emitted after `since` with their sequence numbers, oldest first,
+    /// so request response style integrations such as http apis or ffi hosts can fetch the changes they missed instead of receiving the events.
+    ///
+    /// Pass the sequence number of the last returned event to the next call, or [`SequenceNumber::START`] to get every event which is still kept.
+    /// Only the latest 256 events are kept, when events after `since` are no longer kept [`NetworkEvent::Lagged`] is returned first
+    /// with the amount of missed events and the latest connectivity, under the sequence number of the last missed event.
+    /// No event is consumed, the events can still be [received](Self::recv).
+    #[allow(clippy::must_use_candidate)]
+    pub fn poll_changes(&self, since: SequenceNumber) -> Vec<(SequenceNumber, NetworkEvent)> {
+        let history = self.history.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut changes = Vec::new();
+        if let Some(&(oldest, _)) = history.events.front() {
+            let missed = oldest
+                .value()
+                .saturating_sub(since.value())
+                .saturating_sub(1);
+            if missed != 0 {
+                changes.push((
+                    SequenceNumber::new(oldest.value().saturating_sub(1)),
+                    NetworkEvent::Lagged {
+                        missed,
+                        connectivity: *self.state.borrow(),
+                    },
+                ));
+            }
+        }
+        changes.extend(
+            history
+                .events
+                .iter()
+                .filter(|&&(sequence, _)| sequence > since)
+                .cloned(),
+        );
+        changes
+    }
+
+    /// Get the sequence number of the latest event, [`SequenceNumber::START`] before the first event
+    #[allow(clippy::must_use_candidate)]
+    pub fn sequence(&self) -> SequenceNumber {
+        self.history
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .latest
+    }
+
+    /// Completes when the driver emitted an event since the last call, after which the current value can be read from [`connectivity`](Self::connectivity) or the [state](Self::state) handle.
+    ///
+    /// Like [`watch::Receiver::changed`] several events emitted in between are signaled once and no event is consumed.
+    /// Consumers which never [receive](Self::recv) the events should [close](Self::close_events) them, so they are not queued.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the driver completed.
+    pub async fn changed(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.changed
+            .changed()
+            .await
+            .map_err(|_| "the driver completed".into())
+    }
+
+    /// Completes with the latest connectivity once it satisfies `predicate`, which is evaluated for the latest connectivity and every change of it.
+    ///
+    /// This generalizes waiting for a specific connectivity to arbitrary conditions, no event is consumed.
+    /// Wrap it in [`tokio::time::timeout`] to give up after a while.
+    ///
+    /// ```no_run
+    /// # async fn example(monitor: network_connectivity::Monitor) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// use network_connectivity::ConnectivityState;
+    ///
+    /// monitor
+    ///     .wait_until(|connectivity| connectivity.ipv6 == ConnectivityState::Internet)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the driver completed before the predicate was satisfied.
+    pub async fn wait_until(
+        &self,
+        mut predicate: impl FnMut(&Connectivity) -> bool,
+    ) -> Result<Connectivity, Box<dyn Error + Send + Sync>> {
+        let mut state = self.state.clone();
+        loop {
+            let current = *state.borrow_and_update();
+            if let Some(connectivity) = current {
+                if predicate(&connectivity) {
+                    return Ok(connectivity);
+                }
+            }
+            state.changed().await.map_err(|_| "the driver completed")?;
+        }
+    }
+
+    /// Completes with the latest connectivity and [details](NetworkDetails) once they satisfy `predicate`, which is evaluated again after every event.
+    ///
+    /// ```no_run
+    /// # async fn example(monitor: network_connectivity::Monitor) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// use network_connectivity::{state::InterfaceType, ConnectivityState};
+    ///
+    /// // ipv6 internet connectivity which is not provided by a cellular network
+    /// monitor
+    ///     .wait_until_detailed(|connectivity, details| {
+    ///         connectivity.ipv6 == ConnectivityState::Internet
+    ///             && details.internet_interface_type != Some(InterfaceType::Cellular)
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the driver completed before the predicate was satisfied.
+    pub async fn wait_until_detailed(
+        &self,
+        mut predicate: impl FnMut(&Connectivity, &NetworkDetails) -> bool,
+    ) -> Result<(Connectivity, NetworkDetails), Box<dyn Error + Send + Sync>> {
+        let mut changed = self.changed.clone();
+        loop {
+            let (current, details) = changed.borrow_and_update().clone();
+            if let Some(connectivity) = current {
+                if predicate(&connectivity, &details) {
+                    return Ok((connectivity, details));
+                }
+            }
+            changed
+                .changed()
+                .await
+                .map_err(|_| "the driver completed")?;
+        }
+    }
+
+    /// Completes with the connectivity of the interface with `name` once it reached at least `state` over ipv4 or ipv6,
+    /// for example for provisioning tools which bring up a specific uplink.
+    ///
+    /// The connectivity of an interface is derived from its own addresses and default routes, see [`NetworkEvent::InterfaceConnectivityChanged`].
+    /// An interface which does not exist yet is waited for as well.
+    ///
+    /// ```no_run
+    /// # async fn example(monitor: network_connectivity::Monitor) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// use network_connectivity::ConnectivityState;
+    ///
+    /// monitor
+    ///     .wait_for_interface("wlan0", ConnectivityState::Internet)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the driver completed before the interface reached the state.
+    pub async fn wait_for_interface(
+        &self,
+        name: &str,
+        state: ConnectivityState,
+    ) -> Result<Connectivity, Box<dyn Error + Send + Sync>> {
+        let interface = |details: &NetworkDetails| {
+            details
+                .interfaces
+                .values()
+                .find(|&&(ref known, connectivity)| {
+                    known.as_deref() == Some(name)
+                        && max(connectivity.ipv4, connectivity.ipv6) >= state
+                })
+                .map(|&(_, connectivity)| connectivity)
+        };
+        let (_, details) = self
+            .wait_until_detailed(|_, details| interface(details).is_some())
+            .await?;
+        interface(&details).ok_or_else(|| "the interface is no longer known".into())
+    }
+
+    /// Stops queuing the detailed events, the events which are queued already can still be received.
+    ///
+    /// The connectivity and [changes](Self::changed) are still tracked.
+    pub fn close_events(&mut self) {
+        self.events.close();
+    }
+
+    /// Whether the driver completed, after which no more events are received
+    #[allow(clippy::must_use_candidate)]
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Tears down the platform listener and creates it anew, after which the state of the system is taken anew.
+    ///
+    /// Events keep being received from the same monitor, a connectivity that changed in the meantime is emitted as usual
+    /// and [`Diagnostic::Resynchronized`](crate::Diagnostic::Resynchronized) is reported.
+    /// This is useful after a major reconfiguration of the system.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the driver already completed, a new monitor must be created then.
+    /// Use [`Builder::restart_on_error`](crate::Builder::restart_on_error) to keep the driver running when the platform listener fails.
+    pub fn restart(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.task.is_finished() {
+            return Err("the driver completed, create a new monitor".into());
+        }
+        self.restart.notify_one();
+        Ok(())
+    }
+
+    /// Stops the driver and waits until it completed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the driver failed before it was stopped or its task panicked.
+    pub async fn shutdown(self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.shutdown.send(()).ok();
+        self.task.await?
+    }
+}
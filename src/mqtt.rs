@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: MIT
+
+//! A minimal mqtt 3.1.1 client which publishes retained messages with quality of service 0.
+
+use crate::http::{connect, Url};
+use std::{error::Error, process};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// The port of a broker when the url has none
+const DEFAULT_PORT: u16 = 1883;
+/// The largest remaining length that can be encoded
+const MAX_REMAINING_LENGTH: usize = 268_435_455;
+/// The control packet type and flags of a connect packet
+const CONNECT: u8 = 0x10;
+/// The control packet type and flags of a connack packet
+const CONNACK: u8 = 0x20;
+/// The control packet type and flags of a retained publish packet with quality of service 0
+const PUBLISH_RETAINED: u8 = 0x31;
+/// The control packet type and flags of a disconnect packet
+const DISCONNECT: u8 = 0xe0;
+
+/// Parses a `mqtt://host:port/topic` url.
+///
+/// # Errors
+///
+/// This function will return an error if the url is invalid or has no topic.
+pub fn parse(url: &str) -> Result<Url, Box<dyn Error + Send + Sync>> {
+    let parsed = Url::parse(url, "mqtt://", DEFAULT_PORT)?;
+    if topic(&parsed).is_empty() {
+        return Err(format!("missing topic: {url}").into());
+    }
+    Ok(parsed)
+}
+
+/// The topic of a parsed url
+fn topic(url: &Url) -> &str {
+    url.path().trim_start_matches('/')
+}
+
+/// Appends `value` as a string prefixed by its length.
+///
+/// # Errors
+///
+/// This function will return an error if `value` is too long.
+fn put_string(packet: &mut Vec<u8>, value: &[u8]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    packet.extend_from_slice(&u16::try_from(value.len())?.to_be_bytes());
+    packet.extend_from_slice(value);
+    Ok(())
+}
+
+/// Creates a control packet of `kind` with `body` as variable header and payload.
+///
+/// # Errors
+///
+/// This function will return an error if `body` is too long.
+fn packet(kind: u8, body: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    if body.len() > MAX_REMAINING_LENGTH {
+        return Err("mqtt packet too large".into());
+    }
+    let mut packet = vec![kind];
+    let mut remaining = body.len();
+    loop {
+        let mut byte = u8::try_from(remaining % 128)?;
+        remaining /= 128;
+        if remaining > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if remaining == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    Ok(packet)
+}
+
+/// Publishes `payload` as retained message to the topic of `url`, so new subscribers receive the latest payload.
+///
+/// # Errors
+///
+/// This function will return an error if the connection failed or the broker refused it.
+pub async fn publish(url: &Url, payload: &[u8]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut stream = connect(url, None).await?;
+
+    let mut connect_body = Vec::new();
+    put_string(&mut connect_body, b"MQTT")?;
+    // protocol level 4, a clean session and a keep alive of 60 seconds
+    connect_body.extend_from_slice(&[4, 0x02, 0, 60]);
+    put_string(
+        &mut connect_body,
+        format!("network_connectivity-{}", process::id()).as_bytes(),
+    )?;
+    stream.write_all(&packet(CONNECT, &connect_body)?).await?;
+
+    let mut connack = [0; 4];
+    stream.read_exact(&mut connack).await?;
+    match connack {
+        [CONNACK, 2, _, 0] => {}
+        [CONNACK, 2, _, code] => return Err(format!("mqtt broker refused with {code}").into()),
+        _ => return Err("invalid mqtt connack".into()),
+    }
+
+    let mut publish_body = Vec::new();
+    put_string(&mut publish_body, topic(url).as_bytes())?;
+    publish_body.extend_from_slice(payload);
+    stream
+        .write_all(&packet(PUBLISH_RETAINED, &publish_body)?)
+        .await?;
+    stream.write_all(&[DISCONNECT, 0]).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
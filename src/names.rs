@@ -0,0 +1,63 @@
+// SPDX-License-Identifier: MIT
+
+//! A cache of the names of interfaces by their index.
+
+use crate::state::{InterfaceId, LinkDetails};
+use core::time::Duration;
+use std::{
+    collections::HashMap,
+    sync::{Arc, PoisonError, RwLock},
+    time::Instant,
+};
+
+/// How long the name of a removed interface is remembered
+const REMOVED_RETENTION: Duration = Duration::from_secs(300);
+
+/// The names of the interfaces by their index, shared between the driver and the application.
+///
+/// The driver updates the names from every snapshot of the system, so they are known as long as the platform exposes them.
+/// The name of a removed interface is remembered for five minutes, so events and logs about an interface which disappeared moments ago can still name it.
+/// When the system reuses the index for an interface with a different name the new name replaces it.
+///
+/// Pass a clone to [`Builder::interface_names`](crate::Builder::interface_names) to look up the names the driver knows.
+#[derive(Clone, Debug, Default)]
+pub struct InterfaceNames(Arc<RwLock<HashMap<InterfaceId, (String, Option<Instant>)>>>);
+impl InterfaceNames {
+    /// Create a new [`InterfaceNames`] instance without names
+    #[allow(clippy::must_use_candidate)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the name of the interface with `index`, [`None`] when it is not known or the interface was removed too long ago
+    #[allow(clippy::must_use_candidate)]
+    pub fn get(&self, index: InterfaceId) -> Option<String> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&index)
+            .map(|&(ref name, _)| name.clone())
+    }
+
+    /// Records the names of the `current` interfaces.
+    ///
+    /// The interfaces which are no longer among them are remembered as removed and forgotten once they were removed for too long.
+    /// An interface whose name is not known keeps the name recorded before.
+    pub(crate) fn update(&self, current: &HashMap<InterfaceId, LinkDetails>) {
+        let now = Instant::now();
+        let mut names = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        names.retain(|index, &mut (_, ref mut removed)| {
+            if current.contains_key(index) {
+                *removed = None;
+                return true;
+            }
+            let removed = removed.get_or_insert(now);
+            now.duration_since(*removed) < REMOVED_RETENTION
+        });
+        for (&index, details) in current {
+            if let Some(ref name) = details.name {
+                names.insert(index, (name.clone(), None));
+            }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: MIT
+
+//! Detection of NAT64 on ipv6 only networks by the dns64 synthesis of `ipv4only.arpa` as described in RFC 7050.
+
+use crate::{Connectivity, ConnectivityState, NetworkFingerprint};
+use core::cmp::max;
+use futures::Future;
+use log::debug;
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+};
+use tokio::{
+    net::lookup_host,
+    sync::{watch, Notify},
+};
+
+/// The name which only has ipv4 addresses, an ipv6 address for it is synthesized by dns64
+const IPV4_ONLY: &str = "ipv4only.arpa";
+/// The well known ipv4 addresses of [`IPV4_ONLY`]
+const IPV4_ONLY_ADDRESSES: [Ipv4Addr; 2] =
+    [Ipv4Addr::new(192, 0, 0, 170), Ipv4Addr::new(192, 0, 0, 171)];
+
+/// How ipv4 is reported on an ipv6 only network with NAT64, see [`Builder::nat64`](crate::Builder::nat64).
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum Nat64Policy {
+    /// Ipv4 is reported as the system has it natively, which is usually no connectivity on an ipv6 only network
+    Native,
+    /// Ipv4 is reported as internet connectivity while ipv6 has internet connectivity and NAT64 is detected,
+    /// for applications which connect to ipv4 destinations by name or synthesize the addresses themselves
+    Synthesized,
+}
+
+/// A request to detect NAT64 on the networks with a fingerprint, only when ipv6 has internet connectivity.
+type Request = (Option<NetworkFingerprint>, bool);
+/// The NAT64 prefix detected on the networks with a fingerprint, [`None`] when NAT64 was not detected.
+type Outcome = (Option<NetworkFingerprint>, Option<Ipv6Addr>);
+
+/// The part of the NAT64 detection which lives in the emitter.
+pub(crate) struct Detection {
+    /// How ipv4 is reported when NAT64 is detected
+    policy: Nat64Policy,
+    /// The last requested detection
+    requested: Option<Request>,
+    /// Requests detection by the detector
+    requests: watch::Sender<Option<Request>>,
+    /// The latest outcome of the detector
+    outcomes: watch::Receiver<Outcome>,
+}
+impl Detection {
+    /// Creates the [`Detection`] and the detector which notifies `wake` of every outcome.
+    ///
+    /// The detector completes when the [`Detection`] is dropped.
+    pub(crate) fn new(policy: Nat64Policy, wake: Arc<Notify>) -> (Self, impl Future<Output = ()>) {
+        let (requests, requests_rx) = watch::channel(None);
+        let (outcomes_tx, outcomes) = watch::channel((None, None));
+        let detection = Self {
+            policy,
+            requested: None,
+            requests,
+            outcomes,
+        };
+        (detection, detect(requests_rx, outcomes_tx, wake))
+    }
+
+    /// Applies the policy to the `connectivity` of the networks with `fingerprint`, detecting NAT64 again when they changed.
+    pub(crate) fn apply(
+        &mut self,
+        mut connectivity: Connectivity,
+        fingerprint: Option<NetworkFingerprint>,
+    ) -> Connectivity {
+        let request = (
+            fingerprint,
+            connectivity.ipv6 == ConnectivityState::Internet,
+        );
+        if self.requested != Some(request) {
+            self.requested = Some(request);
+            self.requests.send_replace(Some(request));
+        }
+        let (detected, prefix) = *self.outcomes.borrow();
+        if self.policy == Nat64Policy::Synthesized
+            && detected == fingerprint
+            && prefix.is_some()
+            && connectivity.ipv6 == ConnectivityState::Internet
+        {
+            connectivity.ipv4 = max(connectivity.ipv4, ConnectivityState::Internet);
+        }
+        connectivity
+    }
+}
+
+/// Looks up [`IPV4_ONLY`] and derives the NAT64 prefix from a synthesized address.
+///
+/// Only the `/96` prefix length of the well known prefix and most deployments is recognized.
+async fn nat64_prefix() -> Option<Ipv6Addr> {
+    let addresses = match lookup_host((IPV4_ONLY, 0)).await {
+        Ok(addresses) => addresses,
+        Err(error) => {
+            debug!("looking up {IPV4_ONLY} failed {error}");
+            return None;
+        }
+    };
+    addresses
+        .filter_map(|address| match address.ip() {
+            IpAddr::V6(address) => Some(address),
+            IpAddr::V4(_) => None,
+        })
+        .find_map(|address| {
+            let [a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p] = address.octets();
+            IPV4_ONLY_ADDRESSES
+                .contains(&Ipv4Addr::new(m, n, o, p))
+                .then(|| Ipv6Addr::from([a, b, c, d, e, f, g, h, i, j, k, l, 0, 0, 0, 0]))
+        })
+}
+
+/// Detects NAT64 for every requested network with internet connectivity over ipv6 and sends the outcomes.
+///
+/// # Notes
+///
+/// This function completes when the requests are closed.
+async fn detect(
+    mut requests: watch::Receiver<Option<Request>>,
+    outcomes: watch::Sender<Outcome>,
+    wake: Arc<Notify>,
+) {
+    while requests.changed().await.is_ok() {
+        let (fingerprint, ipv6_internet) = match *requests.borrow_and_update() {
+            Some(request) => request,
+            None => continue,
+        };
+        let prefix = if ipv6_internet {
+            nat64_prefix().await
+        } else {
+            None
+        };
+        debug!("detected nat64 prefix {prefix:?}");
+        outcomes.send_replace((fingerprint, prefix));
+        wake.notify_one();
+    }
+    debug!("no more nat64 requests");
+}
@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: MIT
+
+//! The NetworkManager implementation for this crate on linux, for desktop systems where NetworkManager is authoritative.
+//!
+//! The signals of NetworkManager, like `StateChanged` and the changes of its `Connectivity` property, are received with `gdbus monitor`,
+//! which subscribes to them like any client of the system bus while `busctl monitor` has to become a monitor of the bus which only root may do.
+//! Whenever NetworkManager signals a change the system is sampled like the [polling](crate::unix) backend does
+//! and the verdict of the connectivity check of NetworkManager is read from its `Connectivity` property,
+//! so captive portals and networks without internet access are reported the way NetworkManager sees them.
+
+use crate::{
+    shared::{Listener, Platform},
+    state::SystemConnectivity,
+};
+use futures::FutureExt;
+use log::debug;
+use std::{
+    error::Error,
+    io,
+    process::{Command, Stdio},
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader, Lines},
+    process::{Child, ChildStdout},
+    sync::Mutex,
+};
+
+/// The bus name and the interface of NetworkManager
+const SERVICE: &str = "org.freedesktop.NetworkManager";
+/// The object path of NetworkManager
+const PATH: &str = "/org/freedesktop/NetworkManager";
+/// The prefix of the object paths of the access points, their signals only report scan results
+const ACCESS_POINTS: &str = "/org/freedesktop/NetworkManager/AccessPoint/";
+
+/// Reads the `Connectivity` property of NetworkManager as printed by `gdbus`.
+///
+/// # Errors
+///
+/// This function will return an error if `gdbus` could not be run or NetworkManager did not answer.
+fn connectivity_property() -> io::Result<String> {
+    let output = Command::new("gdbus")
+        .args([
+            "call",
+            "--system",
+            "--dest",
+            SERVICE,
+            "--object-path",
+            PATH,
+            "--method",
+            "org.freedesktop.DBus.Properties.Get",
+            SERVICE,
+            "Connectivity",
+        ])
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "gdbus failed with {} {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parses the `Connectivity` property as printed by `gdbus`, like `(<uint32 4>,)`.
+///
+/// Returns [`None`] when NetworkManager does not know the connectivity, for example because its connectivity check is disabled.
+fn parse_connectivity(property: &str) -> Option<SystemConnectivity> {
+    let value = property
+        .trim()
+        .strip_prefix("(<uint32 ")?
+        .split('>')
+        .next()?;
+    // the values of NMConnectivityState
+    match value.parse::<u32>().ok()? {
+        1 => Some(SystemConnectivity::None),
+        2 => Some(SystemConnectivity::Portal),
+        3 => Some(SystemConnectivity::Limited),
+        4 => Some(SystemConnectivity::Full),
+        _ => None,
+    }
+}
+
+/// Reads the verdict of the connectivity check of NetworkManager, [`None`] when it does not know the connectivity.
+///
+/// # Errors
+///
+/// This function will return an error if NetworkManager could not be asked, usually because it is not running.
+fn connectivity() -> Result<Option<SystemConnectivity>, Box<dyn Error + Send + Sync>> {
+    let connectivity = parse_connectivity(&connectivity_property()?);
+    debug!("networkmanager reports {connectivity:?} connectivity");
+    Ok(connectivity)
+}
+
+/// Checks whether NetworkManager answers on the system bus.
+///
+/// # Errors
+///
+/// This function will return an error if `gdbus` could not be run or NetworkManager did not answer.
+pub(crate) fn preflight() -> io::Result<()> {
+    connectivity_property().map(|_| ())
+}
+
+/// Whether a line printed by `gdbus monitor` is about a change of NetworkManager.
+///
+/// Every signal of NetworkManager and its devices is printed after its object path,
+/// a change of the owner of its bus name means NetworkManager started or stopped.
+fn is_change(line: &str) -> bool {
+    (line.starts_with(PATH) && !line.starts_with(ACCESS_POINTS)) || line.starts_with("The name ")
+}
+
+/// The signals of NetworkManager as printed by `gdbus monitor`.
+struct Signals {
+    /// The monitor, it is killed when dropped
+    _monitor: Child,
+    /// The lines printed by the monitor
+    lines: Mutex<Lines<BufReader<ChildStdout>>>,
+}
+impl Signals {
+    /// Starts monitoring the signals of NetworkManager.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `gdbus` could not be run.
+    fn new() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut monitor = tokio::process::Command::new("gdbus")
+            .args(["monitor", "--system", "--dest", SERVICE])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdout = monitor
+            .stdout
+            .take()
+            .ok_or("the output of gdbus monitor is not captured")?;
+        Ok(Self {
+            _monitor: monitor,
+            lines: Mutex::new(BufReader::new(stdout).lines()),
+        })
+    }
+
+    /// Completes when NetworkManager signaled a change.
+    ///
+    /// The lines which are printed already are read as well, so a burst of signals is sampled once.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the monitor exited or its output could not be read.
+    async fn changed(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut lines = self.lines.lock().await;
+        let mut changed = false;
+        loop {
+            let line = lines.next_line().await?.ok_or("gdbus monitor exited")?;
+            changed |= is_change(&line);
+            while let Some(line) = lines.next_line().now_or_never() {
+                changed |= is_change(&line?.ok_or("gdbus monitor exited")?);
+            }
+            if changed {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Creates the platform listener which publishes snapshots of the system to the shared [listener](Listener).
+///
+/// # Notes
+///
+/// The platform listener never completes by itself.
+///
+/// # Errors
+///
+/// The platform listener fails when the signals of NetworkManager could not be monitored, NetworkManager stopped or the system could not be sampled.
+pub fn listen(listener: &Listener) -> Platform<'_> {
+    Box::pin(run(listener))
+}
+
+/// Samples the system and the connectivity of NetworkManager and publishes a snapshot to the shared [listener](Listener) whenever NetworkManager signaled a change.
+///
+/// # Errors
+///
+/// This function will return an error if the signals of NetworkManager could not be monitored, NetworkManager stopped or the system could not be sampled.
+async fn run(listener: &Listener) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // the monitor is started before the initial sample, so no change in between is missed.
+    debug!("monitoring networkmanager");
+    let signals = Signals::new()?;
+    crate::unix::publish_checked_on(listener, || signals.changed(), connectivity).await
+}
@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: MIT
+
+//! Exports connectivity transitions through the global OpenTelemetry providers installed by the application.
+
+use crate::{InterfaceNames, NetworkEvent};
+use opentelemetry::{
+    global::{self, BoxedTracer},
+    metrics::{Counter, Histogram, Unit},
+    trace::{Span, Tracer},
+    Context, KeyValue,
+};
+
+/// The name of the instrumentation scope of the exported telemetry
+const SCOPE: &str = "network_connectivity";
+
+/// Exports connectivity transitions as span events and metrics.
+pub(crate) struct Exporter {
+    /// Counts the connectivity transitions
+    transitions: Counter<u64>,
+    /// Records how long the previous connectivity lasted
+    durations: Histogram<f64>,
+    /// Creates a span for every connectivity transition
+    tracer: BoxedTracer,
+}
+impl Exporter {
+    /// Creates an [`Exporter`] with the providers which are installed globally.
+    ///
+    /// Providers installed later are not used, the application must install them before building the driver.
+    pub(crate) fn new() -> Self {
+        let meter = global::meter(SCOPE);
+        Self {
+            transitions: meter
+                .u64_counter("network_connectivity.transitions")
+                .with_description("The amount of connectivity transitions")
+                .init(),
+            durations: meter
+                .f64_histogram("network_connectivity.state.duration")
+                .with_description("How long the connectivity lasted before it changed")
+                .with_unit(Unit::new("s"))
+                .init(),
+            tracer: global::tracer(SCOPE),
+        }
+    }
+
+    /// Exports the connectivity transitions among `events` which were emitted for the same change of the system.
+    ///
+    /// The reason of a transition is the kind of the first other event emitted with it and the interface is the one of that event,
+    /// `names` provides the name of the interface when the event does not contain it.
+    /// The reason of the initial connectivity is `initial`, without such an event the reason is `reevaluated`,
+    /// for example when a probe verified the connectivity or a flapping interface settled.
+    pub(crate) fn export(&self, events: &[NetworkEvent], names: &InterfaceNames) {
+        let mut cause = NetworkEvent::cause(events);
+        for event in events {
+            if let NetworkEvent::ConnectivityChanged {
+                connectivity,
+                previous,
+                duration_in_previous_state,
+                verified,
+                ..
+            } = *event
+            {
+                // the events of the initial state did not cause the initial connectivity.
+                if previous.is_none() {
+                    cause = None;
+                }
+                let mut attributes = vec![
+                    KeyValue::new("connectivity.ipv4", connectivity.ipv4.to_string()),
+                    KeyValue::new("connectivity.ipv6", connectivity.ipv6.to_string()),
+                    KeyValue::new("connectivity.verified", verified),
+                    KeyValue::new("reason", NetworkEvent::reason(cause, previous)),
+                ];
+                if let Some(previous) = previous {
+                    attributes.push(KeyValue::new("previous.ipv4", previous.ipv4.to_string()));
+                    attributes.push(KeyValue::new("previous.ipv6", previous.ipv6.to_string()));
+                }
+                if let Some((index, name)) = cause.and_then(|cause| cause.interface(names)) {
+                    attributes.push(KeyValue::new("interface.index", i64::from(index.value())));
+                    if let Some(name) = name {
+                        attributes.push(KeyValue::new("interface.name", name));
+                    }
+                }
+
+                let cx = Context::current();
+                self.transitions.add(&cx, 1, &attributes);
+                if let Some(duration) = duration_in_previous_state {
+                    self.durations
+                        .record(&cx, duration.as_secs_f64(), &attributes);
+                }
+                let mut span = self.tracer.start("connectivity transition");
+                span.add_event("connectivity changed", attributes);
+                span.end();
+            }
+        }
+    }
+}
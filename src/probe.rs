@@ -0,0 +1,550 @@
+// SPDX-License-Identifier: MIT
+
+//! Verifies internet connectivity by actively probing well known endpoints or user defined [probes](Probe).
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    http::{request, Url},
+    Connectivity, ConnectivityState, IpFamily, NetworkFingerprint,
+};
+use core::{cmp::min, pin::Pin, time::Duration};
+use futures::{
+    future::join_all,
+    stream::{FuturesUnordered, StreamExt},
+    Future,
+};
+use log::{debug, warn};
+use std::{
+    collections::hash_map::RandomState,
+    error::Error,
+    hash::{BuildHasher, Hasher},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+use tokio::{
+    net::UdpSocket,
+    sync::{watch, Notify},
+    time::{sleep, timeout},
+};
+
+/// The time after which a single probe is considered failed when no timeout is given
+pub(crate) const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// The time between probes of a generation which is stuck at network connectivity when no interval is given
+pub(crate) const REPROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The result of a [`Probe`].
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[non_exhaustive]
+pub enum ProbeResult {
+    /// The probe verified internet connectivity
+    Verified,
+    /// The probe could not verify internet connectivity
+    Failed,
+    /// A captive portal intercepted the probe, with the url of its login page when it redirected to it
+    CaptivePortal(Option<String>),
+}
+
+/// The future returned by [`Probe::check`].
+pub type ProbeFuture<'a> = Pin<Box<dyn Future<Output = ProbeResult> + Send + 'a>>;
+
+/// Verifies internet connectivity for an ip type, for example by checking whether an own backend can be reached.
+pub trait Probe {
+    /// Checks whether there is internet connectivity over `family`.
+    ///
+    /// The check should complete in a timely manner, a probe that takes too long is considered failed.
+    fn check(&self, family: IpFamily) -> ProbeFuture<'_>;
+}
+
+/// Well known endpoints which are used to verify internet connectivity.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum Preset {
+    /// `http://cp.cloudflare.com/generate_204` which responds with status 204
+    Cloudflare,
+    /// `http://connectivitycheck.gstatic.com/generate_204` which responds with status 204
+    Google,
+    /// `http://www.msftconnecttest.com/connecttest.txt` which responds with `Microsoft Connect Test`
+    Microsoft,
+    /// `http://captive.apple.com/hotspot-detect.html` which responds with a page containing `Success`
+    Apple,
+}
+impl Preset {
+    /// The presets which are used when probing is enabled without choosing endpoints
+    pub const DEFAULT: [Self; 2] = [Self::Cloudflare, Self::Google];
+
+    /// Get the url, the expected status and the expected start of the body of this preset
+    const fn endpoint(self) -> (&'static str, u16, &'static str) {
+        match self {
+            Self::Cloudflare => ("http://cp.cloudflare.com/generate_204", 204, ""),
+            Self::Google => ("http://connectivitycheck.gstatic.com/generate_204", 204, ""),
+            Self::Microsoft => (
+                "http://www.msftconnecttest.com/connecttest.txt",
+                200,
+                "Microsoft Connect Test",
+            ),
+            Self::Apple => (
+                "http://captive.apple.com/hotspot-detect.html",
+                200,
+                "<HTML><HEAD><TITLE>Success</TITLE>",
+            ),
+        }
+    }
+
+    /// Checks whether the endpoint of this preset responds as expected over `family`.
+    async fn check_endpoint(self, family: IpFamily) -> ProbeResult {
+        let (url, status, body) = self.endpoint();
+        let url = match url.parse::<Url>() {
+            Ok(url) => url,
+            Err(error) => {
+                warn!("invalid probe url {url} {error}");
+                return ProbeResult::Failed;
+            }
+        };
+        check_url(&url, status, body.as_bytes(), family).await
+    }
+}
+impl Probe for Preset {
+    fn check(&self, family: IpFamily) -> ProbeFuture<'_> {
+        Box::pin(self.check_endpoint(family))
+    }
+}
+
+/// Verifies internet connectivity when an own `http://` endpoint responds with the expected status.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct HttpProbe {
+    /// The url of the endpoint
+    url: Url,
+    /// The status the endpoint responds with
+    status: u16,
+}
+impl HttpProbe {
+    /// Create a new [`HttpProbe`] instance for the endpoint at `url` which responds with status 204.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `url` is not a valid `http://` url.
+    pub fn new(url: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self {
+            url: url.parse()?,
+            status: 204,
+        })
+    }
+
+    /// Expect the endpoint to respond with `status` instead of 204
+    #[must_use]
+    pub const fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+}
+impl Probe for HttpProbe {
+    fn check(&self, family: IpFamily) -> ProbeFuture<'_> {
+        Box::pin(check_url(&self.url, self.status, &[], family))
+    }
+}
+
+/// Verifies internet connectivity when a dns resolver answers a query for the name servers of the root zone.
+///
+/// Every recursive resolver can answer the query without depending on a domain, so the probe only fails when the resolver is not reachable.
+/// A resolver that does not answer with any record is considered failed.
+/// Unlike an http probe it can not detect a captive portal, so it is best combined with one.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct DnsProbe {
+    /// The resolver queried over ipv4
+    ipv4: SocketAddr,
+    /// The resolver queried over ipv6
+    ipv6: SocketAddr,
+}
+impl DnsProbe {
+    /// The resolvers of Cloudflare, `1.1.1.1` and `2606:4700:4700::1111`
+    pub const CLOUDFLARE: Self = Self::new(
+        Ipv4Addr::new(1, 1, 1, 1),
+        Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111),
+    );
+    /// The resolvers of Google, `8.8.8.8` and `2001:4860:4860::8888`
+    pub const GOOGLE: Self = Self::new(
+        Ipv4Addr::new(8, 8, 8, 8),
+        Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888),
+    );
+    /// The resolvers of Quad9, `9.9.9.9` and `2620:fe::fe`
+    pub const QUAD9: Self = Self::new(
+        Ipv4Addr::new(9, 9, 9, 9),
+        Ipv6Addr::new(0x2620, 0xfe, 0, 0, 0, 0, 0, 0xfe),
+    );
+
+    /// Create a new [`DnsProbe`] instance which queries `ipv4` and `ipv6` on port 53 over the respective ip type.
+    #[allow(clippy::must_use_candidate)]
+    pub const fn new(ipv4: Ipv4Addr, ipv6: Ipv6Addr) -> Self {
+        Self {
+            ipv4: SocketAddr::new(IpAddr::V4(ipv4), 53),
+            ipv6: SocketAddr::new(IpAddr::V6(ipv6), 53),
+        }
+    }
+
+    /// Queries the resolver of `family` and checks whether it answered.
+    async fn query(self, family: IpFamily) -> ProbeResult {
+        let resolver = match family {
+            IpFamily::V4 => self.ipv4,
+            IpFamily::V6 => self.ipv6,
+        };
+        match exchange(resolver).await {
+            Ok(answered) => {
+                debug!("probe dns {resolver} over {family} answered {answered}");
+                if answered {
+                    ProbeResult::Verified
+                } else {
+                    ProbeResult::Failed
+                }
+            }
+            Err(error) => {
+                debug!("probe dns {resolver} over {family} failed {error}");
+                ProbeResult::Failed
+            }
+        }
+    }
+}
+impl Probe for DnsProbe {
+    fn check(&self, family: IpFamily) -> ProbeFuture<'_> {
+        Box::pin(self.query(family))
+    }
+}
+
+/// Sends a query for the name servers of the root zone to `resolver` and checks whether it answered with any record.
+///
+/// Responses which do not match the query are ignored, the caller bounds the time to wait for a matching one.
+async fn exchange(resolver: SocketAddr) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let unspecified = match resolver {
+        SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0)).await?;
+    socket.connect(resolver).await?;
+    let [id_high, id_low, ..] = RandomState::new().build_hasher().finish().to_be_bytes();
+    // the header with recursion desired and one question, followed by the root name, type NS and class IN.
+    let query = [
+        id_high, id_low, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1,
+    ];
+    socket.send(&query).await?;
+    let mut response = [0_u8; 512];
+    loop {
+        let length = socket.recv(&mut response).await?;
+        let (high, low, flags, codes, answers) = match response.get(..length) {
+            Some(&[high, low, flags, codes, _, _, answers_high, answers_low, ..]) => (
+                high,
+                low,
+                flags,
+                codes,
+                u16::from_be_bytes([answers_high, answers_low]),
+            ),
+            _ => continue,
+        };
+        // only a response to this query counts.
+        if high != id_high || low != id_low || flags & 0x80 == 0 {
+            continue;
+        }
+        return Ok(codes & 0x0f == 0 && answers > 0);
+    }
+}
+
+/// Checks whether `url` responds with `status` and a body starting with `body` over `family`.
+///
+/// A captive portal intercepting the request responds differently, for example with a redirect to its login page.
+/// A successful or redirecting response which differs from the expected one is considered a captive portal.
+async fn check_url(url: &Url, status: u16, body: &[u8], family: IpFamily) -> ProbeResult {
+    match request("GET", url, Some(family), "", &[]).await {
+        Ok(response) => {
+            let verified = response.status == status && response.body.starts_with(body);
+            debug!(
+                "probe {url} over {family} responded with status {} verified {verified}",
+                response.status
+            );
+            if verified {
+                ProbeResult::Verified
+            } else if matches!(response.status, 200..=399) {
+                ProbeResult::CaptivePortal(response.location)
+            } else {
+                ProbeResult::Failed
+            }
+        }
+        Err(error) => {
+            debug!("probe {url} over {family} failed {error}");
+            ProbeResult::Failed
+        }
+    }
+}
+
+/// The probes to verify internet connectivity with and how many of them must succeed.
+#[derive(Default)]
+pub(crate) struct Probes {
+    /// The probes with the time after which they are considered failed
+    probes: Vec<(Box<dyn Probe + Send + Sync>, Duration)>,
+    /// The amount of probes that must verify internet connectivity, one when not given
+    quorum: Option<usize>,
+    /// The time between probes of ip types which were not verified, [`REPROBE_INTERVAL`] when not given
+    reprobe: Option<Duration>,
+}
+impl Probes {
+    /// Adds `probe` which is considered failed after `timeout`
+    pub(crate) fn push(&mut self, probe: Box<dyn Probe + Send + Sync>, timeout: Duration) {
+        self.probes.push((probe, timeout));
+    }
+
+    /// Sets the amount of probes that must verify internet connectivity
+    pub(crate) fn set_quorum(&mut self, quorum: usize) {
+        self.quorum = Some(quorum);
+    }
+
+    /// Sets the time between probes of ip types which were not verified
+    pub(crate) fn set_reprobe_interval(&mut self, interval: Duration) {
+        self.reprobe = Some(interval);
+    }
+
+    /// Whether there are no probes
+    pub(crate) fn is_empty(&self) -> bool {
+        self.probes.is_empty()
+    }
+
+    /// Checks whether the quorum can be reached.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the quorum is zero or larger than the amount of probes.
+    pub(crate) fn validate(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match self.quorum {
+            Some(0) => Err("the probe quorum must be at least one".into()),
+            Some(quorum) if quorum > self.probes.len() => Err(format!(
+                "the probe quorum {quorum} is larger than the amount of probes {}",
+                self.probes.len()
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs all probes over `family` and checks whether the quorum verified internet connectivity.
+    ///
+    /// Without a quorum the result is a captive portal when any probe detected one, preferring one with a login page.
+    /// A probe that takes longer than its timeout is considered failed, failed probes are reported to `diagnostics`.
+    async fn verify(&self, family: IpFamily, diagnostics: &Diagnostics) -> ProbeResult {
+        let checks = self.probes.iter().map(|&(ref probe, duration)| async move {
+            #[cfg(feature = "metrics")]
+            let started = std::time::Instant::now();
+            let (result, timed_out) = match timeout(duration, probe.check(family)).await {
+                Ok(result) => (result, false),
+                Err(_) => {
+                    debug!("probe over {family} timed out");
+                    (ProbeResult::Failed, true)
+                }
+            };
+            #[cfg(feature = "metrics")]
+            metrics::histogram!(
+                "network_connectivity_probe_duration_seconds",
+                started.elapsed().as_secs_f64(),
+                "family" => match family {
+                    IpFamily::V4 => "ipv4",
+                    IpFamily::V6 => "ipv6",
+                }
+            );
+            if result == ProbeResult::Failed {
+                diagnostics.report(Diagnostic::ProbeFailed { family, timed_out });
+            }
+            result
+        });
+        let results = join_all(checks).await;
+        let verified = results
+            .iter()
+            .filter(|&result| *result == ProbeResult::Verified)
+            .count();
+        let quorum = self.quorum.unwrap_or(1);
+        debug!(
+            "{verified} of {} probes verified {family}, the quorum is {quorum}",
+            self.probes.len()
+        );
+        if verified >= quorum {
+            ProbeResult::Verified
+        } else {
+            portal(results)
+        }
+    }
+}
+
+/// Combines `results` into a captive portal when any of them detected one, preferring one with a login page.
+fn portal(results: impl IntoIterator<Item = ProbeResult>) -> ProbeResult {
+    results
+        .into_iter()
+        .filter(|result| matches!(*result, ProbeResult::CaptivePortal(_)))
+        .max_by_key(|result| matches!(*result, ProbeResult::CaptivePortal(Some(_))))
+        .unwrap_or(ProbeResult::Failed)
+}
+
+/// A request to verify the connectivity of a generation of the passive state.
+type Request = (u64, Connectivity);
+/// The results of the verification of a generation of the passive state over ipv4 and ipv6.
+///
+/// The result of an ip type is [`None`] until it is probed.
+type Outcome = (u64, Option<ProbeResult>, Option<ProbeResult>);
+
+/// The part of the verification which lives in the emitter.
+///
+/// Every change of the passive connectivity or the networks starts a new generation which must be verified again.
+pub struct Verification {
+    /// The current generation of the passive state
+    generation: u64,
+    /// The passive connectivity and fingerprint of the current generation
+    passive: Option<(Connectivity, Option<NetworkFingerprint>)>,
+    /// Requests verification of a generation by the prober
+    requests: watch::Sender<Request>,
+    /// The latest outcome of the prober
+    outcomes: watch::Receiver<Outcome>,
+    /// The generation of which the captive portal was reported last
+    portal_reported: Option<u64>,
+}
+impl Verification {
+    /// Creates the [`Verification`] and the prober which runs `probes` and notifies `wake` of every outcome.
+    ///
+    /// Failed probes are reported to `diagnostics`.
+    /// The prober completes when the [`Verification`] is dropped.
+    pub(crate) fn new(
+        probes: Probes,
+        wake: Arc<Notify>,
+        diagnostics: Diagnostics,
+    ) -> (Self, impl Future<Output = ()>) {
+        let none = Connectivity {
+            ipv4: ConnectivityState::None,
+            ipv6: ConnectivityState::None,
+        };
+        let (requests, requests_rx) = watch::channel((0, none));
+        let (outcomes_tx, outcomes) = watch::channel((0, None, None));
+        let verification = Self {
+            generation: 0,
+            passive: None,
+            requests,
+            outcomes,
+            portal_reported: None,
+        };
+        (
+            verification,
+            probe(probes, requests_rx, outcomes_tx, wake, diagnostics),
+        )
+    }
+
+    /// Verifies the `passive` connectivity of the networks with `fingerprint`.
+    ///
+    /// Internet connectivity of an ip type is reported as network connectivity until the prober verified it over that ip type.
+    pub(crate) fn verify(
+        &mut self,
+        passive: Connectivity,
+        fingerprint: Option<NetworkFingerprint>,
+    ) -> Connectivity {
+        if self.passive != Some((passive, fingerprint)) {
+            self.generation = self.generation.wrapping_add(1);
+            self.passive = Some((passive, fingerprint));
+            self.requests.send_replace((self.generation, passive));
+        }
+        let outcome = self.outcomes.borrow();
+        let (generation, ref ipv4, ref ipv6) = *outcome;
+        let verify = |state: ConnectivityState, result: &Option<ProbeResult>| {
+            if generation == self.generation && *result == Some(ProbeResult::Verified) {
+                state
+            } else {
+                min(state, ConnectivityState::Network)
+            }
+        };
+        Connectivity {
+            ipv4: verify(passive.ipv4, ipv4),
+            ipv6: verify(passive.ipv6, ipv6),
+        }
+    }
+
+    /// Takes the captive portal detected for the current generation, it is only returned once per generation.
+    ///
+    /// The inner value is the url of the login page when known.
+    pub(crate) fn captive_portal(&mut self) -> Option<Option<String>> {
+        let (generation, ipv4, ipv6) = self.outcomes.borrow().clone();
+        if generation != self.generation || self.portal_reported == Some(generation) {
+            return None;
+        }
+        match portal(ipv4.into_iter().chain(ipv6)) {
+            ProbeResult::CaptivePortal(url) => {
+                self.portal_reported = Some(generation);
+                Some(url)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Runs `probes` for every requested generation which has internet connectivity and sends the outcomes.
+///
+/// The probes run independently over every ip type with internet connectivity and the result of each ip type is sent as soon as it is known,
+/// so a broken ip type does not delay or hide the verification of the other.
+/// Ip types which were not verified, for example because a captive portal intercepted them, are probed again after the reprobe interval
+/// until they are verified or a new generation is requested, so the upgrade is sent as soon as the user logged in to the portal.
+///
+/// # Notes
+///
+/// This function completes when the requests are closed.
+async fn probe(
+    probes: Probes,
+    mut requests: watch::Receiver<Request>,
+    outcomes: watch::Sender<Outcome>,
+    wake: Arc<Notify>,
+    diagnostics: Diagnostics,
+) {
+    let interval = probes.reprobe.unwrap_or(REPROBE_INTERVAL);
+    let mut requested = false;
+    'requests: loop {
+        if !requested && requests.changed().await.is_err() {
+            break;
+        }
+        requested = false;
+        let (generation, connectivity) = *requests.borrow_and_update();
+        if connectivity.any() != ConnectivityState::Internet {
+            continue;
+        }
+        debug!("probing generation {generation}");
+        outcomes.send_replace((generation, None, None));
+        let mut families = [IpFamily::V4, IpFamily::V6]
+            .into_iter()
+            .filter(|&family| connectivity.get(family) == ConnectivityState::Internet)
+            .collect::<Vec<_>>();
+        while !families.is_empty() {
+            let mut checks = families
+                .iter()
+                .map(|&family| {
+                    let probes = &probes;
+                    let diagnostics = &diagnostics;
+                    async move { (family, probes.verify(family, diagnostics).await) }
+                })
+                .collect::<FuturesUnordered<_>>();
+            while let Some((family, result)) = checks.next().await {
+                debug!("generation {generation} probed {result:?} over {family}");
+                if result == ProbeResult::Verified {
+                    families.retain(|&other| other != family);
+                }
+                outcomes.send_modify(|&mut (_, ref mut ipv4, ref mut ipv6)| match family {
+                    IpFamily::V4 => *ipv4 = Some(result),
+                    IpFamily::V6 => *ipv6 = Some(result),
+                });
+                wake.notify_one();
+            }
+            if families.is_empty() {
+                break;
+            }
+            #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+            {
+                tokio::select! {
+                    () = sleep(interval) => debug!("probing generation {generation} again"),
+                    changed = requests.changed() => {
+                        if changed.is_err() {
+                            break 'requests;
+                        }
+                        requested = true;
+                        continue 'requests;
+                    }
+                }
+            }
+        }
+    }
+    debug!("no more probe requests");
+}
@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: MIT
+
+//! The default gateways the system currently selects, shared between the driver and the application.
+
+use crate::{state::RouteDetails, IpFamily};
+use std::{
+    net::IpAddr,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+/// The preferred default route of each ip type.
+#[derive(Clone, Copy, Debug, Default)]
+struct DefaultRoutes {
+    /// The preferred ipv4 default route, [`None`] when there is none
+    ipv4: Option<RouteDetails>,
+    /// The preferred ipv6 default route, [`None`] when there is none
+    ipv6: Option<RouteDetails>,
+}
+
+/// The preferred default route of every ip type, shared between the driver and the application.
+///
+/// The preferred default route is the one through an interface that is up with the lowest metric, ties are broken by the lowest interface index,
+/// like the primary route of [`NetworkEvent::DefaultRouteChanged`](crate::NetworkEvent::DefaultRouteChanged).
+/// Its gateway is where latency probes or UPnP and NAT-PMP clients send their requests to.
+///
+/// Pass a clone to [`Builder::routing_info`](crate::Builder::routing_info) to let the driver maintain it.
+#[derive(Clone, Debug, Default)]
+pub struct RoutingInfo(Arc<RwLock<DefaultRoutes>>);
+impl RoutingInfo {
+    /// Create a new [`RoutingInfo`] instance without default routes
+    #[allow(clippy::must_use_candidate)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the preferred default route of `family`, [`None`] when there is none or the driver ignores the ip type
+    #[allow(clippy::must_use_candidate)]
+    pub fn default_route(&self, family: IpFamily) -> Option<RouteDetails> {
+        let routes = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        match family {
+            IpFamily::V4 => routes.ipv4,
+            IpFamily::V6 => routes.ipv6,
+        }
+    }
+
+    /// Get the gateway of the preferred default route of `family`, [`None`] when there is none or it is an on-link route without a gateway
+    #[allow(clippy::must_use_candidate)]
+    pub fn gateway(&self, family: IpFamily) -> Option<IpAddr> {
+        self.default_route(family)?.gateway
+    }
+
+    /// Records the preferred default `route` of `family`.
+    pub(crate) fn update(&self, family: IpFamily, route: Option<RouteDetails>) {
+        let mut routes = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        match family {
+            IpFamily::V4 => routes.ipv4 = route,
+            IpFamily::V6 => routes.ipv6 = route,
+        }
+    }
+}
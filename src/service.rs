@@ -0,0 +1,259 @@
+// SPDX-License-Identifier: MIT
+
+//! Runs the daemon as a windows service under the service control manager.
+//!
+//! The service is installed with the `--service` argument of `connectivityd`, for example with
+//! `sc.exe create connectivityd binPath= "C:\path\to\connectivityd.exe --service"`.
+//! Stopping the service stops the daemon, pausing it stops sharing events until it is continued.
+//! A daemon which fails reports its error to the application event log with the name of the service as source.
+
+use log::{debug, error, warn};
+use std::{
+    error::Error,
+    ffi::OsStr,
+    os::windows::ffi::OsStrExt,
+    sync::{
+        atomic::{AtomicIsize, Ordering},
+        Mutex, PoisonError,
+    },
+};
+use tokio::sync::watch;
+use windows::{
+    core::{PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{ERROR_CALL_NOT_IMPLEMENTED, ERROR_SERVICE_SPECIFIC_ERROR, NO_ERROR, PSID},
+        System::{
+            EventLog::{
+                DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+            },
+            Services::{
+                RegisterServiceCtrlHandlerExW, SetServiceStatus, StartServiceCtrlDispatcherW,
+                SERVICE_ACCEPT_PAUSE_CONTINUE, SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP,
+                SERVICE_CONTROL_CONTINUE, SERVICE_CONTROL_INTERROGATE, SERVICE_CONTROL_PAUSE,
+                SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_PAUSED, SERVICE_RUNNING,
+                SERVICE_START_PENDING, SERVICE_STATUS, SERVICE_STATUS_CURRENT_STATE,
+                SERVICE_STATUS_HANDLE, SERVICE_STOPPED, SERVICE_STOP_PENDING, SERVICE_TABLE_ENTRYW,
+                SERVICE_WIN32_OWN_PROCESS,
+            },
+        },
+    },
+};
+
+/// The function which runs the service until it is stopped.
+type Service =
+    Box<dyn FnOnce(watch::Receiver<Control>) -> Result<(), Box<dyn Error + Send + Sync>> + Send>;
+
+/// The name and the service function of the service started by the service control manager
+static SERVICE: Mutex<Option<(String, Service)>> = Mutex::new(None);
+/// The status handle of the running service, stored as its raw value so the control handler can read it without a lock
+static STATUS: AtomicIsize = AtomicIsize::new(0);
+
+/// The state the service control manager requested the service to be in.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum Control {
+    /// The service shares events
+    Running,
+    /// The service keeps running but does not share events
+    Paused,
+    /// The service must complete
+    Stopped,
+}
+
+/// Encodes `value` as a nul terminated wide string.
+fn wide(value: &str) -> Vec<u16> {
+    OsStr::new(value).encode_wide().chain(Some(0)).collect()
+}
+
+/// Runs `service` as the windows service `name` once the service control manager starts it.
+///
+/// `service` receives the state requested by the service control manager and must complete when it is [`Control::Stopped`].
+/// An error of `service` is reported to the application event log and as the exit code of the service.
+///
+/// # Notes
+///
+/// This function blocks until the service stopped.
+///
+/// # Errors
+///
+/// This function will return an error if the process was not started by the service control manager.
+pub fn run(
+    name: &str,
+    service: impl FnOnce(watch::Receiver<Control>) -> Result<(), Box<dyn Error + Send + Sync>>
+        + Send
+        + 'static,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut service_name = wide(name);
+    *SERVICE.lock().unwrap_or_else(PoisonError::into_inner) =
+        Some((name.to_owned(), Box::new(service)));
+    let table = [
+        SERVICE_TABLE_ENTRYW {
+            lpServiceName: PWSTR(service_name.as_mut_ptr()),
+            lpServiceProc: Some(service_main),
+        },
+        SERVICE_TABLE_ENTRYW::default(),
+    ];
+    debug!("connecting to the service control manager");
+    // SAFETY:
+    // Invoking an unsafe windows api with a table which is terminated by an empty entry and outlives the call
+    if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) }.as_bool() {
+        Ok(())
+    } else {
+        Err(format!(
+            "connecting to the service control manager failed {}",
+            windows::core::Error::from_win32()
+        )
+        .into())
+    }
+}
+
+/// Reports `message` as an error of the service `source` to the application event log.
+///
+/// Failures are logged because there is no other place left to report them.
+pub fn report_error(source: &str, message: &str) {
+    let source = wide(source);
+    let mut message = wide(message);
+    // SAFETY:
+    // Invoking an unsafe windows api with nul terminated strings which outlive the calls
+    unsafe {
+        match RegisterEventSourceW(PCWSTR::null(), PCWSTR(source.as_ptr())) {
+            Ok(handle) => {
+                let strings = [PWSTR(message.as_mut_ptr())];
+                if !ReportEventW(
+                    handle,
+                    EVENTLOG_ERROR_TYPE,
+                    0,
+                    0,
+                    PSID::default(),
+                    0,
+                    Some(&strings),
+                    None,
+                )
+                .as_bool()
+                {
+                    warn!(
+                        "reporting to the event log failed {}",
+                        windows::core::Error::from_win32()
+                    );
+                }
+                DeregisterEventSource(handle);
+            }
+            Err(error) => warn!("registering the event source failed {error}"),
+        }
+    }
+}
+
+/// Reports `state` to the service control manager with the controls the service accepts in that state.
+///
+/// A non zero `exit_code` is reported as a service specific error.
+fn set_status(state: SERVICE_STATUS_CURRENT_STATE, exit_code: u32) {
+    let controls_accepted = if state == SERVICE_RUNNING || state == SERVICE_PAUSED {
+        SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN | SERVICE_ACCEPT_PAUSE_CONTINUE
+    } else {
+        0
+    };
+    let status = SERVICE_STATUS {
+        dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+        dwCurrentState: state,
+        dwControlsAccepted: controls_accepted,
+        dwWin32ExitCode: if exit_code == 0 {
+            NO_ERROR.0
+        } else {
+            ERROR_SERVICE_SPECIFIC_ERROR.0
+        },
+        dwServiceSpecificExitCode: exit_code,
+        dwCheckPoint: 0,
+        dwWaitHint: 0,
+    };
+    // SAFETY:
+    // Invoking an unsafe windows api with the handle returned by the registration of the control handler
+    let reported = unsafe {
+        SetServiceStatus(
+            SERVICE_STATUS_HANDLE(STATUS.load(Ordering::Acquire)),
+            &status,
+        )
+    };
+    if !reported.as_bool() {
+        warn!(
+            "reporting the service status failed {}",
+            windows::core::Error::from_win32()
+        );
+    }
+}
+
+#[no_mangle]
+/// Callback function for `RegisterServiceCtrlHandlerExW`, `context` points to the transmit end of the controls.
+unsafe extern "system" fn control_handler(
+    control: u32,
+    _: u32,
+    _: *mut core::ffi::c_void,
+    context: *mut core::ffi::c_void,
+) -> u32 {
+    let controls = match context.cast::<watch::Sender<Control>>().as_ref() {
+        Some(controls) => controls,
+        None => return ERROR_CALL_NOT_IMPLEMENTED.0,
+    };
+    match control {
+        SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+            debug!("service control manager requested stop");
+            set_status(SERVICE_STOP_PENDING, 0);
+            controls.send_replace(Control::Stopped);
+        }
+        SERVICE_CONTROL_PAUSE => {
+            debug!("service control manager requested pause");
+            controls.send_replace(Control::Paused);
+            set_status(SERVICE_PAUSED, 0);
+        }
+        SERVICE_CONTROL_CONTINUE => {
+            debug!("service control manager requested continue");
+            controls.send_replace(Control::Running);
+            set_status(SERVICE_RUNNING, 0);
+        }
+        SERVICE_CONTROL_INTERROGATE => {}
+        _ => return ERROR_CALL_NOT_IMPLEMENTED.0,
+    }
+    NO_ERROR.0
+}
+
+#[no_mangle]
+/// Callback function for `StartServiceCtrlDispatcherW`, runs the service stored by [`run`].
+unsafe extern "system" fn service_main(_: u32, _: *mut PWSTR) {
+    let (name, service) = match SERVICE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+    {
+        Some(service) => service,
+        None => return,
+    };
+    let service_name = wide(&name);
+    let (controls, controls_rx) = watch::channel(Control::Running);
+    // SAFETY:
+    // Invoking an unsafe windows api
+    // controls must outlive the control handler, which is no longer called after the service reported it stopped
+    let handle = match RegisterServiceCtrlHandlerExW(
+        PCWSTR(service_name.as_ptr()),
+        Some(control_handler),
+        Some(core::ptr::addr_of!(controls).cast()),
+    ) {
+        Ok(handle) => handle,
+        Err(error) => {
+            error!("registering the service control handler failed {error}");
+            return;
+        }
+    };
+    STATUS.store(handle.0, Ordering::Release);
+    set_status(SERVICE_START_PENDING, 0);
+    set_status(SERVICE_RUNNING, 0);
+
+    let exit_code = match service(controls_rx) {
+        Ok(()) => 0,
+        Err(error) => {
+            error!("service failed {error}");
+            report_error(&name, &error.to_string());
+            1
+        }
+    };
+    set_status(SERVICE_STOPPED, exit_code);
+    drop(controls);
+}
@@ -0,0 +1,628 @@
+// SPDX-License-Identifier: MIT
+
+//! Shares one platform listener between all drivers of the process.
+//!
+//! Every driver competes to lead, the leader runs the platform listener which publishes snapshots of the system to all drivers.
+//! When the leader completes another driver takes over, so the platform resources exist only once per process.
+
+use crate::{
+    backend,
+    diagnostic::{Diagnostic, Diagnostics},
+    emitter::{sleep_until, Emitter},
+    state::Interfaces,
+};
+use core::{cmp::min, pin::Pin};
+use futures::{future::pending, Future};
+use log::{debug, warn};
+use std::{
+    collections::HashMap,
+    error::Error,
+    net::IpAddr,
+    sync::{Arc, Mutex, PoisonError, Weak},
+    time::{Duration, Instant},
+};
+use tokio::sync::{mpsc::UnboundedSender, watch, Mutex as AsyncMutex, Notify};
+
+/// The time after which a failed platform listener is restarted
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// The listener of the process, it only lives as long as a driver uses it
+static LISTENER: Mutex<Option<Weak<Listener>>> = Mutex::new(None);
+
+/// The future of a platform listener which completes when the platform stops sending changes.
+pub type Platform<'a> =
+    Pin<Box<dyn Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send + 'a>>;
+
+/// A snapshot of the system taken by the platform listener.
+struct Snapshot {
+    /// The state of the interfaces
+    state: Interfaces,
+    /// The destinations of all drivers a route could be selected for
+    routed: Vec<IpAddr>,
+}
+
+/// The platform listener shared by all drivers of the process.
+pub struct Listener {
+    /// Held by the driver which runs the platform listener
+    leader: AsyncMutex<()>,
+    /// The latest snapshot, [`None`] until the first one is taken
+    snapshots: watch::Sender<Option<Arc<Snapshot>>>,
+    /// The destinations of all drivers with the amount of drivers that look up a route for them
+    destinations: Mutex<HashMap<IpAddr, usize>>,
+    /// Notified when a destination was added so the platform listener takes a new snapshot
+    refresh: Notify,
+    /// Notified when a driver asked to restart the platform listener
+    restart: Notify,
+    /// The diagnostics channels of all drivers which have one
+    diagnostics: Mutex<Vec<UnboundedSender<Diagnostic>>>,
+}
+impl Listener {
+    /// Gets the listener of the process or creates it when no driver uses one.
+    fn get() -> Arc<Self> {
+        let mut listener = LISTENER.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(existing) = listener.as_ref().and_then(Weak::upgrade) {
+            debug!("sharing the platform listener");
+            return existing;
+        }
+        let (snapshots, _) = watch::channel(None);
+        let created = Arc::new(Self {
+            leader: AsyncMutex::new(()),
+            snapshots,
+            destinations: Mutex::new(HashMap::new()),
+            refresh: Notify::new(),
+            restart: Notify::new(),
+            diagnostics: Mutex::new(Vec::new()),
+        });
+        *listener = Some(Arc::downgrade(&created));
+        created
+    }
+
+    /// The destinations of all drivers to look up a route for
+    pub(crate) fn destinations(&self) -> Vec<IpAddr> {
+        self.destinations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .copied()
+            .collect()
+    }
+
+    /// Publishes a snapshot of the system to all drivers.
+    ///
+    /// `routed` contains the [destinations](Self::destinations) a route could be selected for.
+    pub(crate) fn publish(&self, state: Interfaces, routed: Vec<IpAddr>) {
+        self.snapshots
+            .send_replace(Some(Arc::new(Snapshot { state, routed })));
+    }
+
+    /// Counts a non-fatal problem of the platform listener and reports it to all drivers.
+    pub(crate) fn report(&self, diagnostic: &Diagnostic) {
+        #[cfg(feature = "metrics")]
+        metrics::counter!("network_connectivity_errors_total", 1, "kind" => diagnostic.name());
+        // the senders of drivers whose diagnostics receiver was dropped are forgotten
+        self.diagnostics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|sender| sender.send(diagnostic.clone()).is_ok());
+    }
+
+    /// Restarts the platform listener of the process when a driver uses one.
+    pub(crate) fn restart_shared() {
+        let listener = LISTENER.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(existing) = listener.as_ref().and_then(Weak::upgrade) {
+            existing.resync();
+        }
+    }
+
+    /// Restarts the running platform listener so the state of the system is taken anew.
+    ///
+    /// The platform listener is dropped by the leader, so it must not complete after asking for it.
+    pub(crate) fn resync(&self) {
+        self.restart.notify_one();
+    }
+
+    /// Completes when a destination was added and a new snapshot must be taken
+    pub(crate) async fn refreshed(&self) {
+        self.refresh.notified().await;
+    }
+}
+
+/// Counts the destinations of a driver in the [listener](Listener) and subscribes it to the diagnostics of the listener as long as it lives.
+struct Registration {
+    /// The listener the destinations are counted in
+    listener: Arc<Listener>,
+    /// The destinations of the driver
+    destinations: Vec<IpAddr>,
+    /// The diagnostics channel of the driver
+    diagnostics: Option<UnboundedSender<Diagnostic>>,
+    /// Whether any of the destinations was new, the current snapshot lacks their routes then
+    added: bool,
+}
+impl Registration {
+    /// Counts `destinations` in `listener` and asks for a new snapshot when any of them is new.
+    fn new(listener: Arc<Listener>, destinations: &[IpAddr], diagnostics: &Diagnostics) -> Self {
+        let mut added = false;
+        let mut counts = listener
+            .destinations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        for &destination in destinations {
+            let count = counts.entry(destination).or_insert(0);
+            added |= *count == 0;
+            *count = count.saturating_add(1);
+        }
+        drop(counts);
+        if added {
+            listener.refresh.notify_one();
+        }
+        let diagnostics = diagnostics.sender().cloned();
+        if let Some(ref sender) = diagnostics {
+            listener
+                .diagnostics
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(sender.clone());
+        }
+        Self {
+            listener,
+            destinations: destinations.to_vec(),
+            diagnostics,
+            added,
+        }
+    }
+}
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(ref sender) = self.diagnostics {
+            let mut senders = self
+                .listener
+                .diagnostics
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if let Some(position) = senders.iter().position(|known| known.same_channel(sender)) {
+                senders.swap_remove(position);
+            }
+        }
+        let mut counts = self
+            .listener
+            .destinations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        for destination in &self.destinations {
+            if let Some(count) = counts.get_mut(destination) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    counts.remove(destination);
+                }
+            }
+        }
+    }
+}
+
+/// Determines when the system is settled after it changed at `now`, [`None`] when the change is emitted right away.
+///
+/// Every change after the initial snapshot restarts the wait for the system to settle of the [debounce](Emitter::debounce_interval),
+/// but not beyond the end of the `coalesced` tick the first change started.
+fn settled_at(emitter: &Emitter, now: Instant, coalesced: &mut Option<Instant>) -> Option<Instant> {
+    if coalesced.is_none() {
+        *coalesced = emitter
+            .coalesce_tick()
+            .and_then(|tick| now.checked_add(tick));
+    }
+    let debounced = emitter
+        .debounce_interval()
+        .and_then(|interval| now.checked_add(interval));
+    match (debounced, *coalesced) {
+        (Some(debounced), Some(coalesced)) => Some(min(debounced, coalesced)),
+        (debounced, coalesced) => debounced.or(coalesced),
+    }
+}
+
+/// Sends the snapshots of the shared [listener](Listener) through the [emitter](Emitter) and leads it when no other driver does.
+///
+/// The driver [follows](follow) the snapshots of the listener while it [leads](lead) the platform listener whenever it holds the leader lock.
+///
+/// # Notes
+///
+/// This function completes when the receive end of the channel is dropped or the platform listener completed while leading.
+/// When the emitter [restarts the platform](Emitter::restarts_platform) a failed platform listener is reported as [`Diagnostic::PlatformFailed`] and restarted instead.
+/// The platform listener is also restarted when any driver asks for it through its [restart](Emitter::restart) notify.
+///
+/// # Errors
+///
+/// This function will return an error if the platform listener failed while leading or sending the updates failed.
+pub(crate) async fn drive(mut emitter: Emitter) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let listener = Listener::get();
+    // subscribing before registering ensures the snapshot with the routes of new destinations is not missed
+    let snapshots = listener.snapshots.subscribe();
+    let registration = Registration::new(
+        Arc::clone(&listener),
+        emitter.destinations(),
+        emitter.diagnostics(),
+    );
+    let diagnostics = emitter.diagnostics().clone();
+    let restarts_platform = emitter.restarts_platform();
+    let restart_requested = emitter.restart();
+    // the follower goes first so a dropped receive end stops leading as well
+    #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+    let result = tokio::select! {
+        biased;
+        result = follow(&mut emitter, snapshots, registration.added) => result,
+        result = lead(&listener, &diagnostics, restarts_platform, &restart_requested) => result,
+    };
+    result
+}
+
+/// Leads the platform listener of `listener` whenever no other driver does.
+///
+/// The leader runs the platform listener of the first [selected backend](backend::selected) and falls back to the next one when it fails.
+/// A failed platform listener without fallbacks is restarted after a delay when `restarts_platform`, otherwise this function completes with its result.
+/// A notification of `restart_requested` restarts the platform listener of whichever driver leads it.
+///
+/// # Errors
+///
+/// This function will return an error if the platform listener failed while leading.
+async fn lead(
+    listener: &Arc<Listener>,
+    diagnostics: &Diagnostics,
+    restarts_platform: bool,
+    restart_requested: &Notify,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut leading = None;
+    // the backends to fall back to when the platform listener fails
+    let mut fallbacks = Vec::new();
+    let mut restart = None;
+    loop {
+        #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+        {
+            tokio::select! {
+                biased;
+                guard = listener.leader.lock(), if leading.is_none() && restart.is_none() => {
+                    debug!("leading the platform listener");
+                    // a snapshot exists when a platform listener ran before
+                    if listener.snapshots.borrow().is_some() {
+                        diagnostics.report(Diagnostic::Resynchronized);
+                    }
+                    if fallbacks.is_empty() {
+                        fallbacks = backend::selected();
+                    }
+                    let backend = fallbacks.remove(0);
+                    debug!("listening with the {backend} backend");
+                    #[cfg(feature = "tokio-console")]
+                    let platform = {
+                        let listener = Arc::clone(listener);
+                        crate::task::spawn("network_connectivity::platform", async move {
+                            backend.listen(&listener).await
+                        })?
+                    };
+                    #[cfg(not(feature = "tokio-console"))]
+                    let platform = backend.listen(listener);
+                    leading = Some((guard, platform));
+                },
+                result = async {
+                    match leading {
+                        Some((_, ref mut platform)) => platform.await,
+                        None => pending().await,
+                    }
+                } => {
+                    debug!("platform listener completed");
+                    match result {
+                        Err(error) if !fallbacks.is_empty() => {
+                            warn!("platform listener failed {error}, falling back to the next backend");
+                            diagnostics.report(Diagnostic::PlatformFailed {
+                                error: error.to_string(),
+                            });
+                            leading = None;
+                        }
+                        Err(error) if restarts_platform => {
+                            warn!("platform listener failed {error}");
+                            diagnostics.report(Diagnostic::PlatformFailed {
+                                error: error.to_string(),
+                            });
+                            leading = None;
+                            restart = Instant::now().checked_add(RESTART_DELAY);
+                        }
+                        result => return result,
+                    }
+                },
+                _ = sleep_until(restart) => {
+                    debug!("restarting the platform listener");
+                    restart = None;
+                },
+                // the leader restarts the platform listener on behalf of every driver.
+                _ = restart_requested.notified() => {
+                    debug!("restart requested");
+                    listener.restart.notify_one();
+                },
+                _ = listener.restart.notified(), if leading.is_some() => {
+                    debug!("restarting the platform listener on request");
+                    leading = None;
+                    fallbacks.clear();
+                },
+            }
+        }
+    }
+}
+
+/// Sends the `snapshots` of the shared [listener](Listener) through the [emitter](Emitter) once the system settled.
+///
+/// The current snapshot is sent right away unless the destinations of the emitter were `added` to the listener, it lacks their routes then.
+///
+/// # Notes
+///
+/// This function completes when the receive end of the channel is dropped.
+///
+/// # Errors
+///
+/// This function will return an error if sending the updates failed.
+async fn follow(
+    emitter: &mut Emitter,
+    mut snapshots: watch::Receiver<Option<Arc<Snapshot>>>,
+    added: bool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let wake = emitter.wake();
+    // the latest snapshot and the one that was emitted, they differ while waiting for the system to settle.
+    let mut latest = if added {
+        None
+    } else {
+        snapshots.borrow_and_update().clone()
+    };
+    let mut emitted = latest.clone();
+    let mut settled = None;
+    // the end of the coalescing tick which started with the first change after an emission
+    let mut coalesced = None;
+    let mut heartbeat = emitter
+        .heartbeat_interval()
+        .and_then(|interval| Instant::now().checked_add(interval));
+    if let Some(ref snapshot) = emitted {
+        emitter.capture_snapshot(&snapshot.state, &snapshot.routed);
+        emitter.update(&snapshot.state, &snapshot.routed)?;
+    }
+
+    loop {
+        // the emitted snapshot is also updated when the suppression of a flapping interface ends or the emitter is woken.
+        #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+        {
+            tokio::select! {
+                biased;
+                _ = emitter.sender().closed() => {
+                    debug!("transmit channel closed");
+                    break;
+                },
+                _ = snapshots.changed() => {
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!("network_connectivity_snapshots_total", 1);
+                    latest = snapshots.borrow_and_update().clone();
+                    if let Some(ref snapshot) = latest {
+                        emitter.capture_snapshot(&snapshot.state, &snapshot.routed);
+                    }
+                    if emitted.is_some() {
+                        settled = settled_at(emitter, Instant::now(), &mut coalesced);
+                    }
+                },
+                _ = sleep_until(settled) => {
+                    debug!("system settled");
+                    settled = None;
+                    coalesced = None;
+                },
+                _ = sleep_until(heartbeat) => {
+                    heartbeat = emitter
+                        .heartbeat_interval()
+                        .and_then(|interval| Instant::now().checked_add(interval));
+                    emitter.beat()?;
+                    continue;
+                },
+                _ = sleep_until(emitter.deadline()) => debug!("flap suppression ended"),
+                _ = wake.notified() => debug!("emitter woken"),
+            }
+        }
+        if settled.is_none() {
+            emitted = latest.clone();
+        }
+        if let Some(ref snapshot) = emitted {
+            emitter.update(&snapshot.state, &snapshot.routed)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{settled_at, Listener, Registration, LISTENER};
+    use crate::{
+        diagnostic::Diagnostics,
+        emitter::{Emitter, Sender},
+        state::{AddressInfo, InterfaceId, Interfaces, LinkInfo, RouteInfo, RouteMetric},
+        Connectivity, ConnectivityState, IpFamily, NetworkEvent,
+    };
+    use std::{
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        sync::{Arc, Mutex, PoisonError, Weak},
+        time::{Duration, Instant},
+    };
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+    /// Serializes the tests which use the listener of the process
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    /// Adds the interface with `index` and `name` that is up with `address` and a default route to `gateway` to `state`.
+    fn add_interface(
+        state: &mut Interfaces,
+        index: InterfaceId,
+        name: &str,
+        (address, prefix_length): (IpAddr, u8),
+        gateway: IpAddr,
+    ) {
+        state.add_link(LinkInfo {
+            index,
+            loop_back: false,
+            carrier: true,
+            name: Some(name.into()),
+            hardware_address: None,
+            mtu: None,
+        });
+        state.add_address(AddressInfo {
+            index,
+            address,
+            prefix_length,
+        });
+        state.add_default_route(RouteInfo {
+            index,
+            gateway,
+            priority: RouteMetric::new(100),
+        });
+    }
+
+    /// Creates the state of ethernet with ipv4 internet connectivity and wireless with ipv6 internet connectivity.
+    fn state() -> Interfaces {
+        let mut state = Interfaces::new();
+        add_interface(
+            &mut state,
+            InterfaceId::new(2),
+            "eth0",
+            (Ipv4Addr::new(192, 0, 2, 2).into(), 24),
+            Ipv4Addr::new(192, 0, 2, 1).into(),
+        );
+        add_interface(
+            &mut state,
+            InterfaceId::new(3),
+            "wlan0",
+            (Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2).into(), 64),
+            Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1).into(),
+        );
+        state
+    }
+
+    /// Creates an emitter which sends detailed events to the returned receiver.
+    fn emitter() -> (Emitter, UnboundedReceiver<NetworkEvent>) {
+        let (sender, receiver) = unbounded_channel();
+        (Emitter::new(Sender::Detailed(sender)), receiver)
+    }
+
+    /// Receives the events that were sent and returns the last connectivity among them and the interfaces they reported the connectivity of.
+    fn received(
+        receiver: &mut UnboundedReceiver<NetworkEvent>,
+    ) -> (Option<Connectivity>, Vec<InterfaceId>) {
+        let mut connectivity = None;
+        let mut interfaces = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                NetworkEvent::ConnectivityChanged {
+                    connectivity: changed,
+                    ..
+                } => connectivity = Some(changed),
+                NetworkEvent::InterfaceConnectivityChanged { index, .. } => interfaces.push(index),
+                _ => {}
+            }
+        }
+        (connectivity, interfaces)
+    }
+
+    #[test]
+    fn drivers_filter_the_same_snapshot_independently() {
+        let _serial = SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+        let listener = Listener::get();
+        let mut first_snapshots = listener.snapshots.subscribe();
+        let mut second_snapshots = listener.snapshots.subscribe();
+        let (mut first, mut first_events) = emitter();
+        first.ignore_interface("wlan0".into());
+        let (mut second, mut second_events) = emitter();
+        second.ignore_family(IpFamily::V4);
+
+        listener.publish(state(), Vec::new());
+        for (emitter, snapshots) in [
+            (&mut first, &mut first_snapshots),
+            (&mut second, &mut second_snapshots),
+        ] {
+            let snapshot = snapshots.borrow_and_update().clone().unwrap();
+            emitter.update(&snapshot.state, &snapshot.routed).unwrap();
+        }
+
+        let (connectivity, interfaces) = received(&mut first_events);
+        assert_eq!(
+            connectivity,
+            Some(Connectivity {
+                ipv4: ConnectivityState::Internet,
+                ipv6: ConnectivityState::None,
+            })
+        );
+        assert!(!interfaces.contains(&InterfaceId::new(3)));
+        let (connectivity, interfaces) = received(&mut second_events);
+        assert_eq!(
+            connectivity,
+            Some(Connectivity {
+                ipv4: ConnectivityState::None,
+                ipv6: ConnectivityState::Internet,
+            })
+        );
+        assert!(interfaces.contains(&InterfaceId::new(3)));
+    }
+
+    #[test]
+    fn dropping_a_driver_keeps_the_listener_of_the_other() {
+        let _serial = SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+        let destination = IpAddr::from(Ipv4Addr::new(198, 51, 100, 1));
+        let diagnostics = Diagnostics::default();
+        let first = Registration::new(Listener::get(), &[destination], &diagnostics);
+        let second = Registration::new(Listener::get(), &[destination], &diagnostics);
+        assert!(Arc::ptr_eq(&first.listener, &second.listener));
+
+        drop(first);
+        let shared = LISTENER
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .unwrap();
+        assert!(Arc::ptr_eq(&shared, &second.listener));
+        assert_eq!(second.listener.destinations(), vec![destination]);
+        drop(shared);
+
+        drop(second);
+        assert!(LISTENER
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .is_none());
+    }
+
+    #[test]
+    fn debounce_restarts_with_every_change() {
+        let (mut emitter, _) = emitter();
+        emitter.debounce(Duration::from_millis(100));
+        let mut coalesced = None;
+        let start = Instant::now();
+
+        let first = settled_at(&emitter, start, &mut coalesced);
+        assert_eq!(first, Some(start + Duration::from_millis(100)));
+        let later = start + Duration::from_millis(60);
+        let second = settled_at(&emitter, later, &mut coalesced);
+        assert_eq!(second, Some(later + Duration::from_millis(100)));
+        assert_eq!(coalesced, None);
+    }
+
+    #[test]
+    fn coalescing_tick_bounds_the_debounce() {
+        let (mut emitter, _) = emitter();
+        emitter.debounce(Duration::from_millis(100));
+        emitter.coalesce(Duration::from_millis(150));
+        let mut coalesced = None;
+        let start = Instant::now();
+        let tick = start + Duration::from_millis(150);
+
+        let first = settled_at(&emitter, start, &mut coalesced);
+        assert_eq!(first, Some(start + Duration::from_millis(100)));
+        assert_eq!(coalesced, Some(tick));
+        let second = settled_at(&emitter, start + Duration::from_millis(80), &mut coalesced);
+        assert_eq!(second, Some(tick));
+    }
+
+    #[test]
+    fn changes_are_emitted_right_away_without_debounce() {
+        let (emitter, _) = emitter();
+        let mut coalesced = None;
+        assert_eq!(settled_at(&emitter, Instant::now(), &mut coalesced), None);
+    }
+}
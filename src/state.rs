@@ -1,43 +1,524 @@
 // SPDX-License-Identifier: MIT
 
-//! The platform independent internal state for this crate
+//! The platform independent state for this crate.
+//!
+//! This module does not depend on std so it can be used to aggregate the connectivity from custom event sources,
+//! for example the network stack of embedded firmware.
 
-use crate::{Connectivity, ConnectivityState};
-use core::cmp::max;
-use std::{
-    collections::{HashMap, HashSet},
-    net::{IpAddr, Ipv4Addr, Ipv6Addr},
-};
+use crate::{Connectivity, ConnectivityState, IpFamily};
+use alloc::{collections::BTreeSet, string::String, vec::Vec};
+#[cfg(not(feature = "std"))]
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use core::{cmp::min, fmt::Display, time::Duration};
+use hashbrown::{HashMap, HashSet};
+#[cfg(feature = "std")]
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// The main routing table on platforms with several tables, which holds the routes that are used unless a policy selects another table
+const MAIN_TABLE: u32 = 254;
 
-/// Represents an interface index.
-type InterfaceIndex = u32;
 /// Boolean indicating an interface is a loopback device
-type LoopBack = bool;
+pub type LoopBack = bool;
 /// Boolean indicating an interface has a carrier
-type Carrier = bool;
-/// Represents a route priority.
-type Priority = u32;
+pub type Carrier = bool;
+/// Represents the prefix length of an address.
+pub type PrefixLength = u8;
+/// Represents the name of an interface.
+pub type Name = String;
+/// Represents the hardware address of an interface.
+pub type HardwareAddress = Vec<u8>;
+/// Represents the maximum transmission unit of an interface.
+pub type Mtu = u32;
+/// Represents the negotiated speed of a link in bits per second.
+pub type Speed = u64;
+/// Represents the raw flags of an interface as the platform reports them.
+///
+/// On linux these are the `IFF_*` flags of the link, on windows the `OperStatus` is in the lowest byte and the `InterfaceAndOperStatusFlags` are in the byte above it.
+/// Their meaning differs per platform and is not interpreted by this crate.
+#[cfg(feature = "unstable-raw")]
+pub type RawFlags = u32;
+
+/// Identifies an interface by the index the system assigned to it.
+///
+/// The system can reuse the index of a removed interface for a new interface.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct InterfaceId(u32);
+impl InterfaceId {
+    /// Create an [`InterfaceId`] from the index the system assigned to the interface
+    #[allow(clippy::must_use_candidate)]
+    pub const fn new(index: u32) -> Self {
+        Self(index)
+    }
+
+    /// Get the index the system assigned to the interface
+    #[allow(clippy::must_use_candidate)]
+    pub const fn value(self) -> u32 {
+        self.0
+    }
+}
+impl From<u32> for InterfaceId {
+    fn from(index: u32) -> Self {
+        Self(index)
+    }
+}
+impl Display for InterfaceId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The metric of a route, lower is preferred.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct RouteMetric(u32);
+impl RouteMetric {
+    /// Create a [`RouteMetric`] from the metric the system reported
+    #[allow(clippy::must_use_candidate)]
+    pub const fn new(metric: u32) -> Self {
+        Self(metric)
+    }
+
+    /// Get the metric the system reported
+    #[allow(clippy::must_use_candidate)]
+    pub const fn value(self) -> u32 {
+        self.0
+    }
+}
+impl From<u32> for RouteMetric {
+    fn from(metric: u32) -> Self {
+        Self(metric)
+    }
+}
+impl Display for RouteMetric {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Represents the negotiated duplex mode of a link.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum Duplex {
+    /// Sending and receiving take turns
+    Half,
+    /// Sending and receiving happen simultaneously
+    Full,
+}
+
+/// How the connectivity of a dormant interface is reported, one which waits for an authentication like 802.1X before it passes traffic.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+#[non_exhaustive]
+pub enum DormantPolicy {
+    /// The interface provides no connectivity as if it is down
+    #[default]
+    Disconnected,
+    /// The interface provides at most network connectivity while it authenticates, its default routes are not counted
+    Network,
+}
+
+/// Represents the kind of an interface.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum InterfaceType {
+    /// A wired ethernet device
+    Ethernet,
+    /// A wireless lan device
+    Wireless,
+    /// A point to point protocol link, for example pppoe or a modem
+    Ppp,
+    /// A mobile broadband device, for example an lte or 5g modem
+    Cellular,
+    /// A tunnel which encapsulates ip packets, for example a vpn
+    Tunnel,
+    /// Any other kind of interface
+    Other,
+}
+
+/// Represents the scope in which an address is valid.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum AddressScope {
+    /// Only valid on the host itself, like a loopback address
+    Host,
+    /// Only valid on the link of the interface, like an ipv4 auto configured or ipv6 link local address
+    Link,
+    /// Only valid within the site, like an ipv6 unique local address
+    Site,
+    /// Globally valid, this includes private ipv4 addresses
+    Global,
+}
+impl AddressScope {
+    /// Determines the scope of `address` from the range it is in
+    #[allow(clippy::must_use_candidate)]
+    pub fn of(address: IpAddr) -> Self {
+        match address {
+            IpAddr::V4(address) if address.is_loopback() => Self::Host,
+            IpAddr::V4(address) if address.is_link_local() => Self::Link,
+            IpAddr::V4(_) => Self::Global,
+            IpAddr::V6(address) if address.is_loopback() => Self::Host,
+            IpAddr::V6(address) => match address.segments().first().copied().unwrap_or(0) {
+                // fe80::/10
+                segment if segment & 0xffc0 == 0xfe80 => Self::Link,
+                // fc00::/7 and the deprecated site local fec0::/10
+                segment if segment & 0xfe00 == 0xfc00 || segment & 0xffc0 == 0xfec0 => Self::Site,
+                _ => Self::Global,
+            },
+        }
+    }
+}
+
+/// Represents the state of an address as far as the platform exposes it.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[allow(clippy::struct_excessive_bools)]
+pub struct AddressFlags {
+    /// A temporary address for privacy extensions which is replaced regularly
+    pub temporary: bool,
+    /// The preferred lifetime expired, the address is no longer used for new connections
+    pub deprecated: bool,
+    /// Duplicate address detection did not complete yet, the address can not be used yet
+    pub tentative: bool,
+    /// Duplicate address detection found another host using the address, see [`Diagnostic::AddressConflict`](crate::Diagnostic::AddressConflict)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub duplicate: bool,
+}
+
+/// The lease of an address which was assigned for a limited time, for example by a dhcp server.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct AddressLease {
+    /// How long the address remained valid when the platform last reported it
+    pub valid_lifetime: Duration,
+    /// How long the address remained preferred for new connections when the platform last reported it
+    pub preferred_lifetime: Duration,
+    /// When the lifetimes were last renewed, since an epoch of the platform such as the boot of the system
+    pub renewed: Duration,
+}
+
+/// The parameters which the ipv6 routers on the link of an interface advertised, as far as the platform exposes them.
+///
+/// Only linux exposes them, on other platforms they keep their default.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct RouterAdvertisement {
+    /// The managed flag of the last advertisement, addresses are assigned by dhcpv6
+    pub managed: bool,
+    /// The other configuration flag of the last advertisement, dhcpv6 provides other configuration like the dns servers
+    pub other: bool,
+    /// How long a router remained a default router when its default route was learned, [`None`] when no router is a default router
+    pub router_lifetime: Option<Duration>,
+    /// The recursive dns servers advertised with the RDNSS option until a router withdraws them
+    pub dns_servers: Vec<Ipv6Addr>,
+}
+
+/// The verdict of the connectivity check of the system, as far as the platform performs one.
+///
+/// Only the NetworkManager backend on linux reports it, it limits the aggregated [Connectivity] of the [`Interfaces`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum SystemConnectivity {
+    /// The system is not connected to any network
+    None,
+    /// The system is connected to a network behind a captive portal
+    Portal,
+    /// The system is connected to a network but can not reach the internet
+    Limited,
+    /// The system can reach the internet
+    Full,
+}
+
+/// Represents the origin of a route.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum RouteProtocol {
+    /// Created by the system, for example for the subnet of an address
+    Kernel,
+    /// Created while booting or by a tool without a protocol of its own
+    Boot,
+    /// Configured by an administrator
+    Static,
+    /// Learned from an ipv6 router advertisement
+    RouterAdvertisement,
+    /// Learned from a dhcp server
+    Dhcp,
+    /// Created by any other protocol, for example a routing daemon
+    Other,
+}
+
+/// The complete information of a route.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct RouteDetails {
+    /// The index of the interface the route goes out of
+    pub index: InterfaceId,
+    /// The destination prefix, the unspecified address for a default route
+    pub destination: IpAddr,
+    /// The length of the destination prefix, zero for a default route
+    pub prefix_length: PrefixLength,
+    /// The gateway, [`None`] when the destination is on-link
+    pub gateway: Option<IpAddr>,
+    /// The metric of the route, lower is preferred
+    pub priority: RouteMetric,
+    /// The routing table the route is in, [`None`] when the platform has a single table
+    pub table: Option<u32>,
+    /// The origin of the route, [`None`] when the platform does not expose it
+    pub protocol: Option<RouteProtocol>,
+}
 
 /// Required information for links
-pub type LinkInfo = (InterfaceIndex, LoopBack, Carrier);
+///
+/// The name and hardware address are optional and used to detect when an interface index is reused by a different interface.
+/// The mtu is optional as well.
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct LinkInfo {
+    /// The index of the interface
+    pub index: InterfaceId,
+    /// Whether the interface is a loopback device
+    pub loop_back: LoopBack,
+    /// Whether the interface has a carrier
+    pub carrier: Carrier,
+    /// The name of the interface, [`None`] when the platform does not expose it
+    pub name: Option<Name>,
+    /// The hardware address of the interface, [`None`] when the platform does not expose it or the interface has none
+    pub hardware_address: Option<HardwareAddress>,
+    /// The mtu of the interface, [`None`] when the platform does not expose it
+    pub mtu: Option<Mtu>,
+}
+/// Optional information about the speed of links
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct LinkSpeedInfo {
+    /// The index of the interface
+    pub index: InterfaceId,
+    /// The negotiated speed of the link, [`None`] when it is not known
+    pub speed: Option<Speed>,
+    /// The negotiated duplex mode of the link, [`None`] when it is not known
+    pub duplex: Option<Duplex>,
+}
+/// The name, kind and hardware address of a link as far as they are known
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct LinkDetails {
+    /// The name of the interface, [`None`] when it is not known
+    pub name: Option<Name>,
+    /// The kind of the interface, [`None`] when it is not known
+    pub interface_type: Option<InterfaceType>,
+    /// The hardware address of the interface, [`None`] when it is not known or the interface has none
+    pub hardware_address: Option<HardwareAddress>,
+}
 /// Required information for addresses
-pub type AddressInfo = (InterfaceIndex, IpAddr);
-/// Required information for routes
-pub type RouteInfo = (InterfaceIndex, IpAddr, Priority);
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct AddressInfo {
+    /// The index of the interface the address is assigned to
+    pub index: InterfaceId,
+    /// The address
+    pub address: IpAddr,
+    /// The length of the prefix of the subnet of the address
+    pub prefix_length: PrefixLength,
+}
+/// The interface, address, prefix length and flags of an address
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct AddressDetails {
+    /// The index of the interface the address is assigned to
+    pub index: InterfaceId,
+    /// The address
+    pub address: IpAddr,
+    /// The length of the prefix of the subnet of the address
+    pub prefix_length: PrefixLength,
+    /// The flags of the address, the default flags when they are not known
+    pub flags: AddressFlags,
+}
+/// Required information for default routes
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct RouteInfo {
+    /// The index of the interface the route goes out of
+    pub index: InterfaceId,
+    /// The gateway of the route
+    pub gateway: IpAddr,
+    /// The metric of the route, lower is preferred
+    pub priority: RouteMetric,
+}
+
+/// The details of an interface as far as they are known, including interfaces that are down.
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct InterfaceSnapshot {
+    /// The index of the interface
+    pub index: InterfaceId,
+    /// The name of the interface, [`None`] when the platform does not expose it
+    pub name: Option<Name>,
+    /// The kind of the interface, [`None`] when the platform does not expose it
+    pub interface_type: Option<InterfaceType>,
+    /// The hardware address of the interface, [`None`] when the platform does not expose it or the interface has none
+    pub hardware_address: Option<HardwareAddress>,
+    /// The mtu of the interface, [`None`] when the platform does not expose it
+    pub mtu: Option<Mtu>,
+    /// Whether the interface is able to communicate with the network
+    pub up: bool,
+    /// The addresses of the interface with their prefix length, sorted
+    pub addresses: Vec<(IpAddr, PrefixLength)>,
+    /// Whether the interface was removed, its details are the last known ones then
+    pub removed: bool,
+}
+
+/// Identifies the network behind an interface for a specific ip type.
+#[derive(PartialEq, Eq, Clone, Default, Debug)]
+pub struct NetworkIdentity {
+    /// The gateways of the default routes
+    pub gateways: BTreeSet<IpAddr>,
+    /// The subnets derived from the addresses and their prefix length
+    pub subnets: BTreeSet<(IpAddr, PrefixLength)>,
+}
+impl NetworkIdentity {
+    /// Whether this identity has neither gateways nor subnets
+    #[allow(clippy::must_use_candidate)]
+    pub fn is_empty(&self) -> bool {
+        self.gateways.is_empty() && self.subnets.is_empty()
+    }
+}
+
+/// Masks an ipv4 address with a prefix length.
+fn ipv4_subnet(address: Ipv4Addr, prefix_length: PrefixLength) -> IpAddr {
+    let mask = u32::MAX
+        .checked_shl(32_u32.saturating_sub(u32::from(prefix_length)))
+        .unwrap_or(0);
+    IpAddr::V4(Ipv4Addr::from(u32::from(address) & mask))
+}
+/// Masks an ipv6 address with a prefix length.
+fn ipv6_subnet(address: Ipv6Addr, prefix_length: PrefixLength) -> IpAddr {
+    let mask = u128::MAX
+        .checked_shl(128_u32.saturating_sub(u32::from(prefix_length)))
+        .unwrap_or(0);
+    IpAddr::V6(Ipv6Addr::from(u128::from(address) & mask))
+}
+
+/// Masks an address with a prefix length.
+#[cfg(feature = "std")]
+pub(crate) fn subnet(address: IpAddr, prefix_length: PrefixLength) -> IpAddr {
+    match address {
+        IpAddr::V4(ipv4_address) => ipv4_subnet(ipv4_address, prefix_length),
+        IpAddr::V6(ipv6_address) => ipv6_subnet(ipv6_address, prefix_length),
+    }
+}
 
 /// Records the state for a specific ip type.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: serde::Serialize + Eq + core::hash::Hash",
+        deserialize = "T: serde::Deserialize<'de> + Eq + core::hash::Hash"
+    ))
+)]
 struct AddressGateway<T> {
     /// The addresses associated with this [AddressGateway]
-    addresses: HashSet<T>,
+    addresses: HashSet<(T, PrefixLength)>,
     /// The gateways associated with this [AddressGateway]
-    gateways: HashSet<(T, Priority)>,
+    gateways: HashSet<(T, RouteMetric)>,
 }
-impl<T> AddressGateway<T> {
+impl<T> AddressGateway<T>
+where
+    T: Copy + Into<IpAddr>,
+{
+    /// Convert to [`NetworkIdentity`] using `subnet` to mask the addresses
+    fn network_identity(&self, subnet: fn(T, PrefixLength) -> IpAddr) -> NetworkIdentity {
+        NetworkIdentity {
+            gateways: self
+                .gateways
+                .iter()
+                .map(|&(gateway, _)| gateway.into())
+                .collect(),
+            subnets: self
+                .addresses
+                .iter()
+                .map(|&(address, prefix_length)| (subnet(address, prefix_length), prefix_length))
+                .collect(),
+        }
+    }
+
+    /// Whether `gateway` falls within the subnet of one of the addresses using `subnet` to mask them
+    fn is_on_link(&self, gateway: T, subnet: fn(T, PrefixLength) -> IpAddr) -> bool {
+        self.addresses.iter().any(|&(address, prefix_length)| {
+            subnet(address, prefix_length) == subnet(gateway, prefix_length)
+        })
+    }
+
+    /// Whether one of the addresses is routable beyond the link, which is required for [internet](ConnectivityState::Internet) connectivity
+    ///
+    /// Only ipv6 link local addresses are not counted, an ipv4 auto configured address with a gateway still counts like before
+    /// because a router can translate it, for example on a zeroconf network.
+    fn has_routable_address(&self) -> bool {
+        self.addresses.iter().any(|&(address, _)| {
+            let address = address.into();
+            address.is_ipv4() || AddressScope::of(address) != AddressScope::Link
+        })
+    }
+
     /// Convert to [`ConnectivityState`]
-    fn connectivity_state(&self, up: bool) -> ConnectivityState {
-        let address = !self.addresses.is_empty();
-        let gateway = !self.gateways.is_empty();
+    ///
+    /// When `on_link` is given only gateways within the subnet of one of the addresses are counted, using it to mask them.
+    /// Without an ipv6 address that is routable beyond the link an ipv6 gateway is not counted either.
+    fn connectivity_state(
+        &self,
+        up: bool,
+        on_link: Option<fn(T, PrefixLength) -> IpAddr>,
+    ) -> ConnectivityState {
+        // a loopback address does not connect to any network
+        let address = self
+            .addresses
+            .iter()
+            .any(|&(address, _)| AddressScope::of(address.into()) != AddressScope::Host);
+        let gateway = self.has_routable_address()
+            && match on_link {
+                Some(subnet) => self
+                    .gateways
+                    .iter()
+                    .any(|&(gateway, _)| self.is_on_link(gateway, subnet)),
+                None => !self.gateways.is_empty(),
+            };
         match (up, address, gateway) {
             (false, _, _) | (true, false, _) => ConnectivityState::None,
             (true, true, false) => ConnectivityState::Network,
@@ -46,10 +527,41 @@ impl<T> AddressGateway<T> {
     }
 }
 /// Records the complete state for a single interface.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Interface {
     /// Whether the interface is able to communicate with the network
     up: bool,
+    /// The name of the interface if known
+    name: Option<Name>,
+    /// The hardware address of the interface if known
+    hardware_address: Option<HardwareAddress>,
+    /// The mtu of the interface if known
+    mtu: Option<Mtu>,
+    /// The kind of the interface if known
+    interface_type: Option<InterfaceType>,
+    /// The negotiated speed of the link if known
+    speed: Option<Speed>,
+    /// The negotiated duplex mode of the link if known
+    duplex: Option<Duplex>,
+    /// The raw flags of the interface if known
+    #[cfg(feature = "unstable-raw")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    raw_flags: Option<RawFlags>,
+    /// Whether the link is dormant, waiting for an authentication like 802.1X before it passes traffic
+    #[cfg_attr(feature = "serde", serde(default))]
+    dormant: bool,
+    /// The flags of the addresses of the interface which are known
+    address_flags: HashMap<IpAddr, AddressFlags>,
+    /// The leases of the addresses of the interface which were assigned for a limited time
+    #[cfg_attr(feature = "serde", serde(default))]
+    address_leases: HashMap<IpAddr, AddressLease>,
+    /// The hardware addresses last resolved for the neighbors of the interface
+    #[cfg_attr(feature = "serde", serde(default))]
+    neighbors: HashMap<IpAddr, HardwareAddress>,
+    /// The parameters advertised by the ipv6 routers on the link of the interface
+    #[cfg_attr(feature = "serde", serde(default))]
+    router_advertisement: RouterAdvertisement,
     /// The ipv4 [AddressGateway]  for the interface
     ipv4: AddressGateway<Ipv4Addr>,
     /// The ipv6 [AddressGateway]  for the interface
@@ -60,6 +572,19 @@ impl Interface {
     fn new(up: bool) -> Self {
         Self {
             up,
+            name: None,
+            hardware_address: None,
+            mtu: None,
+            interface_type: None,
+            speed: None,
+            duplex: None,
+            #[cfg(feature = "unstable-raw")]
+            raw_flags: None,
+            dormant: false,
+            address_flags: HashMap::new(),
+            address_leases: HashMap::new(),
+            neighbors: HashMap::new(),
+            router_advertisement: RouterAdvertisement::default(),
             ipv4: AddressGateway {
                 addresses: HashSet::new(),
                 gateways: HashSet::new(),
@@ -71,87 +596,620 @@ impl Interface {
         }
     }
 
+    /// Whether this interface is known by a different name or hardware address than given
+    fn is_other(&self, name: Option<&Name>, hardware_address: Option<&HardwareAddress>) -> bool {
+        let other_name =
+            matches!((self.name.as_ref(), name), (Some(known), Some(new)) if known != new);
+        let other_hardware_address = matches!(
+            (self.hardware_address.as_ref(), hardware_address),
+            (Some(known), Some(new)) if known != new
+        );
+        other_name || other_hardware_address
+    }
+
+    /// Whether the interface is up with a default ipv6 route but only link local ipv6 addresses
+    fn ipv6_link_local_only(&self) -> bool {
+        self.up
+            && !self.ipv6.gateways.is_empty()
+            && !self.ipv6.addresses.is_empty()
+            && !self.ipv6.has_routable_address()
+    }
+
     /// Convert to [Connectivity]
-    fn connectivity(&self) -> Connectivity {
+    ///
+    /// When `on_link` is set only gateways within the subnet of one of the addresses are counted.
+    fn connectivity(&self, on_link: bool) -> Connectivity {
         Connectivity {
-            ipv4: self.ipv4.connectivity_state(self.up),
-            ipv6: self.ipv6.connectivity_state(self.up),
+            ipv4: self
+                .ipv4
+                .connectivity_state(self.up, on_link.then_some(ipv4_subnet)),
+            ipv6: self
+                .ipv6
+                .connectivity_state(self.up, on_link.then_some(ipv6_subnet)),
         }
     }
 }
 
 /// Records the complete state for all interfaces.
+///
+/// With the `serde` feature it can be serialized, for example to record the snapshots of a platform.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Interfaces {
-    /// The mapping between [InterfaceIndex] and [Interface]
-    state: HashMap<InterfaceIndex, Interface>,
+    /// The mapping between [InterfaceId] and [Interface]
+    state: HashMap<InterfaceId, Interface>,
+    /// The indices of removed interfaces which can be reused by the system for a new interface
+    removed: HashSet<InterfaceId>,
+    /// The indices of loopback interfaces, see [`Interfaces::remove_loop_backs`]
+    loop_backs: HashSet<InterfaceId>,
+    /// All routes with their complete information as far as the platform reports them
+    routes: HashSet<RouteDetails>,
+    /// The verdict of the connectivity check of the system, [`None`] when the platform does not perform one
+    #[cfg_attr(feature = "serde", serde(default))]
+    system_connectivity: Option<SystemConnectivity>,
 }
 impl Interfaces {
     /// Create a new [`Interfaces`] instance
-    pub(crate) fn new() -> Self {
-        Self {
-            state: HashMap::new(),
-        }
+    #[allow(clippy::must_use_candidate)]
+    pub fn new() -> Self {
+        Self::default()
     }
 
     /// Convert to [Connectivity]
-    pub(crate) fn connectivity(&self) -> Connectivity {
-        self.state.values().fold(
-            Connectivity {
-                ipv4: ConnectivityState::None,
-                ipv6: ConnectivityState::None,
-            },
-            |mut accumulator, interface_state| {
-                let interface_connectivity = interface_state.connectivity();
-                accumulator.ipv4 = max(accumulator.ipv4, interface_connectivity.ipv4);
-                accumulator.ipv6 = max(accumulator.ipv6, interface_connectivity.ipv6);
-                accumulator
-            },
-        )
+    ///
+    /// The [verdict](SystemConnectivity) of the connectivity check of the system limits it when there is one.
+    #[allow(clippy::must_use_candidate)]
+    pub fn connectivity(&self) -> Connectivity {
+        self.aggregate_connectivity(false)
+    }
+
+    /// Convert to [Connectivity] only counting gateways within the subnet of one of the addresses of their interface.
+    ///
+    /// This prevents leftover default routes to unreachable gateways from being reported as [internet](ConnectivityState::Internet) connectivity.
+    /// Gateways that are reachable through an on-link route outside of these subnets are not counted either.
+    #[allow(clippy::must_use_candidate)]
+    pub fn on_link_connectivity(&self) -> Connectivity {
+        self.aggregate_connectivity(true)
+    }
+
+    /// Get the [Connectivity] of every interface, only counting gateways within the subnet of one of the addresses of their interface when `on_link` is set
+    #[allow(clippy::must_use_candidate)]
+    pub fn interface_connectivity(&self, on_link: bool) -> HashMap<InterfaceId, Connectivity> {
+        self.state
+            .iter()
+            .map(|(&index, interface)| (index, interface.connectivity(on_link)))
+            .collect()
+    }
+
+    /// Aggregates the [Connectivity] of all interfaces, see [`Interface::connectivity`] for `on_link`.
+    fn aggregate_connectivity(&self, on_link: bool) -> Connectivity {
+        let connectivity: Connectivity = self
+            .state
+            .values()
+            .map(|interface_state| interface_state.connectivity(on_link))
+            .collect();
+        let limit = match self.system_connectivity {
+            None | Some(SystemConnectivity::Full) => return connectivity,
+            Some(SystemConnectivity::Portal | SystemConnectivity::Limited) => {
+                ConnectivityState::Network
+            }
+            Some(SystemConnectivity::None) => ConnectivityState::None,
+        };
+        Connectivity {
+            ipv4: min(connectivity.ipv4, limit),
+            ipv6: min(connectivity.ipv6, limit),
+        }
+    }
+
+    /// Whether an interface is up with a default ipv6 route, usually from a router advertisement, but only link local ipv6 addresses.
+    ///
+    /// Such an interface only has [network](ConnectivityState::Network) connectivity over ipv6 because nothing can be reached through the gateway.
+    /// This is a common broken state, for example when the router advertises a default route but dhcpv6 or slaac did not provide an address.
+    #[allow(clippy::must_use_candidate)]
+    pub fn ipv6_link_local_only(&self) -> bool {
+        self.state.values().any(Interface::ipv6_link_local_only)
+    }
+
+    /// Get the ipv4 and ipv6 [`NetworkIdentity`] of every interface that is up and not a loopback interface
+    #[allow(clippy::must_use_candidate)]
+    pub fn network_identities(&self) -> HashMap<InterfaceId, (NetworkIdentity, NetworkIdentity)> {
+        self.state
+            .iter()
+            // a loopback interface is not connected to any network
+            .filter(|&(index, interface)| interface.up && !self.loop_backs.contains(index))
+            .map(|(&index, interface)| {
+                (
+                    index,
+                    (
+                        interface.ipv4.network_identity(ipv4_subnet),
+                        interface.ipv6.network_identity(ipv6_subnet),
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    /// Get an [`InterfaceSnapshot`] of every interface including the ones that are down, sorted by their index
+    #[allow(clippy::must_use_candidate)]
+    pub fn snapshot(&self) -> Vec<InterfaceSnapshot> {
+        let mut snapshot: Vec<InterfaceSnapshot> =
+            self.state
+                .iter()
+                .map(|(&index, interface)| {
+                    let mut addresses: Vec<(IpAddr, PrefixLength)> =
+                        interface
+                            .ipv4
+                            .addresses
+                            .iter()
+                            .map(|&(address, prefix_length)| (IpAddr::V4(address), prefix_length))
+                            .chain(interface.ipv6.addresses.iter().map(
+                                |&(address, prefix_length)| (IpAddr::V6(address), prefix_length),
+                            ))
+                            .collect();
+                    addresses.sort_unstable();
+                    InterfaceSnapshot {
+                        index,
+                        name: interface.name.clone(),
+                        interface_type: interface.interface_type,
+                        hardware_address: interface.hardware_address.clone(),
+                        mtu: interface.mtu,
+                        up: interface.up,
+                        addresses,
+                        removed: false,
+                    }
+                })
+                .collect();
+        snapshot.sort_unstable_by_key(|interface| interface.index);
+        snapshot
+    }
+
+    /// Get the [`Carrier`] of every interface
+    #[allow(clippy::must_use_candidate)]
+    pub fn carriers(&self) -> HashMap<InterfaceId, Carrier> {
+        self.state
+            .iter()
+            .map(|(&index, interface)| (index, interface.up))
+            .collect()
+    }
+
+    /// Get the indices of the interfaces which are dormant, waiting for an authentication like 802.1X
+    #[allow(clippy::must_use_candidate)]
+    pub fn dormant(&self) -> HashSet<InterfaceId> {
+        self.state
+            .iter()
+            .filter(|&(_, interface)| interface.dormant)
+            .map(|(&index, _)| index)
+            .collect()
+    }
+
+    /// Get the [`RawFlags`] of every interface for which they are known
+    #[cfg(feature = "unstable-raw")]
+    #[allow(clippy::must_use_candidate)]
+    pub fn raw_flags(&self) -> HashMap<InterfaceId, RawFlags> {
+        self.state
+            .iter()
+            .filter_map(|(&index, interface)| Some((index, interface.raw_flags?)))
+            .collect()
+    }
+
+    /// Get the hardware address last resolved for the neighbor with `address` on the interface with `index`, [`None`] when it is not known
+    #[allow(clippy::must_use_candidate)]
+    pub fn neighbor(&self, index: InterfaceId, address: IpAddr) -> Option<&HardwareAddress> {
+        self.state.get(&index)?.neighbors.get(&address)
+    }
+
+    /// Get the [`Mtu`] of every interface for which it is known
+    #[allow(clippy::must_use_candidate)]
+    pub fn mtus(&self) -> HashMap<InterfaceId, Mtu> {
+        self.state
+            .iter()
+            .filter_map(|(&index, interface)| Some((index, interface.mtu?)))
+            .collect()
+    }
+
+    /// Get the indices of the loopback interfaces, see [`Interfaces::remove_loop_backs`]
+    #[allow(clippy::must_use_candidate)]
+    pub fn loop_backs(&self) -> HashSet<InterfaceId> {
+        self.loop_backs.clone()
+    }
+
+    /// Get the [`LinkDetails`] of every interface
+    #[allow(clippy::must_use_candidate)]
+    pub fn link_details(&self) -> HashMap<InterfaceId, LinkDetails> {
+        self.state
+            .iter()
+            .map(|(&index, interface)| {
+                (
+                    index,
+                    LinkDetails {
+                        name: interface.name.clone(),
+                        interface_type: interface.interface_type,
+                        hardware_address: interface.hardware_address.clone(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Get the [`AddressDetails`] of every address, with the default flags when they are not known
+    #[allow(clippy::must_use_candidate)]
+    pub fn address_details(&self) -> Vec<AddressDetails> {
+        let mut details = Vec::new();
+        for (&index, interface) in &self.state {
+            let ipv4 = interface
+                .ipv4
+                .addresses
+                .iter()
+                .map(|&(address, prefix_length)| (IpAddr::V4(address), prefix_length));
+            let ipv6 = interface
+                .ipv6
+                .addresses
+                .iter()
+                .map(|&(address, prefix_length)| (IpAddr::V6(address), prefix_length));
+            for (address, prefix_length) in ipv4.chain(ipv6) {
+                let flags = interface
+                    .address_flags
+                    .get(&address)
+                    .copied()
+                    .unwrap_or_default();
+                details.push(AddressDetails {
+                    index,
+                    address,
+                    prefix_length,
+                    flags,
+                });
+            }
+        }
+        details
+    }
+
+    /// Get the [`AddressLease`] of every address which was assigned for a limited time
+    #[allow(clippy::must_use_candidate)]
+    pub fn address_leases(&self) -> Vec<(InterfaceId, IpAddr, AddressLease)> {
+        self.state
+            .iter()
+            .flat_map(|(&index, interface)| {
+                interface
+                    .address_leases
+                    .iter()
+                    .map(move |(&address, &lease)| (index, address, lease))
+            })
+            .collect()
+    }
+
+    /// Get the [`RouteDetails`] of every route
+    #[allow(clippy::must_use_candidate)]
+    pub fn routes(&self) -> Vec<RouteDetails> {
+        self.routes.iter().copied().collect()
+    }
+
+    /// Get the default routes of `family` through interfaces that are up in the order they are preferred.
+    ///
+    /// Routes with a lower metric are preferred. Among routes with the same metric the one through the interface with the lowest index is preferred
+    /// and after that the one with the lowest gateway, so the order does not depend on the order in which the platform reported the routes.
+    /// Only the routes of the main routing table are considered on platforms with several tables.
+    #[allow(clippy::must_use_candidate)]
+    pub fn default_routes(&self, family: IpFamily) -> Vec<RouteDetails> {
+        let mut routes: Vec<RouteDetails> = self
+            .routes
+            .iter()
+            .filter(|route| {
+                route.destination.is_ipv4() == (family == IpFamily::V4)
+                    && route.prefix_length == 0
+                    && route.table.map_or(true, |table| table == MAIN_TABLE)
+                    && self
+                        .state
+                        .get(&route.index)
+                        .map_or(false, |interface| interface.up)
+            })
+            .copied()
+            .collect();
+        routes.sort_unstable_by_key(|route| (route.priority, route.index, route.gateway));
+        routes
+    }
+
+    /// Get the [`Speed`] and [`Duplex`] of every interface
+    #[allow(clippy::must_use_candidate)]
+    pub fn link_speeds(&self) -> HashMap<InterfaceId, (Option<Speed>, Option<Duplex>)> {
+        self.state
+            .iter()
+            .map(|(&index, interface)| (index, (interface.speed, interface.duplex)))
+            .collect()
+    }
+
+    /// Get the [`RouterAdvertisement`] of every interface
+    #[allow(clippy::must_use_candidate)]
+    pub fn router_advertisements(&self) -> HashMap<InterfaceId, RouterAdvertisement> {
+        self.state
+            .iter()
+            .map(|(&index, interface)| (index, interface.router_advertisement.clone()))
+            .collect()
+    }
+
+    /// Get the verdict of the connectivity check of the system, [`None`] when the platform does not perform one
+    #[allow(clippy::must_use_candidate)]
+    pub const fn system_connectivity(&self) -> Option<SystemConnectivity> {
+        self.system_connectivity
+    }
+
+    /// Sets the verdict of the connectivity check of the system, [`None`] when it is unknown
+    pub fn set_system_connectivity(&mut self, connectivity: Option<SystemConnectivity>) {
+        self.system_connectivity = connectivity;
     }
 
     /// Adds a link entry
-    pub(crate) fn add_link(&mut self, link: LinkInfo) {
-        let (index, loop_back, carrier) = link;
-        if !loop_back {
-            let s = self
-                .state
-                .entry(index)
-                .or_insert_with(|| Interface::new(false));
-            s.up = carrier;
+    ///
+    /// When the index was removed before or the interface is now known by a different name or hardware address, the index was reused for a new interface and the state recorded for it is cleared.
+    /// A loopback interface is recorded like any other interface and can be removed with [`Interfaces::remove_loop_backs`].
+    pub fn add_link(&mut self, link: LinkInfo) {
+        let LinkInfo {
+            index,
+            loop_back,
+            carrier,
+            name,
+            hardware_address,
+            mtu,
+        } = link;
+        let reused = self.removed.remove(&index);
+        if loop_back {
+            self.loop_backs.insert(index);
+        } else {
+            self.loop_backs.remove(&index);
+        }
+        let s = self
+            .state
+            .entry(index)
+            .or_insert_with(|| Interface::new(false));
+        if reused || s.is_other(name.as_ref(), hardware_address.as_ref()) {
+            *s = Interface::new(false);
+        }
+        s.up = carrier;
+        if name.is_some() {
+            s.name = name;
+        }
+        if hardware_address.is_some() {
+            s.hardware_address = hardware_address;
+        }
+        if mtu.is_some() {
+            s.mtu = mtu;
+        }
+    }
+    /// Sets the carrier of a link entry that was added before
+    pub fn set_carrier(&mut self, index: InterfaceId, carrier: Carrier) {
+        self.state
+            .entry(index)
+            .and_modify(|entry| entry.up = carrier);
+    }
+    /// Sets whether a link entry that was added before is dormant, waiting for an authentication like 802.1X
+    pub fn set_dormant(&mut self, index: InterfaceId, dormant: bool) {
+        self.state
+            .entry(index)
+            .and_modify(|entry| entry.dormant = dormant);
+    }
+    /// Removes the default routes of a link entry, so it provides at most network connectivity
+    pub fn remove_default_routes(&mut self, index: InterfaceId) {
+        self.state.entry(index).and_modify(|entry| {
+            entry.ipv4.gateways.clear();
+            entry.ipv6.gateways.clear();
+        });
+    }
+    /// Sets the kind of a link entry that was added before
+    pub fn set_interface_type(&mut self, index: InterfaceId, interface_type: InterfaceType) {
+        self.state
+            .entry(index)
+            .and_modify(|entry| entry.interface_type = Some(interface_type));
+    }
+    /// Sets the speed of a link entry that was added before
+    pub fn set_link_speed(&mut self, link_speed: LinkSpeedInfo) {
+        let LinkSpeedInfo {
+            index,
+            speed,
+            duplex,
+        } = link_speed;
+        self.state.entry(index).and_modify(|entry| {
+            entry.speed = speed;
+            entry.duplex = duplex;
+        });
+    }
+    /// Sets the managed and other configuration flags of the last router advertisement of a link entry that was added before
+    pub fn set_router_flags(&mut self, index: InterfaceId, managed: bool, other: bool) {
+        if !IpFamily::V6.is_compiled() {
+            return;
+        }
+        self.state.entry(index).and_modify(|entry| {
+            entry.router_advertisement.managed = managed;
+            entry.router_advertisement.other = other;
+        });
+    }
+    /// Sets the lifetime of the default router of a link entry that was added before, [`None`] when it has none
+    pub fn set_router_lifetime(&mut self, index: InterfaceId, lifetime: Option<Duration>) {
+        if !IpFamily::V6.is_compiled() {
+            return;
+        }
+        self.state
+            .entry(index)
+            .and_modify(|entry| entry.router_advertisement.router_lifetime = lifetime);
+    }
+    /// Adds the advertised recursive dns `servers` of a link entry that was added before, or withdraws them when `withdrawn`
+    pub fn set_dns_servers(&mut self, index: InterfaceId, servers: &[Ipv6Addr], withdrawn: bool) {
+        if !IpFamily::V6.is_compiled() {
+            return;
+        }
+        self.state.entry(index).and_modify(|entry| {
+            let known = &mut entry.router_advertisement.dns_servers;
+            if withdrawn {
+                known.retain(|server| !servers.contains(server));
+            } else {
+                for server in servers {
+                    if !known.contains(server) {
+                        known.push(*server);
+                    }
+                }
+            }
+        });
+    }
+    /// Sets the raw flags of a link entry that was added before
+    #[cfg(feature = "unstable-raw")]
+    pub fn set_raw_flags(&mut self, index: InterfaceId, raw_flags: RawFlags) {
+        self.state
+            .entry(index)
+            .and_modify(|entry| entry.raw_flags = Some(raw_flags));
+    }
+    /// Sets the hardware address resolved for the neighbor with `address` of a link entry that was added before.
+    ///
+    /// The hardware address is remembered when the neighbor expires until it is resolved anew or the link entry is removed.
+    /// Returns whether the hardware address changed.
+    pub fn set_neighbor(
+        &mut self,
+        index: InterfaceId,
+        address: IpAddr,
+        hardware_address: HardwareAddress,
+    ) -> bool {
+        if !IpFamily::of(&address).is_compiled() {
+            return false;
+        }
+        match self.state.get_mut(&index) {
+            Some(entry) if entry.neighbors.get(&address) != Some(&hardware_address) => {
+                entry.neighbors.insert(address, hardware_address);
+                true
+            }
+            _ => false,
+        }
+    }
+    /// Keeps only the link entries and their routes for which `keep` returns true given their index and name if known
+    pub fn retain_links(&mut self, mut keep: impl FnMut(InterfaceId, Option<&str>) -> bool) {
+        self.state
+            .retain(|&index, interface| keep(index, interface.name.as_deref()));
+        let state = &self.state;
+        self.routes.retain(|route| state.contains_key(&route.index));
+    }
+    /// Keeps only the link entries and their routes for which `keep` returns true given their index and hardware address if known
+    pub fn retain_hardware_addresses(
+        &mut self,
+        mut keep: impl FnMut(InterfaceId, Option<&[u8]>) -> bool,
+    ) {
+        self.state
+            .retain(|&index, interface| keep(index, interface.hardware_address.as_deref()));
+        let state = &self.state;
+        self.routes.retain(|route| state.contains_key(&route.index));
+    }
+    /// Removes the loopback interfaces together with their addresses and routes
+    pub fn remove_loop_backs(&mut self) {
+        let loop_backs = &self.loop_backs;
+        self.state.retain(|index, _| !loop_backs.contains(index));
+        self.routes
+            .retain(|route| !loop_backs.contains(&route.index));
+    }
+    /// Removes the addresses, routes and neighbors of `family` from every interface
+    pub fn remove_family(&mut self, family: IpFamily) {
+        let other = |address: &IpAddr| match family {
+            IpFamily::V4 => address.is_ipv6(),
+            IpFamily::V6 => address.is_ipv4(),
+        };
+        for interface in self.state.values_mut() {
+            interface.address_flags.retain(|address, _| other(address));
+            interface.address_leases.retain(|address, _| other(address));
+            interface.neighbors.retain(|address, _| other(address));
+            match family {
+                IpFamily::V4 => {
+                    interface.ipv4.addresses.clear();
+                    interface.ipv4.gateways.clear();
+                }
+                IpFamily::V6 => {
+                    interface.ipv6.addresses.clear();
+                    interface.ipv6.gateways.clear();
+                    interface.router_advertisement = RouterAdvertisement::default();
+                }
+            }
         }
+        self.routes.retain(|route| other(&route.destination));
     }
     /// Removes a link entry
-    pub(crate) fn remove_link(&mut self, link: LinkInfo) {
-        let (index, _, _) = link;
+    pub fn remove_link(&mut self, link: LinkInfo) {
+        let index = link.index;
         self.state.remove(&index);
+        self.loop_backs.remove(&index);
+        self.routes.retain(|route| route.index != index);
+        self.removed.insert(index);
     }
 
     /// Adds an address entry
-    pub(crate) fn add_address(&mut self, address_info: AddressInfo) {
-        let (index, address) = address_info;
+    pub fn add_address(&mut self, address_info: AddressInfo) {
+        let AddressInfo {
+            index,
+            address,
+            prefix_length,
+        } = address_info;
+        if !IpFamily::of(&address).is_compiled() {
+            return;
+        }
         let entry = self
             .state
             .entry(index)
             .or_insert_with(|| Interface::new(false));
         match address {
-            IpAddr::V4(ipv4_address) => entry.ipv4.addresses.insert(ipv4_address),
-            IpAddr::V6(ipv6_address) => entry.ipv6.addresses.insert(ipv6_address),
+            IpAddr::V4(ipv4_address) => entry.ipv4.addresses.insert((ipv4_address, prefix_length)),
+            IpAddr::V6(ipv6_address) => entry.ipv6.addresses.insert((ipv6_address, prefix_length)),
         };
     }
+    /// Sets the flags of an address entry that was added before
+    pub fn set_address_flags(&mut self, address_info: AddressInfo, flags: AddressFlags) {
+        let AddressInfo { index, address, .. } = address_info;
+        self.state.entry(index).and_modify(|entry| {
+            entry.address_flags.insert(address, flags);
+        });
+    }
+    /// Sets the lease of an address entry that was added before
+    pub fn set_address_lease(&mut self, address_info: AddressInfo, lease: AddressLease) {
+        let AddressInfo { index, address, .. } = address_info;
+        self.state.entry(index).and_modify(|entry| {
+            entry.address_leases.insert(address, lease);
+        });
+    }
     /// Removes an address entry
-    pub(crate) fn remove_address(&mut self, address_info: AddressInfo) {
-        let (index, address) = address_info;
+    pub fn remove_address(&mut self, address_info: AddressInfo) {
+        let AddressInfo {
+            index,
+            address,
+            prefix_length,
+        } = address_info;
         self.state.entry(index).and_modify(|entry| {
+            entry.address_flags.remove(&address);
+            entry.address_leases.remove(&address);
             match address {
-                IpAddr::V4(ipv4_address) => entry.ipv4.addresses.remove(&ipv4_address),
-                IpAddr::V6(ipv6_address) => entry.ipv6.addresses.remove(&ipv6_address),
+                IpAddr::V4(ipv4_address) => {
+                    entry.ipv4.addresses.remove(&(ipv4_address, prefix_length))
+                }
+                IpAddr::V6(ipv6_address) => {
+                    entry.ipv6.addresses.remove(&(ipv6_address, prefix_length))
+                }
             };
         });
     }
 
+    /// Adds a route with its complete information, this does not influence the connectivity
+    ///
+    /// Returns whether the route was not known yet, platforms repeat known routes when one of their attributes the state does not record changed.
+    pub fn add_route(&mut self, route: RouteDetails) -> bool {
+        IpFamily::of(&route.destination).is_compiled() && self.routes.insert(route)
+    }
+    /// Removes a route with its complete information
+    ///
+    /// Returns whether the route was known.
+    pub fn remove_route(&mut self, route: &RouteDetails) -> bool {
+        self.routes.remove(route)
+    }
+
     /// Adds a default route entry
-    pub(crate) fn add_default_route(&mut self, route: RouteInfo) {
-        let (index, address, priority) = route;
+    pub fn add_default_route(&mut self, route: RouteInfo) {
+        let RouteInfo {
+            index,
+            gateway: address,
+            priority,
+        } = route;
+        if !IpFamily::of(&address).is_compiled() {
+            return;
+        }
         let entry = self
             .state
             .entry(index)
@@ -162,8 +1220,12 @@ impl Interfaces {
         };
     }
     /// Removes a default route entry
-    pub(crate) fn remove_default_route(&mut self, route: RouteInfo) {
-        let (index, address, priority) = route;
+    pub fn remove_default_route(&mut self, route: RouteInfo) {
+        let RouteInfo {
+            index,
+            gateway: address,
+            priority,
+        } = route;
         self.state.entry(index).and_modify(|entry| {
             match address {
                 IpAddr::V4(ipv4_address) => entry.ipv4.gateways.remove(&(ipv4_address, priority)),
@@ -172,3 +1234,236 @@ impl Interfaces {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AddressInfo, InterfaceId, Interfaces, IpAddr, Ipv4Addr, Ipv6Addr, LinkInfo, RouteDetails,
+        RouteInfo, RouteMetric,
+    };
+    use crate::{Connectivity, ConnectivityState, IpFamily};
+
+    /// The index of the interface
+    const INDEX: InterfaceId = InterfaceId::new(2);
+
+    /// Creates the link of the interface that is up with `name` and `hardware_address`.
+    fn link(name: &str, hardware_address: [u8; 6]) -> LinkInfo {
+        LinkInfo {
+            index: INDEX,
+            loop_back: false,
+            carrier: true,
+            name: Some(name.into()),
+            hardware_address: Some(hardware_address.to_vec()),
+            mtu: None,
+        }
+    }
+
+    /// Creates the state of an ethernet interface that is up with `address` and a default route to `gateway`.
+    fn interface(address: IpAddr, prefix_length: u8, gateway: IpAddr) -> Interfaces {
+        let mut state = Interfaces::new();
+        state.add_link(LinkInfo {
+            index: INDEX,
+            loop_back: false,
+            carrier: true,
+            name: Some("eth0".into()),
+            hardware_address: None,
+            mtu: None,
+        });
+        state.add_address(AddressInfo {
+            index: INDEX,
+            address,
+            prefix_length,
+        });
+        state.add_default_route(RouteInfo {
+            index: INDEX,
+            gateway,
+            priority: RouteMetric::new(100),
+        });
+        state
+    }
+
+    #[test]
+    fn ipv6_link_local_address_with_default_route_is_network() {
+        let state = interface(
+            Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2).into(),
+            64,
+            Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1).into(),
+        );
+        assert_eq!(
+            state.connectivity(),
+            Connectivity {
+                ipv4: ConnectivityState::None,
+                ipv6: ConnectivityState::Network,
+            }
+        );
+        assert!(state.ipv6_link_local_only());
+    }
+
+    #[test]
+    fn ipv6_global_address_with_default_route_is_internet() {
+        let mut state = interface(
+            Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2).into(),
+            64,
+            Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1).into(),
+        );
+        state.add_address(AddressInfo {
+            index: INDEX,
+            address: Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2).into(),
+            prefix_length: 64,
+        });
+        assert_eq!(state.connectivity().ipv6, ConnectivityState::Internet);
+        assert!(!state.ipv6_link_local_only());
+    }
+
+    #[test]
+    fn ipv4_link_local_address_with_gateway_is_internet() {
+        let state = interface(
+            Ipv4Addr::new(169, 254, 1, 2).into(),
+            16,
+            Ipv4Addr::new(169, 254, 1, 1).into(),
+        );
+        assert_eq!(
+            state.connectivity(),
+            Connectivity {
+                ipv4: ConnectivityState::Internet,
+                ipv6: ConnectivityState::None,
+            }
+        );
+        assert!(!state.ipv6_link_local_only());
+    }
+
+    #[test]
+    fn retaining_links_removes_the_others_with_their_routes() {
+        let mut state = interface(
+            Ipv4Addr::new(192, 0, 2, 2).into(),
+            24,
+            Ipv4Addr::new(192, 0, 2, 1).into(),
+        );
+        let other = InterfaceId::new(3);
+        state.add_link(LinkInfo {
+            index: other,
+            loop_back: false,
+            carrier: true,
+            name: Some("docker0".into()),
+            hardware_address: None,
+            mtu: None,
+        });
+        for index in [INDEX, other] {
+            state.add_route(RouteDetails {
+                index,
+                destination: Ipv4Addr::UNSPECIFIED.into(),
+                prefix_length: 0,
+                gateway: Some(Ipv4Addr::new(192, 0, 2, 1).into()),
+                priority: RouteMetric::new(100),
+                table: None,
+                protocol: None,
+            });
+        }
+
+        state.retain_links(|index, name| {
+            assert_eq!(name, Some(if index == INDEX { "eth0" } else { "docker0" }));
+            name != Some("docker0")
+        });
+        assert_eq!(
+            state.link_details().into_keys().collect::<Vec<_>>(),
+            [INDEX]
+        );
+        assert!(state.routes().iter().all(|route| route.index == INDEX));
+        assert_eq!(state.routes().len(), 1);
+        assert_eq!(state.connectivity().ipv4, ConnectivityState::Internet);
+    }
+
+    /// Creates the state of an interface with `link` that has ipv4 internet connectivity.
+    fn connected(link: LinkInfo) -> Interfaces {
+        let mut state = interface(
+            Ipv4Addr::new(192, 0, 2, 2).into(),
+            24,
+            Ipv4Addr::new(192, 0, 2, 1).into(),
+        );
+        state.add_link(link);
+        state
+    }
+
+    #[test]
+    fn index_reused_with_another_name_clears_the_state() {
+        let mut state = connected(link("eth0", [2, 0, 0, 0, 0, 1]));
+        state.add_link(link("wlan0", [2, 0, 0, 0, 0, 1]));
+        assert!(state.address_details().is_empty());
+        assert_eq!(state.connectivity().any(), ConnectivityState::None);
+        // the default route of the previous interface is gone as well
+        state.add_address(AddressInfo {
+            index: INDEX,
+            address: Ipv4Addr::new(192, 0, 2, 2).into(),
+            prefix_length: 24,
+        });
+        assert_eq!(state.connectivity().ipv4, ConnectivityState::Network);
+        assert_eq!(state.link_details()[&INDEX].name.as_deref(), Some("wlan0"));
+    }
+
+    #[test]
+    fn hardware_address_change_clears_the_state() {
+        let mut state = connected(link("eth0", [2, 0, 0, 0, 0, 1]));
+        state.add_link(link("eth0", [2, 0, 0, 0, 0, 2]));
+        assert!(state.address_details().is_empty());
+        assert_eq!(state.connectivity().any(), ConnectivityState::None);
+    }
+
+    #[test]
+    fn reannouncing_the_same_interface_keeps_the_state() {
+        let mut state = connected(link("eth0", [2, 0, 0, 0, 0, 1]));
+        let mut down = link("eth0", [2, 0, 0, 0, 0, 1]);
+        down.carrier = false;
+        state.add_link(down);
+        assert_eq!(state.connectivity().any(), ConnectivityState::None);
+        // a link without a name or hardware address does not tell a different interface either
+        let mut anonymous = link("eth0", [2, 0, 0, 0, 0, 1]);
+        anonymous.name = None;
+        anonymous.hardware_address = None;
+        state.add_link(anonymous);
+        assert_eq!(state.address_details().len(), 1);
+        assert_eq!(state.connectivity().ipv4, ConnectivityState::Internet);
+        assert_eq!(
+            state.link_details()[&INDEX].hardware_address,
+            Some(vec![2, 0, 0, 0, 0, 1])
+        );
+    }
+
+    #[test]
+    fn default_routes_are_ordered_by_metric_index_and_gateway() {
+        let mut state = Interfaces::new();
+        for index in [3, 2, 4] {
+            state.add_link(LinkInfo {
+                index: InterfaceId::new(index),
+                loop_back: false,
+                carrier: index != 4,
+                name: None,
+                hardware_address: None,
+                mtu: None,
+            });
+        }
+        let route = |index, gateway, priority, table| RouteDetails {
+            index: InterfaceId::new(index),
+            destination: Ipv4Addr::UNSPECIFIED.into(),
+            prefix_length: 0,
+            gateway: Some(Ipv4Addr::new(192, 0, 2, gateway).into()),
+            priority: RouteMetric::new(priority),
+            table,
+            protocol: None,
+        };
+        let expected = [
+            route(3, 9, 50, Some(254)),
+            route(2, 1, 100, None),
+            route(2, 5, 100, Some(254)),
+            route(3, 1, 100, Some(254)),
+        ];
+        for details in expected.iter().rev() {
+            state.add_route(*details);
+        }
+        // the routes of another table and of an interface that is down are not selected
+        state.add_route(route(2, 1, 10, Some(100)));
+        state.add_route(route(4, 1, 10, Some(254)));
+
+        assert_eq!(state.default_routes(IpFamily::V4), expected);
+        assert!(state.default_routes(IpFamily::V6).is_empty());
+    }
+}
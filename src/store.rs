@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: MIT
+
+//! Persistence of the last reported connectivity.
+
+use crate::Connectivity;
+use std::error::Error;
+#[cfg(feature = "json")]
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+/// A store which persists the last reported connectivity.
+pub trait Store {
+    /// Loads the persisted connectivity.
+    ///
+    /// # Returns
+    ///
+    /// [`None`] when nothing was persisted yet.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the persisted connectivity could not be loaded.
+    fn load(&mut self) -> Result<Option<Connectivity>, Box<dyn Error + Send + Sync>>;
+
+    /// Persists the connectivity.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the connectivity could not be persisted.
+    fn save(&mut self, connectivity: Connectivity) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+/// A [`Store`] which persists the connectivity as json in a file.
+#[cfg(feature = "json")]
+#[derive(Clone, Debug)]
+pub struct JsonFileStore {
+    /// The path of the file
+    path: PathBuf,
+}
+#[cfg(feature = "json")]
+impl JsonFileStore {
+    /// Create a new [`JsonFileStore`] instance which persists to `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+#[cfg(feature = "json")]
+impl Store for JsonFileStore {
+    fn load(&mut self) -> Result<Option<Connectivity>, Box<dyn Error + Send + Sync>> {
+        match fs::read(&self.path) {
+            Ok(contents) => Ok(Some(serde_json::from_slice(&contents)?)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(Box::new(error)),
+        }
+    }
+
+    fn save(&mut self, connectivity: Connectivity) -> Result<(), Box<dyn Error + Send + Sync>> {
+        fs::write(&self.path, serde_json::to_vec(&connectivity)?)?;
+        Ok(())
+    }
+}
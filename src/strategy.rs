@@ -0,0 +1,89 @@
+// SPDX-License-Identifier: MIT
+
+//! [Proptest](proptest) strategies for the public types, so applications can property test their handling of connectivity updates and detailed events.
+//!
+//! The connectivity has strategies of its own which shrink towards no connectivity,
+//! the strategies of the other types are derived from their [`Arbitrary`] implementation with [`from_arbitrary`].
+
+use crate::{Connectivity, ConnectivityState, IpFamily, NetworkEvent};
+use arbitrary::{Arbitrary, Unstructured};
+use core::fmt::Debug;
+use proptest::{
+    collection::vec,
+    prelude::{any, Just},
+    prop_oneof,
+    strategy::Strategy,
+};
+
+/// The maximum amount of bytes an [`Arbitrary`] value is generated from
+const ARBITRARY_BYTES: usize = 512;
+
+/// A strategy for a [`ConnectivityState`] which shrinks towards [`ConnectivityState::None`]
+pub fn connectivity_state() -> impl Strategy<Value = ConnectivityState> {
+    prop_oneof![
+        Just(ConnectivityState::None),
+        Just(ConnectivityState::Network),
+        Just(ConnectivityState::Internet),
+    ]
+}
+
+/// A strategy for a [`Connectivity`] which shrinks towards no connectivity for both ip types
+pub fn connectivity() -> impl Strategy<Value = Connectivity> {
+    (connectivity_state(), connectivity_state())
+        .prop_map(|(ipv4, ipv6)| Connectivity { ipv4, ipv6 })
+}
+
+/// A strategy for an [`IpFamily`] which shrinks towards [`IpFamily::V4`]
+pub fn ip_family() -> impl Strategy<Value = IpFamily> {
+    prop_oneof![Just(IpFamily::V4), Just(IpFamily::V6)]
+}
+
+/// A strategy for a detailed [`NetworkEvent`] of any kind
+pub fn network_event() -> impl Strategy<Value = NetworkEvent> {
+    from_arbitrary()
+}
+
+/// A strategy for any type implementing [`Arbitrary`], generated from random bytes which shrink towards fewer and smaller bytes.
+pub fn from_arbitrary<T>() -> impl Strategy<Value = T>
+where
+    T: for<'a> Arbitrary<'a> + Debug,
+{
+    vec(any::<u8>(), 0..=ARBITRARY_BYTES)
+        .prop_filter_map("the bytes do not form a value", |bytes| {
+            T::arbitrary(&mut Unstructured::new(&bytes)).ok()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{connectivity, ip_family};
+    use crate::Connectivity;
+    use proptest::proptest;
+
+    proptest! {
+        #[test]
+        fn merging_keeps_the_highest_state_per_family(first in connectivity(), second in connectivity(), family in ip_family()) {
+            let merged = first.merge(second);
+            assert_eq!(merged.get(family), first.get(family).max(second.get(family)));
+            assert_eq!(merged, second.merge(first));
+            assert!(merged.all() <= merged.any());
+        }
+
+        #[test]
+        fn summing_merges_every_connectivity(all in proptest::collection::vec(connectivity(), 0..8)) {
+            let sum: Connectivity = all.iter().copied().sum();
+            for connectivity in all {
+                assert_eq!(sum.merge(connectivity), sum);
+            }
+        }
+    }
+
+    #[cfg(feature = "json")]
+    proptest! {
+        #[test]
+        fn detailed_events_survive_json(event in super::network_event()) {
+            let json = serde_json::to_string(&event).unwrap();
+            assert_eq!(serde_json::from_str::<crate::NetworkEvent>(&json).unwrap(), event);
+        }
+    }
+}
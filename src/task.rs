@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: MIT
+
+//! Runs the components of a driver as named tokio tasks, so tokio-console shows where time is spent and which component stalled.
+//!
+//! Tokio only names the tasks and instruments them for tokio-console when the application is built with `--cfg tokio_unstable`,
+//! without it they are spawned unnamed.
+
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use log::debug;
+use std::{io, panic::resume_unwind};
+use tokio::task::JoinHandle;
+
+/// A spawned task which is aborted when it is dropped, so dropping the driver stops its components as if they were not spawned.
+pub(crate) struct Spawned<T>(JoinHandle<T>);
+impl<T> Drop for Spawned<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+impl<T> Future for Spawned<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        match Pin::new(&mut self.0).poll(cx) {
+            Poll::Ready(Ok(output)) => Poll::Ready(output),
+            // a panic of a component is a panic of the driver
+            Poll::Ready(Err(error)) if error.is_panic() => resume_unwind(error.into_panic()),
+            // the task is only cancelled when the runtime shuts down, which drops the driver as well
+            Poll::Ready(Err(_)) | Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Spawns `future` as the task named `name` on the current runtime.
+///
+/// # Errors
+///
+/// This function will return an error if the runtime could not spawn the task.
+pub(crate) fn spawn<T: Send + 'static>(
+    name: &'static str,
+    future: impl Future<Output = T> + Send + 'static,
+) -> io::Result<Spawned<T>> {
+    debug!("spawning {name}");
+    #[cfg(tokio_unstable)]
+    let handle = tokio::task::Builder::new().name(name).spawn(future)?;
+    #[cfg(not(tokio_unstable))]
+    let handle = tokio::spawn(future);
+    Ok(Spawned(handle))
+}
@@ -0,0 +1,367 @@
+// SPDX-License-Identifier: MIT
+
+//! A portable polling implementation for unix systems without rtnetlink, like macos or aix.
+//! On linux it can be [selected](crate::select_backends) as a fallback for when netlink is not available,
+//! on the bsds as a fallback for the routing socket which samples the system the same way.
+//!
+//! The interfaces and addresses are read with `getifaddrs` and the default routes are read from the output of `netstat -rn`.
+//! Only the default routes are reported as routes.
+//! Changes are only noticed when polling, so they are reported up to [`POLL_INTERVAL`] late.
+//! The mtu and the hardware address of the interfaces are not known.
+
+use crate::{
+    shared::{Listener, Platform},
+    state::{
+        AddressInfo, InterfaceId, Interfaces, LinkInfo, RouteDetails, RouteInfo, RouteMetric,
+        SystemConnectivity,
+    },
+    ReachabilityState,
+};
+use core::{ptr::null_mut, time::Duration};
+use futures::Future;
+use log::debug;
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    ffi::CStr,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    process::Command,
+};
+
+/// The interval in which the system is polled for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// The port a socket is connected to for a route lookup, nothing is sent to it
+const DISCARD_PORT: u16 = 9;
+
+/// The linked list of interface addresses returned by `getifaddrs` which calls `freeifaddrs` on drop
+struct InterfaceAddresses(*mut libc::ifaddrs);
+impl InterfaceAddresses {
+    /// Gets the interface addresses of the system.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `getifaddrs` failed.
+    fn new() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut addresses = null_mut();
+        // SAFETY:
+        // the list is freed with `freeifaddrs` on drop
+        if unsafe { libc::getifaddrs(&mut addresses) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(Self(addresses))
+    }
+
+    /// Iterates the entries of the list
+    fn iter(&self) -> impl Iterator<Item = &libc::ifaddrs> {
+        // SAFETY:
+        // the entries live as long as the list and every entry points to the next one or is the last
+        core::iter::successors(unsafe { self.0.as_ref() }, |entry| unsafe {
+            entry.ifa_next.as_ref()
+        })
+    }
+}
+impl Drop for InterfaceAddresses {
+    fn drop(&mut self) {
+        // SAFETY:
+        // the list was created with `getifaddrs` and is not used afterwards
+        unsafe { libc::freeifaddrs(self.0) }
+    }
+}
+
+/// Converts a socket address of the system to an [`IpAddr`], [`None`] for other families
+fn sockaddr_to_ip(address: *const libc::sockaddr) -> Option<IpAddr> {
+    // SAFETY:
+    // the family tells which structure the address actually is
+    unsafe {
+        let family = i32::from(address.as_ref()?.sa_family);
+        if family == libc::AF_INET {
+            let address = &*address.cast::<libc::sockaddr_in>();
+            Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                address.sin_addr.s_addr,
+            ))))
+        } else if family == libc::AF_INET6 {
+            let address = &*address.cast::<libc::sockaddr_in6>();
+            Some(IpAddr::V6(Ipv6Addr::from(address.sin6_addr.s6_addr)))
+        } else {
+            None
+        }
+    }
+}
+
+/// Converts a netmask to the length of its prefix
+fn prefix_length(netmask: IpAddr) -> Option<u8> {
+    let ones = match netmask {
+        IpAddr::V4(netmask) => u32::from(netmask).count_ones(),
+        IpAddr::V6(netmask) => u128::from(netmask).count_ones(),
+    };
+    u8::try_from(ones).ok()
+}
+
+/// The information taken from the system in one poll, used to only publish a snapshot when the system changed.
+#[derive(PartialEq, Eq, Default)]
+struct Sample {
+    /// The interfaces by their index
+    links: BTreeMap<InterfaceId, LinkInfo>,
+    /// The addresses of the interfaces
+    addresses: Vec<AddressInfo>,
+    /// The default routes
+    routes: Vec<RouteInfo>,
+    /// The destinations a route could be selected for
+    routed: Vec<IpAddr>,
+    /// The verdict of the connectivity check of the system, [`None`] when there is none
+    system_connectivity: Option<SystemConnectivity>,
+}
+impl Sample {
+    /// Polls the system, looking up a route for all `destinations`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the interfaces could not be read.
+    fn poll(destinations: &[IpAddr]) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut sample = Self::default();
+        let mut indices = HashMap::new();
+        for entry in InterfaceAddresses::new()?.iter() {
+            // SAFETY:
+            // the name is a nul terminated string that lives as long as the entry
+            let (index, name) = unsafe {
+                (
+                    InterfaceId::new(libc::if_nametoindex(entry.ifa_name)),
+                    CStr::from_ptr(entry.ifa_name)
+                        .to_string_lossy()
+                        .into_owned(),
+                )
+            };
+            if index.value() == 0 {
+                continue;
+            }
+            let flags = i64::from(entry.ifa_flags);
+            let loopback = flags & i64::from(libc::IFF_LOOPBACK) != 0;
+            let running = i64::from(libc::IFF_UP | libc::IFF_RUNNING);
+            let carrier = flags & running == running;
+            sample.links.entry(index).or_insert_with(|| LinkInfo {
+                index,
+                loop_back: loopback,
+                carrier,
+                name: Some(name.clone()),
+                hardware_address: None,
+                mtu: None,
+            });
+            indices.insert(name, index);
+            if let (Some(address), Some(length)) = (
+                sockaddr_to_ip(entry.ifa_addr),
+                sockaddr_to_ip(entry.ifa_netmask).and_then(prefix_length),
+            ) {
+                sample.addresses.push(AddressInfo {
+                    index,
+                    address,
+                    prefix_length: length,
+                });
+            }
+        }
+        sample.routes = default_routes(&indices)?;
+        sample.routed = destinations
+            .iter()
+            .copied()
+            .filter(|&destination| can_reach(destination) != ReachabilityState::Unreachable)
+            .collect();
+        Ok(sample)
+    }
+
+    /// Converts the sample to the [state](Interfaces)
+    fn state(&self) -> Interfaces {
+        let mut state = Interfaces::new();
+        state.set_system_connectivity(self.system_connectivity);
+        for link in self.links.values() {
+            state.add_link(link.clone());
+        }
+        for &address in &self.addresses {
+            state.add_address(address);
+        }
+        for &route in &self.routes {
+            state.add_default_route(route);
+            let RouteInfo {
+                index,
+                gateway,
+                priority,
+            } = route;
+            let destination = match gateway {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            };
+            state.add_route(RouteDetails {
+                index,
+                destination,
+                prefix_length: 0,
+                gateway: Some(gateway),
+                priority,
+                table: None,
+                protocol: None,
+            });
+        }
+        state
+    }
+}
+
+/// Reads the default routes from the output of `netstat -rn`.
+///
+/// A default route is a line with the destination `default`, `0.0.0.0` or `::/0` followed by the gateway,
+/// its interface is the first of the other columns which is the name of a known interface.
+///
+/// # Errors
+///
+/// This function will return an error if `netstat` could not be run.
+fn default_routes(
+    indices: &HashMap<String, InterfaceId>,
+) -> Result<Vec<RouteInfo>, Box<dyn Error + Send + Sync>> {
+    let output = Command::new("netstat").arg("-rn").output()?;
+    if !output.status.success() {
+        return Err(format!("netstat failed with {}", output.status).into());
+    }
+    let mut routes = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut columns = line.split_whitespace();
+        if !matches!(
+            columns.next(),
+            Some("default" | "0.0.0.0" | "0.0.0.0/0" | "::/0")
+        ) {
+            continue;
+        }
+        // link local gateways are scoped to their interface with a suffix
+        let gateway = columns
+            .next()
+            .and_then(|gateway| gateway.split('%').next())
+            .and_then(|gateway| gateway.parse::<IpAddr>().ok());
+        let index = columns.find_map(|column| indices.get(column).copied());
+        if let (Some(gateway), Some(index)) = (gateway, index) {
+            routes.push(RouteInfo {
+                index,
+                gateway,
+                priority: RouteMetric::default(),
+            });
+        }
+    }
+    Ok(routes)
+}
+
+/// Determines the [reachability](ReachabilityState) of `destination` by asking the system which route it would select for it.
+///
+/// Connecting a udp socket selects a route without sending anything.
+/// The destination is reached directly when it is within the subnet of an address of the system and through a gateway otherwise.
+pub fn can_reach(destination: IpAddr) -> ReachabilityState {
+    let unspecified = match destination {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let connected = UdpSocket::bind(SocketAddr::new(unspecified, 0))
+        .and_then(|socket| socket.connect(SocketAddr::new(destination, DISCARD_PORT)));
+    if let Err(error) = connected {
+        debug!("no route to {destination}: {error}");
+        return ReachabilityState::Unreachable;
+    }
+    let on_link = InterfaceAddresses::new().map_or(false, |addresses| {
+        addresses.iter().any(|entry| {
+            match (
+                destination,
+                sockaddr_to_ip(entry.ifa_addr),
+                sockaddr_to_ip(entry.ifa_netmask),
+            ) {
+                (IpAddr::V4(destination), Some(IpAddr::V4(address)), Some(IpAddr::V4(netmask))) => {
+                    let netmask = u32::from(netmask);
+                    netmask != 0 && u32::from(address) & netmask == u32::from(destination) & netmask
+                }
+                (IpAddr::V6(destination), Some(IpAddr::V6(address)), Some(IpAddr::V6(netmask))) => {
+                    let netmask = u128::from(netmask);
+                    netmask != 0
+                        && u128::from(address) & netmask == u128::from(destination) & netmask
+                }
+                _ => false,
+            }
+        })
+    });
+    if on_link {
+        ReachabilityState::Direct
+    } else {
+        ReachabilityState::Gateway
+    }
+}
+
+/// Creates the platform listener which publishes snapshots of the system to the shared [listener](Listener).
+///
+/// # Notes
+///
+/// The platform listener never completes by itself.
+///
+/// # Errors
+///
+/// The platform listener fails when the system could not be polled.
+pub fn listen(listener: &Listener) -> Platform<'_> {
+    Box::pin(run(listener))
+}
+
+/// Polls the system and publishes a snapshot to the shared [listener](Listener) whenever it changed.
+///
+/// # Errors
+///
+/// This function will return an error if the system could not be polled.
+async fn run(listener: &Listener) -> Result<(), Box<dyn Error + Send + Sync>> {
+    publish_on(listener, || async {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        Ok(())
+    })
+    .await
+}
+
+/// Samples the system and publishes a snapshot to the shared [listener](Listener) whenever it changed,
+/// the system is sampled again every time `changed` completes.
+///
+/// # Errors
+///
+/// This function will return an error if the system could not be sampled or `changed` failed.
+pub(crate) async fn publish_on<C, F>(
+    listener: &Listener,
+    changed: C,
+) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    C: FnMut() -> F,
+    F: Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+{
+    publish_checked_on(listener, changed, || Ok(None)).await
+}
+
+/// Samples the system together with the verdict of its connectivity check and publishes a snapshot to the shared [listener](Listener) whenever either changed,
+/// the system is sampled and `check` is called again every time `changed` completes.
+///
+/// # Errors
+///
+/// This function will return an error if the system could not be sampled, `check` failed or `changed` failed.
+pub(crate) async fn publish_checked_on<C, F, S>(
+    listener: &Listener,
+    mut changed: C,
+    mut check: S,
+) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    C: FnMut() -> F,
+    F: Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+    S: FnMut() -> Result<Option<SystemConnectivity>, Box<dyn Error + Send + Sync>>,
+{
+    let mut published = None;
+    loop {
+        let mut sample = Sample::poll(&listener.destinations())?;
+        sample.system_connectivity = check()?;
+        if published.as_ref() != Some(&sample) {
+            debug!("system changed");
+            listener.publish(sample.state(), sample.routed.clone());
+            published = Some(sample);
+        }
+
+        // a new snapshot is also taken without waiting when a driver added a destination.
+        #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+        {
+            tokio::select! {
+                result = changed() => result?,
+                _ = listener.refreshed() => debug!("destinations added"),
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: MIT
+
+//! Posts connectivity transitions as json to webhooks and mqtt brokers.
+//!
+//! The payloads are delivered without tls, so only `http://` webhooks and `mqtt://` brokers are supported.
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    http::{request, Url},
+    ConnectivityState, NetworkEvent,
+};
+use core::{fmt::Display, time::Duration};
+use log::{debug, warn};
+use std::{collections::VecDeque, error::Error, sync::Arc};
+use tokio::{sync::mpsc::UnboundedReceiver, time::timeout};
+
+/// The maximum amount of undelivered payloads per webhook, the oldest payloads are dropped first
+const MAX_PENDING: usize = 64;
+/// The time after which undelivered payloads are retried while online
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// The time after which a single delivery is abandoned
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where connectivity transitions are delivered to.
+#[derive(Debug)]
+pub enum Endpoint {
+    /// A webhook which receives the payloads as post requests
+    Http(Url),
+    /// A mqtt broker which receives the payloads as retained messages
+    #[cfg(feature = "mqtt")]
+    Mqtt(Url),
+}
+impl Endpoint {
+    /// Delivers `payload` to this endpoint.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the delivery failed.
+    async fn deliver(&self, payload: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match *self {
+            Self::Http(ref url) => {
+                let response =
+                    request("POST", url, None, "application/json", payload.as_bytes()).await?;
+                if response.is_success() {
+                    Ok(())
+                } else {
+                    Err(format!("responded with status {}", response.status).into())
+                }
+            }
+            #[cfg(feature = "mqtt")]
+            Self::Mqtt(ref url) => crate::mqtt::publish(url, payload.as_bytes()).await,
+        }
+    }
+}
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Self::Http(ref url) => write!(f, "http://{url}"),
+            #[cfg(feature = "mqtt")]
+            Self::Mqtt(ref url) => write!(f, "mqtt://{url}"),
+        }
+    }
+}
+
+/// A single endpoint with its undelivered payloads.
+struct Webhook {
+    /// The endpoint to deliver to
+    endpoint: Endpoint,
+    /// The payloads that are not delivered yet
+    pending: VecDeque<Arc<str>>,
+}
+impl Webhook {
+    /// Delivers the pending payloads in order until one fails, the failure is reported to `diagnostics`.
+    async fn deliver(&mut self, diagnostics: &Diagnostics) {
+        while let Some(payload) = self.pending.front() {
+            match timeout(REQUEST_TIMEOUT, self.endpoint.deliver(payload)).await {
+                Ok(Ok(())) => {
+                    debug!("delivered to {}", self.endpoint);
+                    self.pending.pop_front();
+                }
+                Ok(Err(error)) => {
+                    warn!("delivering to {} failed {error}", self.endpoint);
+                    diagnostics.report(Diagnostic::DeliveryFailed {
+                        endpoint: self.endpoint.to_string(),
+                        error: error.to_string(),
+                    });
+                    return;
+                }
+                Err(_) => {
+                    warn!("delivering to {} timed out", self.endpoint);
+                    diagnostics.report(Diagnostic::DeliveryFailed {
+                        endpoint: self.endpoint.to_string(),
+                        error: "timed out".to_owned(),
+                    });
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Parses the webhook urls.
+///
+/// # Errors
+///
+/// This function will return an error if any of the urls is not a valid `http://` url, `https://` urls are not supported because there is no tls.
+pub fn parse(urls: &[String]) -> Result<Vec<Endpoint>, Box<dyn Error + Send + Sync>> {
+    urls.iter()
+        .map(|url| {
+            if url.starts_with("https://") {
+                return Err(format!(
+                    "https webhooks are not supported, post to an http relay instead: {url}"
+                )
+                .into());
+            }
+            Ok(Endpoint::Http(url.parse()?))
+        })
+        .collect()
+}
+
+/// Delivers every connectivity transition received from `events` as json to every endpoint.
+///
+/// Payloads are only delivered while there is internet connectivity,
+/// undelivered payloads are retried when internet connectivity returns or periodically while online.
+///
+/// # Notes
+///
+/// This function completes when `events` is closed, undelivered payloads are dropped.
+pub async fn notify(
+    endpoints: Vec<Endpoint>,
+    mut events: UnboundedReceiver<NetworkEvent>,
+    diagnostics: Diagnostics,
+) {
+    let mut webhooks = endpoints
+        .into_iter()
+        .map(|endpoint| Webhook {
+            endpoint,
+            pending: VecDeque::new(),
+        })
+        .collect::<Vec<_>>();
+    let mut online = false;
+
+    loop {
+        let pending = webhooks.iter().any(|webhook| !webhook.pending.is_empty());
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(event @ NetworkEvent::ConnectivityChanged { connectivity, .. }) => {
+                    online = connectivity.any() == ConnectivityState::Internet;
+                    let payload: Arc<str> = match serde_json::to_string(&event) {
+                        Ok(payload) => payload.into(),
+                        Err(error) => {
+                            warn!("serializing webhook payload failed {error}");
+                            continue;
+                        }
+                    };
+                    for webhook in &mut webhooks {
+                        if webhook.pending.len() >= MAX_PENDING {
+                            webhook.pending.pop_front();
+                        }
+                        webhook.pending.push_back(Arc::clone(&payload));
+                    }
+                }
+                Some(_) => continue,
+                None => break,
+            },
+            _ = tokio::time::sleep(RETRY_INTERVAL), if online && pending => {},
+        }
+        if online {
+            for webhook in &mut webhooks {
+                webhook.deliver(&diagnostics).await;
+            }
+        }
+    }
+
+    debug!("no more webhook events");
+}
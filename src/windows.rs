@@ -2,38 +2,93 @@
 
 //! The windows implementation for this crate.
 
-use crate::{Connectivity, ConnectivityState};
+use crate::{
+    diagnostic::Diagnostic,
+    shared::{Listener, Platform},
+    state::{
+        AddressFlags, AddressInfo, InterfaceId, InterfaceType, Interfaces, LinkInfo, LinkSpeedInfo,
+        RouteDetails, RouteInfo, RouteProtocol,
+    },
+    ReachabilityState,
+};
 use core::{
-    cmp::max,
     ffi::c_void,
     ptr::{addr_of, addr_of_mut, null_mut},
 };
-use futures::Future;
 use log::{debug, warn};
-use std::{error::Error, sync::Mutex};
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use std::{
+    error::Error,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+use tokio::sync::Notify;
 use windows::Win32::{
     Foundation::HANDLE,
     NetworkManagement::{
         IpHelper::{
-            CancelMibChangeNotify2, FreeMibTable, GetIfTable2, GetIpForwardTable2,
-            GetUnicastIpAddressTable, MibAddInstance, MibDeleteInstance, MibInitialNotification,
-            MibParameterNotification, NotifyIpInterfaceChange, IF_TYPE_SOFTWARE_LOOPBACK,
-            MIB_IF_ROW2, MIB_IF_TABLE2, MIB_IPFORWARD_ROW2, MIB_IPFORWARD_TABLE2,
-            MIB_IPINTERFACE_ROW, MIB_NOTIFICATION_TYPE, MIB_UNICASTIPADDRESS_ROW,
+            CancelMibChangeNotify2, FreeMibTable, GetBestRoute2, GetIfTable2, GetIpForwardTable2,
+            GetIpNetTable2, GetUnicastIpAddressTable, MibAddInstance, MibDeleteInstance,
+            MibInitialNotification, MibParameterNotification, NotifyIpInterfaceChange,
+            IF_TYPE_ETHERNET_CSMACD, IF_TYPE_IEEE80211, IF_TYPE_PPP, IF_TYPE_SOFTWARE_LOOPBACK,
+            IF_TYPE_TUNNEL, IF_TYPE_WWANPP, IF_TYPE_WWANPP2, MIB_IF_ROW2, MIB_IF_TABLE2,
+            MIB_IPFORWARD_ROW2, MIB_IPFORWARD_TABLE2, MIB_IPINTERFACE_ROW, MIB_IPNET_ROW2,
+            MIB_IPNET_TABLE2, MIB_NOTIFICATION_TYPE, MIB_UNICASTIPADDRESS_ROW,
             MIB_UNICASTIPADDRESS_TABLE,
         },
-        Ndis::IfOperStatusUp,
+        Ndis::{IfOperStatusDormant, IfOperStatusUp},
+    },
+    Networking::WinSock::{
+        IpDadStateDeprecated, IpDadStateDuplicate, IpDadStateTentative, IpSuffixOriginRandom,
+        NlnsIncomplete, NlnsUnreachable, RouteProtocolDhcp, RouteProtocolIcmp, RouteProtocolLocal,
+        RouteProtocolNetMgmt, ADDRESS_FAMILY, AF_INET, AF_INET6, MIB_IPPROTO_NT_STATIC,
+        MIB_IPPROTO_NT_STATIC_NON_DOD, NL_ROUTE_PROTOCOL, SOCKADDR_INET,
     },
-    Networking::WinSock::{ADDRESS_FAMILY, AF_INET, AF_INET6, AF_UNSPEC, SOCKADDR_INET},
 };
 
-/// Struct with named fields containing the sender channel and the current state
-struct SenderState {
-    /// The transmit end of a channel to send notifications to
-    tx: Mutex<UnboundedSender<Connectivity>>,
-    /// The current connectivity
-    state: Mutex<Connectivity>,
+/// The address family of the tables and notifications, only the ip type which is compiled in when one is left out
+#[cfg(all(feature = "ipv4-only", not(feature = "ipv6-only")))]
+const FAMILY: ADDRESS_FAMILY = AF_INET;
+/// The address family of the tables and notifications, only the ip type which is compiled in when one is left out
+#[cfg(all(feature = "ipv6-only", not(feature = "ipv4-only")))]
+const FAMILY: ADDRESS_FAMILY = AF_INET6;
+/// The address family of the tables and notifications, only the ip type which is compiled in when one is left out
+#[cfg(not(any(
+    all(feature = "ipv4-only", not(feature = "ipv6-only")),
+    all(feature = "ipv6-only", not(feature = "ipv4-only"))
+)))]
+const FAMILY: ADDRESS_FAMILY = windows::Win32::Networking::WinSock::AF_UNSPEC;
+
+/// Publishes a snapshot of the system to the shared `listener`.
+///
+/// # Errors
+///
+/// This function will return an error if the snapshot failed.
+fn publish(listener: &Listener) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let routed = routed_destinations(&listener.destinations());
+    listener.publish(interfaces_from_system()?, routed);
+    Ok(())
+}
+
+/// Publishes a snapshot of the system to the shared `listener` and reports a failure to its drivers.
+fn refresh(listener: &Listener) {
+    if let Err(error) = publish(listener) {
+        warn!("publish failed {error}");
+        listener.report(&Diagnostic::NotificationFailed {
+            error: error.to_string(),
+        });
+    }
+}
+
+/// Cancels the ip interface change notification when dropped
+struct Notification(HANDLE);
+impl Drop for Notification {
+    fn drop(&mut self) {
+        debug!("canceling ip interface change notification");
+        // SAFETY:
+        // cleanup of handle for earlier unsafe windows api
+        if let Err(error) = unsafe { CancelMibChangeNotify2(self.0) } {
+            warn!("canceling ip interface change notification failed {error}");
+        }
+    }
 }
 
 /// Wrapper around windows MIB_*_TABLE* structures which calls `FreeMibTable` on drop
@@ -90,6 +145,7 @@ macro_rules! create_mib_table_new {
 create_mib_table_new!(MIB_IF_TABLE2, GetIfTable2);
 create_mib_table_new!(MIB_UNICASTIPADDRESS_TABLE, GetUnicastIpAddressTable, u16);
 create_mib_table_new!(MIB_IPFORWARD_TABLE2, GetIpForwardTable2, u16);
+create_mib_table_new!(MIB_IPNET_TABLE2, GetIpNetTable2, u16);
 /// Helper macro for creating `MibTable` iterator boilerplate
 macro_rules! create_mib_table_iterator {
     ($table:ty,$row:ty) => {
@@ -133,113 +189,240 @@ macro_rules! create_mib_table_iterator {
 create_mib_table_iterator!(MIB_IF_TABLE2, MIB_IF_ROW2);
 create_mib_table_iterator!(MIB_UNICASTIPADDRESS_TABLE, MIB_UNICASTIPADDRESS_ROW);
 create_mib_table_iterator!(MIB_IPFORWARD_TABLE2, MIB_IPFORWARD_ROW2);
+create_mib_table_iterator!(MIB_IPNET_TABLE2, MIB_IPNET_ROW2);
 
-/// wrapper to check a windows address structure it's ip type
-fn sockaddr_inet_check_ip_type(address: SOCKADDR_INET, ip_type: ADDRESS_FAMILY) -> bool {
+/// Converts a windows address structure to an [`IpAddr`]
+fn sockaddr_inet_to_ip(address: SOCKADDR_INET) -> Option<IpAddr> {
     // SAFETY:
-    // accessing union's identifier field
-    ADDRESS_FAMILY(u32::from(unsafe { address.si_family })) == ip_type
+    // accessing union's identifier field and the variant it identifies
+    unsafe {
+        match ADDRESS_FAMILY(u32::from(address.si_family)) {
+            AF_INET => Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                address.Ipv4.sin_addr.S_un.S_addr,
+            )))),
+            AF_INET6 => Some(IpAddr::V6(Ipv6Addr::from(address.Ipv6.sin6_addr.u.Byte))),
+            _ => None,
+        }
+    }
+}
+
+/// Converts the protocol of a route to a [`RouteProtocol`], router advertisements are icmp messages.
+fn route_protocol(protocol: NL_ROUTE_PROTOCOL) -> RouteProtocol {
+    if protocol == RouteProtocolLocal {
+        RouteProtocol::Kernel
+    } else if protocol == RouteProtocolNetMgmt
+        || protocol == MIB_IPPROTO_NT_STATIC
+        || protocol == MIB_IPPROTO_NT_STATIC_NON_DOD
+    {
+        RouteProtocol::Static
+    } else if protocol == RouteProtocolIcmp {
+        RouteProtocol::RouterAdvertisement
+    } else if protocol == RouteProtocolDhcp {
+        RouteProtocol::Dhcp
+    } else {
+        RouteProtocol::Other
+    }
 }
 
-/// Get the connectivity state from the system
-fn connectivity_from_system() -> Result<Connectivity, Box<dyn Error + Send + Sync>> {
+/// Get the [state](Interfaces) from the system
+fn interfaces_from_system() -> Result<Interfaces, Box<dyn Error + Send + Sync>> {
     let interfaces = MibTable::<MIB_IF_TABLE2>::new()?;
-    let addresses = MibTable::<MIB_UNICASTIPADDRESS_TABLE>::new(AF_UNSPEC.0.try_into()?)?;
-    let routes = MibTable::<MIB_IPFORWARD_TABLE2>::new(AF_UNSPEC.0.try_into()?)?;
+    let addresses = MibTable::<MIB_UNICASTIPADDRESS_TABLE>::new(FAMILY.0.try_into()?)?;
+    let routes = MibTable::<MIB_IPFORWARD_TABLE2>::new(FAMILY.0.try_into()?)?;
+    let neighbors = MibTable::<MIB_IPNET_TABLE2>::new(FAMILY.0.try_into()?)?;
 
-    let default_routes = routes.into_iter().filter(|route| {
+    let mut state = Interfaces::new();
+    for interface in &interfaces {
+        // interfaces that are not hardware interfaces are ignored just like loopback interfaces
+        #[allow(clippy::used_underscore_binding)]
+        let ignored = interface.InterfaceAndOperStatusFlags._bitfield & 1 != 1
+            || interface.Type == IF_TYPE_SOFTWARE_LOOPBACK;
+        let name_length = interface
+            .Alias
+            .iter()
+            .position(|&character| character == 0)
+            .unwrap_or(interface.Alias.len());
+        let name = interface
+            .Alias
+            .get(..name_length)
+            .map(String::from_utf16_lossy);
+        let hardware_address = usize::try_from(interface.PhysicalAddressLength)
+            .ok()
+            .and_then(|length| interface.PhysicalAddress.get(..length))
+            .map(<[u8]>::to_vec);
+        let index = InterfaceId::new(interface.InterfaceIndex);
+        state.add_link(LinkInfo {
+            index,
+            loop_back: ignored,
+            carrier: interface.OperStatus == IfOperStatusUp,
+            name,
+            hardware_address,
+            mtu: Some(interface.Mtu),
+        });
+        // the speed is unknown when it is zero or the maximum value
+        let speed =
+            Some(interface.ReceiveLinkSpeed).filter(|&speed| speed != 0 && speed != u64::MAX);
+        state.set_link_speed(LinkSpeedInfo {
+            index,
+            speed,
+            duplex: None,
+        });
+        state.set_dormant(index, interface.OperStatus == IfOperStatusDormant);
+        #[cfg(feature = "unstable-raw")]
+        #[allow(clippy::used_underscore_binding)]
+        state.set_raw_flags(
+            index,
+            u32::try_from(interface.OperStatus.0).unwrap_or_default()
+                | u32::from(interface.InterfaceAndOperStatusFlags._bitfield) << 8,
+        );
+        state.set_interface_type(
+            index,
+            match interface.Type {
+                IF_TYPE_ETHERNET_CSMACD => InterfaceType::Ethernet,
+                IF_TYPE_IEEE80211 => InterfaceType::Wireless,
+                IF_TYPE_PPP => InterfaceType::Ppp,
+                IF_TYPE_TUNNEL => InterfaceType::Tunnel,
+                IF_TYPE_WWANPP | IF_TYPE_WWANPP2 => InterfaceType::Cellular,
+                _ => InterfaceType::Other,
+            },
+        );
+    }
+    for address in &addresses {
+        if let Some(ip_address) = sockaddr_inet_to_ip(address.Address) {
+            let address_info = AddressInfo {
+                index: address.InterfaceIndex.into(),
+                address: ip_address,
+                prefix_length: address.OnLinkPrefixLength,
+            };
+            state.add_address(address_info);
+            state.set_address_flags(
+                address_info,
+                AddressFlags {
+                    temporary: address.SuffixOrigin == IpSuffixOriginRandom,
+                    deprecated: address.DadState == IpDadStateDeprecated,
+                    tentative: address.DadState == IpDadStateTentative,
+                    duplicate: address.DadState == IpDadStateDuplicate,
+                },
+            );
+        }
+    }
+    for route in &routes {
         let mut prefix_compare = SOCKADDR_INET::default();
+        // SAFETY:
+        // accessing union's identifier field
         unsafe {
             prefix_compare.si_family = route.DestinationPrefix.Prefix.si_family;
         }
-        route.DestinationPrefix.PrefixLength == 0
+        if route.DestinationPrefix.PrefixLength == 0
             && route.DestinationPrefix.Prefix == prefix_compare
-    });
-
-    let connectivity = interfaces
-        .into_iter()
-        .filter(|interface| {
-            #[allow(clippy::used_underscore_binding)]
-            return interface.InterfaceAndOperStatusFlags._bitfield & 1 == 1
-                && interface.Type != IF_TYPE_SOFTWARE_LOOPBACK
-                && interface.OperStatus == IfOperStatusUp;
-        })
-        .map(|interface| {
-            let interface_addresses = addresses
-                .into_iter()
-                .filter(|address| address.InterfaceIndex == interface.InterfaceIndex);
-            let mut ipv4_interface_addresses = interface_addresses
-                .clone()
-                .filter(|address| sockaddr_inet_check_ip_type(address.Address, AF_INET));
-            let mut ipv6_interface_addresses = interface_addresses
-                .clone()
-                .filter(|address| sockaddr_inet_check_ip_type(address.Address, AF_INET6));
-            let interface_default_routes = default_routes
-                .clone()
-                .filter(|route| route.InterfaceIndex == interface.InterfaceIndex);
-            let mut ipv4_interface_default_routes = interface_default_routes
-                .clone()
-                .filter(|route| sockaddr_inet_check_ip_type(route.NextHop, AF_INET));
-            let mut ipv6_interface_default_routes = interface_default_routes
-                .clone()
-                .filter(|route| sockaddr_inet_check_ip_type(route.NextHop, AF_INET6));
+        {
+            if let Some(next_hop) = sockaddr_inet_to_ip(route.NextHop) {
+                state.add_default_route(RouteInfo {
+                    index: route.InterfaceIndex.into(),
+                    gateway: next_hop,
+                    priority: route.Metric.into(),
+                });
+            }
+        }
+        if let Some(destination) = sockaddr_inet_to_ip(route.DestinationPrefix.Prefix) {
+            state.add_route(RouteDetails {
+                index: route.InterfaceIndex.into(),
+                destination,
+                prefix_length: route.DestinationPrefix.PrefixLength,
+                // on-link routes have the unspecified address as next hop
+                gateway: sockaddr_inet_to_ip(route.NextHop)
+                    .filter(|next_hop| !next_hop.is_unspecified()),
+                priority: route.Metric.into(),
+                table: None,
+                protocol: Some(route_protocol(route.Protocol)),
+            });
+        }
+    }
+    for neighbor in &neighbors {
+        // unresolved neighbors have no hardware address
+        if neighbor.State == NlnsUnreachable || neighbor.State == NlnsIncomplete {
+            continue;
+        }
+        let hardware_address = usize::try_from(neighbor.PhysicalAddressLength)
+            .ok()
+            .and_then(|length| neighbor.PhysicalAddress.get(..length));
+        if let (Some(address), Some(hardware_address)) =
+            (sockaddr_inet_to_ip(neighbor.Address), hardware_address)
+        {
+            state.set_neighbor(
+                neighbor.InterfaceIndex.into(),
+                address,
+                hardware_address.to_vec(),
+            );
+        }
+    }
 
-            let ipv4 = match (
-                ipv4_interface_addresses.next(),
-                ipv4_interface_default_routes.next(),
-            ) {
-                (None, _) => ConnectivityState::None,
-                (Some(_), None) => ConnectivityState::Network,
-                (Some(_), Some(_)) => ConnectivityState::Internet,
-            };
-            let ipv6 = match (
-                ipv6_interface_addresses.next(),
-                ipv6_interface_default_routes.next(),
-            ) {
-                (None, _) => ConnectivityState::None,
-                (Some(_), None) => ConnectivityState::Network,
-                (Some(_), Some(_)) => ConnectivityState::Internet,
-            };
+    Ok(state)
+}
 
-            Connectivity { ipv4, ipv6 }
-        })
-        .reduce(|a, b| Connectivity {
-            ipv4: max(a.ipv4, b.ipv4),
-            ipv6: max(a.ipv6, b.ipv6),
-        });
+/// Determines the [reachability](ReachabilityState) of `destination` by asking the system which route it would select for it.
+pub fn can_reach(destination: IpAddr) -> ReachabilityState {
+    let mut address = SOCKADDR_INET::default();
+    // writing the union's variant together with its identifier field
+    match destination {
+        IpAddr::V4(ipv4_address) => {
+            address.Ipv4.sin_family = AF_INET.0.try_into().unwrap_or_default();
+            address.Ipv4.sin_addr.S_un.S_addr = u32::from(ipv4_address).to_be();
+        }
+        IpAddr::V6(ipv6_address) => {
+            address.Ipv6.sin6_family = AF_INET6.0.try_into().unwrap_or_default();
+            address.Ipv6.sin6_addr.u.Byte = ipv6_address.octets();
+        }
+    }
+    let mut route = MIB_IPFORWARD_ROW2::default();
+    let mut source = SOCKADDR_INET::default();
+    // SAFETY:
+    // Invoking an unsafe windows api with pointers to initialized structures
+    let result = unsafe {
+        GetBestRoute2(
+            None,
+            0,
+            None,
+            addr_of!(address),
+            0,
+            addr_of_mut!(route),
+            addr_of_mut!(source),
+        )
+    };
+    match result {
+        Ok(()) => match sockaddr_inet_to_ip(route.NextHop) {
+            Some(next_hop) if !next_hop.is_unspecified() => ReachabilityState::Gateway,
+            _ => ReachabilityState::Direct,
+        },
+        Err(error) => {
+            debug!("no route to {destination}: {error}");
+            ReachabilityState::Unreachable
+        }
+    }
+}
 
-    Ok(connectivity.unwrap_or(Connectivity {
-        ipv4: ConnectivityState::None,
-        ipv6: ConnectivityState::None,
-    }))
+/// Looks up a route for all `destinations` and returns the ones that can be routed.
+fn routed_destinations(destinations: &[IpAddr]) -> Vec<IpAddr> {
+    destinations
+        .iter()
+        .copied()
+        .filter(|&destination| can_reach(destination) != ReachabilityState::Unreachable)
+        .collect()
 }
 
-/// the handler function for `connectivity_changed` that returns a result which writes better to read code.
-unsafe fn handle_connectivity_changed(
-    caller_context: *const c_void,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let sender_state_pointer = caller_context.cast::<SenderState>().cast_mut();
-    if let Some(sender_state) = sender_state_pointer.as_mut() {
-        let mut state = sender_state
-            .state
-            .lock()
-            .map_err(|error| format!("failed to lock state: {error}"))?;
-        let new_connectivity = connectivity_from_system()?;
-        if *state != new_connectivity {
-            debug!("emitting updated connectivity {new_connectivity:?}");
-            sender_state
-                .tx
-                .lock()
-                .map_err(|error| format!("failed to lock sender: {error}"))?
-                .send(new_connectivity)?;
-            *state = new_connectivity;
-        }
+/// the handler function for `connectivity_changed`, it only notifies the platform listener which takes the snapshot.
+///
+/// Notifying never blocks, so the callback thread of the system can not wait on a contended or poisoned lock.
+unsafe fn handle_connectivity_changed(caller_context: *const c_void) {
+    let changed_pointer = caller_context.cast::<Notify>();
+    if let Some(changed) = changed_pointer.as_ref() {
+        changed.notify_one();
     }
-    Ok(())
 }
 
-#[no_mangle]
 /// Callback function for `NotifyIpInterfaceChange`
+///
+/// It is not exported, so several versions of this crate can be linked into one process without their callbacks colliding.
 unsafe extern "system" fn connectivity_changed(
     caller_context: *const c_void,
     _: *const MIB_IPINTERFACE_ROW,
@@ -248,83 +431,74 @@ unsafe extern "system" fn connectivity_changed(
     #[allow(non_upper_case_globals)]
     match notification_type {
         MibParameterNotification | MibAddInstance | MibDeleteInstance | MibInitialNotification => {
-            if let Err(error) = handle_connectivity_changed(caller_context) {
-                warn!("handle_connectivity_changed failed {error}");
-            }
+            handle_connectivity_changed(caller_context);
         }
         _ => {}
     }
 }
 
-/// Subscribes some functions to the windows api and sends connectivity updates.
+/// Creates the platform listener which publishes snapshots of the system to the shared [listener](Listener).
 ///
-/// # Returns
+/// # Notes
 ///
-/// The return value consists of a future that must be awaited and the receive end of a channel through which connectivity updates are received.
+/// The platform listener never completes by itself, the notification is canceled when it is dropped.
 ///
-/// # Notes
+/// # Errors
 ///
-/// When the receive end of the channel is dropped, the future will run to completion.
+/// The platform listener fails when the subscription or the initial snapshot failed.
+pub fn listen(listener: &Listener) -> Platform<'_> {
+    Box::pin(run(listener))
+}
+
+/// Subscribes to ip interface changes, `changed` is notified of every change.
 ///
 /// # Errors
 ///
 /// This function will return an error if the subscription failed.
-/// The returned future can fail when a cleanup of the subscription failed.
-pub fn new() -> Result<
-    (
-        impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
-        UnboundedReceiver<Connectivity>,
-    ),
-    Box<dyn Error + Send + Sync>,
-> {
-    let (tx, rx) = unbounded_channel();
-    let connectivity = connectivity_from_system()?;
-    let sender_state = Box::pin(SenderState {
-        tx: Mutex::new(tx),
-        state: Mutex::new(connectivity),
-    });
-
-    {
-        debug!("emitting initial connectivity {:?}", connectivity);
-        sender_state
-            .tx
-            .lock()
-            .map_err(|error| error.to_string())?
-            .send(connectivity)?;
-    }
-
+fn subscribe(changed: &Notify) -> Result<Notification, Box<dyn Error + Send + Sync>> {
     debug!("creating ip interface change notification");
     let mut handle = HANDLE::default();
     // SAFETY:
     // Invoking an unsafe windows api
-    // sender_state must be stationary in memory
-    // handle must be cleaned up when there is no more interest in the notification
+    // changed must be stationary in memory and outlive the returned notification
     unsafe {
         NotifyIpInterfaceChange(
-            AF_UNSPEC.0.try_into()?,
+            FAMILY.0.try_into()?,
             Some(connectivity_changed),
-            Some(addr_of!(*sender_state).cast::<c_void>()),
+            Some(addr_of!(*changed).cast::<c_void>()),
             false,
             &mut handle,
         )?;
     }
+    Ok(Notification(handle))
+}
 
-    let driver = async move {
-        let locked_tx = sender_state
-            .tx
-            .lock()
-            .map_err(|error| error.to_string())?
-            .clone();
-        debug!("waiting on sender closed");
-        locked_tx.closed().await;
-        debug!("canceling ip interface change notification");
-        // SAFETY:
-        // cleanup of handle for earlier unsafe windows api
-        unsafe {
-            CancelMibChangeNotify2(handle)?;
-        }
-        Ok(())
-    };
+/// Subscribes some functions to the windows api and publishes snapshots of the system to the shared [listener](Listener).
+///
+/// # Errors
+///
+/// This function will return an error if the subscription or the initial snapshot failed.
+async fn run(listener: &Listener) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let changed = Box::pin(Notify::new());
 
-    Ok((driver, rx))
+    // the notification is created before taking the initial snapshot so no change can be missed in between.
+    // notifications arriving while a snapshot is taken leave a permit behind, so a newer snapshot is taken afterwards.
+    // the notification is dropped before changed so changed outlives it.
+    let _notification = subscribe(&changed)?;
+
+    debug!("taking initial snapshot");
+    publish(listener)?;
+
+    // snapshots are only taken by this task so they are published in order.
+    // a new snapshot is also published without a notification when a driver added a destination.
+    loop {
+        #[allow(clippy::arithmetic_side_effects, clippy::integer_arithmetic)]
+        {
+            tokio::select! {
+                () = changed.notified() => debug!("ip interface changed"),
+                () = listener.refreshed() => debug!("destinations added"),
+            }
+        }
+        refresh(listener);
+    }
 }